@@ -1,8 +1,10 @@
+use crate::PitchforkConfig;
 use haybale::Project;
 use lazy_static::lazy_static;
 use llvm_ir::types::{NamedStructDef, Type};
 use log::warn;
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryInto;
 use std::fmt;
 use std::sync::Mutex;
@@ -15,6 +17,7 @@ use std::sync::Mutex;
 /// Unlike `AbstractData`, these may never be "underspecified" - that is, they
 /// must be a complete description of the data structure.
 #[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(try_from = "crate::abstractdata_serde::DataRepr"))]
 #[allow(dead_code)]  // as of this writing, we get warnings about many members being never constructed, which I believe is false
 pub(crate) enum CompleteAbstractData {
     /// A public value, of the given size in bits. The `AbstractValue` is used to
@@ -31,6 +34,15 @@ pub(crate) enum CompleteAbstractData {
     /// A (first-class) array of values
     Array { element_type: Box<Self>, num_elements: usize },
 
+    /// A (first-class) vector of values, i.e., an LLVM `VectorType`. This is
+    /// laid out in memory just like an array, but is kept distinct so that
+    /// the lanes of SIMD values can be told apart from ordinary arrays.
+    Vector { element_type: Box<Self>, num_elements: usize },
+
+    /// A (first-class) array of values, where each element has its own
+    /// description. The elements must all have the same size.
+    TupleArray { elements: Vec<Self> },
+
     /// A (first-class) structure of values
     Struct { name: String, elements: Vec<Self> },
 
@@ -41,6 +53,10 @@ pub(crate) enum CompleteAbstractData {
         /// If `false`, the pointer must point to the pointee; if `true`,
         /// it may either point to the pointee or be `NULL`
         maybe_null: bool,
+        /// Required alignment of the pointee's address, in bytes (a power of
+        /// two). `1` imposes no requirement beyond what `State::allocate()`
+        /// provides.
+        align: u64,
     },
 
     /// A (public) pointer to the LLVM `Function` with the given name
@@ -115,6 +131,22 @@ pub(crate) enum CompleteAbstractData {
     /// with the given `name` to the `State` covering the memory region it
     /// occupies.
     WithWatchpoint { name: String, data: Box<Self> },
+
+    /// Use the given `data`, which is a pointer to an array of `max_len`
+    /// elements, but also (after all the function parameters are allocated)
+    /// constrain the value named `len_name` to be at most `max_len`. That
+    /// value is the number of valid elements in the array. `len_name` is
+    /// either the name of an `AbstractValue::Named`, or else a parameter name.
+    WithLengthBound { len_name: String, max_len: usize, data: Box<Self> },
+
+    /// A null-terminated string occupying `max_len` bytes, all public.
+    /// The position of the first null byte is symbolic (anywhere in the
+    /// `max_len` bytes), and all bytes before it are nonzero.
+    PublicCString { max_len: usize },
+
+    /// A null-terminated string consisting of exactly `len` secret bytes
+    /// followed by a public null byte, so `len + 1` bytes total.
+    SecretCString { len: usize },
 }
 
 // methods which mirror the ones on `AbstractData` for the most part
@@ -140,6 +172,11 @@ impl CompleteAbstractData {
         Self::PublicValue { bits: 64, value }
     }
 
+    /// a 128-bit public value
+    pub fn pub_i128(value: AbstractValue) -> Self {
+        Self::PublicValue { bits: 128, value }
+    }
+
     /// a public value with the given number of bits
     pub fn pub_integer(bits: u32, value: AbstractValue) -> Self {
         Self::PublicValue { bits, value }
@@ -165,6 +202,11 @@ impl CompleteAbstractData {
         Self::Secret { bits: 64 }
     }
 
+    /// a 128-bit secret value
+    pub fn sec_i128() -> Self {
+        Self::Secret { bits: 128 }
+    }
+
     /// a secret value with the given number of bits
     pub fn sec_integer(bits: u32) -> Self {
         Self::Secret { bits }
@@ -172,12 +214,12 @@ impl CompleteAbstractData {
 
     /// a (public) pointer to something - another value, an array, etc
     pub fn pub_pointer_to(data: Self) -> Self {
-        Self::PublicPointerTo { pointee: Box::new(data), maybe_null: false }
+        Self::PublicPointerTo { pointee: Box::new(data), maybe_null: false, align: 1 }
     }
 
     /// A (public) pointer which may either point to the given data or be `NULL`
     pub fn pub_maybe_null_pointer_to(data: Self) -> Self {
-        Self::PublicPointerTo { pointee: Box::new(data), maybe_null: true }
+        Self::PublicPointerTo { pointee: Box::new(data), maybe_null: true, align: 1 }
     }
 
     /// a (public) pointer to the LLVM `Function` with the given name
@@ -228,6 +270,16 @@ impl CompleteAbstractData {
         Self::Array { element_type: Box::new(element_type), num_elements }
     }
 
+    /// A (first-class) vector of values
+    pub fn vector_of(element_type: Self, num_elements: usize) -> Self {
+        Self::Vector { element_type: Box::new(element_type), num_elements }
+    }
+
+    /// A (first-class) array of values, with one description per element
+    pub fn tuple_array(elements: impl IntoIterator<Item = Self>) -> Self {
+        Self::TupleArray { elements: elements.into_iter().collect() }
+    }
+
     /// A (first-class) structure of values.  Name used only for debugging purposes, need not match the (mangled) LLVM struct name.
     ///
     /// (`_struct` used instead of `struct` to avoid collision with the Rust keyword)
@@ -290,6 +342,18 @@ impl CompleteAbstractData {
     pub fn with_watchpoint(name: impl Into<String>, data: Self) -> Self {
         Self::WithWatchpoint { name: name.into(), data: Box::new(data) }
     }
+
+    /// a null-terminated string of public bytes; see notes on
+    /// `CompleteAbstractData::PublicCString`
+    pub fn pub_cstring(max_len: usize) -> Self {
+        Self::PublicCString { max_len }
+    }
+
+    /// a null-terminated string of secret bytes with a public length; see notes
+    /// on `CompleteAbstractData::SecretCString`
+    pub fn sec_cstring(len: usize) -> Self {
+        Self::SecretCString { len }
+    }
 }
 
 #[allow(dead_code)]
@@ -300,11 +364,11 @@ impl CompleteAbstractData {
     pub fn size_in_bits(&self) -> u32 {
         match self {
             Self::PublicValue { bits, .. } => *bits,
-            Self::Array { element_type, num_elements } => {
+            Self::Array { element_type, num_elements } | Self::Vector { element_type, num_elements } => {
                 let num_elements: u32 = (*num_elements).try_into().unwrap();
                 element_type.size_in_bits() * num_elements
             },
-            Self::Struct { elements, .. } => {
+            Self::TupleArray { elements } | Self::Struct { elements, .. } => {
                 elements.iter().map(Self::size_in_bits).sum()
             },
             Self::PublicPointerTo { .. } => Self::POINTER_SIZE_BITS,
@@ -317,36 +381,47 @@ impl CompleteAbstractData {
             Self::PointerOverride { .. } => Self::POINTER_SIZE_BITS,
             Self::SameSizeOverride { data, .. } => data.size_in_bits(),
             Self::WithWatchpoint { data, .. } => data.size_in_bits(),
+            Self::WithLengthBound { data, .. } => data.size_in_bits(),
+            Self::PublicCString { max_len } => {
+                let max_len: u32 = (*max_len).try_into().unwrap();
+                max_len * 8
+            },
+            Self::SecretCString { len } => {
+                let len: u32 = (*len).try_into().unwrap();
+                (len + 1) * 8
+            },
         }
     }
 
     /// Get the size of the nth (0-indexed) field/element of the `CompleteAbstractData`, in bits.
-    /// The `CompleteAbstractData` must be a `Struct` or `Array`.
+    /// The `CompleteAbstractData` must be a `Struct`, `Array`, `Vector`, or `TupleArray`.
     pub fn field_size_in_bits(&self, n: usize) -> u32 {
         match self {
-            Self::Struct { elements, .. } => Self::size_in_bits(&elements[n]),
-            Self::Array { element_type, .. } => Self::size_in_bits(element_type),
+            Self::TupleArray { elements } | Self::Struct { elements, .. } => Self::size_in_bits(&elements[n]),
+            Self::Array { element_type, .. } | Self::Vector { element_type, .. } => Self::size_in_bits(element_type),
             Self::VoidOverride { data, .. } => data.field_size_in_bits(n),
             Self::SameSizeOverride { data, .. } => data.field_size_in_bits(n),
             Self::WithWatchpoint { data, .. } => data.field_size_in_bits(n),
+            Self::WithLengthBound { data, .. } => data.field_size_in_bits(n),
             _ => panic!("field_size_in_bits called on {:?}", self),
         }
     }
 
     /// Get the offset of the nth (0-indexed) field/element of the `CompleteAbstractData`, in bits.
-    /// The `CompleteAbstractData` must be a `Struct` or `Array`.
+    /// The `CompleteAbstractData` must be a `Struct`, `Array`, `Vector`, or `TupleArray`.
     pub fn offset_in_bits(&self, n: usize) -> u32 {
         match self {
-            Self::Struct { elements, .. } => {
+            Self::TupleArray { elements } | Self::Struct { elements, .. } => {
                 elements.iter().take(n).map(Self::size_in_bits).sum()
             },
-            Self::Array { element_type, .. } => {
+            Self::Array { element_type, .. } | Self::Vector { element_type, .. } => {
                 let n: u32 = n.try_into().unwrap();
                 element_type.size_in_bits() * n
             },
             Self::VoidOverride { data, .. } => data.offset_in_bits(n),
             Self::SameSizeOverride { data, .. } => data.offset_in_bits(n),
             Self::WithWatchpoint { data, .. } => data.offset_in_bits(n),
+            Self::WithLengthBound { data, .. } => data.offset_in_bits(n),
             _ => panic!("offset_in_bits called on {:?}", self),
         }
     }
@@ -357,6 +432,8 @@ impl CompleteAbstractData {
             Self::PublicValue { .. } => false,
             Self::Secret { .. } => panic!("is_pointer on a Secret"),
             Self::Array { .. } => false,
+            Self::Vector { .. } => false,
+            Self::TupleArray { .. } => false,
             Self::Struct { .. } => false,
             Self::PublicPointerTo { .. } => true,
             Self::PublicPointerToFunction(_) => true,
@@ -367,6 +444,9 @@ impl CompleteAbstractData {
             Self::PointerOverride { .. } => true,
             Self::SameSizeOverride { data, .. } => data.is_pointer(),
             Self::WithWatchpoint { data, .. } => data.is_pointer(),
+            Self::WithLengthBound { data, .. } => data.is_pointer(),
+            Self::PublicCString { .. } => false,
+            Self::SecretCString { .. } => false,
         }
     }
 
@@ -377,6 +457,8 @@ impl CompleteAbstractData {
         match self {
             Self::PublicValue { .. } => panic!("pointee_size_in_bits() on a non-pointer: {:?}", self),
             Self::Array { .. } => panic!("pointee_size_in_bits() on a non-pointer: {:?}", self),
+            Self::Vector { .. } => panic!("pointee_size_in_bits() on a non-pointer: {:?}", self),
+            Self::TupleArray { .. } => panic!("pointee_size_in_bits() on a non-pointer: {:?}", self),
             Self::Struct { .. } => panic!("pointee_size_in_bits() on a non-pointer: {:?}", self),
             Self::PublicPointerTo { pointee, .. } => pointee.size_in_bits(),
             Self::PublicPointerToFunction(_) => 64,  // as of this writing, haybale allocates 64 bits for functions; see State::new()
@@ -389,6 +471,188 @@ impl CompleteAbstractData {
             Self::PointerOverride { data, .. } => data.size_in_bits(),  // here, 'data' is the pointee, not the pointer
             Self::SameSizeOverride { data, .. } => data.pointee_size_in_bits(),
             Self::WithWatchpoint { data, .. } => data.pointee_size_in_bits(),
+            Self::WithLengthBound { data, .. } => data.pointee_size_in_bits(),
+            Self::PublicCString { .. } => panic!("pointee_size_in_bits() on a non-pointer: {:?}", self),
+            Self::SecretCString { .. } => panic!("pointee_size_in_bits() on a non-pointer: {:?}", self),
+        }
+    }
+
+    /// Prefix for the names of the watchpoints we place just past the end of
+    /// the memory allocated for an entirely opaque struct. The rest of the
+    /// watchpoint name is the LLVM struct name.
+    pub(crate) const OPAQUE_STRUCT_GUARD_PREFIX: &'static str = "past_end_of_opaque_struct:";
+
+    /// Number of bytes we allocate past the end of each entirely opaque struct,
+    /// for the watchpoint described above
+    pub(crate) const OPAQUE_STRUCT_GUARD_BYTES: usize = 64;
+
+    /// The `CompleteAbstractData` we use for a struct which is entirely opaque
+    /// (no definition anywhere in the `Project`): `size_bytes` unconstrained
+    /// bytes, followed by `OPAQUE_STRUCT_GUARD_BYTES` unconstrained bytes with
+    /// a watchpoint on them, so that any access past the end of the
+    /// `size_bytes` gets reported
+    pub(crate) fn opaque_struct(llvm_struct_name: &str, size_bytes: usize) -> Self {
+        Self::opaque_struct_with_contents(llvm_struct_name, Self::array_of(Self::pub_i8(AbstractValue::Unconstrained), size_bytes))
+    }
+
+    /// Like `opaque_struct()`, but the `size_bytes` bytes are secret
+    pub(crate) fn secret_opaque_struct(llvm_struct_name: &str, size_bytes: usize) -> Self {
+        Self::opaque_struct_with_contents(llvm_struct_name, Self::array_of(Self::sec_i8(), size_bytes))
+    }
+
+    fn opaque_struct_with_contents(llvm_struct_name: &str, contents: Self) -> Self {
+        Self::_struct(llvm_struct_name, vec![
+            contents,
+            Self::with_watchpoint(
+                format!("{}{}", Self::OPAQUE_STRUCT_GUARD_PREFIX, llvm_struct_name),
+                Self::array_of(Self::pub_i8(AbstractValue::Unconstrained), Self::OPAQUE_STRUCT_GUARD_BYTES),
+            ),
+        ])
+    }
+
+    /// Insert into `names` the LLVM struct names of all the entirely opaque
+    /// structs (as created by `opaque_struct()`) which appear anywhere in this
+    /// `CompleteAbstractData`
+    pub(crate) fn collect_opaque_struct_names(&self, names: &mut BTreeSet<String>) {
+        match self {
+            Self::PublicValue { .. } => {},
+            Self::Secret { .. } => {},
+            Self::Array { element_type, .. } => element_type.collect_opaque_struct_names(names),
+            Self::Vector { element_type, .. } => element_type.collect_opaque_struct_names(names),
+            Self::TupleArray { elements } | Self::Struct { elements, .. } => {
+                for element in elements {
+                    element.collect_opaque_struct_names(names);
+                }
+            },
+            Self::PublicPointerTo { pointee, .. } => pointee.collect_opaque_struct_names(names),
+            Self::PublicPointerToFunction(_) => {},
+            Self::PublicPointerToHook(_) => {},
+            Self::PublicPointerToSelf => {},
+            Self::PublicPointerToParentOr(None) => {},
+            Self::PublicPointerToParentOr(Some(data)) => data.collect_opaque_struct_names(names),
+            Self::VoidOverride { data, .. } => data.collect_opaque_struct_names(names),
+            Self::PointerOverride { data, .. } => data.collect_opaque_struct_names(names),
+            Self::SameSizeOverride { data } => data.collect_opaque_struct_names(names),
+            Self::WithWatchpoint { name, data } => {
+                if let Some(llvm_struct_name) = name.strip_prefix(Self::OPAQUE_STRUCT_GUARD_PREFIX) {
+                    names.insert(llvm_struct_name.to_owned());
+                }
+                data.collect_opaque_struct_names(names);
+            },
+            Self::WithLengthBound { data, .. } => data.collect_opaque_struct_names(names),
+            Self::PublicCString { .. } => {},
+            Self::SecretCString { .. } => {},
+        }
+    }
+
+    /// Add every `AbstractValue` appearing anywhere in the
+    /// `CompleteAbstractData` to `values`
+    pub(crate) fn collect_abstract_values<'a>(&'a self, values: &mut Vec<&'a AbstractValue>) {
+        match self {
+            Self::PublicValue { value, .. } => values.push(value),
+            Self::Secret { .. } => {},
+            Self::Array { element_type, .. } => element_type.collect_abstract_values(values),
+            Self::Vector { element_type, .. } => element_type.collect_abstract_values(values),
+            Self::TupleArray { elements } | Self::Struct { elements, .. } => {
+                for element in elements {
+                    element.collect_abstract_values(values);
+                }
+            },
+            Self::PublicPointerTo { pointee, .. } => pointee.collect_abstract_values(values),
+            Self::PublicPointerToFunction(_) => {},
+            Self::PublicPointerToHook(_) => {},
+            Self::PublicPointerToSelf => {},
+            Self::PublicPointerToParentOr(None) => {},
+            Self::PublicPointerToParentOr(Some(data)) => data.collect_abstract_values(values),
+            Self::VoidOverride { data, .. } => data.collect_abstract_values(values),
+            Self::PointerOverride { data, .. } => data.collect_abstract_values(values),
+            Self::SameSizeOverride { data } => data.collect_abstract_values(values),
+            Self::WithWatchpoint { data, .. } => data.collect_abstract_values(values),
+            Self::WithLengthBound { data, .. } => data.collect_abstract_values(values),
+            Self::PublicCString { .. } => {},
+            Self::SecretCString { .. } => {},
+        }
+    }
+
+    /// Pretty-print the full structure of the `CompleteAbstractData` as an
+    /// indented tree, with one line per node. Unlike the `Display`, this
+    /// includes every field of every struct and the pointee of every pointer.
+    ///
+    /// Each line gives the bit offset of the node (relative to the start of
+    /// the enclosing allocation) and its size in bits, and values which are
+    /// entirely public or entirely secret are marked `P` or `S` respectively.
+    /// The pointee of a pointer is a separate allocation, so offsets restart
+    /// from 0 there. The elements of an array or vector all have the same
+    /// description, so it is only printed once.
+    pub fn to_tree_string(&self) -> String {
+        let mut lines = Vec::new();
+        self.tree_lines("", 0, self.size_in_bits(), 0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn tree_lines(&self, label: &str, offset_bits: u32, size_bits: u32, depth: usize, lines: &mut Vec<String>) {
+        let marker = match self {
+            Self::Secret { .. } | Self::SecretCString { .. } => "S",
+            Self::PublicValue { .. }
+            | Self::PublicCString { .. }
+            | Self::PublicPointerTo { .. }
+            | Self::PublicPointerToFunction(_)
+            | Self::PublicPointerToHook(_)
+            | Self::PublicPointerToSelf
+            | Self::PublicPointerToParentOr(_)
+            | Self::PointerOverride { .. }
+            => "P",
+            Self::Array { .. }
+            | Self::Vector { .. }
+            | Self::TupleArray { .. }
+            | Self::Struct { .. }
+            | Self::VoidOverride { .. }
+            | Self::SameSizeOverride { .. }
+            | Self::WithWatchpoint { .. }
+            | Self::WithLengthBound { .. }
+            => " ",
+        };
+        let description = match self {
+            Self::PublicPointerTo { maybe_null, align, .. } => {
+                let mut description = "a pointer".to_owned();
+                if *maybe_null {
+                    description.push_str(" (maybe NULL)");
+                }
+                if *align > 1 {
+                    description.push_str(&format!(" (aligned to {} bytes)", align));
+                }
+                description
+            },
+            Self::PublicPointerToParentOr(Some(_)) => "a pointer to this struct's parent, or else".to_owned(),
+            Self::VoidOverride { .. } => "a void override of".to_owned(),
+            Self::PointerOverride { .. } => "a pointer override".to_owned(),
+            Self::SameSizeOverride { .. } => "a same-size override of".to_owned(),
+            Self::WithWatchpoint { name, .. } => format!("a watchpoint named {} on", name),
+            Self::WithLengthBound { len_name, max_len, .. } => format!("a length bound ({} <= {}) on", len_name, max_len),
+            _ => self.to_string(),
+        };
+        lines.push(format!("{:indent$}{} {}{} (offset {}, {} bits)", "", marker, label, description, offset_bits, size_bits, indent = 2 * depth));
+        match self {
+            Self::TupleArray { elements } | Self::Struct { elements, .. } => {
+                for (i, element) in elements.iter().enumerate() {
+                    element.tree_lines(&format!("[{}] ", i), offset_bits + self.offset_in_bits(i), self.field_size_in_bits(i), depth + 1, lines);
+                }
+            },
+            Self::Array { element_type, num_elements } | Self::Vector { element_type, num_elements } => {
+                if *num_elements > 0 {
+                    element_type.tree_lines(&format!("[each of {}] ", num_elements), offset_bits + self.offset_in_bits(0), self.field_size_in_bits(0), depth + 1, lines);
+                }
+            },
+            Self::PublicPointerTo { pointee: data, .. }
+            | Self::PublicPointerToParentOr(Some(data))
+            | Self::PointerOverride { data, .. }
+            => data.tree_lines("* ", 0, data.size_in_bits(), depth + 1, lines),
+            Self::VoidOverride { data, .. }
+            | Self::SameSizeOverride { data }
+            | Self::WithWatchpoint { data, .. }
+            | Self::WithLengthBound { data, .. }
+            => data.tree_lines("", offset_bits, size_bits, depth + 1, lines),
+            _ => {},
         }
     }
 
@@ -415,10 +679,15 @@ impl fmt::Display for CompleteAbstractData {
             Self::PublicValue { bits, .. } => write!(f, "a {}-bit public value", bits),
             Self::Secret { bits, .. } => write!(f, "a {}-bit secret value", bits),
             Self::Array { num_elements, .. } => write!(f, "an array of {} elements", num_elements),
+            Self::Vector { num_elements, .. } => write!(f, "a vector of {} elements", num_elements),
+            Self::TupleArray { elements } => write!(f, "an array of {} individually-described elements", elements.len()),
             Self::Struct { name, elements } => write!(f, "a struct named {} with {} elements", name, elements.len()),
-            Self::PublicPointerTo { pointee, .. } => {
+            Self::PublicPointerTo { pointee, align, .. } => {
                 write!(f, "a pointer to ")?;
                 pointee.fmt(f)?;
+                if *align > 1 {
+                    write!(f, " (aligned to {} bytes)", align)?;
+                }
                 Ok(())
             },
             Self::PublicPointerToFunction(funcname) => write!(f, "a pointer to a function named {}", funcname),
@@ -448,6 +717,13 @@ impl fmt::Display for CompleteAbstractData {
                 write!(f, ", with a watchpoint named {}", name)?;
                 Ok(())
             },
+            Self::WithLengthBound { len_name, data, .. } => {
+                data.fmt(f)?;
+                write!(f, ", with the number of valid elements given by {}", len_name)?;
+                Ok(())
+            },
+            Self::PublicCString { max_len } => write!(f, "a public null-terminated string of at most {} bytes", max_len),
+            Self::SecretCString { len } => write!(f, "a secret null-terminated string of length {}", len),
         }
     }
 }
@@ -462,6 +738,7 @@ impl fmt::Display for CompleteAbstractData {
 /// [`default()`](struct.AbstractData.html#method.default), meaning to just use
 /// the default based on the LLVM type.
 #[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(try_from = "crate::abstractdata_serde::DataRepr"))]
 // we wrap the actual enum so that external users can't rely on the actual enum
 // variants, and only see the (nicer and more stable) function constructors
 pub struct AbstractData(pub(crate) UnderspecifiedAbstractData);
@@ -475,6 +752,11 @@ pub(crate) enum UnderspecifiedAbstractData {
     /// See [`AbstractData::default`](struct.AbstractData.html#method.default)
     Unspecified,
 
+    /// Like `Unspecified`, but making all integer contents secret.
+    ///
+    /// See [`AbstractData::secret_default`](struct.AbstractData.html#method.secret_default)
+    SecretDefault,
+
     /// Just fill with the appropriate number of unconstrained public bytes based
     /// on the LLVM type
     Unconstrained,
@@ -492,6 +774,10 @@ pub(crate) enum UnderspecifiedAbstractData {
         /// If `false`, the pointer must point to the pointee; if `true`,
         /// it may either point to the pointee or be `NULL`
         maybe_null: bool,
+        /// Required alignment of the pointee's address, in bytes (a power of
+        /// two). `1` imposes no requirement beyond what `State::allocate()`
+        /// provides.
+        align: u64,
     },
 
     /// Like `CompleteAbstractData::PublicPointerToParentOr`, but the `Or` part
@@ -503,10 +789,23 @@ pub(crate) enum UnderspecifiedAbstractData {
     /// an array with underspecified elements
     Array { element_type: Box<AbstractData>, num_elements: usize },
 
+    /// a vector with underspecified elements
+    Vector { element_type: Box<AbstractData>, num_elements: usize },
+
+    /// an array with a separate (possibly underspecified) description for
+    /// each element
+    TupleArray { elements: Vec<AbstractData> },
+
     /// a struct with underspecified fields
     /// (for instance, some unspecified and some fully-specified fields)
     Struct { name: String, elements: Vec<AbstractData> },
 
+    /// a struct where only the fields at the given indices are specified, and
+    /// the remaining fields are `Unspecified`
+    ///
+    /// See [`AbstractData::struct_override`](struct.AbstractData.html#method.struct_override)
+    StructOverride { name: String, overrides: BTreeMap<usize, AbstractData> },
+
     /// Use the default structure for the given LLVM struct name.
     ///
     /// If we are not in the middle of an override, this struct name must match
@@ -537,6 +836,12 @@ pub(crate) enum UnderspecifiedAbstractData {
     /// with the given `name` to the `State` covering the memory region it
     /// occupies.
     WithWatchpoint { name: String, data: Box<AbstractData> },
+
+    /// A (public) pointer to an array of `max_len` underspecified elements,
+    /// of which the number of valid elements is the value named `len_name`.
+    ///
+    /// See [`AbstractData::pub_pointer_to_array_of_len`](struct.AbstractData.html#method.pub_pointer_to_array_of_len)
+    PublicPointerToArrayOfLen { element_type: Box<AbstractData>, len_name: String, max_len: usize },
 }
 
 impl AbstractData {
@@ -560,6 +865,11 @@ impl AbstractData {
         Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::pub_i64(value)))
     }
 
+    /// a 128-bit public value
+    pub fn pub_i128(value: AbstractValue) -> Self {
+        Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::pub_i128(value)))
+    }
+
     /// a public value with the given number of bits
     pub fn pub_integer(bits: u32, value: AbstractValue) -> Self {
         Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::pub_integer(bits, value)))
@@ -585,6 +895,11 @@ impl AbstractData {
         Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::sec_i64()))
     }
 
+    /// a 128-bit secret value
+    pub fn sec_i128() -> Self {
+        Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::sec_i128()))
+    }
+
     /// a secret value with the given number of bits
     pub fn sec_integer(bits: u32) -> Self {
         Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::sec_integer(bits)))
@@ -592,12 +907,23 @@ impl AbstractData {
 
     /// A (public) pointer to something - another value, an array, etc
     pub fn pub_pointer_to(data: Self) -> Self {
-        Self(UnderspecifiedAbstractData::PublicPointerTo { pointee: Box::new(data), maybe_null: false })
+        Self(UnderspecifiedAbstractData::PublicPointerTo { pointee: Box::new(data), maybe_null: false, align: 1 })
     }
 
     /// A (public) pointer which may either point to the given data or be `NULL`
     pub fn pub_maybe_null_pointer_to(data: Self) -> Self {
-        Self(UnderspecifiedAbstractData::PublicPointerTo { pointee: Box::new(data), maybe_null: true })
+        Self(UnderspecifiedAbstractData::PublicPointerTo { pointee: Box::new(data), maybe_null: true, align: 1 })
+    }
+
+    /// A (public) pointer to the given data, where the address of the data is
+    /// a multiple of `align` bytes. This is useful for code which uses the low
+    /// bits of pointers (tagged pointers), or which requires aligned memory
+    /// (e.g., for SIMD loads).
+    ///
+    /// `align` must be a power of two.
+    pub fn aligned_pointer_to(data: Self, align: u64) -> Self {
+        assert!(align.is_power_of_two(), "aligned_pointer_to: alignment must be a power of two, but got {}", align);
+        Self(UnderspecifiedAbstractData::PublicPointerTo { pointee: Box::new(data), maybe_null: false, align })
     }
 
     /// a (public) pointer to the LLVM `Function` with the given name
@@ -655,6 +981,29 @@ impl AbstractData {
         Self(UnderspecifiedAbstractData::Array { element_type: Box::new(element_type), num_elements })
     }
 
+    /// A (first-class) vector of values, i.e., an LLVM `VectorType`.
+    ///
+    /// `array_of()` may also be used for vectors, but the resulting
+    /// `CompleteAbstractData` is a vector either way: vectors are never
+    /// collapsed into arrays.
+    pub fn vector_of(element_type: Self, num_elements: usize) -> Self {
+        Self(UnderspecifiedAbstractData::Vector { element_type: Box::new(element_type), num_elements })
+    }
+
+    /// A (first-class) array of values, with a separate description for each
+    /// element, laid out contiguously just like `array_of()`. This is useful
+    /// when the elements have the same LLVM type but should be initialized
+    /// differently; e.g., for an `argv`-style `char**`, a
+    /// `pub_pointer_to(tuple_array(...))` with a differently-sized string for
+    /// each element.
+    ///
+    /// The number of elements must match the LLVM array type (if it has a
+    /// nonzero length), and the elements must all have the same size, or the
+    /// analysis will panic with a type mismatch.
+    pub fn tuple_array(elements: impl IntoIterator<Item = Self>) -> Self {
+        Self(UnderspecifiedAbstractData::TupleArray { elements: elements.into_iter().collect() })
+    }
+
     /// A (first-class) structure of values
     ///
     /// (`_struct` used instead of `struct` to avoid collision with the Rust keyword)
@@ -662,6 +1011,91 @@ impl AbstractData {
         Self(UnderspecifiedAbstractData::Struct { name: name.into(), elements: elements.into_iter().collect() })
     }
 
+    /// A (first-class) structure of values, where only some of the fields are
+    /// specified, by their (0-based) index. The remaining fields use the
+    /// default structure based on their LLVM types, as in `default()`.
+    ///
+    /// For instance, `struct_override("foo", vec![(7, AbstractData::sec_i32())])`
+    /// describes a struct whose field 7 is a secret `i32` and whose other
+    /// fields are all defaults.
+    ///
+    /// Since the number and types of the remaining fields come from the LLVM
+    /// type, this can't be used where no LLVM type is available (e.g., inside
+    /// a `void_override()` with `llvm_struct_name == None`, or for an entirely
+    /// opaque struct). If the same index is given more than once, the last
+    /// one is used.
+    pub fn struct_override(name: impl Into<String>, overrides: impl IntoIterator<Item = (usize, Self)>) -> Self {
+        Self(UnderspecifiedAbstractData::StructOverride { name: name.into(), overrides: overrides.into_iter().collect() })
+    }
+
+    /// An explicit structure of values matching the definition of the named
+    /// struct type in the `Project`, with all integer fields public and
+    /// unconstrained. This is intended as a starting point which can then be
+    /// tweaked with [`with_field`](#method.with_field), e.g. to mark specific
+    /// fields secret.
+    ///
+    /// `struct_name` may be either the LLVM struct name (e.g.,
+    /// `"struct.foo"`) or the source-level name (e.g., `"foo"`), matched as
+    /// for the keys of `StructDescriptions`.
+    ///
+    /// The structure is derived from the LLVM struct type, as the LLVM IR we
+    /// parse doesn't expose the debug-info (DWARF) type graph. So fields are
+    /// identified by index rather than name, a union appears as whatever
+    /// single member type LLVM chose for it, and bitfields are grouped into
+    /// the integers LLVM stores them in. Nested structs and arrays are
+    /// expanded in place; pointers, and any types other than integers, are
+    /// left as `default()`. A constructor that walks the DWARF type instead
+    /// (addressing fields by their source names) would need debug-info type
+    /// support in `llvm-ir`, and isn't provided.
+    pub fn from_struct_type(proj: &Project, struct_name: &str) -> Self {
+        let llvm_name = find_struct_type(proj, struct_name);
+        match proj.get_named_struct_def(&llvm_name) {
+            Ok((NamedStructDef::Defined(ty), _)) => Self::from_llvm_type(proj, &llvm_name, ty),
+            Ok((NamedStructDef::Opaque, _)) => panic!("AbstractData::from_struct_type: struct {:?} is opaque in the Project", llvm_name),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// `name`: name to give the `AbstractData` if `ty` is a struct
+    fn from_llvm_type(proj: &Project, name: &str, ty: &Type) -> Self {
+        match ty {
+            Type::IntegerType { bits } => Self::pub_integer(*bits, AbstractValue::Unconstrained),
+            Type::ArrayType { element_type, num_elements } =>
+                Self::array_of(Self::from_llvm_type(proj, "unspecified_struct", element_type), *num_elements),
+            Type::StructType { element_types, .. } => Self::_struct(name, element_types
+                .iter()
+                .map(|element_type| Self::from_llvm_type(proj, "unspecified_struct", element_type))
+            ),
+            Type::NamedStructType { name } => match proj.get_named_struct_def(name) {
+                Ok((NamedStructDef::Defined(ty), _)) => Self::from_llvm_type(proj, name, ty),
+                _ => Self::default(),
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Replace the field at the given `path` of (0-based) field indices with
+    /// `data`. For instance, `with_field(&[2, 0], AbstractData::sec_i32())`
+    /// makes field 0 of the struct in field 2 a secret `i32`.
+    ///
+    /// Panics if the path doesn't lead through `AbstractData`s created with
+    /// `_struct()` (or [`from_struct_type`](#method.from_struct_type)).
+    pub fn with_field(mut self, path: &[usize], data: Self) -> Self {
+        match path.split_first() {
+            None => data,
+            Some((&idx, rest)) => match &mut self.0 {
+                UnderspecifiedAbstractData::Struct { name, elements } => {
+                    let num_elements = elements.len();
+                    let element = elements.get_mut(idx)
+                        .unwrap_or_else(|| panic!("AbstractData::with_field: index {} out of range for struct {:?} with {} elements", idx, name, num_elements));
+                    *element = std::mem::replace(element, Self::default()).with_field(rest, data);
+                    self
+                },
+                _ => panic!("AbstractData::with_field: expected a struct, but have {}", self),
+            },
+        }
+    }
+
     /// Just use the default structure based on the LLVM type and/or the `StructDescriptions`.
     /// (The `StructDescriptions` override the LLVM type when they apply.)
     ///
@@ -669,24 +1103,44 @@ impl AbstractData {
     ///
     /// - for LLVM integer type: public unconstrained value of the appropriate size
     /// - for LLVM pointer type (except function pointer): public concrete pointer value to allocated memory, depending on pointer type:
-    ///   - pointee is an integer type: pointer to allocated array of `DEFAULT_ARRAY_LENGTH` pointees
-    ///       (e.g., default for `char*` is pointer to array of 1024 chars)
-    ///   - pointee is an array type with 0 elements: pointer to allocated array of `DEFAULT_ARRAY_LENGTH` elements
+    ///   - pointee is an integer type: pointer to allocated array of `default_array_length` pointees
+    ///       (e.g., default for `char*` is pointer to array of 1024 chars, if
+    ///       `default_array_length` in `PitchforkConfig` is left at its default)
+    ///   - pointee is an array type with 0 elements: pointer to allocated array of `default_array_length` elements
     ///   - pointee is any other type: pointer to one of that other type
     ///   - (then in any case, apply these rules recursively to each pointee type)
     /// - for LLVM function pointer type: concrete function pointer value which, when called, will raise an error
-    /// - for LLVM vector or array type: array of the appropriate length, containing public values
+    /// - for LLVM vector or array type: vector or array (respectively) of the appropriate length, containing public values
     ///   - (then apply these rules recursively to each element)
     /// - for LLVM structure type:
     ///   - if this struct is one of those named in the `StructDescriptions`, then use the appropriate struct description
     ///   - if the structure type is entirely opaque (no definition anywhere in the `Project`), then allocate
-    ///       `OPAQUE_STRUCT_SIZE_BYTES` unconstrained bytes for it and assume that's enough
-    ///       (probably most of that memory will go unused, but that's fine)
+    ///       `opaque_struct_size_bytes` unconstrained bytes for it and assume that's enough
+    ///       (probably most of that memory will go unused, but that's fine).
+    ///       A watchpoint is placed just past the end of those bytes, so that
+    ///       accesses beyond them are reported in the log.
+    ///       `opaque_struct_size_bytes` is a setting in `PitchforkConfig`.
     ///   - else, apply these rules recursively to each field
     pub fn default() -> Self {
         Self(UnderspecifiedAbstractData::Unspecified)
     }
 
+    /// Like `default()`, but with all integer values secret rather than public.
+    ///
+    /// The structure is determined exactly as for `default()`: pointers are
+    /// still public pointers to allocated memory, function pointers still
+    /// point to `hook_uninitialized_function_pointer`, and structs named in the
+    /// `StructDescriptions` still use their struct description. Only the
+    /// integer values which `default()` would make public and unconstrained
+    /// (including the contents of entirely opaque structs) are made secret.
+    ///
+    /// For instance, for a `struct foo*`, this gives a public pointer to a
+    /// `struct foo` whose integer fields are all secret, and whose pointer
+    /// fields are public pointers to all-secret data.
+    pub fn secret_default() -> Self {
+        Self(UnderspecifiedAbstractData::SecretDefault)
+    }
+
     /// Use the default structure for the given LLVM struct name.
     ///
     /// If we are not in the middle of an override, this struct name must match
@@ -730,6 +1184,10 @@ impl AbstractData {
     /// `AbstractData`, and for sanity typechecking. Otherwise, the
     /// `AbstractData` must be fully-specified, and no sanity typechecking will
     /// be performed (the `AbstractData` will be assumed correct).
+    ///
+    /// Used directly for a function parameter (rather than behind a pointer),
+    /// this just relaxes the type checks on the parameter, as for
+    /// `same_size_override()`.
     pub fn void_override(llvm_struct_name: Option<&str>, data: AbstractData) -> Self {
         Self(UnderspecifiedAbstractData::VoidOverride { llvm_struct_name: llvm_struct_name.map(Into::into), data: Box::new(data) })
     }
@@ -768,9 +1226,95 @@ impl AbstractData {
     /// Use the given `data`, but also (during initialization) add a watchpoint
     /// with the given `name` to the `State` covering the memory region it
     /// occupies.
+    ///
+    /// If the data is at a non-constant address (e.g., behind a maybe-null
+    /// pointer) which the constraints don't pin down to a single value, no
+    /// watchpoint is added, and a warning is logged. Likewise if this is used
+    /// directly for a function parameter (rather than behind a pointer), as
+    /// the parameter value isn't in memory.
     pub fn with_watchpoint(name: impl Into<String>, data: Self) -> Self {
         Self(UnderspecifiedAbstractData::WithWatchpoint { name: name.into(), data: Box::new(data) })
     }
+
+    /// A (public) pointer to an array whose number of valid elements is given
+    /// by another value, as in the common C idiom `f(buf, len)`. Space is
+    /// allocated for `max_len` elements, and the length value is constrained
+    /// to be at most `max_len`, rather than being entirely unconstrained.
+    ///
+    /// `len_name` is the name of the length value: either a name given with
+    /// `AbstractValue::named()` (e.g., in the description of the length
+    /// parameter), or else the (LLVM) name of a parameter of the function
+    /// being analyzed. The length may be described before or after this
+    /// pointer. If the length is a parameter with the default (unconstrained)
+    /// description, this is what constrains it.
+    ///
+    /// Note that accesses past the length (but within the `max_len` elements)
+    /// are not reported. To catch accesses past the `max_len` elements, see
+    /// `bounds_check_pointees` in `PitchforkConfig`.
+    pub fn pub_pointer_to_array_of_len(element_type: Self, len_name: impl Into<String>, max_len: usize) -> Self {
+        Self(UnderspecifiedAbstractData::PublicPointerToArrayOfLen { element_type: Box::new(element_type), len_name: len_name.into(), max_len })
+    }
+
+    /// A null-terminated C string of public bytes, occupying `max_len` bytes
+    /// in total (including the null terminator).
+    ///
+    /// The position of the first null byte is symbolic: it may be anywhere in
+    /// the `max_len` bytes, and all bytes before it are constrained to be
+    /// nonzero. This matches how `strlen`-style code walks the buffer.
+    ///
+    /// This describes the string itself, so you probably want to use it as the
+    /// pointee of a `pub_pointer_to()`, e.g. for a `char*` parameter.
+    pub fn pub_cstring(max_len: usize) -> Self {
+        Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::pub_cstring(max_len)))
+    }
+
+    /// A null-terminated C string whose contents are secret but whose length
+    /// is public: exactly `len` secret bytes followed by a public null byte,
+    /// so `len + 1` bytes in total.
+    ///
+    /// Note that the secret bytes are not constrained to be nonzero, as secret
+    /// data has no value in Pitchfork's model; in any case, code which walks
+    /// the string looking for the terminator is branching on secret data.
+    ///
+    /// Like `pub_cstring()`, this describes the string itself, so you probably
+    /// want to use it as the pointee of a `pub_pointer_to()`.
+    pub fn sec_cstring(len: usize) -> Self {
+        Self(UnderspecifiedAbstractData::Complete(CompleteAbstractData::sec_cstring(len)))
+    }
+
+    /// `total_size_bytes` bytes of data, of which the `secret_len_bytes`
+    /// bytes starting at byte offset `secret_offset_bytes` are secret, and the
+    /// rest are public and unconstrained. This is useful when you know the
+    /// byte layout of some data (e.g., a large context struct containing a
+    /// key) but don't want to describe each of its fields.
+    ///
+    /// This is a `same_size_override()`, so it can describe data of any LLVM
+    /// type, as long as that type is `total_size_bytes` bytes in size. Like
+    /// `pub_cstring()`, it describes the data itself, so you probably want to
+    /// use it as the pointee of a `pub_pointer_to()`. Note that any pointers
+    /// within the data will be public and unconstrained, not pointing to
+    /// allocated memory.
+    ///
+    /// Panics if the secret range extends past the end of the data.
+    pub fn mostly_public_with_secret_range(total_size_bytes: usize, secret_offset_bytes: usize, secret_len_bytes: usize) -> Self {
+        assert!(
+            secret_offset_bytes + secret_len_bytes <= total_size_bytes,
+            "mostly_public_with_secret_range: secret range of {} bytes at offset {} extends past the end of the {} bytes of data",
+            secret_len_bytes, secret_offset_bytes, total_size_bytes,
+        );
+        let public_bytes = |num_bytes| Self::array_of(Self::pub_i8(AbstractValue::Unconstrained), num_bytes);
+        let pieces = vec![
+            public_bytes(secret_offset_bytes),
+            Self::array_of(Self::sec_i8(), secret_len_bytes),
+            public_bytes(total_size_bytes - secret_offset_bytes - secret_len_bytes),
+        ];
+        // leave out empty pieces, since arrays of 0 elements can't be initialized
+        let pieces = pieces.into_iter().filter(|piece| match &piece.0 {
+            UnderspecifiedAbstractData::Array { num_elements, .. } => *num_elements > 0,
+            _ => true,
+        });
+        Self::same_size_override(Self::_struct("mostly_public_with_secret_range", pieces))
+    }
 }
 
 /// This `Display` is not meant to completely replace the derived `Debug`
@@ -789,6 +1333,7 @@ impl fmt::Display for UnderspecifiedAbstractData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             UnderspecifiedAbstractData::Unspecified => write!(f, "an unspecified value"),
+            UnderspecifiedAbstractData::SecretDefault => write!(f, "an unspecified value with secret contents"),
             UnderspecifiedAbstractData::Unconstrained => write!(f, "an unconstrained value"),
             UnderspecifiedAbstractData::Secret => write!(f, "a secret value"),
             UnderspecifiedAbstractData::Complete(cad) => {
@@ -796,14 +1341,20 @@ impl fmt::Display for UnderspecifiedAbstractData {
                 cad.fmt(f)?;
                 Ok(())
             },
-            UnderspecifiedAbstractData::PublicPointerTo { pointee, .. } => {
+            UnderspecifiedAbstractData::PublicPointerTo { pointee, align, .. } => {
                 write!(f, "a pointer to ")?;
                 pointee.fmt(f)?;
+                if *align > 1 {
+                    write!(f, " (aligned to {} bytes)", align)?;
+                }
                 Ok(())
             },
             UnderspecifiedAbstractData::PublicPointerToParentOr(_) => write!(f, "a public pointer to parent, with a backup"),
             UnderspecifiedAbstractData::Array { num_elements, .. } => write!(f, "an array of {} elements", num_elements),
+            UnderspecifiedAbstractData::Vector { num_elements, .. } => write!(f, "a vector of {} elements", num_elements),
+            UnderspecifiedAbstractData::TupleArray { elements } => write!(f, "an array of {} individually-described elements", elements.len()),
             UnderspecifiedAbstractData::Struct { name, elements } => write!(f, "a struct named {} with {} elements", name, elements.len()),
+            UnderspecifiedAbstractData::StructOverride { name, overrides } => write!(f, "a struct named {} with overrides for elements {:?}", name, overrides.keys().collect::<Vec<_>>()),
             UnderspecifiedAbstractData::DefaultForLLVMStructName { llvm_struct_name } => write!(f, "the default for the LLVM struct {}", llvm_struct_name),
             UnderspecifiedAbstractData::VoidOverride { data, .. } => {
                 write!(f, "a void override with data ")?;
@@ -825,17 +1376,135 @@ impl fmt::Display for UnderspecifiedAbstractData {
                 write!(f, " with a watchpoint named {}", name)?;
                 Ok(())
             },
+            UnderspecifiedAbstractData::PublicPointerToArrayOfLen { len_name, max_len, .. } => write!(f, "a pointer to an array of at most {} elements, with the number of valid elements given by {}", max_len, len_name),
         }
     }
 }
 
 /// A map from struct name to an `AbstractData` description of the struct
+///
+/// To describe only some of a struct's fields, use `AbstractData::struct_override()`.
 pub type StructDescriptions = HashMap<String, AbstractData>;
 
+/// Check that all of the struct names in `sd` refer to named struct types in
+/// the `Project`, panicking (with a suggestion of the closest actual struct
+/// name) if not.
+///
+/// Struct names in `sd` don't need to be exactly the LLVM struct names. A name
+/// which doesn't exactly match any LLVM struct name is also matched against
+/// the source-level names of the LLVM structs: i.e., without the `struct.`,
+/// `class.`, or `union.` prefix, without the numeric suffix LLVM adds when
+/// linking modules, without libc++ or libstdc++ inline namespaces such as
+/// `__1::`, and ignoring whitespace. So for instance,
+/// `std::vector<int, std::allocator<int>>` can be used for
+/// `class.std::__1::vector<int, std::__1::allocator<int> >`. (Default template
+/// arguments must still be written out.) If a source-level name matches
+/// several LLVM structs, the description applies to all of them.
+///
+/// Returns the `StructDescriptions` keyed by LLVM struct names.
+pub(crate) fn resolve_struct_description_names<'s>(sd: &'s StructDescriptions, proj: &Project) -> Cow<'s, StructDescriptions> {
+    let proj_names: HashSet<&String> = proj.all_named_struct_types().map(|(name, _, _)| name).collect();
+    if sd.keys().all(|name| proj_names.contains(name)) {
+        return Cow::Borrowed(sd);
+    }
+    let mut resolved = StructDescriptions::new();
+    for (name, data) in sd {
+        if proj_names.contains(name) {
+            resolved.insert(name.clone(), data.clone());
+            continue;
+        }
+        let source_name = source_struct_name(name);
+        let matches: Vec<&String> = proj_names.iter()
+            .filter(|proj_name| source_struct_name(proj_name) == source_name)
+            .copied()
+            .collect();
+        if matches.is_empty() {
+            let closest = proj_names.iter().min_by_key(|proj_name| edit_distance(&source_struct_name(proj_name), &source_name));
+            match closest {
+                Some(closest) => panic!("Struct name {:?} appears in StructDescriptions but not found in the Project; did you mean {:?}?", name, closest),
+                None => panic!("Struct name {:?} appears in StructDescriptions but the Project has no named struct types", name),
+            }
+        }
+        for llvm_name in matches {
+            if sd.contains_key(llvm_name) {
+                // an exact match elsewhere in `sd` takes precedence
+                continue;
+            }
+            if resolved.insert(llvm_name.clone(), data.clone()).is_some() {
+                panic!("Multiple entries in StructDescriptions refer to the LLVM struct {:?}", llvm_name);
+            }
+        }
+    }
+    Cow::Owned(resolved)
+}
+
+/// Find the LLVM name of the struct type with the given name, which may be
+/// either the LLVM name or the source-level name (see `source_struct_name()`)
+fn find_struct_type(proj: &Project, struct_name: &str) -> String {
+    let mut proj_names: Vec<&String> = proj.all_named_struct_types().map(|(name, _, _)| name).collect();
+    proj_names.sort();
+    proj_names.dedup();
+    if let Some(name) = proj_names.iter().find(|proj_name| proj_name.as_str() == struct_name) {
+        return (*name).clone();
+    }
+    let source_name = source_struct_name(struct_name);
+    let matches: Vec<&String> = proj_names.iter()
+        .filter(|proj_name| source_struct_name(proj_name) == source_name)
+        .copied()
+        .collect();
+    match matches.as_slice() {
+        [] => match proj_names.iter().min_by_key(|proj_name| edit_distance(&source_struct_name(proj_name), &source_name)) {
+            Some(closest) => panic!("Struct name {:?} not found in the Project; did you mean {:?}?", struct_name, closest),
+            None => panic!("Struct name {:?} not found: the Project has no named struct types", struct_name),
+        },
+        [name] => (*name).clone(),
+        [name, ..] => {
+            warn!("Struct name {:?} matches multiple LLVM structs {:?}; using {:?}", struct_name, matches, name);
+            (*name).clone()
+        },
+    }
+}
+
+/// The source-level name for the given LLVM struct name, as described in
+/// the docs for `resolve_struct_description_names()`
+fn source_struct_name(llvm_struct_name: &str) -> String {
+    let name = ["struct.", "class.", "union."].iter()
+        .find_map(|prefix| llvm_struct_name.strip_prefix(prefix))
+        .unwrap_or(llvm_struct_name);
+    let name = match name.rfind('.') {
+        Some(idx) if idx + 1 < name.len() && name[idx + 1 ..].chars().all(|c| c.is_ascii_digit()) => &name[.. idx],
+        _ => name,
+    };
+    name.replace("__1::", "")
+        .replace("__cxx11::", "")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0 ..= b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut cur_row = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev_row[j] + if a_char == *b_char { 0 } else { 1 };
+            cur_row.push(substitution.min(prev_row[j + 1] + 1).min(cur_row[j] + 1));
+        }
+        prev_row = cur_row;
+    }
+    prev_row[b.len()]
+}
+
 impl AbstractData {
+    /// Default value of the `default_array_length` setting in `PitchforkConfig`
     pub const DEFAULT_ARRAY_LENGTH: usize = 1024;
     pub const POINTER_SIZE_BITS: u32 = CompleteAbstractData::POINTER_SIZE_BITS;
+    /// Default value of the `opaque_struct_size_bytes` setting in `PitchforkConfig`
     pub const OPAQUE_STRUCT_SIZE_BYTES: usize = 1024 * 64;
+    /// Default value of the `max_struct_nesting_depth` setting in `PitchforkConfig`
+    pub const MAX_STRUCT_NESTING_DEPTH: usize = 128;
 
     /// Fill in the default `CompleteAbstractData` for any parts of the
     /// `AbstractData` which are marked `default()`, using the information in the
@@ -843,13 +1512,132 @@ impl AbstractData {
     /// type.
     ///
     /// For more information, see [`AbstractData::default()`](struct.AbstractData.html#method.default).
-    pub(crate) fn to_complete(self, ty: &Type, proj: &Project, sd: &StructDescriptions) -> CompleteAbstractData {
-        self.0.to_complete(ty, proj, sd)
+    pub(crate) fn to_complete(self, ty: &Type, proj: &Project, sd: &StructDescriptions, pitchfork_config: &PitchforkConfig) -> CompleteAbstractData {
+        self.0.to_complete(ty, proj, sd, pitchfork_config)
     }
 
     fn to_complete_rec<'a>(self, ty: Option<&'a Type>, ctx: ToCompleteContext<'a, '_>) -> CompleteAbstractData {
         self.0.to_complete_rec(ty, ctx)
     }
+
+    /// Add every `AbstractValue` appearing anywhere in the `AbstractData` to
+    /// `values`
+    pub(crate) fn collect_abstract_values<'a>(&'a self, values: &mut Vec<&'a AbstractValue>) {
+        match &self.0 {
+            UnderspecifiedAbstractData::Unspecified => {},
+            UnderspecifiedAbstractData::SecretDefault => {},
+            UnderspecifiedAbstractData::Unconstrained => {},
+            UnderspecifiedAbstractData::Secret => {},
+            UnderspecifiedAbstractData::Complete(cad) => cad.collect_abstract_values(values),
+            UnderspecifiedAbstractData::PublicPointerTo { pointee, .. } => pointee.collect_abstract_values(values),
+            UnderspecifiedAbstractData::PublicPointerToParentOr(data) => data.collect_abstract_values(values),
+            UnderspecifiedAbstractData::Array { element_type, .. } => element_type.collect_abstract_values(values),
+            UnderspecifiedAbstractData::Vector { element_type, .. } => element_type.collect_abstract_values(values),
+            UnderspecifiedAbstractData::TupleArray { elements } | UnderspecifiedAbstractData::Struct { elements, .. } => {
+                for element in elements {
+                    element.collect_abstract_values(values);
+                }
+            },
+            UnderspecifiedAbstractData::StructOverride { overrides, .. } => {
+                for data in overrides.values() {
+                    data.collect_abstract_values(values);
+                }
+            },
+            UnderspecifiedAbstractData::DefaultForLLVMStructName { .. } => {},
+            UnderspecifiedAbstractData::VoidOverride { data, .. } => data.collect_abstract_values(values),
+            UnderspecifiedAbstractData::PointerOverride { data, .. } => data.collect_abstract_values(values),
+            UnderspecifiedAbstractData::SameSizeOverride { data } => data.collect_abstract_values(values),
+            UnderspecifiedAbstractData::WithWatchpoint { data, .. } => data.collect_abstract_values(values),
+            UnderspecifiedAbstractData::PublicPointerToArrayOfLen { element_type, .. } => element_type.collect_abstract_values(values),
+        }
+    }
+}
+
+/// Check the `AbstractValue::Named` values in the given `AbstractData`s for
+/// consistency, before any of them are allocated. Every name referenced by
+/// `EqualTo`, `SignedLessThan`, etc must be defined by some
+/// `AbstractValue::Named`, and the names must not depend on each other in a
+/// cycle (e.g., `a` equal to `b` and `b` signed-less-than `a`), which would
+/// give an unsatisfiable or meaningless set of constraints.
+///
+/// Returns a description of the problem, if there is one.
+pub(crate) fn check_named_values<'a>(datas: impl IntoIterator<Item = &'a AbstractData>) -> Result<(), String> {
+    let mut values = Vec::new();
+    for data in datas {
+        data.collect_abstract_values(&mut values);
+    }
+    // map from each defined name to the names its value is constrained in terms of
+    let mut deps: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut referenced: BTreeSet<&str> = BTreeSet::new();
+    for value in values {
+        add_name_dependencies(value, &mut Vec::new(), &mut deps, &mut referenced);
+    }
+    let undefined: Vec<&str> = referenced.into_iter().filter(|name| !deps.contains_key(name)).collect();
+    if !undefined.is_empty() {
+        return Err(format!("these names are referenced (by `EqualTo`, `SignedLessThan`, etc) but never defined with `AbstractValue::Named`: {:?}", undefined));
+    }
+    let mut finished = HashSet::new();
+    for &name in deps.keys() {
+        if let Some(cycle) = find_name_cycle(name, &deps, &mut Vec::new(), &mut finished) {
+            return Err(format!("the named values depend on each other in a cycle: {}", cycle.join(" -> ")));
+        }
+    }
+    Ok(())
+}
+
+/// `definers`: the names of the `AbstractValue::Named`s enclosing `value`,
+/// which all depend on any name `value` refers to
+fn add_name_dependencies<'a>(value: &'a AbstractValue, definers: &mut Vec<&'a str>, deps: &mut BTreeMap<&'a str, BTreeSet<&'a str>>, referenced: &mut BTreeSet<&'a str>) {
+    match value {
+        AbstractValue::Named { name, value } => {
+            deps.entry(name).or_default();
+            definers.push(name);
+            add_name_dependencies(value, definers, deps, referenced);
+            definers.pop();
+        },
+        AbstractValue::EqualTo(name)
+        | AbstractValue::SignedLessThan(name)
+        | AbstractValue::SignedGreaterThan(name)
+        | AbstractValue::UnsignedLessThan(name)
+        | AbstractValue::UnsignedGreaterThan(name)
+        => {
+            referenced.insert(name);
+            for &definer in definers.iter() {
+                deps.entry(definer).or_default().insert(name);
+            }
+        },
+        AbstractValue::All(values) => {
+            for value in values {
+                add_name_dependencies(value, definers, deps, referenced);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Depth-first search for a cycle in `deps` reachable from `name`. `path` is
+/// the current search path, and `finished` holds names already known not to
+/// lead to a cycle.
+///
+/// Returns the cycle, starting and ending with the same name, if one is found.
+fn find_name_cycle<'a>(name: &'a str, deps: &BTreeMap<&'a str, BTreeSet<&'a str>>, path: &mut Vec<&'a str>, finished: &mut HashSet<&'a str>) -> Option<Vec<&'a str>> {
+    if finished.contains(name) {
+        return None;
+    }
+    if let Some(pos) = path.iter().position(|&n| n == name) {
+        let mut cycle = path[pos ..].to_vec();
+        cycle.push(name);
+        return Some(cycle);
+    }
+    path.push(name);
+    for &dep in deps.get(name).into_iter().flatten() {
+        if let Some(cycle) = find_name_cycle(dep, deps, path, finished) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    finished.insert(name);
+    None
 }
 
 /// Struct containing information we need to carry around during recursive calls to to_complete_rec()
@@ -860,6 +1648,10 @@ struct ToCompleteContext<'a, 'p> {
     /// `StructDescriptions` which we are working with
     sd: &'p StructDescriptions,
 
+    /// `PitchforkConfig` which we are working with; we consult it for settings
+    /// such as `default_array_length`
+    pitchfork_config: &'p PitchforkConfig,
+
     /// set of struct names we are within which were given
     /// `UnderspecifiedAbstractData::Unspecified` (whether they appear in `sd` or
     /// not). We keep track of these only so we can detect infinite recursion.
@@ -872,10 +1664,11 @@ struct ToCompleteContext<'a, 'p> {
 }
 
 impl<'a, 'p> ToCompleteContext<'a, 'p> {
-    fn new(proj: &'p Project, sd: &'p StructDescriptions) -> Self {
+    fn new(proj: &'p Project, sd: &'p StructDescriptions, pitchfork_config: &'p PitchforkConfig) -> Self {
         Self {
             proj,
             sd,
+            pitchfork_config,
             unspecified_named_structs: HashSet::new(),
             within_structs: Vec::new(),
         }
@@ -887,16 +1680,70 @@ impl<'a, 'p> ToCompleteContext<'a, 'p> {
             eprintln!("within struct {}:", w);
         }
     }
+
+    /// Record that we are now within the struct `name`, panicking if this
+    /// exceeds the `max_struct_nesting_depth` setting
+    fn enter_struct(&mut self, name: &str) {
+        self.within_structs.push(name.to_owned());
+        let max_depth = self.pitchfork_config.max_struct_nesting_depth;
+        if self.within_structs.len() > max_depth {
+            self.error_backtrace();
+            panic!(
+                "Exceeded the maximum struct nesting depth of {} while expanding AbstractData ({}). If these structs really are nested this deeply, increase `max_struct_nesting_depth` in the `PitchforkConfig`; otherwise, describe some of them explicitly in the `StructDescriptions`",
+                max_depth,
+                self.within_structs_description(),
+            );
+        }
+    }
+
+    /// The structs we are within, as a single line for including in panic messages
+    fn within_structs_description(&self) -> String {
+        if self.within_structs.is_empty() {
+            "not within any struct".into()
+        } else {
+            format!("within struct {}", self.within_structs.join(" > "))
+        }
+    }
+}
+
+/// For error messages about a struct whose number of elements doesn't match
+/// its LLVM type: a table with one line per element, showing the LLVM
+/// element type side-by-side with the provided description of that element
+pub(crate) fn struct_elements_side_by_side(llvm_types: &[String], descriptions: &[String]) -> String {
+    let missing = "(missing)";
+    let type_width = llvm_types.iter()
+        .map(String::len)
+        .chain(std::iter::once(missing.len()))
+        .max()
+        .unwrap_or(0);
+    let mut table = format!("  {:>7}  {:<width$}  {}\n", "element", "LLVM type", "provided description", width = type_width);
+    for idx in 0 .. std::cmp::max(llvm_types.len(), descriptions.len()) {
+        let llvm_type = llvm_types.get(idx).map(String::as_str).unwrap_or(missing);
+        let description = descriptions.get(idx).map(String::as_str).unwrap_or(missing);
+        table.push_str(&format!("  {:>7}  {:<width$}  {}\n", idx, llvm_type, description, width = type_width));
+    }
+    table
 }
 
 impl UnderspecifiedAbstractData {
+    /// for internal use: the `CompleteAbstractData` which `Unspecified` or
+    /// `SecretDefault` gives for an integer of the given size
+    fn default_leaf(&self, bits: u32) -> CompleteAbstractData {
+        match self {
+            Self::SecretDefault => CompleteAbstractData::sec_integer(bits),
+            _ => CompleteAbstractData::pub_integer(bits, AbstractValue::Unconstrained),
+        }
+    }
+
     /// for internal use: could this `UnderspecifiedAbstractData` be valid for describing a struct of one element?
     pub(crate) fn could_describe_a_struct_of_one_element(&self) -> bool {
         match self {
             Self::Unspecified => true,  // compatible with the struct-of-one-element type
+            Self::SecretDefault => true,  // compatible with the struct-of-one-element type
             Self::Unconstrained => true,  // compatible with the struct-of-one-element type
             Self::Secret => true,  // compatible with the struct-of-one-element type
             Self::Struct { elements, .. } => elements.len() == 1,  // compatible iff the number of elements is 1
+            Self::StructOverride { overrides, .. } => overrides.keys().all(|&idx| idx == 0),  // compatible iff it doesn't override any element past the first
             Self::Complete(CompleteAbstractData::Struct { elements, .. }) => elements.len() == 1,  // compatible iff the number of elements is 1
             Self::VoidOverride { .. } => true,  // could be compatible with the struct-of-one-element type
             Self::SameSizeOverride { .. } => true,  // could be compatible with the struct-of-one-element type
@@ -906,8 +1753,8 @@ impl UnderspecifiedAbstractData {
     }
 
     /// See method description on [`AbstractData::to_complete`](enum.AbstractData.html#method.to_complete)
-    pub(crate) fn to_complete(self, ty: &Type, proj: &Project, sd: &StructDescriptions) -> CompleteAbstractData {
-        self.to_complete_rec(Some(ty), ToCompleteContext::new(proj, sd))
+    pub(crate) fn to_complete(self, ty: &Type, proj: &Project, sd: &StructDescriptions, pitchfork_config: &PitchforkConfig) -> CompleteAbstractData {
+        self.to_complete_rec(Some(ty), ToCompleteContext::new(proj, sd, pitchfork_config))
     }
 
     /// If `ty` is `None`, this indicates that either:
@@ -980,6 +1827,10 @@ impl UnderspecifiedAbstractData {
                 },
             },
             Self::WithWatchpoint { name, data } => CompleteAbstractData::with_watchpoint(name, data.to_complete_rec(ty, ctx)),
+            Self::PublicPointerToArrayOfLen { element_type, len_name, max_len } => {
+                let pointer = Self::PublicPointerTo { pointee: Box::new(AbstractData::array_of(*element_type, max_len)), maybe_null: false, align: 1 };
+                CompleteAbstractData::WithLengthBound { len_name, max_len, data: Box::new(pointer.to_complete_rec(ty, ctx)) }
+            },
             Self::VoidOverride { llvm_struct_name, data } => match llvm_struct_name {
                 None => CompleteAbstractData::void_override(None, data.to_complete_rec(None, ctx)),
                 Some(llvm_struct_name) => {
@@ -1007,7 +1858,7 @@ impl UnderspecifiedAbstractData {
                 },
             },
             Self::SameSizeOverride { data } => CompleteAbstractData::same_size_override(data.to_complete_rec(None, ctx)),
-            Self::PublicPointerTo { pointee, maybe_null } => match ty {
+            Self::PublicPointerTo { pointee, maybe_null, align } => match ty {
                 Some(Type::PointerType { pointee_type, .. }) =>
                     CompleteAbstractData::PublicPointerTo { pointee: Box::new(match &pointee.0 {
                         Self::Array { num_elements, .. } => {
@@ -1023,16 +1874,26 @@ impl UnderspecifiedAbstractData {
                                 },
                             }
                         },
+                        Self::TupleArray { elements } => {
+                            // likewise for a tuple array
+                            match pointee_type.as_ref() {
+                                ty@Type::ArrayType { .. } => pointee.to_complete_rec(Some(ty), ctx),
+                                _ => {
+                                    let num_elements = elements.len();
+                                    pointee.to_complete_rec(Some(&Type::ArrayType { element_type: pointee_type.clone(), num_elements }), ctx)
+                                },
+                            }
+                        },
                         _ => {
                             // AbstractData is pointer-to-something-else, just let the recursive call handle it
                             pointee.to_complete_rec(Some(&**pointee_type), ctx)
                         },
-                    }), maybe_null },
-                None => CompleteAbstractData::PublicPointerTo { pointee: Box::new(pointee.to_complete_rec(None, ctx)), maybe_null },
+                    }), maybe_null, align },
+                None => CompleteAbstractData::PublicPointerTo { pointee: Box::new(pointee.to_complete_rec(None, ctx)), maybe_null, align },
                 _ => {
                     // auto-unwrap LLVM type if it is array or vector of one element
                     if let Some(Some(element_type)) = ty.map(array_of_one_element) {
-                        Self::PublicPointerTo { pointee, maybe_null }.to_complete_rec(Some(element_type), ctx)
+                        Self::PublicPointerTo { pointee, maybe_null, align }.to_complete_rec(Some(element_type), ctx)
                     } else {
                         // otherwise it's a type mismatch
                         ctx.error_backtrace();
@@ -1052,20 +1913,66 @@ impl UnderspecifiedAbstractData {
                 CompleteAbstractData::pub_pointer_to_parent_or(ad.to_complete_rec(pointee_ty, ctx))
             },
             Self::Array { element_type, num_elements } => match ty {
-                Some(Type::ArrayType { element_type: llvm_element_type, num_elements: llvm_num_elements })
-                | Some(Type::VectorType { element_type: llvm_element_type, num_elements: llvm_num_elements, .. }) => {
+                Some(Type::ArrayType { element_type: llvm_element_type, num_elements: llvm_num_elements }) => {
                     if *llvm_num_elements != 0 && *llvm_num_elements != num_elements {
                         ctx.error_backtrace();
                         panic!("Type mismatch: AbstractData specifies an array with {} elements, but found an array with {} elements", num_elements, llvm_num_elements);
                     }
                     CompleteAbstractData::array_of(element_type.to_complete_rec(Some(&**llvm_element_type), ctx.clone()), num_elements)
                 },
+                Some(Type::VectorType { .. }) => {
+                    // LLVM type is a vector: treat this as `vector_of()`, so that we don't lose the distinction
+                    Self::Vector { element_type, num_elements }.to_complete_rec(ty, ctx)
+                },
                 None => CompleteAbstractData::array_of(element_type.to_complete_rec(None, ctx.clone()), num_elements),
                 _ => {
                     ctx.error_backtrace();
                     panic!("Type mismatch: AbstractData::Array with {} elements, but LLVM type is {:?}", num_elements, ty);
                 },
             }
+            Self::Vector { element_type, num_elements } => match ty {
+                #[cfg(feature = "llvm-11")]
+                Some(Type::VectorType { scalable: true, .. }) => {
+                    ctx.error_backtrace();
+                    unimplemented!("scalable vectors")
+                },
+                Some(Type::VectorType { element_type: llvm_element_type, num_elements: llvm_num_elements, .. }) => {
+                    if *llvm_num_elements != num_elements {
+                        ctx.error_backtrace();
+                        panic!("Type mismatch: AbstractData specifies a vector with {} elements, but found a vector with {} elements", num_elements, llvm_num_elements);
+                    }
+                    CompleteAbstractData::vector_of(element_type.to_complete_rec(Some(&**llvm_element_type), ctx.clone()), num_elements)
+                },
+                None => CompleteAbstractData::vector_of(element_type.to_complete_rec(None, ctx.clone()), num_elements),
+                _ => {
+                    ctx.error_backtrace();
+                    panic!("Type mismatch: AbstractData::Vector with {} elements, but LLVM type is {:?}", num_elements, ty);
+                },
+            }
+            Self::TupleArray { elements } => {
+                let elements: Vec<CompleteAbstractData> = match ty {
+                    Some(Type::ArrayType { element_type: llvm_element_type, num_elements: llvm_num_elements }) => {
+                        if *llvm_num_elements != 0 && *llvm_num_elements != elements.len() {
+                            ctx.error_backtrace();
+                            panic!("Type mismatch: AbstractData specifies an array with {} elements, but found an array with {} elements", elements.len(), llvm_num_elements);
+                        }
+                        elements.into_iter().map(|el_data| el_data.to_complete_rec(Some(&**llvm_element_type), ctx.clone())).collect()
+                    },
+                    None => elements.into_iter().map(|el_data| el_data.to_complete_rec(None, ctx.clone())).collect(),
+                    _ => {
+                        ctx.error_backtrace();
+                        panic!("Type mismatch: AbstractData::TupleArray with {} elements, but LLVM type is {:?}", elements.len(), ty);
+                    },
+                };
+                if let Some(first) = elements.first() {
+                    let first_size_bits = first.size_in_bits();
+                    if let Some((i, el)) = elements.iter().enumerate().find(|(_, el)| el.size_in_bits() != first_size_bits) {
+                        ctx.error_backtrace();
+                        panic!("AbstractData::TupleArray elements must all have the same size, but element 0 ({}) has {} bits and element {} ({}) has {} bits", first, first_size_bits, i, el, el.size_in_bits());
+                    }
+                }
+                CompleteAbstractData::tuple_array(elements)
+            },
             Self::Struct { elements, name } => match ty {
                 Some(Type::NamedStructType { name: llvm_name }) => {
                     match ctx.proj.get_named_struct_def(llvm_name).expect("Named struct type should be defined in the given Project") {
@@ -1078,10 +1985,20 @@ impl UnderspecifiedAbstractData {
                     }
                 },
                 Some(Type::StructType { element_types, .. }) => {
-                    ctx.within_structs.push(name.clone());
+                    ctx.enter_struct(name);
                     if elements.len() != element_types.len() {
                         ctx.error_backtrace();
-                        panic!("Type mismatch: AbstractData::Struct with {} elements, but LLVM type has {} elements: {:?}", elements.len(), element_types.len(), element_types);
+                        panic!(
+                            "Type mismatch: AbstractData::Struct {} with {} elements, but LLVM type has {} elements ({}):\n{}",
+                            name,
+                            elements.len(),
+                            element_types.len(),
+                            ctx.within_structs_description(),
+                            struct_elements_side_by_side(
+                                &element_types.iter().map(|ty| ty.to_string()).collect::<Vec<_>>(),
+                                &elements.iter().map(|el| el.to_string()).collect::<Vec<_>>(),
+                            ),
+                        );
                     }
                     CompleteAbstractData::_struct(name, elements
                         .into_iter()
@@ -1090,7 +2007,7 @@ impl UnderspecifiedAbstractData {
                     )
                 },
                 None => {
-                    ctx.within_structs.push(name.clone());
+                    ctx.enter_struct(name);
                     CompleteAbstractData::_struct(name, elements.into_iter().map(|el_data| el_data.to_complete_rec(None, ctx.clone())))
                 }
                 _ => {
@@ -1104,6 +2021,48 @@ impl UnderspecifiedAbstractData {
                     }
                 },
             },
+            Self::StructOverride { name, mut overrides } => match ty {
+                Some(Type::NamedStructType { name: llvm_name }) => {
+                    match ctx.proj.get_named_struct_def(llvm_name).expect("Named struct type should be defined in the given Project") {
+                        (NamedStructDef::Defined(ty), _) => {
+                            Self::StructOverride { name, overrides }.to_complete_rec(Some(ty), ctx)
+                        },
+                        (NamedStructDef::Opaque, _) => {
+                            ctx.error_backtrace();
+                            panic!("AbstractData::struct_override {}, but LLVM struct {:?} is entirely opaque in this Project, so we don't know its other elements", name, llvm_name);
+                        },
+                    }
+                },
+                Some(Type::StructType { element_types, .. }) => {
+                    ctx.enter_struct(name);
+                    if let Some(idx) = overrides.keys().find(|&&idx| idx >= element_types.len()) {
+                        ctx.error_backtrace();
+                        panic!("Type mismatch: AbstractData::struct_override {} overrides element {}, but LLVM type has only {} elements: {:?}", name, idx, element_types.len(), element_types);
+                    }
+                    CompleteAbstractData::_struct(name, element_types
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, el_type)| {
+                            let el_data = overrides.remove(&idx).unwrap_or_else(AbstractData::default);
+                            el_data.to_complete_rec(Some(el_type), ctx.clone())
+                        })
+                    )
+                },
+                None => {
+                    ctx.error_backtrace();
+                    panic!("Encountered an AbstractData::struct_override {} but don't have an LLVM type to use for the other elements", name);
+                },
+                _ => {
+                    // auto-unwrap LLVM type if it is array or vector of one element
+                    if let Some(Some(element_type)) = ty.map(array_of_one_element) {
+                        Self::StructOverride { name, overrides }.to_complete_rec(Some(element_type), ctx.clone())
+                    } else {
+                        // otherwise it's a type mismatch
+                        ctx.error_backtrace();
+                        panic!("Type mismatch: AbstractData::struct_override {}, but LLVM type is {:?}", name, ty);
+                    }
+                },
+            },
             Self::DefaultForLLVMStructName { llvm_struct_name } => match ty {
                 Some(Type::NamedStructType { name, .. }) => {
                     if name == &llvm_struct_name {
@@ -1138,37 +2097,43 @@ impl UnderspecifiedAbstractData {
                     }
                 },
             },
-            Self::Unspecified => match ty {
+            Self::Unspecified | Self::SecretDefault => match ty {
                 None => {
                     ctx.error_backtrace();
-                    panic!("Encountered an AbstractData::default() but don't have an LLVM type to use; this is either because:\n  (1) either same_size_override or void_override with llvm_struct_name == None were used, but the specified AbstractData contained a default() somewhere; or\n  (2) a struct in the StructDescriptions is opaque in this Project, but the specified AbstractData contained a default() somewhere");
+                    panic!("Encountered an AbstractData::{}() but don't have an LLVM type to use; this is either because:\n  (1) either same_size_override or void_override with llvm_struct_name == None were used, but the specified AbstractData contained a default() somewhere; or\n  (2) a struct in the StructDescriptions is opaque in this Project, but the specified AbstractData contained a default() somewhere", if self == Self::SecretDefault { "secret_default" } else { "default" });
                 },
                 Some(ty) => match ty {
-                    Type::IntegerType { bits, .. } =>
-                        CompleteAbstractData::pub_integer(*bits, AbstractValue::Unconstrained),
+                    Type::IntegerType { bits, .. } => self.default_leaf(*bits),
                     Type::PointerType { pointee_type, .. } => match &**pointee_type {
                         Type::FuncType { .. } =>
                             CompleteAbstractData::pub_pointer_to_hook("hook_uninitialized_function_pointer"),
                         Type::IntegerType { bits } =>
                             CompleteAbstractData::pub_pointer_to(CompleteAbstractData::array_of(
-                                CompleteAbstractData::pub_integer(*bits, AbstractValue::Unconstrained),
-                                AbstractData::DEFAULT_ARRAY_LENGTH,
+                                self.default_leaf(*bits),
+                                ctx.pitchfork_config.default_array_length,
                             )),
-                        Type::ArrayType { num_elements: 0, element_type } =>
+                        Type::ArrayType { num_elements: 0, element_type } => {
+                            let default_array_length = ctx.pitchfork_config.default_array_length;
                             CompleteAbstractData::pub_pointer_to(CompleteAbstractData::array_of(
-                                Self::Unspecified.to_complete_rec(Some(element_type), ctx),
-                                AbstractData::DEFAULT_ARRAY_LENGTH,
-                            )),
-                        ty => CompleteAbstractData::pub_pointer_to(Self::Unspecified.to_complete_rec(Some(ty), ctx)),
+                                self.to_complete_rec(Some(element_type), ctx),
+                                default_array_length,
+                            ))
+                        },
+                        ty => CompleteAbstractData::pub_pointer_to(self.to_complete_rec(Some(ty), ctx)),
                     },
                     #[cfg(feature = "llvm-11")]
                     Type::VectorType { scalable: true, .. } => {
                         ctx.error_backtrace();
                         unimplemented!("scalable vectors")
                     },
-                    Type::VectorType { element_type, num_elements, .. } | Type::ArrayType { element_type, num_elements } =>
+                    Type::VectorType { element_type, num_elements, .. } =>
+                        CompleteAbstractData::vector_of(
+                            self.to_complete_rec(Some(element_type), ctx),
+                            *num_elements,
+                        ),
+                    Type::ArrayType { element_type, num_elements } =>
                         CompleteAbstractData::array_of(
-                            Self::Unspecified.to_complete_rec(Some(element_type), ctx),
+                            self.to_complete_rec(Some(element_type), ctx),
                             *num_elements,
                         ),
                     Type::NamedStructType { name, .. } => {
@@ -1181,7 +2146,7 @@ impl UnderspecifiedAbstractData {
                                         warn!("Setting the contents of a {:?} to unconstrained in order to avoid infinite recursion. We will not warn again for infinite recursion on a {:?}", name, name);
                                     }
                                     let bits = ctx.proj.size_in_bits(ty).expect("Inner struct type shouldn't be an opaque struct type");
-                                    return CompleteAbstractData::PublicValue { bits, value: AbstractValue::Unconstrained };
+                                    return self.default_leaf(bits);
                                 },
                                 NamedStructDef::Opaque => {
                                     ctx.error_backtrace();
@@ -1192,7 +2157,7 @@ impl UnderspecifiedAbstractData {
                         match ctx.sd.get(name) {
                             Some(abstractdata) => {
                                 // This is in the StructDescriptions, so use the description there
-                                ctx.within_structs.push(name.clone());
+                                ctx.enter_struct(name);
                                 match structdef {
                                     NamedStructDef::Defined(ty) => {
                                         abstractdata.clone().to_complete_rec(Some(ty), ctx)
@@ -1203,7 +2168,7 @@ impl UnderspecifiedAbstractData {
                             None => match structdef {
                                 NamedStructDef::Defined(ty) => {
                                     // We have an LLVM struct definition, so use that
-                                    ctx.within_structs.push(name.clone());
+                                    ctx.enter_struct(name);
                                     match self.to_complete_rec(Some(ty), ctx) {
                                         CompleteAbstractData::Struct { elements, .. } => CompleteAbstractData::_struct(name.clone(), elements),  // put in the correct struct name
                                         cad => panic!("Expected to end up with a Struct from this call, but got {:?}", cad),
@@ -1211,15 +2176,19 @@ impl UnderspecifiedAbstractData {
                                 },
                                 NamedStructDef::Opaque => {
                                     // all definitions of the struct in the project are opaque, and it isn't in the StructDescriptions
-                                    // allocate OPAQUE_STRUCT_SIZE_BYTES unconstrained bytes and call it good
-                                    CompleteAbstractData::array_of(CompleteAbstractData::pub_i8(AbstractValue::Unconstrained), AbstractData::OPAQUE_STRUCT_SIZE_BYTES)
+                                    // allocate `opaque_struct_size_bytes` unconstrained bytes and call it good
+                                    if self == Self::SecretDefault {
+                                        CompleteAbstractData::secret_opaque_struct(name, ctx.pitchfork_config.opaque_struct_size_bytes)
+                                    } else {
+                                        CompleteAbstractData::opaque_struct(name, ctx.pitchfork_config.opaque_struct_size_bytes)
+                                    }
                                 },
                             },
                         }
                     },
                     Type::StructType { element_types, .. } => CompleteAbstractData::_struct("unspecified_struct", element_types
                         .iter()
-                        .map(|el_type| Self::Unspecified.to_complete_rec(Some(el_type), ctx.clone()))
+                        .map(|el_type| self.clone().to_complete_rec(Some(el_type), ctx.clone()))
                     ),
                     _ => unimplemented!("AbstractData::to_complete with {:?}", ty),
                 },
@@ -1231,11 +2200,13 @@ impl UnderspecifiedAbstractData {
 /// A variety of ways to specify a numerical value, from completely unconstrained
 /// to fully constrained.
 #[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum AbstractValue {
     /// This exact numerical value
-    ExactValue(u64),
+    ExactValue(u128),
     /// Any numerical value in the range (inclusive)
-    Range(u64, u64),
+    Range(u128, u128),
     /// Any value whatsoever
     Unconstrained,
     /// A value with a (unique) name, so that it can be referenced in a `Equal`, `SignedLessThan`, `SignedGreaterThan`, etc.
@@ -1255,6 +2226,40 @@ pub enum AbstractValue {
     UnsignedLessThan(String),
     /// A value unsigned-greater-than the value with the given name
     UnsignedGreaterThan(String),
+    /// A value equal to the value of the field with the given (0-based) index
+    /// in the immediately containing struct.
+    ///
+    /// Unlike `EqualTo`, this refers to the field of the same struct instance,
+    /// so it doesn't collide with other instances of the struct. The referenced
+    /// field must be a public integer field which comes earlier in the struct.
+    EqualToField(usize),
+    /// A value signed-less-than the value of the field with the given index in
+    /// the immediately containing struct; see notes on `EqualToField`
+    SignedLessThanField(usize),
+    /// A value signed-greater-than the value of the field with the given index
+    /// in the immediately containing struct; see notes on `EqualToField`
+    SignedGreaterThanField(usize),
+    /// A value unsigned-less-than the value of the field with the given index
+    /// in the immediately containing struct; see notes on `EqualToField`
+    UnsignedLessThanField(usize),
+    /// A value unsigned-greater-than the value of the field with the given
+    /// index in the immediately containing struct; see notes on `EqualToField`
+    UnsignedGreaterThanField(usize),
+    /// A value signed-less-than the given constant
+    SignedLessThanConst(i128),
+    /// A value signed-greater-than the given constant
+    SignedGreaterThanConst(i128),
+    /// A value unsigned-less-than the given constant
+    UnsignedLessThanConst(u128),
+    /// A value unsigned-greater-than the given constant
+    UnsignedGreaterThanConst(u128),
+    /// Any value except zero
+    NonZero,
+    /// Any value which is a multiple of the given power of two
+    Aligned(u64),
+    /// A value satisfying all of the given constraints, e.g.
+    /// `All(vec![Range(0, 100), UnsignedLessThanField(0)])`
+    All(Vec<AbstractValue>),
 }
 
 impl AbstractValue {
@@ -1264,6 +2269,20 @@ impl AbstractValue {
             value: Box::new(value),
         }
     }
+
+    /// For the variants which refer to another field of the containing
+    /// struct (`EqualToField`, etc), the index of that field
+    pub(crate) fn field_index(&self) -> Option<usize> {
+        match self {
+            Self::EqualToField(idx)
+            | Self::SignedLessThanField(idx)
+            | Self::SignedGreaterThanField(idx)
+            | Self::UnsignedLessThanField(idx)
+            | Self::UnsignedGreaterThanField(idx)
+            => Some(*idx),
+            _ => None,
+        }
+    }
 }
 
 /// Miscellaneous helper function
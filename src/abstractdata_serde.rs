@@ -0,0 +1,308 @@
+//! `Serialize` and `Deserialize` implementations for `AbstractData` and
+//! `CompleteAbstractData`, enabled by the `serde` feature.
+//!
+//! Both are (de)serialized via `DataRepr`, whose variants correspond to the
+//! `AbstractData` constructors, so that the serialized form doesn't depend on
+//! the private `UnderspecifiedAbstractData` variants. Deserializing an
+//! `AbstractData` calls the corresponding constructors, so any `AbstractData`
+//! built with the constructors round-trips exactly. Deserialization goes
+//! through `TryFrom<DataRepr>` (see the `serde(try_from)` attributes on the
+//! types), so invalid input, such as an alignment which isn't a power of two,
+//! is reported as a deserialization error.
+
+use crate::abstractdata::{CompleteAbstractData, UnderspecifiedAbstractData};
+use crate::{AbstractData, AbstractValue};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// Constructor-shaped representation of an `AbstractData` or
+/// `CompleteAbstractData`. E.g., `AbstractData::pub_pointer_to(AbstractData::sec_i32())`
+/// is represented (in JSON) as `{"pub_pointer_to": {"sec_integer": {"bits": 32}}}`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DataRepr {
+    PubInteger { bits: u32, value: AbstractValue },
+    SecInteger { bits: u32 },
+    PubPointerTo(Box<DataRepr>),
+    PubMaybeNullPointerTo(Box<DataRepr>),
+    AlignedPointerTo { pointee: Box<DataRepr>, align: u64, #[serde(default)] maybe_null: bool },
+    PubPointerToFunc(String),
+    PubPointerToHook(String),
+    PubPointerToSelf,
+    PubPointerToParent,
+    PubPointerToParentOr(Box<DataRepr>),
+    ArrayOf { element_type: Box<DataRepr>, num_elements: usize },
+    VectorOf { element_type: Box<DataRepr>, num_elements: usize },
+    TupleArray(Vec<DataRepr>),
+    Struct { name: String, elements: Vec<DataRepr> },
+    StructOverride { name: String, overrides: BTreeMap<usize, DataRepr> },
+    Default,
+    SecretDefault,
+    DefaultForLlvmStructName(String),
+    Unconstrained,
+    Secret,
+    VoidOverride { llvm_struct_name: Option<String>, data: Box<DataRepr> },
+    PointerOverride { llvm_struct_name: Option<String>, data: Box<DataRepr> },
+    SameSizeOverride(Box<DataRepr>),
+    WithWatchpoint { name: String, data: Box<DataRepr> },
+    PubPointerToArrayOfLen { element_type: Box<DataRepr>, len_name: String, max_len: usize },
+    PubCstring { max_len: usize },
+    SecCstring { len: usize },
+}
+
+impl From<&CompleteAbstractData> for DataRepr {
+    fn from(data: &CompleteAbstractData) -> Self {
+        let boxed = |data: &CompleteAbstractData| Box::new(Self::from(data));
+        match data {
+            CompleteAbstractData::PublicValue { bits, value } => Self::PubInteger { bits: *bits, value: value.clone() },
+            CompleteAbstractData::Secret { bits } => Self::SecInteger { bits: *bits },
+            CompleteAbstractData::Array { element_type, num_elements } => Self::ArrayOf { element_type: boxed(element_type), num_elements: *num_elements },
+            CompleteAbstractData::Vector { element_type, num_elements } => Self::VectorOf { element_type: boxed(element_type), num_elements: *num_elements },
+            CompleteAbstractData::TupleArray { elements } => Self::TupleArray(elements.iter().map(Self::from).collect()),
+            CompleteAbstractData::Struct { name, elements } => Self::Struct { name: name.clone(), elements: elements.iter().map(Self::from).collect() },
+            CompleteAbstractData::PublicPointerTo { pointee, maybe_null: false, align: 1 } => Self::PubPointerTo(boxed(pointee)),
+            CompleteAbstractData::PublicPointerTo { pointee, maybe_null: true, align: 1 } => Self::PubMaybeNullPointerTo(boxed(pointee)),
+            CompleteAbstractData::PublicPointerTo { pointee, maybe_null, align } => Self::AlignedPointerTo { pointee: boxed(pointee), align: *align, maybe_null: *maybe_null },
+            CompleteAbstractData::PublicPointerToFunction(funcname) => Self::PubPointerToFunc(funcname.clone()),
+            CompleteAbstractData::PublicPointerToHook(funcname) => Self::PubPointerToHook(funcname.clone()),
+            CompleteAbstractData::PublicPointerToSelf => Self::PubPointerToSelf,
+            CompleteAbstractData::PublicPointerToParentOr(None) => Self::PubPointerToParent,
+            CompleteAbstractData::PublicPointerToParentOr(Some(data)) => Self::PubPointerToParentOr(boxed(data)),
+            CompleteAbstractData::VoidOverride { llvm_struct_name, data } => Self::VoidOverride { llvm_struct_name: llvm_struct_name.clone(), data: boxed(data) },
+            CompleteAbstractData::PointerOverride { llvm_struct_name, data } => Self::PointerOverride { llvm_struct_name: llvm_struct_name.clone(), data: boxed(data) },
+            CompleteAbstractData::SameSizeOverride { data } => Self::SameSizeOverride(boxed(data)),
+            CompleteAbstractData::WithWatchpoint { name, data } => Self::WithWatchpoint { name: name.clone(), data: boxed(data) },
+            CompleteAbstractData::WithLengthBound { len_name, max_len, data } => match data.as_ref() {
+                CompleteAbstractData::PublicPointerTo { pointee, .. } => match pointee.as_ref() {
+                    CompleteAbstractData::Array { element_type, .. } | CompleteAbstractData::Vector { element_type, .. } => {
+                        Self::PubPointerToArrayOfLen { element_type: boxed(element_type), len_name: len_name.clone(), max_len: *max_len }
+                    },
+                    _ => unreachable!("WithLengthBound should always be on a pointer to an array"),
+                },
+                _ => unreachable!("WithLengthBound should always be on a pointer to an array"),
+            },
+            CompleteAbstractData::PublicCString { max_len } => Self::PubCstring { max_len: *max_len },
+            CompleteAbstractData::SecretCString { len } => Self::SecCstring { len: *len },
+        }
+    }
+}
+
+impl From<&AbstractData> for DataRepr {
+    fn from(data: &AbstractData) -> Self {
+        let boxed = |data: &AbstractData| Box::new(Self::from(data));
+        match &data.0 {
+            UnderspecifiedAbstractData::Unspecified => Self::Default,
+            UnderspecifiedAbstractData::SecretDefault => Self::SecretDefault,
+            UnderspecifiedAbstractData::Unconstrained => Self::Unconstrained,
+            UnderspecifiedAbstractData::Secret => Self::Secret,
+            UnderspecifiedAbstractData::Complete(data) => Self::from(data),
+            UnderspecifiedAbstractData::PublicPointerTo { pointee, maybe_null: false, align: 1 } => Self::PubPointerTo(boxed(pointee)),
+            UnderspecifiedAbstractData::PublicPointerTo { pointee, maybe_null: true, align: 1 } => Self::PubMaybeNullPointerTo(boxed(pointee)),
+            UnderspecifiedAbstractData::PublicPointerTo { pointee, maybe_null, align } => Self::AlignedPointerTo { pointee: boxed(pointee), align: *align, maybe_null: *maybe_null },
+            UnderspecifiedAbstractData::PublicPointerToParentOr(data) => Self::PubPointerToParentOr(boxed(data)),
+            UnderspecifiedAbstractData::Array { element_type, num_elements } => Self::ArrayOf { element_type: boxed(element_type), num_elements: *num_elements },
+            UnderspecifiedAbstractData::Vector { element_type, num_elements } => Self::VectorOf { element_type: boxed(element_type), num_elements: *num_elements },
+            UnderspecifiedAbstractData::TupleArray { elements } => Self::TupleArray(elements.iter().map(Self::from).collect()),
+            UnderspecifiedAbstractData::Struct { name, elements } => Self::Struct { name: name.clone(), elements: elements.iter().map(Self::from).collect() },
+            UnderspecifiedAbstractData::StructOverride { name, overrides } => Self::StructOverride {
+                name: name.clone(),
+                overrides: overrides.iter().map(|(&idx, data)| (idx, Self::from(data))).collect(),
+            },
+            UnderspecifiedAbstractData::DefaultForLLVMStructName { llvm_struct_name } => Self::DefaultForLlvmStructName(llvm_struct_name.clone()),
+            UnderspecifiedAbstractData::VoidOverride { llvm_struct_name, data } => Self::VoidOverride { llvm_struct_name: llvm_struct_name.clone(), data: boxed(data) },
+            UnderspecifiedAbstractData::PointerOverride { llvm_struct_name, data } => Self::PointerOverride { llvm_struct_name: llvm_struct_name.clone(), data: boxed(data) },
+            UnderspecifiedAbstractData::SameSizeOverride { data } => Self::SameSizeOverride(boxed(data)),
+            UnderspecifiedAbstractData::WithWatchpoint { name, data } => Self::WithWatchpoint { name: name.clone(), data: boxed(data) },
+            UnderspecifiedAbstractData::PublicPointerToArrayOfLen { element_type, len_name, max_len } => Self::PubPointerToArrayOfLen {
+                element_type: boxed(element_type),
+                len_name: len_name.clone(),
+                max_len: *max_len,
+            },
+        }
+    }
+}
+
+impl TryFrom<DataRepr> for AbstractData {
+    type Error = String;
+
+    fn try_from(repr: DataRepr) -> Result<Self, String> {
+        let unbox = |repr: Box<DataRepr>| Self::try_from(*repr);
+        let all = |reprs: Vec<DataRepr>| reprs.into_iter().map(Self::try_from).collect::<Result<Vec<_>, _>>();
+        Ok(match repr {
+            DataRepr::PubInteger { bits, value } => Self::pub_integer(bits, value),
+            DataRepr::SecInteger { bits } => Self::sec_integer(bits),
+            DataRepr::PubPointerTo(pointee) => Self::pub_pointer_to(unbox(pointee)?),
+            DataRepr::PubMaybeNullPointerTo(pointee) => Self::pub_maybe_null_pointer_to(unbox(pointee)?),
+            DataRepr::AlignedPointerTo { pointee, align, maybe_null } => {
+                check_align(align)?;
+                Self(UnderspecifiedAbstractData::PublicPointerTo { pointee: Box::new(unbox(pointee)?), maybe_null, align })
+            },
+            DataRepr::PubPointerToFunc(funcname) => Self::pub_pointer_to_func(funcname),
+            DataRepr::PubPointerToHook(funcname) => Self::pub_pointer_to_hook(funcname),
+            DataRepr::PubPointerToSelf => Self::pub_pointer_to_self(),
+            DataRepr::PubPointerToParent => Self::pub_pointer_to_parent(),
+            DataRepr::PubPointerToParentOr(data) => Self::pub_pointer_to_parent_or(unbox(data)?),
+            DataRepr::ArrayOf { element_type, num_elements } => Self::array_of(unbox(element_type)?, num_elements),
+            DataRepr::VectorOf { element_type, num_elements } => Self::vector_of(unbox(element_type)?, num_elements),
+            DataRepr::TupleArray(elements) => Self::tuple_array(all(elements)?),
+            DataRepr::Struct { name, elements } => Self::_struct(name, all(elements)?),
+            DataRepr::StructOverride { name, overrides } => Self::struct_override(
+                name,
+                overrides.into_iter().map(|(idx, data)| Ok((idx, Self::try_from(data)?))).collect::<Result<Vec<_>, String>>()?,
+            ),
+            DataRepr::Default => Self::default(),
+            DataRepr::SecretDefault => Self::secret_default(),
+            DataRepr::DefaultForLlvmStructName(llvm_struct_name) => Self::default_for_llvm_struct_name(llvm_struct_name),
+            DataRepr::Unconstrained => Self::unconstrained(),
+            DataRepr::Secret => Self::secret(),
+            DataRepr::VoidOverride { llvm_struct_name, data } => Self::void_override(llvm_struct_name.as_deref(), unbox(data)?),
+            DataRepr::PointerOverride { llvm_struct_name, data } => Self::pointer_override(llvm_struct_name.as_deref(), unbox(data)?),
+            DataRepr::SameSizeOverride(data) => Self::same_size_override(unbox(data)?),
+            DataRepr::WithWatchpoint { name, data } => Self::with_watchpoint(name, unbox(data)?),
+            DataRepr::PubPointerToArrayOfLen { element_type, len_name, max_len } => Self::pub_pointer_to_array_of_len(unbox(element_type)?, len_name, max_len),
+            DataRepr::PubCstring { max_len } => Self::pub_cstring(max_len),
+            DataRepr::SecCstring { len } => Self::sec_cstring(len),
+        })
+    }
+}
+
+impl TryFrom<DataRepr> for CompleteAbstractData {
+    type Error = String;
+
+    fn try_from(repr: DataRepr) -> Result<Self, String> {
+        repr.into_complete()
+    }
+}
+
+/// Pointer alignments must be powers of two (see `AbstractData::aligned_pointer_to()`)
+fn check_align(align: u64) -> Result<(), String> {
+    if align.is_power_of_two() {
+        Ok(())
+    } else {
+        Err(format!("aligned_pointer_to: alignment must be a power of two, but got {}", align))
+    }
+}
+
+impl DataRepr {
+    /// Fails if the representation contains any underspecified parts
+    /// (`default`, `unconstrained`, etc)
+    fn into_complete(self) -> Result<CompleteAbstractData, String> {
+        let unbox = |repr: Box<DataRepr>| repr.into_complete().map(Box::new);
+        Ok(match self {
+            DataRepr::PubInteger { bits, value } => CompleteAbstractData::pub_integer(bits, value),
+            DataRepr::SecInteger { bits } => CompleteAbstractData::sec_integer(bits),
+            DataRepr::PubPointerTo(pointee) => CompleteAbstractData::PublicPointerTo { pointee: unbox(pointee)?, maybe_null: false, align: 1 },
+            DataRepr::PubMaybeNullPointerTo(pointee) => CompleteAbstractData::PublicPointerTo { pointee: unbox(pointee)?, maybe_null: true, align: 1 },
+            DataRepr::AlignedPointerTo { pointee, align, maybe_null } => {
+                check_align(align)?;
+                CompleteAbstractData::PublicPointerTo { pointee: unbox(pointee)?, maybe_null, align }
+            },
+            DataRepr::PubPointerToFunc(funcname) => CompleteAbstractData::pub_pointer_to_func(funcname),
+            DataRepr::PubPointerToHook(funcname) => CompleteAbstractData::pub_pointer_to_hook(funcname),
+            DataRepr::PubPointerToSelf => CompleteAbstractData::pub_pointer_to_self(),
+            DataRepr::PubPointerToParent => CompleteAbstractData::pub_pointer_to_parent(),
+            DataRepr::PubPointerToParentOr(data) => CompleteAbstractData::PublicPointerToParentOr(Some(unbox(data)?)),
+            DataRepr::ArrayOf { element_type, num_elements } => CompleteAbstractData::Array { element_type: unbox(element_type)?, num_elements },
+            DataRepr::VectorOf { element_type, num_elements } => CompleteAbstractData::Vector { element_type: unbox(element_type)?, num_elements },
+            DataRepr::TupleArray(elements) => CompleteAbstractData::TupleArray {
+                elements: elements.into_iter().map(DataRepr::into_complete).collect::<Result<_, _>>()?,
+            },
+            DataRepr::Struct { name, elements } => CompleteAbstractData::Struct {
+                name,
+                elements: elements.into_iter().map(DataRepr::into_complete).collect::<Result<_, _>>()?,
+            },
+            DataRepr::VoidOverride { llvm_struct_name, data } => CompleteAbstractData::VoidOverride { llvm_struct_name, data: unbox(data)? },
+            DataRepr::PointerOverride { llvm_struct_name, data } => CompleteAbstractData::PointerOverride { llvm_struct_name, data: unbox(data)? },
+            DataRepr::SameSizeOverride(data) => CompleteAbstractData::SameSizeOverride { data: unbox(data)? },
+            DataRepr::WithWatchpoint { name, data } => CompleteAbstractData::WithWatchpoint { name, data: unbox(data)? },
+            DataRepr::PubPointerToArrayOfLen { element_type, len_name, max_len } => CompleteAbstractData::WithLengthBound {
+                len_name,
+                max_len,
+                data: Box::new(CompleteAbstractData::pub_pointer_to(CompleteAbstractData::array_of(*unbox(element_type)?, max_len))),
+            },
+            DataRepr::PubCstring { max_len } => CompleteAbstractData::pub_cstring(max_len),
+            DataRepr::SecCstring { len } => CompleteAbstractData::sec_cstring(len),
+            DataRepr::Default
+            | DataRepr::SecretDefault
+            | DataRepr::StructOverride { .. }
+            | DataRepr::DefaultForLlvmStructName(_)
+            | DataRepr::Unconstrained
+            | DataRepr::Secret
+            => return Err("expected a complete description of the data, but found an underspecified part".into()),
+        })
+    }
+}
+
+impl Serialize for AbstractData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DataRepr::from(self).serialize(serializer)
+    }
+}
+
+impl Serialize for CompleteAbstractData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DataRepr::from(self).serialize(serializer)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(data: AbstractData) {
+        let json = serde_json::to_string(&data).unwrap();
+        let deserialized: AbstractData = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, data, "JSON was {}", json);
+    }
+
+    #[test]
+    fn roundtrip_abstractdata() {
+        roundtrip(AbstractData::default());
+        roundtrip(AbstractData::secret());
+        roundtrip(AbstractData::pub_pointer_to(AbstractData::secret_default()));
+        roundtrip(AbstractData::unconstrained_pointer());
+        roundtrip(AbstractData::pub_i32(AbstractValue::named("len", AbstractValue::Range(0, 32))));
+        roundtrip(AbstractData::pub_maybe_null_pointer_to(AbstractData::array_of(AbstractData::sec_i8(), 32)));
+        roundtrip(AbstractData::aligned_pointer_to(AbstractData::array_of(AbstractData::sec_i8(), 32), 16));
+        roundtrip(AbstractData::vector_of(AbstractData::sec_i32(), 4));
+        roundtrip(AbstractData::pub_i128(AbstractValue::All(vec![AbstractValue::Range(1 << 64, u128::MAX), AbstractValue::SignedGreaterThanConst(i128::MIN)])));
+        roundtrip(AbstractData::tuple_array(vec![AbstractData::sec_i32(), AbstractData::pub_i32(AbstractValue::ExactValue(0))]));
+        roundtrip(AbstractData::_struct("foo", vec![
+            AbstractData::pub_i64(AbstractValue::UnsignedLessThan("len".into())),
+            AbstractData::pub_pointer_to_parent(),
+            AbstractData::pub_pointer_to_parent_or(AbstractData::default_for_llvm_struct_name("struct.bar")),
+            AbstractData::pub_pointer_to_func("f"),
+            AbstractData::with_watchpoint("w", AbstractData::pub_cstring(16)),
+        ]));
+        roundtrip(AbstractData::struct_override("bar", vec![(1, AbstractData::sec_i64()), (4, AbstractData::unconstrained())]));
+        roundtrip(AbstractData::void_override(Some("struct.foo"), AbstractData::same_size_override(AbstractData::sec_cstring(8))));
+    }
+
+    #[test]
+    fn constructor_shaped_json() {
+        let data = AbstractData::pub_pointer_to(AbstractData::sec_i32());
+        assert_eq!(serde_json::to_string(&data).unwrap(), r#"{"pub_pointer_to":{"sec_integer":{"bits":32}}}"#);
+        let data: AbstractData = serde_json::from_str(r#"{"array_of":{"element_type":"default","num_elements":4}}"#).unwrap();
+        assert_eq!(data, AbstractData::array_of(AbstractData::default(), 4));
+    }
+
+    #[test]
+    fn complete_rejects_underspecified() {
+        let complete: CompleteAbstractData = serde_json::from_str(r#"{"pub_pointer_to":{"sec_integer":{"bits":8}}}"#).unwrap();
+        assert_eq!(complete, CompleteAbstractData::PublicPointerTo { pointee: Box::new(CompleteAbstractData::sec_i8()), maybe_null: false, align: 1 });
+        assert!(serde_json::from_str::<CompleteAbstractData>(r#"{"pub_pointer_to":"default"}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_alignment() {
+        let json = r#"{"aligned_pointer_to":{"pointee":{"sec_integer":{"bits":8}},"align":3}}"#;
+        let err = serde_json::from_str::<AbstractData>(json).unwrap_err();
+        assert!(err.to_string().contains("power of two"), "unexpected error: {}", err);
+        let err = serde_json::from_str::<CompleteAbstractData>(json).unwrap_err();
+        assert!(err.to_string().contains("power of two"), "unexpected error: {}", err);
+        let nested = r#"{"array_of":{"element_type":{"aligned_pointer_to":{"pointee":"default","align":0}},"num_elements":2}}"#;
+        assert!(serde_json::from_str::<AbstractData>(nested).is_err());
+    }
+}
@@ -1,30 +1,18 @@
 use crate::abstractdata::*;
 use crate::secret;
+use crate::PitchforkConfig;
 use haybale::{Project, State};
 use haybale::backend::*;
 use haybale::Result;
 use haybale::watchpoints::Watchpoint;
 use llvm_ir::*;
 use llvm_ir::types::NamedStructDef;
-use log::debug;
-use std::collections::HashMap;
+use log::{debug, warn};
+use std::collections::{BTreeSet, HashMap};
 use std::collections::hash_map::Entry::*;
 use std::convert::TryInto;
 use std::fmt;
 
-/// Allocate the function parameters given in `params` with their corresponding `AbstractData` descriptions.
-///
-/// Returns a vector of the `secret::BV`s representing the parameters. Many callers won't need this, though.
-pub fn allocate_args<'p>(
-    proj: &'p Project,
-    state: &mut State<'p, secret::Backend>,
-    sd: &StructDescriptions,
-    params: impl IntoIterator<Item = (&'p function::Parameter, AbstractData)>,
-) -> Result<Vec<secret::BV>> {
-    let mut ctx = Context::new(proj, state, sd);
-    params.into_iter().map(|(param, arg)| ctx.allocate_arg(param, arg)).collect()
-}
-
 /// This `Context` serves two purposes:
 /// first, simply collecting some objects together so we can pass them around as a unit;
 /// but second, allowing some state to persist across invocations of `allocate_arg`
@@ -33,24 +21,340 @@ pub struct Context<'p, 's> {
     proj: &'p Project,
     state: &'s mut State<'p, secret::Backend>,
     sd: &'s StructDescriptions,
+    pitchfork_config: &'s PitchforkConfig,
     namedvals: HashMap<String, secret::BV>,
+    /// LLVM struct names of the entirely opaque structs we have allocated
+    /// (default) space for so far
+    opaque_structs: BTreeSet<String>,
+    /// The values we have allocated for function parameters so far, by
+    /// parameter name
+    allocated_args: HashMap<Name, AllocatedArg>,
+    /// The watchpoints we have added (for `WithWatchpoint`, and for
+    /// `bounds_check_pointees`) so far, as (name, address, size in bytes)
+    watchpoints: Vec<(String, u64, u64)>,
+    /// Length bounds (for `WithLengthBound`) which we have yet to apply, as
+    /// (name of the length value, maximum length). These are applied once all
+    /// the function parameters are allocated, so that the length value may be
+    /// described either before or after the pointer it bounds.
+    pending_length_bounds: Vec<(String, usize)>,
+}
+
+/// The value allocated for a single function parameter
+#[derive(Clone, Debug)]
+pub struct AllocatedArg {
+    /// The value of the parameter itself. For a pointer which may be null, this
+    /// is the maybe-null value.
+    pub value: secret::BV,
+    /// For pointer parameters which we allocated memory for (i.e.,
+    /// `pub_pointer_to()` and `pointer_override()`), the base address returned
+    /// by `State::allocate()`. This is never null, even if `value` may be.
+    /// `None` for all other parameters.
+    pub base_address: Option<secret::BV>,
+    /// For pointer parameters which we allocated memory for, the size of that
+    /// allocation in bits. `None` for all other parameters.
+    pub allocation_bits: Option<u64>,
+}
+
+impl AllocatedArg {
+    /// An `AllocatedArg` for a parameter which we didn't allocate any memory for
+    fn non_allocated(value: secret::BV) -> Self {
+        Self { value, base_address: None, allocation_bits: None }
+    }
 }
 
 impl<'p, 's> Context<'p, 's> {
-    pub fn new(proj: &'p Project, state: &'s mut State<'p, secret::Backend>, sd: &'s StructDescriptions) -> Self {
+    /// Prefix for the names of the watchpoints we place on the guard bytes
+    /// around each pointee with `bounds_check_pointees`. The rest of the
+    /// watchpoint name describes the pointee.
+    pub(crate) const POINTEE_GUARD_PREFIX: &'static str = "outside_of_pointee:";
+
+    /// Number of guard bytes we place on each side of each pointee with
+    /// `bounds_check_pointees`. This is a multiple of any alignment we expect
+    /// the pointee to need.
+    pub(crate) const POINTEE_GUARD_BYTES: u64 = 64;
+
+    pub fn new(proj: &'p Project, state: &'s mut State<'p, secret::Backend>, sd: &'s StructDescriptions, pitchfork_config: &'s PitchforkConfig) -> Self {
         Self {
             proj,
             state,
             sd,
+            pitchfork_config,
             namedvals: HashMap::new(),
+            opaque_structs: BTreeSet::new(),
+            allocated_args: HashMap::new(),
+            watchpoints: Vec::new(),
+            pending_length_bounds: Vec::new(),
+        }
+    }
+
+    /// Reinitialize the contents of each of the given global variables (by
+    /// LLVM name) according to its corresponding `AbstractData` description.
+    ///
+    /// Panics if any of the globals doesn't exist in the `Project`.
+    pub fn initialize_globals<'g>(
+        &mut self,
+        globals: impl IntoIterator<Item = (&'g String, &'g AbstractData)>,
+    ) -> Result<()> {
+        for (name, data) in globals {
+            let llvm_name = Name::from(name.as_str());
+            let (global, _) = self.proj.all_global_vars()
+                .find(|(global, _)| global.name == llvm_name)
+                .unwrap_or_else(|| panic!("Failed to find global variable {:?}", name));
+            let ty = match global.ty.as_ref() {
+                Type::PointerType { pointee_type, .. } => pointee_type,
+                ty => panic!("Expected global variable {:?} to have pointer type, but it has type {:?}", name, ty),
+            };
+            debug!("Initializing global variable {:?}: {}", name, data);
+            // getting the address of the global first ensures that its LLVM
+            // initializer (if any) has been applied, so that it doesn't later
+            // overwrite our initialization
+            let addr = self.state.operand_to_bv(&Operand::ConstantOperand(ConstantRef::new(Constant::GlobalReference {
+                name: global.name.clone(),
+                ty: global.ty.clone(),
+            })))?;
+            InitializationContext::blank().initialize_data_in_memory(self, &addr, data.clone(), ty)?;
+        }
+        self.apply_length_bounds()
+    }
+
+    /// Allocate the function parameters given in `params` with their corresponding `AbstractData` descriptions.
+    ///
+    /// Returns a vector of the `secret::BV`s representing the parameters. Many callers won't need this, though.
+    pub fn allocate_args(
+        &mut self,
+        params: impl IntoIterator<Item = (&'p function::Parameter, AbstractData)>,
+    ) -> Result<Vec<secret::BV>> {
+        let bvs = params.into_iter().map(|(param, arg)| self.allocate_arg(param, arg)).collect::<Result<Vec<_>>>()?;
+        self.apply_length_bounds()?;
+        Ok(bvs)
+    }
+
+    /// Apply all of the pending length bounds (see `pending_length_bounds`).
+    ///
+    /// Panics if any of the length values can't be found, as either a named
+    /// value or a function parameter.
+    fn apply_length_bounds(&mut self) -> Result<()> {
+        for (len_name, max_len) in std::mem::replace(&mut self.pending_length_bounds, Vec::new()) {
+            let len = match self.namedvals.get(&len_name) {
+                Some(bv) => bv.clone(),
+                None => match self.allocated_args.get(&Name::from(len_name.as_str())) {
+                    Some(allocated) => allocated.value.clone(),
+                    None => panic!("pub_pointer_to_array_of_len: no value or parameter named {:?}. You may want to describe the length parameter with AbstractValue::named({:?}, ...)", len_name, len_name),
+                },
+            };
+            debug!("Constraining length value {:?} to at most {}", len_name, max_len);
+            let width = len.get_width();
+            len.ulte(&self.state.bv_from_u64(max_len as u64, width)).assert()?;
+        }
+        Ok(())
+    }
+
+    /// LLVM struct names of the entirely opaque structs which we have allocated
+    /// space for (using the `opaque_struct_size_bytes` setting in
+    /// `PitchforkConfig`) in this `Context` so far
+    pub fn opaque_structs(&self) -> &BTreeSet<String> {
+        &self.opaque_structs
+    }
+
+    /// The values we have allocated for function parameters in this `Context`
+    /// so far, by parameter name
+    pub fn allocated_args(&self) -> &HashMap<Name, AllocatedArg> {
+        &self.allocated_args
+    }
+
+    /// The watchpoints added (for `AbstractData::with_watchpoint()`) in this
+    /// `Context` so far, as (name, address, size in bytes)
+    pub fn watchpoints(&self) -> &[(String, u64, u64)] {
+        &self.watchpoints
+    }
+
+    /// A constant `BV` of width `bits` with the value `value`. Unlike
+    /// `State::bv_from_u64()`, this supports values and widths above 64 bits.
+    /// Panics if `value` doesn't fit in `bits` bits.
+    fn bv_from_u128(&self, value: u128, bits: u32) -> secret::BV {
+        if bits < 128 && value >> bits != 0 {
+            panic!("AbstractValue: value {:#x} doesn't fit in {} bits", value, bits);
+        }
+        if bits <= 64 {
+            self.state.bv_from_u64(value as u64, bits)
+        } else {
+            // build the value from its low 64 bits and its (possibly wider) high bits
+            let low = self.state.bv_from_u64(value as u64, 64);
+            let high_bits = bits - 64;
+            let high = if high_bits <= 64 {
+                self.state.bv_from_u64((value >> 64) as u64, high_bits)
+            } else {
+                self.state.bv_from_u64((value >> 64) as u64, 64).zext(high_bits - 64)
+            };
+            high.concat(&low)
+        }
+    }
+
+    /// Like `bv_from_u128()`, but for a signed value, which is sign-extended
+    /// to `bits` bits. Panics if `value` doesn't fit in a signed `bits`-bit
+    /// value.
+    fn bv_from_i128(&self, value: i128, bits: u32) -> secret::BV {
+        if bits < 128 {
+            let min = -(1i128 << (bits - 1));
+            let max = (1i128 << (bits - 1)) - 1;
+            if value < min || value > max {
+                panic!("AbstractValue: value {} doesn't fit in a signed {}-bit value", value, bits);
+            }
+            self.bv_from_u128(value as u128 & ((1u128 << bits) - 1), bits)
+        } else if bits == 128 {
+            self.bv_from_u128(value as u128, 128)
+        } else {
+            self.bv_from_u128(value as u128, 128).sext(bits - 128)
+        }
+    }
+
+    /// Constrain `bv` to satisfy `value`. This is used for `AbstractValue::All`,
+    /// where all the sub-constraints apply to the same value.
+    ///
+    /// `struct_fields`: the values of the fields of the containing struct, for
+    /// `AbstractValue::EqualToField` etc; or `None` if there is no containing struct.
+    fn constrain_bv(&mut self, bv: &secret::BV, value: &AbstractValue, struct_fields: Option<&[Option<secret::BV>]>) -> Result<()> {
+        let bits = bv.get_width();
+        let namedval = |namedvals: &HashMap<String, secret::BV>, name: &String| -> secret::BV {
+            match namedvals.get(name) {
+                None => panic!("AbstractValue::Named {:?} not found", name),
+                Some(named_bv) => {
+                    let width = named_bv.get_width();
+                    assert_eq!(width, bits, "AbstractValue::All: value named {:?} has {} bits, but current value has {} bits", name, width, bits);
+                    named_bv.clone()
+                },
+            }
+        };
+        let fieldval = |field_idx: usize| -> secret::BV {
+            match struct_fields.map(|fields| fields.get(field_idx)) {
+                None => panic!("AbstractValue {:?} refers to a field of the containing struct, but there is no containing struct", value),
+                Some(None) => panic!("AbstractValue {:?}: field {} must come before the field referencing it", value, field_idx),
+                Some(Some(None)) => panic!("AbstractValue {:?}: field {} is not a public integer", value, field_idx),
+                Some(Some(Some(field_bv))) => {
+                    let width = field_bv.get_width();
+                    assert_eq!(width, bits, "AbstractValue {:?}: field {} has {} bits, but current value has {} bits", value, field_idx, width, bits);
+                    field_bv.clone()
+                },
+            }
+        };
+        match value {
+            AbstractValue::ExactValue(exact) => bv._eq(&self.bv_from_u128(*exact, bits)).assert(),
+            AbstractValue::Range(min, max) => {
+                bv.ugte(&self.bv_from_u128(*min, bits)).assert()?;
+                bv.ulte(&self.bv_from_u128(*max, bits)).assert()
+            },
+            AbstractValue::Unconstrained => Ok(()),
+            AbstractValue::SignedLessThanConst(c) => bv.slt(&self.bv_from_i128(*c, bits)).assert(),
+            AbstractValue::SignedGreaterThanConst(c) => bv.sgt(&self.bv_from_i128(*c, bits)).assert(),
+            AbstractValue::UnsignedLessThanConst(c) => bv.ult(&self.bv_from_u128(*c, bits)).assert(),
+            AbstractValue::UnsignedGreaterThanConst(c) => bv.ugt(&self.bv_from_u128(*c, bits)).assert(),
+            AbstractValue::NonZero => bv._ne(&self.state.zero(bits)).assert(),
+            AbstractValue::Aligned(alignment) => {
+                if !alignment.is_power_of_two() {
+                    panic!("AbstractValue::Aligned({}): alignment must be a power of two", alignment);
+                }
+                let align_bits = alignment.trailing_zeros();
+                if align_bits >= bits {
+                    // the only value with that many low zero bits is 0
+                    bv._eq(&self.state.zero(bits)).assert()
+                } else {
+                    let mask = self.state.bv_from_u64(alignment - 1, bits);
+                    bv.and(&mask)._eq(&self.state.zero(bits)).assert()
+                }
+            },
+            AbstractValue::Named { name, value } => {
+                self.constrain_bv(bv, value, struct_fields)?;
+                match self.namedvals.entry(name.to_owned()) {
+                    Vacant(v) => {
+                        v.insert(bv.clone());
+                        Ok(())
+                    },
+                    Occupied(bv_for_name) => {
+                        let bv_for_name = bv_for_name.get();
+                        let width = bv_for_name.get_width();
+                        assert_eq!(width, bits, "AbstractValue::Named {:?}: multiple values with different bitwidths given this name: one with width {} bits, another with width {} bits", name, width, bits);
+                        bv._eq(&bv_for_name).assert()
+                    },
+                }
+            },
+            AbstractValue::EqualTo(name) => bv._eq(&namedval(&self.namedvals, name)).assert(),
+            AbstractValue::SignedLessThan(name) => bv.slt(&namedval(&self.namedvals, name)).assert(),
+            AbstractValue::SignedGreaterThan(name) => bv.sgt(&namedval(&self.namedvals, name)).assert(),
+            AbstractValue::UnsignedLessThan(name) => bv.ult(&namedval(&self.namedvals, name)).assert(),
+            AbstractValue::UnsignedGreaterThan(name) => bv.ugt(&namedval(&self.namedvals, name)).assert(),
+            AbstractValue::EqualToField(idx) => bv._eq(&fieldval(*idx)).assert(),
+            AbstractValue::SignedLessThanField(idx) => bv.slt(&fieldval(*idx)).assert(),
+            AbstractValue::SignedGreaterThanField(idx) => bv.sgt(&fieldval(*idx)).assert(),
+            AbstractValue::UnsignedLessThanField(idx) => bv.ult(&fieldval(*idx)).assert(),
+            AbstractValue::UnsignedGreaterThanField(idx) => bv.ugt(&fieldval(*idx)).assert(),
+            AbstractValue::All(values) => {
+                for value in values {
+                    self.constrain_bv(bv, value, struct_fields)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Allocate `bits` bits for a pointee, and return the address of the
+    /// pointee, which will be a multiple of `align` bytes.
+    ///
+    /// With `bounds_check_pointees`, this also allocates guard bytes on each
+    /// side of the pointee, with a watchpoint on them named after
+    /// `description`.
+    fn allocate_pointee(&mut self, bits: u64, align: u64, description: impl fmt::Display) -> Result<secret::BV> {
+        let bounds_check = self.pitchfork_config.bounds_check_pointees;
+        if !bounds_check && align <= 1 {
+            return Ok(self.state.allocate(bits));
+        }
+        let guard_bytes = if bounds_check { Self::POINTEE_GUARD_BYTES } else { 0 };
+        let padding_bytes = align.saturating_sub(1);  // enough to reach an aligned address from anywhere
+        let pointee_bytes = (bits + 7) / 8;
+        let allocation = self.state.allocate((guard_bytes + padding_bytes + pointee_bytes + guard_bytes) * 8);
+        let allocation_addr = allocation.as_u64().expect("Allocated address should be constant");
+        let align = std::cmp::max(align, 1);
+        let pointee_addr = (allocation_addr + guard_bytes + align - 1) / align * align;
+        if bounds_check {
+            let name = format!("{}{}", Self::POINTEE_GUARD_PREFIX, description);
+            let guards = [
+                (format!("{} (before)", name), pointee_addr - guard_bytes, guard_bytes),
+                (format!("{} (after)", name), pointee_addr + pointee_bytes, guard_bytes),
+            ];
+            for (name, addr, bytes) in guards.iter() {
+                self.state.add_mem_watchpoint(name, Watchpoint::new(*addr, *bytes));
+                self.watchpoints.push((name.clone(), *addr, *bytes));
+            }
+        }
+        let ptr = self.state.bv_from_u64(pointee_addr, allocation.get_width());
+        debug!("Allocated pointee at {:#x}, aligned to {} bytes, with {} guard bytes on each side", pointee_addr, align, guard_bytes);
+        Ok(ptr)
+    }
+
+    /// Get the public unconstrained value of the `bits` bits at `addr`.
+    ///
+    /// Normally, memory which has never been written already holds public
+    /// unconstrained data. But with `strict_uninitialized_memory`, reading it
+    /// would be an error, so we first write a fresh unconstrained value, which
+    /// also marks the memory as initialized.
+    fn public_unconstrained_in_memory(&mut self, addr: &secret::BV, bits: u32) -> Result<secret::BV> {
+        if secret::strict_uninitialized_memory() {
+            let bv = self.state.new_bv_with_name(Name::from("unconstrained_mem"), bits)?;
+            self.state.write(addr, bv.clone())?;
+            Ok(bv)
+        } else {
+            self.state.read(addr, bits)
         }
     }
 
     /// Returns the `secret::BV` representing the argument. Many callers won't need this, though.
     fn allocate_arg(&mut self, param: &'p function::Parameter, arg: AbstractData) -> Result<secret::BV> {
         debug!("Allocating function parameter {:?}", &param.name);
-        let arg = arg.to_complete(&param.ty, &self.proj, &self.sd);
-        self.allocate_arg_from_cad(param, arg, false)
+        let arg = arg.to_complete(&param.ty, &self.proj, &self.sd, &self.pitchfork_config);
+        arg.collect_opaque_struct_names(&mut self.opaque_structs);
+        let allocated = self.allocate_arg_from_cad(param, arg, false)?;
+        let value = allocated.value.clone();
+        self.allocated_args.insert(param.name.clone(), allocated);
+        Ok(value)
     }
 
     /// Same as above, but takes a `CompleteAbstractData` instead of an `AbstractData`.
@@ -61,7 +365,7 @@ impl<'p, 's> Context<'p, 's> {
         param: &'p function::Parameter,
         arg: CompleteAbstractData,
         type_override: bool,
-    ) -> Result<secret::BV> {
+    ) -> Result<AllocatedArg> {
         let arg_size = arg.size_in_bits();
         match self.state.size_in_bits(&param.ty) {
             Some(param_size) => assert_eq!(arg_size, param_size, "Parameter size mismatch for parameter {:?}: parameter is {} bits but CompleteAbstractData is {} bits", &param.name, param_size, arg_size),
@@ -72,31 +376,31 @@ impl<'p, 's> Context<'p, 's> {
                 debug!("Parameter is marked secret");
                 let bv = secret::BV::Secret { btor: self.state.solver.clone(), width: bits as u32, symbol: None };
                 self.state.overwrite_latest_version_of_bv(&param.name, bv.clone());
-                Ok(bv)
+                Ok(AllocatedArg::non_allocated(bv))
             },
             CompleteAbstractData::PublicValue { bits, value: AbstractValue::ExactValue(value) } => {
                 debug!("Parameter is marked public, equal to {}", value);
-                let bv = self.state.bv_from_u64(value, bits as u32);
+                let bv = self.bv_from_u128(value, bits as u32);
                 self.state.overwrite_latest_version_of_bv(&param.name, bv.clone());
-                Ok(bv)
+                Ok(AllocatedArg::non_allocated(bv))
             },
             CompleteAbstractData::PublicValue { bits, value: AbstractValue::Range(min, max) } => {
                 debug!("Parameter is marked public, in the range ({}, {}) inclusive", min, max);
                 let parambv = self.state.new_bv_with_name(param.name.clone(), bits as u32).unwrap();
-                parambv.ugte(&self.state.bv_from_u64(min, bits as u32)).assert()?;
-                parambv.ulte(&self.state.bv_from_u64(max, bits as u32)).assert()?;
+                parambv.ugte(&self.bv_from_u128(min, bits as u32)).assert()?;
+                parambv.ulte(&self.bv_from_u128(max, bits as u32)).assert()?;
                 self.state.overwrite_latest_version_of_bv(&param.name, parambv.clone());
-                Ok(parambv)
+                Ok(AllocatedArg::non_allocated(parambv))
             }
             CompleteAbstractData::PublicValue { value: AbstractValue::Unconstrained, .. } => {
                 debug!("Parameter is marked public, unconstrained value");
                 // nothing to do, just return the BV representing that parameter
                 let op = Operand::LocalOperand { name: param.name.clone(), ty: param.ty.clone() };
-                self.state.operand_to_bv(&op)
+                self.state.operand_to_bv(&op).map(AllocatedArg::non_allocated)
             },
             CompleteAbstractData::PublicValue { bits, value: AbstractValue::Named { name, value } } => {
                 let unwrapped_arg = CompleteAbstractData::pub_integer(bits, *value);
-                let bv = self.allocate_arg_from_cad(param, unwrapped_arg, type_override)?;
+                let bv = self.allocate_arg_from_cad(param, unwrapped_arg, type_override)?.value;
                 match self.namedvals.entry(name.to_owned()) {
                     Vacant(v) => {
                         v.insert(bv.clone());
@@ -109,7 +413,7 @@ impl<'p, 's> Context<'p, 's> {
                     },
                 };
                 self.state.overwrite_latest_version_of_bv(&param.name, bv.clone());
-                Ok(bv)
+                Ok(AllocatedArg::non_allocated(bv))
             }
             CompleteAbstractData::PublicValue { bits, value: AbstractValue::EqualTo(name) } => {
                 match self.namedvals.get(&name) {
@@ -118,7 +422,7 @@ impl<'p, 's> Context<'p, 's> {
                         let width = bv.get_width();
                         assert_eq!(width, bits as u32, "AbstractValue::EqualTo {:?}, which has {} bits, but current value has {} bits", name, width, bits);
                         self.state.overwrite_latest_version_of_bv(&param.name, bv.clone());
-                        Ok(bv.clone())
+                        Ok(AllocatedArg::non_allocated(bv.clone()))
                     }
                 }
             }
@@ -131,7 +435,7 @@ impl<'p, 's> Context<'p, 's> {
                         let new_bv = self.state.new_bv_with_name(Name::from(format!("SignedLessThan{}:", name)), width)?;
                         new_bv.slt(&bv).assert()?;
                         self.state.overwrite_latest_version_of_bv(&param.name, new_bv.clone());
-                        Ok(new_bv)
+                        Ok(AllocatedArg::non_allocated(new_bv))
                     }
                 }
             }
@@ -144,7 +448,7 @@ impl<'p, 's> Context<'p, 's> {
                         let new_bv = self.state.new_bv_with_name(Name::from(format!("SignedGreaterThan:{}", name)), width)?;
                         new_bv.sgt(&bv).assert()?;
                         self.state.overwrite_latest_version_of_bv(&param.name, new_bv.clone());
-                        Ok(new_bv)
+                        Ok(AllocatedArg::non_allocated(new_bv))
                     }
                 }
             }
@@ -157,7 +461,7 @@ impl<'p, 's> Context<'p, 's> {
                         let new_bv = self.state.new_bv_with_name(Name::from(format!("UnsignedLessThan:{}", name)), width)?;
                         new_bv.ult(&bv).assert()?;
                         self.state.overwrite_latest_version_of_bv(&param.name, new_bv.clone());
-                        Ok(new_bv)
+                        Ok(AllocatedArg::non_allocated(new_bv))
                     }
                 }
             }
@@ -170,22 +474,51 @@ impl<'p, 's> Context<'p, 's> {
                         let new_bv = self.state.new_bv_with_name(Name::from(format!("UnsignedGreaterThan:{}", name)), width)?;
                         new_bv.ugt(&bv).assert()?;
                         self.state.overwrite_latest_version_of_bv(&param.name, new_bv.clone());
-                        Ok(new_bv)
+                        Ok(AllocatedArg::non_allocated(new_bv))
                     }
                 }
             }
-            CompleteAbstractData::PublicPointerTo { pointee, maybe_null } => {
+            CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::SignedLessThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::SignedGreaterThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::UnsignedLessThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::UnsignedGreaterThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::NonZero }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::Aligned(_) } => {
+                debug!("Parameter is marked public, {:?}", value);
+                let parambv = self.state.new_bv_with_name(param.name.clone(), bits as u32)?;
+                self.constrain_bv(&parambv, &value, None)?;
+                self.state.overwrite_latest_version_of_bv(&param.name, parambv.clone());
+                Ok(AllocatedArg::non_allocated(parambv))
+            },
+            CompleteAbstractData::PublicValue { bits, value: AbstractValue::All(values) } => {
+                debug!("Parameter is marked public, satisfying all of {:?}", values);
+                let parambv = self.state.new_bv_with_name(param.name.clone(), bits as u32)?;
+                for value in &values {
+                    self.constrain_bv(&parambv, value, None)?;
+                }
+                self.state.overwrite_latest_version_of_bv(&param.name, parambv.clone());
+                Ok(AllocatedArg::non_allocated(parambv))
+            },
+            CompleteAbstractData::PublicValue { value: value @ AbstractValue::EqualToField(_), .. }
+            | CompleteAbstractData::PublicValue { value: value @ AbstractValue::SignedLessThanField(_), .. }
+            | CompleteAbstractData::PublicValue { value: value @ AbstractValue::SignedGreaterThanField(_), .. }
+            | CompleteAbstractData::PublicValue { value: value @ AbstractValue::UnsignedLessThanField(_), .. }
+            | CompleteAbstractData::PublicValue { value: value @ AbstractValue::UnsignedGreaterThanField(_), .. }
+            => {
+                panic!("Parameter {:?} is given AbstractValue {:?}, which refers to a field of the containing struct, but a parameter has no containing struct", &param.name, value)
+            },
+            CompleteAbstractData::PublicPointerTo { pointee, maybe_null, align } => {
                 debug!("Parameter is marked as a public pointer which {} be null", if maybe_null { "may" } else { "cannot" });
-                let ptr = self.state.allocate(pointee.size_in_bits() as u64);
+                let ptr = self.allocate_pointee(pointee.size_in_bits() as u64, align, &param.name)?;
                 debug!("Allocated the parameter at {:?}", ptr);
-                if maybe_null {
+                let value = if maybe_null {
                     let ptr_width = ptr.get_width();
                     let condition = self.state.new_bv_with_name(Name::from("pointer_is_null"), 1)?;
-                    let maybe_null_ptr = condition.cond_bv(&self.state.zero(ptr_width), &ptr);
-                    self.state.overwrite_latest_version_of_bv(&param.name, maybe_null_ptr);
+                    condition.cond_bv(&self.state.zero(ptr_width), &ptr)
                 } else {
-                    self.state.overwrite_latest_version_of_bv(&param.name, ptr.clone());
-                }
+                    ptr.clone()
+                };
+                self.state.overwrite_latest_version_of_bv(&param.name, value.clone());
                 // in either case, initialize the concrete pointer, not the maybe-null location
                 if type_override {
                     InitializationContext::blank().initialize_cad_in_memory(self, &ptr, &*pointee, None)?;
@@ -196,7 +529,7 @@ impl<'p, 's> Context<'p, 's> {
                     };
                     InitializationContext::blank().initialize_cad_in_memory(self, &ptr, &*pointee, Some(pointee_ty))?;
                 }
-                Ok(ptr)
+                Ok(AllocatedArg { value, base_address: Some(ptr), allocation_bits: Some(pointee.size_in_bits() as u64) })
             },
             CompleteAbstractData::PublicPointerToFunction(funcname) => {
                 debug!("Parameter is marked as a public pointer to the function {:?}", funcname);
@@ -210,7 +543,7 @@ impl<'p, 's> Context<'p, 's> {
                     .unwrap_or_else(|| panic!("Failed to find function {:?}", &funcname))
                     .clone();
                 self.state.overwrite_latest_version_of_bv(&param.name, ptr.clone());
-                Ok(ptr)
+                Ok(AllocatedArg::non_allocated(ptr))
             }
             CompleteAbstractData::PublicPointerToHook(funcname) => {
                 debug!("Parameter is marked as a public pointer to the active hook for function {:?}", funcname);
@@ -224,16 +557,41 @@ impl<'p, 's> Context<'p, 's> {
                     .unwrap_or_else(|| panic!("Failed to find hook for function {:?}", &funcname))
                     .clone();
                 self.state.overwrite_latest_version_of_bv(&param.name, ptr.clone());
-                Ok(ptr)
+                Ok(AllocatedArg::non_allocated(ptr))
             }
-            CompleteAbstractData::PublicPointerToSelf => panic!("Pointer-to-self is not supported for toplevel parameter (requires support for struct-passed-by-value, which at the time of this writing is also unimplemented)"),
+            CompleteAbstractData::PublicPointerToSelf => panic!("Pointer-to-self is not supported for toplevel parameter; a toplevel parameter isn't contained in a struct"),
             CompleteAbstractData::PublicPointerToParentOr(_) => panic!("Pointer-to-parent is not supported for toplevel parameter; we have no way to know what struct it is contained in"),
-            CompleteAbstractData::Array { .. } => unimplemented!("Array passed by value"),
-            CompleteAbstractData::Struct { .. } => unimplemented!("Struct passed by value"),
-            CompleteAbstractData::VoidOverride { .. } => unimplemented!("VoidOverride used as an argument directly.  You probably meant to use a pointer to a VoidOverride"),
+            arg @ CompleteAbstractData::Array { .. } | arg @ CompleteAbstractData::Vector { .. } | arg @ CompleteAbstractData::TupleArray { .. } | arg @ CompleteAbstractData::Struct { .. } => {
+                debug!("Parameter is an aggregate passed by value: {}", arg);
+                // The value of an aggregate is the concatenation of its elements, laid
+                // out just as they are in memory. So the easiest way to construct it is
+                // to initialize the aggregate in some scratch memory, then read it back.
+                let scratch = self.state.allocate(arg_size as u64);
+                let ty = match param.ty.as_ref() {
+                    _ if type_override => None,
+                    ty @ Type::ArrayType { .. } | ty @ Type::VectorType { .. } | ty @ Type::StructType { .. } | ty @ Type::NamedStructType { .. } => Some(ty),
+                    _ => {
+                        // the ABI lowering has replaced the aggregate with some other type
+                        // (e.g., an integer of the same size); we checked above that the sizes match
+                        debug!("Parameter type is {:?}; skipping the type checks for the aggregate", param.ty);
+                        None
+                    },
+                };
+                InitializationContext::blank().initialize_cad_in_memory(self, &scratch, &arg, ty)?;
+                let parambv = self.state.read(&scratch, arg_size as u32)?;
+                self.state.overwrite_latest_version_of_bv(&param.name, parambv.clone());
+                Ok(AllocatedArg::non_allocated(parambv))
+            },
+            CompleteAbstractData::VoidOverride { data, .. } => {
+                // there is no `void` value to override, so this must be relaxing the
+                // type checks on a value passed directly. (If you meant to override a
+                // `void*`, you want a pointer to a VoidOverride.)
+                debug!("Parameter is marked as {}, ignoring LLVM type", data);
+                self.allocate_arg_from_cad(param, *data, true)
+            },
             CompleteAbstractData::PointerOverride { llvm_struct_name, data } => {
                 debug!("Parameter is marked as a public pointer to {}, overriding LLVM type", data);
-                let ptr = self.state.allocate(data.size_in_bits() as u64);
+                let ptr = self.allocate_pointee(data.size_in_bits() as u64, 1, &param.name)?;
                 debug!("Allocated the parameter at {:?}", ptr);
                 self.state.overwrite_latest_version_of_bv(&param.name, ptr.clone());
 
@@ -263,13 +621,23 @@ impl<'p, 's> Context<'p, 's> {
                     },
                 }
 
-                Ok(ptr)
+                Ok(AllocatedArg { value: ptr.clone(), base_address: Some(ptr), allocation_bits: Some(data.size_in_bits() as u64) })
             },
             CompleteAbstractData::SameSizeOverride { data } => {
                 // we already checked above that the param size == the data size; and we will again on the recursive call, actually
                 self.allocate_arg_from_cad(param, *data, true)
             },
-            CompleteAbstractData::WithWatchpoint { .. } => unimplemented!("WithWatchpoint is not supported for toplevel parameter (its value usually does not reside in memory). You may want a pointer to a WithWatchpoint instead"),
+            CompleteAbstractData::WithWatchpoint { name, data } => {
+                warn!("Not adding watchpoint {:?}: parameter {:?} is passed directly, so it has no memory to watch. You may want a pointer to a WithWatchpoint instead", name, &param.name);
+                self.allocate_arg_from_cad(param, *data, type_override)
+            },
+            CompleteAbstractData::WithLengthBound { len_name, max_len, data } => {
+                self.pending_length_bounds.push((len_name, max_len));
+                self.allocate_arg_from_cad(param, *data, type_override)
+            },
+            CompleteAbstractData::PublicCString { .. } | CompleteAbstractData::SecretCString { .. } => {
+                panic!("Mismatch for parameter {:?}: CompleteAbstractData specifies a C string passed by value, but parameter type is {:?}. You probably want a pointer to a C string instead", &param.name, &param.ty);
+            },
         }
     }
 }
@@ -298,6 +666,11 @@ pub struct InitializationContext<'a> {
     /// within, etc), purely for debugging purposes. First in the vec is the
     /// top-level struct, last is the most immediate struct.
     within_structs: Vec<WithinStruct>,
+
+    /// For each field of the (instance of the) struct we are currently within
+    /// which has already been initialized, its value if it is a public
+    /// integer. Used for `AbstractValue::EqualToField` etc.
+    struct_fields: Vec<Option<secret::BV>>,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
@@ -314,6 +687,15 @@ impl fmt::Display for WithinStruct {
     }
 }
 
+/// Describes the pointee of the pointer stored at `addr`, for naming the
+/// `bounds_check_pointees` watchpoints
+fn pointee_description(addr: &secret::BV) -> String {
+    match addr.as_u64() {
+        Some(addr) => format!("pointee of the pointer at {:#x}", addr),
+        None => "pointee of a pointer at a non-constant address".to_owned(),
+    }
+}
+
 impl<'a> InitializationContext<'a> {
     /// A default/blank initialization context. If you're not doing anything fancy,
     /// this is what you're looking for.
@@ -322,6 +704,7 @@ impl<'a> InitializationContext<'a> {
             cur_struct: None,
             parent: None,
             within_structs: Vec::new(),
+            struct_fields: Vec::new(),
         }
     }
 
@@ -332,6 +715,15 @@ impl<'a> InitializationContext<'a> {
         }
     }
 
+    /// The structs we are within, as a single line for including in panic messages
+    fn within_structs_description(&self) -> String {
+        if self.within_structs.is_empty() {
+            "not within any struct".into()
+        } else {
+            format!("within {}", self.within_structs.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(" > "))
+        }
+    }
+
     /// Check that `ty` represents a value of `bits` bits, panicking if not
     fn size_check_ty(&self, ctx: &Context, ty: &'a Type, bits: u32) {
         match ctx.state.size_in_bits(ty) {
@@ -358,7 +750,22 @@ impl<'a> InitializationContext<'a> {
         data: AbstractData,
         ty: &'a Type,
     ) -> Result<u32> {
-        self.initialize_cad_in_memory(ctx, addr, &data.to_complete(ty, ctx.proj, ctx.sd), Some(ty))
+        let data = data.to_complete(ty, ctx.proj, ctx.sd, ctx.pitchfork_config);
+        data.collect_opaque_struct_names(&mut ctx.opaque_structs);
+        self.initialize_cad_in_memory(ctx, addr, &data, Some(ty))
+    }
+
+    /// If `addr` can only have one value (under the current constraints),
+    /// return that value. Addresses are usually constant, but may not be, e.g.,
+    /// inside a maybe-null pointer.
+    fn unique_address(ctx: &mut Context, addr: &secret::BV) -> Result<Option<u64>> {
+        if let Some(addr) = addr.as_u64() {
+            return Ok(Some(addr));
+        }
+        match ctx.state.get_a_solution_for_bv(addr)?.and_then(|solution| solution.as_u64()) {
+            Some(candidate) if ctx.state.bvs_must_be_equal(addr, &ctx.state.bv_from_u64(candidate, addr.get_width()))? => Ok(Some(candidate)),
+            _ => Ok(None),
+        }
     }
 
     /// Like `initialize_data_in_memory`, but takes a `CompleteAbstractData`
@@ -379,10 +786,15 @@ impl<'a> InitializationContext<'a> {
         // First we handle the case where the LLVM type is array-of-one-element
         if let Some(Some(element_type)) = ty.map(array_of_one_element) {
             match data {
-                CompleteAbstractData::Array { num_elements: 1, element_type: element_abstractdata } => {
+                CompleteAbstractData::Array { num_elements: 1, element_type: element_abstractdata }
+                | CompleteAbstractData::Vector { num_elements: 1, element_type: element_abstractdata } => {
                     // both LLVM and CAD type are array-of-one-element.  Unwrap and call recursively
                     return self.initialize_cad_in_memory(ctx, addr, element_abstractdata, Some(element_type));
                 },
+                CompleteAbstractData::TupleArray { elements } if elements.len() == 1 => {
+                    // likewise for a tuple array of one element
+                    return self.initialize_cad_in_memory(ctx, addr, &elements[0], Some(element_type));
+                },
                 data => {
                     // LLVM type is array-of-one-element but CAD type is not.  Unwrap the LLVM type and call recursively
                     return self.initialize_cad_in_memory(ctx, addr, data, Some(element_type));
@@ -433,7 +845,7 @@ impl<'a> InitializationContext<'a> {
                 if let Some(ty) = ty {
                     self.size_check_ty(ctx, ty, *bits);
                 }
-                let bv = ctx.state.bv_from_u64(*value, *bits);
+                let bv = ctx.bv_from_u128(*value, *bits);
                 ctx.state.write(&addr, bv)?;
                 Ok(*bits)
             },
@@ -442,16 +854,19 @@ impl<'a> InitializationContext<'a> {
                 if let Some(ty) = ty {
                     self.size_check_ty(ctx, ty, *bits);
                 }
-                let bv = ctx.state.read(&addr, *bits)?;
-                bv.ugte(&ctx.state.bv_from_u64(*min, *bits)).assert()?;
-                bv.ulte(&ctx.state.bv_from_u64(*max, *bits)).assert()?;
+                let bv = ctx.public_unconstrained_in_memory(addr, *bits)?;
+                bv.ugte(&ctx.bv_from_u128(*min, *bits)).assert()?;
+                bv.ulte(&ctx.bv_from_u128(*max, *bits)).assert()?;
                 Ok(*bits)
             }
             CompleteAbstractData::PublicValue { bits, value: AbstractValue::Unconstrained } => {
-                // nothing to do, just check that the type matches
+                // nothing to do (except under `strict_uninitialized_memory`), just check that the type matches
                 if let Some(ty) = ty {
                     self.size_check_ty(ctx, ty, *bits);
                 }
+                if secret::strict_uninitialized_memory() {
+                    ctx.public_unconstrained_in_memory(addr, *bits)?;
+                }
                 Ok(*bits)
             },
             CompleteAbstractData::PublicValue { bits, value: AbstractValue::Named { name, value } } => {
@@ -583,7 +998,83 @@ impl<'a> InitializationContext<'a> {
                     }
                 }
             }
-            CompleteAbstractData::PublicPointerTo { pointee, maybe_null } => {
+            CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::SignedLessThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::SignedGreaterThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::UnsignedLessThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::UnsignedGreaterThanConst(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::NonZero }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::Aligned(_) } => {
+                debug!("constraining the memory contents to be {:?}", value);
+                if let Some(ty) = ty {
+                    self.size_check_ty(ctx, ty, *bits);
+                }
+                let bv = ctx.public_unconstrained_in_memory(addr, *bits)?;
+                ctx.constrain_bv(&bv, value, None)?;
+                Ok(*bits)
+            },
+            CompleteAbstractData::PublicValue { bits, value: AbstractValue::All(values) } => {
+                debug!("constraining the memory contents to satisfy all of {:?}", values);
+                if let Some(ty) = ty {
+                    self.size_check_ty(ctx, ty, *bits);
+                }
+                let bv = ctx.public_unconstrained_in_memory(addr, *bits)?;
+                let struct_fields = if self.within_structs.is_empty() { None } else { Some(self.struct_fields.as_slice()) };
+                for value in values {
+                    ctx.constrain_bv(&bv, value, struct_fields)?;
+                }
+                Ok(*bits)
+            },
+            CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::EqualToField(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::SignedLessThanField(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::SignedGreaterThanField(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::UnsignedLessThanField(_) }
+            | CompleteAbstractData::PublicValue { bits, value: value @ AbstractValue::UnsignedGreaterThanField(_) }
+            => {
+                let field_idx = value.field_index().expect("should be one of the *Field variants");
+                let cur_struct_name = match self.within_structs.last() {
+                    Some(w) => &w.name,
+                    None => {
+                        self.error_backtrace();
+                        panic!("AbstractValue {:?} refers to a field of the containing struct, but there is no containing struct", value)
+                    },
+                };
+                let field_bv = match self.struct_fields.get(field_idx) {
+                    Some(Some(bv)) => bv,
+                    Some(None) => {
+                        self.error_backtrace();
+                        panic!("AbstractValue {:?}: field {} of struct {} is not a public integer", value, field_idx, cur_struct_name)
+                    },
+                    None => {
+                        self.error_backtrace();
+                        panic!("AbstractValue {:?}: field {} of struct {} must come before the field referencing it", value, field_idx, cur_struct_name)
+                    },
+                };
+                let width = field_bv.get_width();
+                if width != *bits {
+                    self.error_backtrace();
+                    panic!("AbstractValue {:?}: field {} of struct {} has {} bits, but current value has {} bits", value, field_idx, cur_struct_name, width, bits);
+                }
+                if let Some(ty) = ty {
+                    self.size_check_ty(ctx, ty, *bits);
+                }
+                let new_bv = match value {
+                    AbstractValue::EqualToField(_) => field_bv.clone(),
+                    _ => {
+                        let new_bv = ctx.state.new_bv_with_name(Name::from(format!("{:?}:{}", value, cur_struct_name)), width)?;
+                        match value {
+                            AbstractValue::SignedLessThanField(_) => new_bv.slt(field_bv).assert()?,
+                            AbstractValue::SignedGreaterThanField(_) => new_bv.sgt(field_bv).assert()?,
+                            AbstractValue::UnsignedLessThanField(_) => new_bv.ult(field_bv).assert()?,
+                            AbstractValue::UnsignedGreaterThanField(_) => new_bv.ugt(field_bv).assert()?,
+                            _ => unreachable!("field_index() is only Some for the *Field variants"),
+                        }
+                        new_bv
+                    },
+                };
+                ctx.state.write(&addr, new_bv)?;
+                Ok(*bits)
+            },
+            CompleteAbstractData::PublicPointerTo { pointee, maybe_null, align } => {
                 debug!("memory contents are marked as a public pointer which {} be null", if *maybe_null { "may" } else { "cannot"});
 
                 // type-check
@@ -596,7 +1087,7 @@ impl<'a> InitializationContext<'a> {
                 });
 
                 // allocate memory for the pointee
-                let inner_ptr = ctx.state.allocate(pointee.size_in_bits() as u64);
+                let inner_ptr = ctx.allocate_pointee(pointee.size_in_bits() as u64, *align, pointee_description(addr))?;
                 let bits = inner_ptr.get_width();
                 debug!("allocated memory for the pointee at {:?}, and will constrain the memory contents at {:?} to have that pointer value{}", inner_ptr, addr, if *maybe_null { " or null" } else { "" });
 
@@ -771,9 +1262,12 @@ impl<'a> InitializationContext<'a> {
                     }
                 }
             },
-            CompleteAbstractData::Array { element_type: element_abstractdata, num_elements } => {
+            // a vector is laid out in memory just like an array
+            CompleteAbstractData::Array { element_type: element_abstractdata, num_elements }
+            | CompleteAbstractData::Vector { element_type: element_abstractdata, num_elements } => {
                 let element_type: Option<&Type> = ty.map(|ty| match ty {
-                    Type::ArrayType { element_type, num_elements: found_num_elements } => {
+                    Type::ArrayType { element_type, num_elements: found_num_elements }
+                    | Type::VectorType { element_type, num_elements: found_num_elements, .. } => {
                         if *found_num_elements != 0 {
                             if num_elements != found_num_elements {
                                 self.error_backtrace();
@@ -822,8 +1316,11 @@ impl<'a> InitializationContext<'a> {
                         self.initialize_cad_in_memory(ctx, &addr, &CompleteAbstractData::sec_integer(array_size_bits), ty)
                     },
                     CompleteAbstractData::PublicValue { bits, value: AbstractValue::Unconstrained } => {
-                        // special-case this, as no initialization is necessary for the entire array
+                        // special-case this, as no initialization is necessary for the entire array (except under `strict_uninitialized_memory`)
                         debug!("array contents are entirely public unconstrained bits");
+                        if secret::strict_uninitialized_memory() && num_elements > 0 {
+                            ctx.public_unconstrained_in_memory(&addr, bits * num_elements)?;
+                        }
                         Ok(bits * num_elements)
                     },
                     _ => {
@@ -850,6 +1347,39 @@ impl<'a> InitializationContext<'a> {
                     },
                 }
             },
+            CompleteAbstractData::TupleArray { elements } => {
+                let element_type: Option<&Type> = ty.map(|ty| match ty {
+                    Type::ArrayType { element_type, num_elements: found_num_elements } => {
+                        // as for `Array`, a 0-element array in LLVM probably just means an array of unspecified length
+                        if *found_num_elements != 0 && *found_num_elements != elements.len() {
+                            self.error_backtrace();
+                            panic!("Type mismatch: CompleteAbstractData specifies an array with {} elements, but found an array with {} elements", elements.len(), found_num_elements);
+                        }
+                        element_type
+                    },
+                    _ => ty,  // an array, but the LLVM type is just pointer.  E.g., *int instead of *{array of 16 ints}.
+                });
+                if elements.is_empty() {
+                    // as for `Array`, there's no way to return the correct value
+                    self.error_backtrace();
+                    panic!("Array with 0 elements (and element type {:?})", element_type);
+                }
+                let mut cur_addr = addr.clone();
+                let mut total_bits = 0;
+                for (i, element) in elements.iter().enumerate() {
+                    let element_size_bits = element.size_in_bits();
+                    if element_size_bits % 8 != 0 {
+                        self.error_backtrace();
+                        panic!("Array element size is not a multiple of 8 bits: {}", element_size_bits);
+                    }
+                    debug!("initializing element {} of the array: {}", i, element);
+                    self.clone().initialize_cad_in_memory(ctx, &cur_addr, element, element_type)?;
+                    cur_addr = cur_addr.add(&ctx.state.bv_from_u64(u64::from(element_size_bits / 8), addr.get_width()));
+                    total_bits += element_size_bits;
+                }
+                debug!("done initializing the array at {:?}", addr);
+                Ok(total_bits)
+            },
             CompleteAbstractData::Struct { name, elements } => {
                 let mut cur_addr = addr.clone();
                 let element_types = match ty {
@@ -883,11 +1413,22 @@ impl<'a> InitializationContext<'a> {
                 };
                 if elements.len() != element_types.len() {
                     self.error_backtrace();
-                    panic!("Have {} struct elements but {} element types", elements.len(), element_types.len());
+                    panic!(
+                        "Type mismatch: CompleteAbstractData::Struct {} has {} elements, but LLVM type has {} elements ({}):\n{}",
+                        name,
+                        elements.len(),
+                        element_types.len(),
+                        self.within_structs_description(),
+                        struct_elements_side_by_side(
+                            &element_types.iter().map(|ty| ty.as_ref().map_or_else(|| "(opaque)".into(), |ty| ty.to_string())).collect::<Vec<_>>(),
+                            &elements.iter().map(|el| el.to_string()).collect::<Vec<_>>(),
+                        ),
+                    );
                 }
                 self.within_structs.push(WithinStruct { name: name.clone(), element_index: 0 });
                 self.parent = self.cur_struct;
                 self.cur_struct = ty.map(|ty| (addr, ty));
+                self.struct_fields = Vec::with_capacity(elements.len());
                 let mut total_bits = 0;
                 for (element_idx, (element, element_ty)) in elements.iter().zip(element_types).enumerate() {
                     let within_structs_len = self.within_structs.len();
@@ -916,6 +1457,11 @@ impl<'a> InitializationContext<'a> {
                         self.error_backtrace();
                         panic!("Element {} of struct {} should be {} bits based on its type, but we seem to have initialized {} bits", element_idx, name, element_size_bits, bits);
                     }
+                    let field_value = match element {
+                        CompleteAbstractData::PublicValue { bits, .. } => Some(ctx.state.read(&cur_addr, *bits)?),
+                        _ => None,
+                    };
+                    self.struct_fields.push(field_value);
                     cur_addr = cur_addr.add(&ctx.state.bv_from_u64(element_size_bytes as u64, addr.get_width()));
                 }
                 debug!("done initializing struct {} at {:?}", name, addr);
@@ -965,7 +1511,7 @@ impl<'a> InitializationContext<'a> {
                 }
 
                 // allocate memory for the pointee, which is `data` (ignoring LLVM type)
-                let inner_ptr = ctx.state.allocate(data.size_in_bits() as u64);
+                let inner_ptr = ctx.allocate_pointee(data.size_in_bits() as u64, 1, pointee_description(addr))?;
                 debug!("allocated memory for the pointee at {:?}, and will constrain the memory contents at {:?} to have that pointer value", inner_ptr, addr);
 
                 // make `addr` point to a pointer to the newly allocated memory
@@ -1008,11 +1554,81 @@ impl<'a> InitializationContext<'a> {
                 self.initialize_cad_in_memory(ctx, addr, &**data, None)
             }
             CompleteAbstractData::WithWatchpoint { name, data } => {
-                let watch_addr = addr.as_u64().expect("WithWatchpoint not compatible with a non-constant initialization address");
-                let watch_size_in_bytes = data.size_in_bits() / 8;
-                ctx.state.add_mem_watchpoint(name, Watchpoint::new(watch_addr, watch_size_in_bytes as u64));
+                match Self::unique_address(ctx, addr)? {
+                    Some(watch_addr) => {
+                        let watch_size_in_bytes = data.size_in_bits() / 8;
+                        ctx.state.add_mem_watchpoint(name, Watchpoint::new(watch_addr, watch_size_in_bytes as u64));
+                        ctx.watchpoints.push((name.clone(), watch_addr, watch_size_in_bytes as u64));
+                    },
+                    None => warn!("Not adding watchpoint {:?}: the watched data is at a non-constant address", name),
+                }
+                self.initialize_cad_in_memory(ctx, addr, &**data, ty)
+            }
+            CompleteAbstractData::WithLengthBound { len_name, max_len, data } => {
+                ctx.pending_length_bounds.push((len_name.clone(), *max_len));
                 self.initialize_cad_in_memory(ctx, addr, &**data, ty)
             }
+            CompleteAbstractData::PublicCString { max_len } => {
+                self.cstring_type_check(ty, *max_len);
+                if *max_len == 0 {
+                    self.error_backtrace();
+                    panic!("C string with max_len 0 has no room for the null terminator");
+                }
+                // the array contents are public unconstrained bytes (no write necessary, except under `strict_uninitialized_memory`); we just need to add the terminator constraints
+                let len_bits = addr.get_width();
+                let len = ctx.state.new_bv_with_name(Name::from("cstring_length"), len_bits)?;
+                len.ult(&ctx.state.bv_from_u64(*max_len as u64, len_bits)).assert()?;
+                debug!("initializing a public C string of at most {} bytes, with symbolic length {:?}", max_len, len);
+                let zero_byte = ctx.state.zero(8);
+                for i in 0 .. *max_len {
+                    let i_bv = ctx.state.bv_from_u64(i as u64, len_bits);
+                    let byte = ctx.public_unconstrained_in_memory(&addr.add(&i_bv), 8)?;
+                    // the first null byte is at index `len`, and all bytes before it are nonzero
+                    i_bv._eq(&len).implies(&byte._eq(&zero_byte)).assert()?;
+                    i_bv.ult(&len).implies(&byte._ne(&zero_byte)).assert()?;
+                }
+                Ok(CompleteAbstractData::pub_cstring(*max_len).size_in_bits())
+            }
+            CompleteAbstractData::SecretCString { len } => {
+                self.cstring_type_check(ty, *len + 1);
+                debug!("initializing a secret C string of length {}", len);
+                if *len > 0 {
+                    self.clone().initialize_cad_in_memory(ctx, addr, &CompleteAbstractData::sec_integer((*len * 8).try_into().unwrap()), None)?;
+                }
+                let terminator_addr = addr.add(&ctx.state.bv_from_u64(*len as u64, addr.get_width()));
+                let terminator = ctx.state.zero(8);
+                ctx.state.write(&terminator_addr, terminator)?;
+                Ok(CompleteAbstractData::sec_cstring(*len).size_in_bits())
+            }
+        }
+    }
+
+    /// Check that `ty` is a valid LLVM type for a C string occupying `num_bytes`
+    /// bytes, panicking if not.
+    ///
+    /// We accept `i8` (the pointee type of a `char*`) or an array of `i8`.
+    fn cstring_type_check(&self, ty: Option<&'a Type>, num_bytes: usize) {
+        match ty {
+            None => {},
+            Some(Type::IntegerType { bits: 8 }) => {},
+            Some(Type::ArrayType { element_type, num_elements }) => {
+                match &**element_type {
+                    Type::IntegerType { bits: 8 } => {},
+                    _ => {
+                        self.error_backtrace();
+                        panic!("Type mismatch: CompleteAbstractData specifies a C string, but found an array of {:?}", element_type);
+                    },
+                }
+                // as for `Array`, a 0-element array in LLVM probably just means an array of unspecified length
+                if *num_elements != 0 && *num_elements != num_bytes {
+                    self.error_backtrace();
+                    panic!("Type mismatch: CompleteAbstractData specifies a C string of {} bytes, but found an array with {} elements", num_bytes, num_elements);
+                }
+            },
+            Some(ty) => {
+                self.error_backtrace();
+                panic!("Type mismatch: CompleteAbstractData specifies a C string, but found type {:?}", ty);
+            },
         }
     }
 
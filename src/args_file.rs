@@ -0,0 +1,512 @@
+//! Reading `AbstractData` argument specifications from a file, for the
+//! `--args-file` option of `main_func()`.
+//!
+//! The file is a JSON object mapping function names to argument specs. The
+//! argument specs for a function are either a JSON array of strings (one per
+//! parameter, in order), or a JSON object mapping `param<n>` to strings, e.g.
+//!
+//! ```json
+//! {
+//!     "my_func": { "param0": "sec_i32", "param1": "pub_pointer_to_array(sec_i8, 32)" },
+//!     "my_other_func": [ "pub_i64(range(0, 16))", "default" ]
+//! }
+//! ```
+//!
+//! With the object form, any parameter not mentioned gets
+//! `AbstractData::default()`.
+//!
+//! Each string uses the names of the `AbstractData` constructors, with
+//! arguments in parentheses (parentheses may be omitted for constructors
+//! taking no arguments). `AbstractValue`s are written as a number,
+//! `range(lo, hi)`, `unconstrained`, `named(name, value)`, `equal_to(name)`,
+//! `signed_less_than(name)`, `unsigned_less_than_field(index)`,
+//! `signed_greater_than_const(-16)`, etc. Names may be bare identifiers or
+//! double-quoted strings. For convenience, `secret_<x>` is accepted as a
+//! synonym for `sec_<x>`, `pub_<x>` with no value means an unconstrained
+//! public value, and `pub_pointer_to_array(elem, n)` means
+//! `pub_pointer_to(array_of(elem, n))`. `aligned_pointer_to` takes the data
+//! followed by the alignment in bytes. `struct_override` takes the struct
+//! name followed by alternating indices and `AbstractData`s, e.g.
+//! `struct_override(foo, 7, sec_i32)`.
+//!
+//! With the `serde` feature, argument specs may alternately be given as the
+//! serialized form of an `AbstractData`.
+
+use crate::{AbstractData, AbstractValue};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Argument specs read from an args file: for each function name, the spec
+/// for each parameter by position. `None` means the parameter wasn't
+/// mentioned, and gets `AbstractData::default()`.
+pub(crate) type ArgsFile = HashMap<String, Vec<Option<AbstractData>>>;
+
+/// Read the args file at the given path. See module-level docs for the format.
+pub(crate) fn read_args_file(path: impl AsRef<Path>) -> Result<ArgsFile, String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let json: serde_json::Value = serde_json::from_str(&contents)
+        .map_err(|e| format!("failed to parse {} as JSON: {}", path.display(), e))?;
+    let funcs = json.as_object()
+        .ok_or_else(|| format!("{}: expected a JSON object mapping function names to argument specs", path.display()))?;
+    let mut args_file = HashMap::new();
+    for (funcname, specs) in funcs {
+        let args = match specs {
+            serde_json::Value::Array(specs) => specs.iter()
+                .map(|spec| parse_spec(funcname, spec).map(Some))
+                .collect::<Result<Vec<_>, String>>()?,
+            serde_json::Value::Object(specs) => {
+                let mut args = Vec::new();
+                for (param, spec) in specs {
+                    let idx: usize = param.strip_prefix("param").and_then(|idx| idx.parse().ok())
+                        .ok_or_else(|| format!("{}: expected keys of the form `param<n>`, got {:?}", funcname, param))?;
+                    if args.len() <= idx {
+                        args.resize(idx + 1, None);
+                    }
+                    args[idx] = Some(parse_spec(funcname, spec)?);
+                }
+                args
+            },
+            _ => return Err(format!("{}: expected an array or object of argument specs", funcname)),
+        };
+        args_file.insert(funcname.clone(), args);
+    }
+    Ok(args_file)
+}
+
+/// Get the `AbstractData`s to use for a function with the given number of
+/// parameters, according to its entry in the args file
+pub(crate) fn args_for_function(specs: &[Option<AbstractData>], num_params: usize) -> Vec<AbstractData> {
+    let mut args: Vec<AbstractData> = specs.iter()
+        .map(|spec| spec.clone().unwrap_or_else(AbstractData::default))
+        .collect();
+    if args.len() < num_params {
+        args.resize_with(num_params, AbstractData::default);
+    }
+    args
+}
+
+fn parse_spec(funcname: &str, spec: &serde_json::Value) -> Result<AbstractData, String> {
+    #[cfg(feature = "serde")]
+    {
+        if !spec.is_string() {
+            return serde_json::from_value(spec.clone()).map_err(|e| format!("{}: invalid argument spec {}: {}", funcname, spec, e));
+        }
+    }
+    let spec = spec.as_str()
+        .ok_or_else(|| format!("{}: expected argument specs to be strings, got {}", funcname, spec))?;
+    spec.parse().map_err(|e| format!("{}: {}", funcname, e))
+}
+
+impl FromStr for AbstractData {
+    type Err = String;
+
+    /// Parse an `AbstractData` from a string such as
+    /// `"pub_pointer_to(array_of(sec_i8, 32))"`, using the names of the
+    /// `AbstractData` constructors (and for `AbstractValue`s, a number,
+    /// `range(lo, hi)`, `unconstrained`, `named(name, value)`, `equal_to(name)`,
+    /// etc).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expr = Parser::new(s).parse_all()?;
+        expr.to_abstractdata().map_err(|e| format!("in {:?}: {}", s, e))
+    }
+}
+
+/// A parsed (but not yet interpreted) spec expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    /// An identifier, possibly followed by a parenthesized list of arguments
+    Call { name: String, args: Vec<Expr> },
+    Number(u128),
+    /// A number written with a leading `-`, holding its magnitude. This is
+    /// only accepted where a signed number is expected.
+    NegativeNumber(u128),
+    Str(String),
+}
+
+impl Expr {
+    fn to_abstractdata(&self) -> Result<AbstractData, String> {
+        let (name, args) = match self {
+            Expr::Call { name, args } => (name.as_str(), args.as_slice()),
+            _ => return Err(format!("expected an AbstractData, got {}", self)),
+        };
+        let name = match name.strip_prefix("secret_") {
+            Some(rest) => format!("sec_{}", rest),
+            None => name.to_owned(),
+        };
+        let pub_value = |args: &[Expr]| match args {
+            [] => Ok(AbstractValue::Unconstrained),
+            [value] => value.to_abstractvalue(),
+            _ => Err(format!("{} takes at most one argument", name)),
+        };
+        match (name.as_str(), args) {
+            ("pub_i8", _) => Ok(AbstractData::pub_i8(pub_value(args)?)),
+            ("pub_i16", _) => Ok(AbstractData::pub_i16(pub_value(args)?)),
+            ("pub_i32", _) => Ok(AbstractData::pub_i32(pub_value(args)?)),
+            ("pub_i64", _) => Ok(AbstractData::pub_i64(pub_value(args)?)),
+            ("pub_i128", _) => Ok(AbstractData::pub_i128(pub_value(args)?)),
+            ("pub_integer", [bits]) => Ok(AbstractData::pub_integer(bits.to_number()? as u32, AbstractValue::Unconstrained)),
+            ("pub_integer", [bits, value]) => Ok(AbstractData::pub_integer(bits.to_number()? as u32, value.to_abstractvalue()?)),
+            ("sec_i8", []) => Ok(AbstractData::sec_i8()),
+            ("sec_i16", []) => Ok(AbstractData::sec_i16()),
+            ("sec_i32", []) => Ok(AbstractData::sec_i32()),
+            ("sec_i64", []) => Ok(AbstractData::sec_i64()),
+            ("sec_i128", []) => Ok(AbstractData::sec_i128()),
+            ("sec_integer", [bits]) => Ok(AbstractData::sec_integer(bits.to_number()? as u32)),
+            ("pub_pointer_to", [data]) => Ok(AbstractData::pub_pointer_to(data.to_abstractdata()?)),
+            ("pub_maybe_null_pointer_to", [data]) => Ok(AbstractData::pub_maybe_null_pointer_to(data.to_abstractdata()?)),
+            ("aligned_pointer_to", [data, align]) => {
+                let align = align.to_number()?;
+                if !align.is_power_of_two() {
+                    return Err(format!("aligned_pointer_to: alignment must be a power of two, got {}", align));
+                }
+                Ok(AbstractData::aligned_pointer_to(data.to_abstractdata()?, align))
+            },
+            ("pub_pointer_to_array", [elem, n]) => Ok(AbstractData::pub_pointer_to(AbstractData::array_of(elem.to_abstractdata()?, n.to_number()? as usize))),
+            ("pub_pointer_to_func", [funcname]) => Ok(AbstractData::pub_pointer_to_func(funcname.to_name()?)),
+            ("pub_pointer_to_hook", [funcname]) => Ok(AbstractData::pub_pointer_to_hook(funcname.to_name()?)),
+            ("pub_pointer_to_self", []) => Ok(AbstractData::pub_pointer_to_self()),
+            ("pub_pointer_to_parent", []) => Ok(AbstractData::pub_pointer_to_parent()),
+            ("pub_pointer_to_parent_or", [data]) => Ok(AbstractData::pub_pointer_to_parent_or(data.to_abstractdata()?)),
+            ("array_of", [elem, n]) => Ok(AbstractData::array_of(elem.to_abstractdata()?, n.to_number()? as usize)),
+            ("vector_of", [elem, n]) => Ok(AbstractData::vector_of(elem.to_abstractdata()?, n.to_number()? as usize)),
+            ("tuple_array", elements) => Ok(AbstractData::tuple_array(
+                elements.iter().map(Expr::to_abstractdata).collect::<Result<Vec<_>, _>>()?,
+            )),
+            ("_struct", [structname, elements @ ..]) | ("struct", [structname, elements @ ..]) => Ok(AbstractData::_struct(
+                structname.to_name()?,
+                elements.iter().map(Expr::to_abstractdata).collect::<Result<Vec<_>, _>>()?,
+            )),
+            ("struct_override", [structname, overrides @ ..]) => {
+                if overrides.len() % 2 != 0 {
+                    return Err(format!("struct_override expects a struct name followed by pairs of index and data, got {} argument(s)", args.len()));
+                }
+                Ok(AbstractData::struct_override(
+                    structname.to_name()?,
+                    overrides.chunks(2)
+                        .map(|pair| Ok((pair[0].to_number()? as usize, pair[1].to_abstractdata()?)))
+                        .collect::<Result<Vec<_>, String>>()?,
+                ))
+            },
+            ("default", []) => Ok(AbstractData::default()),
+            ("sec_default", []) => Ok(AbstractData::secret_default()),
+            ("default_for_llvm_struct_name", [structname]) => Ok(AbstractData::default_for_llvm_struct_name(structname.to_name()?)),
+            ("unconstrained_pointer", []) => Ok(AbstractData::unconstrained_pointer()),
+            ("unconstrained", []) => Ok(AbstractData::unconstrained()),
+            ("secret", []) => Ok(AbstractData::secret()),
+            ("void_override", [data]) => Ok(AbstractData::void_override(None, data.to_abstractdata()?)),
+            ("void_override", [structname, data]) => Ok(AbstractData::void_override(Some(&structname.to_name()?), data.to_abstractdata()?)),
+            ("pointer_override", [data]) => Ok(AbstractData::pointer_override(None, data.to_abstractdata()?)),
+            ("pointer_override", [structname, data]) => Ok(AbstractData::pointer_override(Some(&structname.to_name()?), data.to_abstractdata()?)),
+            ("same_size_override", [data]) => Ok(AbstractData::same_size_override(data.to_abstractdata()?)),
+            ("with_watchpoint", [wpname, data]) => Ok(AbstractData::with_watchpoint(wpname.to_name()?, data.to_abstractdata()?)),
+            ("pub_pointer_to_array_of_len", [elem, len_name, max_len]) => Ok(AbstractData::pub_pointer_to_array_of_len(
+                elem.to_abstractdata()?,
+                len_name.to_name()?,
+                max_len.to_number()? as usize,
+            )),
+            ("pub_cstring", [max_len]) => Ok(AbstractData::pub_cstring(max_len.to_number()? as usize)),
+            ("sec_cstring", [len]) => Ok(AbstractData::sec_cstring(len.to_number()? as usize)),
+            ("mostly_public_with_secret_range", [total, offset, len]) => {
+                let (total, offset, len) = (total.to_number()? as usize, offset.to_number()? as usize, len.to_number()? as usize);
+                if offset + len > total {
+                    return Err(format!("mostly_public_with_secret_range: secret range of {} bytes at offset {} extends past the end of the {} bytes of data", len, offset, total));
+                }
+                Ok(AbstractData::mostly_public_with_secret_range(total, offset, len))
+            },
+            _ => Err(format!("unknown AbstractData constructor {} with {} argument(s)", name, args.len())),
+        }
+    }
+
+    fn to_abstractvalue(&self) -> Result<AbstractValue, String> {
+        match self {
+            Expr::Number(n) => Ok(AbstractValue::ExactValue(*n)),
+            Expr::NegativeNumber(_) | Expr::Str(_) => Err(format!("expected an AbstractValue, got {}", self)),
+            Expr::Call { name, args } => match (name.as_str(), args.as_slice()) {
+                ("unconstrained", []) => Ok(AbstractValue::Unconstrained),
+                ("exact", [n]) => Ok(AbstractValue::ExactValue(n.to_value()?)),
+                ("range", [lo, hi]) => Ok(AbstractValue::Range(lo.to_value()?, hi.to_value()?)),
+                ("named", [valname, value]) => Ok(AbstractValue::named(&valname.to_name()?, value.to_abstractvalue()?)),
+                ("equal_to", [valname]) => Ok(AbstractValue::EqualTo(valname.to_name()?)),
+                ("signed_less_than", [valname]) => Ok(AbstractValue::SignedLessThan(valname.to_name()?)),
+                ("signed_greater_than", [valname]) => Ok(AbstractValue::SignedGreaterThan(valname.to_name()?)),
+                ("unsigned_less_than", [valname]) => Ok(AbstractValue::UnsignedLessThan(valname.to_name()?)),
+                ("unsigned_greater_than", [valname]) => Ok(AbstractValue::UnsignedGreaterThan(valname.to_name()?)),
+                ("signed_less_than_const", [n]) => Ok(AbstractValue::SignedLessThanConst(n.to_signed_number()?)),
+                ("signed_greater_than_const", [n]) => Ok(AbstractValue::SignedGreaterThanConst(n.to_signed_number()?)),
+                ("unsigned_less_than_const", [n]) => Ok(AbstractValue::UnsignedLessThanConst(n.to_value()?)),
+                ("unsigned_greater_than_const", [n]) => Ok(AbstractValue::UnsignedGreaterThanConst(n.to_value()?)),
+                ("nonzero", []) | ("non_zero", []) => Ok(AbstractValue::NonZero),
+                ("aligned", [alignment]) => Ok(AbstractValue::Aligned(alignment.to_number()?)),
+                ("all", values) => Ok(AbstractValue::All(values.iter().map(Expr::to_abstractvalue).collect::<Result<Vec<_>, _>>()?)),
+                ("equal_to_field", [idx]) => Ok(AbstractValue::EqualToField(idx.to_number()? as usize)),
+                ("signed_less_than_field", [idx]) => Ok(AbstractValue::SignedLessThanField(idx.to_number()? as usize)),
+                ("signed_greater_than_field", [idx]) => Ok(AbstractValue::SignedGreaterThanField(idx.to_number()? as usize)),
+                ("unsigned_less_than_field", [idx]) => Ok(AbstractValue::UnsignedLessThanField(idx.to_number()? as usize)),
+                ("unsigned_greater_than_field", [idx]) => Ok(AbstractValue::UnsignedGreaterThanField(idx.to_number()? as usize)),
+                _ => Err(format!("unknown AbstractValue {} with {} argument(s)", name, args.len())),
+            },
+        }
+    }
+
+    /// A number used as a size, index, etc
+    fn to_number(&self) -> Result<u64, String> {
+        match self {
+            Expr::Number(n) => u64::try_from(*n).map_err(|_| format!("number {} is too large", self)),
+            _ => Err(format!("expected a number, got {}", self)),
+        }
+    }
+
+    /// A number used as an `AbstractValue`, which may be up to 128 bits
+    fn to_value(&self) -> Result<u128, String> {
+        match self {
+            Expr::Number(n) => Ok(*n),
+            _ => Err(format!("expected a number, got {}", self)),
+        }
+    }
+
+    fn to_signed_number(&self) -> Result<i128, String> {
+        let out_of_range = || format!("number {} is out of range for a signed 128-bit value", self);
+        match self {
+            Expr::Number(n) => i128::try_from(*n).map_err(|_| out_of_range()),
+            Expr::NegativeNumber(n) if *n <= 1 << 127 => Ok((*n as i128).wrapping_neg()),
+            Expr::NegativeNumber(_) => Err(out_of_range()),
+            _ => Err(format!("expected a number, got {}", self)),
+        }
+    }
+
+    /// Names (of functions, structs, etc) may be given either as bare
+    /// identifiers or as quoted strings
+    fn to_name(&self) -> Result<String, String> {
+        match self {
+            Expr::Str(s) => Ok(s.clone()),
+            Expr::Call { name, args } if args.is_empty() => Ok(name.clone()),
+            _ => Err(format!("expected a name, got {}", self)),
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::NegativeNumber(n) => write!(f, "-{}", n),
+            Expr::Str(s) => write!(f, "{:?}", s),
+            Expr::Call { name, args } if args.is_empty() => write!(f, "{}", name),
+            Expr::Call { name, args } => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            },
+        }
+    }
+}
+
+/// Simple recursive-descent parser for spec expressions
+struct Parser<'s> {
+    s: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn new(s: &'s str) -> Self {
+        Self { s, pos: 0 }
+    }
+
+    /// Parse the entire string as a single `Expr`
+    fn parse_all(&mut self) -> Result<Expr, String> {
+        let expr = self.parse_expr()?;
+        self.skip_whitespace();
+        if self.pos < self.s.len() {
+            return Err(self.error("unexpected trailing characters"));
+        }
+        Ok(expr)
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => self.parse_number(),
+            Some('-') => {
+                self.pos += 1;
+                match self.parse_number()? {
+                    Expr::Number(n) => Ok(Expr::NegativeNumber(n)),
+                    _ => unreachable!("parse_number should return a Number"),
+                }
+            },
+            Some('"') => self.parse_string(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.take_while(|c| c.is_alphanumeric() || c == '_').to_owned();
+                self.skip_whitespace();
+                let mut args = Vec::new();
+                if self.peek() == Some('(') {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    if self.peek() == Some(')') {
+                        self.pos += 1;
+                    } else {
+                        loop {
+                            args.push(self.parse_expr()?);
+                            self.skip_whitespace();
+                            match self.peek() {
+                                Some(',') => self.pos += 1,
+                                Some(')') => {
+                                    self.pos += 1;
+                                    break;
+                                },
+                                _ => return Err(self.error("expected `,` or `)`")),
+                            }
+                        }
+                    }
+                }
+                Ok(Expr::Call { name, args })
+            },
+            _ => Err(self.error("expected a constructor name, number, or string")),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Expr, String> {
+        let start = self.pos;
+        let digits = self.take_while(|c| c.is_ascii_alphanumeric());
+        let n = match digits.strip_prefix("0x") {
+            Some(hex) => u128::from_str_radix(hex, 16),
+            None => digits.parse(),
+        };
+        n.map(Expr::Number).map_err(|_| format!("invalid number {:?} at position {} in {:?}", digits, start, self.s))
+    }
+
+    fn parse_string(&mut self) -> Result<Expr, String> {
+        self.pos += 1; // opening quote
+        let contents = self.take_while(|c| c != '"').to_owned();
+        if self.peek() != Some('"') {
+            return Err(self.error("unterminated string"));
+        }
+        self.pos += 1; // closing quote
+        Ok(Expr::Str(contents))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos ..].chars().next()
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'s str {
+        let start = self.pos;
+        let len = self.s[start ..].find(|c| !pred(c)).unwrap_or(self.s.len() - start);
+        self.pos += len;
+        &self.s[start .. self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!("{} at position {} in {:?}", msg, self.pos, self.s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_constructors() {
+        assert_eq!("sec_i32".parse(), Ok(AbstractData::sec_i32()));
+        assert_eq!("secret_i8()".parse(), Ok(AbstractData::sec_i8()));
+        assert_eq!("pub_i64".parse(), Ok(AbstractData::pub_i64(AbstractValue::Unconstrained)));
+        assert_eq!("pub_i32(0x10)".parse(), Ok(AbstractData::pub_i32(AbstractValue::ExactValue(16))));
+        assert_eq!(
+            "pub_i64(named(len, range(0, 32)))".parse(),
+            Ok(AbstractData::pub_i64(AbstractValue::named("len", AbstractValue::Range(0, 32)))),
+        );
+        assert_eq!(
+            " pub_pointer_to_array( secret_i8 , 32 ) ".parse(),
+            Ok(AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::sec_i8(), 32))),
+        );
+        assert_eq!(
+            "struct_override(foo, 7, sec_i32, 2, pub_i8(3))".parse(),
+            Ok(AbstractData::struct_override("foo", vec![
+                (7, AbstractData::sec_i32()),
+                (2, AbstractData::pub_i8(AbstractValue::ExactValue(3))),
+            ])),
+        );
+        assert_eq!(
+            "pub_i64(all(nonzero, aligned(8), unsigned_less_than_field(0)))".parse(),
+            Ok(AbstractData::pub_i64(AbstractValue::All(vec![
+                AbstractValue::NonZero,
+                AbstractValue::Aligned(8),
+                AbstractValue::UnsignedLessThanField(0),
+            ]))),
+        );
+        assert_eq!(
+            "pub_i32(all(signed_greater_than_const(-16), signed_less_than_const(0x10), unsigned_greater_than_const(2), unsigned_less_than_const(99)))".parse(),
+            Ok(AbstractData::pub_i32(AbstractValue::All(vec![
+                AbstractValue::SignedGreaterThanConst(-16),
+                AbstractValue::SignedLessThanConst(16),
+                AbstractValue::UnsignedGreaterThanConst(2),
+                AbstractValue::UnsignedLessThanConst(99),
+            ]))),
+        );
+        assert_eq!("pub_i64(signed_less_than_const(-9223372036854775808))".parse(), Ok(AbstractData::pub_i64(AbstractValue::SignedLessThanConst(-(1 << 63)))));
+        assert_eq!("pub_i128(signed_less_than_const(-170141183460469231731687303715884105728))".parse(), Ok(AbstractData::pub_i128(AbstractValue::SignedLessThanConst(i128::MIN))));
+        assert!("pub_i128(signed_less_than_const(-170141183460469231731687303715884105729))".parse::<AbstractData>().is_err());
+        assert_eq!(
+            "pub_i128(range(0x10000000000000000, 0xffffffffffffffffffffffffffffffff))".parse(),
+            Ok(AbstractData::pub_i128(AbstractValue::Range(1 << 64, u128::MAX))),
+        );
+        assert!("array_of(sec_i8, 0x10000000000000000)".parse::<AbstractData>().is_err());
+        assert!("pub_i64(unsigned_less_than_const(-1))".parse::<AbstractData>().is_err());
+        assert!("pub_i64(-1)".parse::<AbstractData>().is_err());
+        assert_eq!("pub_pointer_to(secret_default)".parse(), Ok(AbstractData::pub_pointer_to(AbstractData::secret_default())));
+        assert_eq!("aligned_pointer_to(array_of(sec_i8, 64), 32)".parse(), Ok(AbstractData::aligned_pointer_to(AbstractData::array_of(AbstractData::sec_i8(), 64), 32)));
+        assert_eq!("vector_of(sec_i32, 8)".parse(), Ok(AbstractData::vector_of(AbstractData::sec_i32(), 8)));
+        assert_eq!("mostly_public_with_secret_range(64, 8, 32)".parse(), Ok(AbstractData::mostly_public_with_secret_range(64, 8, 32)));
+        assert!("mostly_public_with_secret_range(64, 48, 32)".parse::<AbstractData>().is_err());
+        assert_eq!(
+            "pub_pointer_to_array_of_len(sec_i32, len, 16)".parse(),
+            Ok(AbstractData::pub_pointer_to_array_of_len(AbstractData::sec_i32(), "len", 16)),
+        );
+        assert_eq!(
+            "pub_pointer_to(tuple_array(pub_pointer_to(pub_cstring(8)), pub_pointer_to(sec_cstring(4))))".parse(),
+            Ok(AbstractData::pub_pointer_to(AbstractData::tuple_array(vec![
+                AbstractData::pub_pointer_to(AbstractData::pub_cstring(8)),
+                AbstractData::pub_pointer_to(AbstractData::sec_cstring(4)),
+            ]))),
+        );
+        assert!("aligned_pointer_to(sec_i32, 3)".parse::<AbstractData>().is_err());
+        assert_eq!(
+            r#"_struct("my struct", pub_i8(3), pub_pointer_to_func(foo))"#.parse(),
+            Ok(AbstractData::_struct("my struct", vec![
+                AbstractData::pub_i8(AbstractValue::ExactValue(3)),
+                AbstractData::pub_pointer_to_func("foo"),
+            ])),
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!("sec_i33".parse::<AbstractData>().is_err());
+        assert!("sec_i32(5)".parse::<AbstractData>().is_err());
+        assert!("array_of(sec_i8, 4".parse::<AbstractData>().is_err());
+        assert!("pub_i8(3) extra".parse::<AbstractData>().is_err());
+        assert!("pub_i8(sec_i8)".parse::<AbstractData>().is_err());
+    }
+
+    #[test]
+    fn pad_args() {
+        let specs = vec![None, Some(AbstractData::sec_i32())];
+        assert_eq!(args_for_function(&specs, 3), vec![
+            AbstractData::default(),
+            AbstractData::sec_i32(),
+            AbstractData::default(),
+        ]);
+    }
+}
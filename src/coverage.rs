@@ -1,7 +1,16 @@
-use haybale::ExecutionManager;
+use crate::ConstantTimeResultForFunction;
+use either::Either;
+use haybale::{Config, ExecutionManager, State};
+use haybale::state::BBInstrIndex;
 use haybale::backend::Backend;
-use llvm_ir::{Function, Module, Name};
-use std::collections::{BTreeSet, HashMap, HashSet};
+use llvm_ir::{BasicBlock, Constant, DebugLoc, Function, HasDebugLoc, Instruction, Module, Name, Operand, Terminator};
+use llvm_ir::instruction::InlineAssembly;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
 
 #[derive(Clone)]
 struct BB<'p> {
@@ -37,19 +46,41 @@ impl<'p> Ord for BB<'p> {
     }
 }
 
-pub struct BlocksSeen<'p>(BTreeSet<BB<'p>>);
+/// Map from each block seen to the number of its instructions (counting the
+/// terminator as the last instruction) which were seen at least once. This is
+/// less than the total number of instructions only for blocks in which a path
+/// ended with an error or violation, and which weren't executed to the end on
+/// any other path.
+pub struct BlocksSeen<'p>(BTreeMap<BB<'p>, usize>);
 
 impl<'p> BlocksSeen<'p> {
     pub fn new() -> Self {
-        Self(BTreeSet::new())
+        Self(BTreeMap::new())
     }
 
-    pub fn update_with_current_path<B: Backend>(&mut self, em: &ExecutionManager<'p, B>) {
-        self.0.extend(em.state().get_path().iter().map(|pathentry| BB {
-            module: pathentry.0.module,
-            func: pathentry.0.func,
-            bbname: pathentry.0.bb.name.clone(),
-        }));
+    /// `path_ended_in_error`: if `true`, then the current path ended with an
+    /// error or violation at the current location, so the last block on the
+    /// path was executed only up to (and including) the current instruction
+    pub fn update_with_current_path<B: Backend>(&mut self, em: &ExecutionManager<'p, B>, path_ended_in_error: bool) {
+        let state = em.state();
+        let path = state.get_path();
+        for (i, pathentry) in path.iter().enumerate() {
+            let bb = pathentry.0.bb;
+            let num_instrs_seen = if path_ended_in_error && i == path.len() - 1 {
+                match state.cur_loc.instr {
+                    BBInstrIndex::Instr(idx) => idx + 1,
+                    BBInstrIndex::Terminator => bb.instrs.len() + 1,
+                }
+            } else {
+                bb.instrs.len() + 1
+            };
+            let entry = self.0.entry(BB {
+                module: pathentry.0.module,
+                func: pathentry.0.func,
+                bbname: bb.name.clone(),
+            }).or_insert(0);
+            *entry = std::cmp::max(*entry, num_instrs_seen);
+        }
     }
 
     /// Returns an iterator of all the (unique) `BB`s in the given function which
@@ -57,7 +88,7 @@ impl<'p> BlocksSeen<'p> {
     ///
     /// `funcname` must be a fully mangled name, as appears in the LLVM.
     fn seen_blocks_in_fn<'a>(&'a self, funcname: &'a str) -> impl Iterator<Item = &'a BB> {
-        self.0.iter().filter(move |bb| bb.func.name == funcname)
+        self.0.keys().filter(move |bb| bb.func.name == funcname)
     }
 
     /// Returns the percentage of basic blocks in the given function which were seen at least
@@ -76,14 +107,46 @@ impl<'p> BlocksSeen<'p> {
     /// Returns a map from (mangled) function names to the `BlockCoverage` of that
     /// function, as seen by this `BlocksSeen`.
     pub fn full_coverage_stats(&self) -> HashMap<String, BlockCoverage> {
-        let funcs_seen: HashSet<String> = self.0.iter().map(|bb| bb.func.name.clone()).collect();
+        let funcs_seen: HashSet<String> = self.0.keys().map(|bb| bb.func.name.clone()).collect();
         funcs_seen.into_iter().filter_map(|funcname| {
             BlockCoverage::new(&funcname, self).map(|bc| (funcname, bc))
         }).collect()
     }
+
+    /// Returns a map from source file names to the `LineCoverage` of that file,
+    /// as seen by this `BlocksSeen`. Source lines are determined from the debug
+    /// info of the instructions in all the functions which had at least one
+    /// block seen.
+    pub fn full_line_coverage_stats(&self) -> HashMap<String, LineCoverage> {
+        let mut line_coverage: HashMap<String, LineCoverage> = HashMap::new();
+        let mut funcs_seen: Vec<&Function> = self.0.keys().map(|bb| bb.func).collect();
+        funcs_seen.dedup_by(|a, b| std::ptr::eq(*a, *b));  // `self.0` is sorted by module and then function, so blocks of the same function are adjacent
+        for func in funcs_seen {
+            for bb in &func.basic_blocks {
+                for (file, line) in source_lines_of_block(bb) {
+                    line_coverage.entry(file).or_default().missed_lines.insert(line);
+                }
+            }
+        }
+        for (bb, &num_instrs_seen) in &self.0 {
+            let bb = bb.func.get_bb_by_name(&bb.bbname).expect("BlocksSeen should only contain blocks which exist");
+            let instr_locs = bb.instrs.iter().map(|inst| inst.get_debug_loc()).chain(std::iter::once(bb.term.get_debug_loc()));
+            for debugloc in instr_locs.take(num_instrs_seen).flatten() {
+                let (file, line) = source_line(debugloc);
+                let coverage = line_coverage.entry(file).or_default();
+                coverage.missed_lines.remove(&line);
+                coverage.covered_lines.insert(line);
+            }
+        }
+        for coverage in line_coverage.values_mut() {
+            coverage.percentage = coverage.covered_lines.len() as f64 / (coverage.covered_lines.len() + coverage.missed_lines.len()) as f64;
+        }
+        line_coverage
+    }
 }
 
 /// This struct describes block coverage of a single function.
+#[derive(Clone)]
 pub struct BlockCoverage {
     /// The percentage of basic blocks in the function which were seen at least
     /// once by the `BlocksSeen`. Will be in the range [0,1].
@@ -94,6 +157,19 @@ pub struct BlockCoverage {
 
     /// The block names in the function which were not seen by the `BlocksSeen`.
     pub missed_blocks: BTreeSet<Name>,  // BTreeSet rather than HashSet so that you can easily iterate over them in order if desired
+
+    /// Name of the LLVM module containing the function
+    pub module_name: String,
+
+    /// For each block in the function, the source lines (filename and line
+    /// number) of its instructions and terminator, according to the debug info.
+    /// Blocks with no debug info do not appear in this map.
+    pub block_source_lines: BTreeMap<Name, BTreeSet<(String, u32)>>,
+
+    /// For each block in the function, the source location (filename and line
+    /// number) of its first instruction (or terminator) which has debug info.
+    /// Blocks with no debug info do not appear in this map.
+    pub block_locations: BTreeMap<Name, (String, u32)>,
 }
 
 impl BlockCoverage {
@@ -101,7 +177,8 @@ impl BlockCoverage {
     ///
     /// Returns `None` if we seem to have seen no blocks from functions named `funcname`.
     pub fn new(funcname: &str, blocks_seen: &BlocksSeen) -> Option<Self> {
-        let func = blocks_seen.seen_blocks_in_fn(funcname).next()?.func;
+        let first_bb = blocks_seen.seen_blocks_in_fn(funcname).next()?;
+        let func = first_bb.func;
         let seen_blocks: BTreeSet<_> = blocks_seen
             .seen_blocks_in_fn(funcname)
             .map(|bb| bb.bbname.clone())
@@ -112,10 +189,224 @@ impl BlockCoverage {
             .filter(|bb| !seen_blocks.contains(&bb.name))
             .map(|bb| bb.name.clone())
             .collect();
+        let block_source_lines = func
+            .basic_blocks
+            .iter()
+            .map(|bb| (bb.name.clone(), source_lines_of_block(bb)))
+            .filter(|(_, lines)| !lines.is_empty())
+            .collect();
+        let block_locations = func
+            .basic_blocks
+            .iter()
+            .filter_map(|bb| source_location_of_block(bb).map(|loc| (bb.name.clone(), loc)))
+            .collect();
         Some(Self {
             percentage: seen_blocks.len() as f64 / (seen_blocks.len() + missed_blocks.len()) as f64,
             seen_blocks,
             missed_blocks,
+            module_name: first_bb.module.name.clone(),
+            block_source_lines,
+            block_locations,
         })
     }
+
+    /// Describe each of the `missed_blocks`, by its source location (see
+    /// `block_locations`) if known, or else by its block name
+    pub fn missed_block_descriptions(&self) -> Vec<String> {
+        self.missed_blocks.iter().map(|bbname| match self.block_locations.get(bbname) {
+            Some((file, line)) => format!("{}:{} (block {:?})", file, line, bbname),
+            None => format!("block {:?} (no debug info)", bbname),
+        }).collect()
+    }
+}
+
+/// Merge the `block_coverage` of several `ConstantTimeResultForFunction`s (e.g.,
+/// from analyzing several toplevel functions which call some of the same
+/// functions), to get the cumulative block coverage of each function.
+///
+/// A block is considered covered in the merged result if it was covered in any
+/// of the given results. So, the merged coverage percentage of each function is
+/// at least as high as its coverage percentage in any of the given results.
+pub fn merge_coverage(results: &[ConstantTimeResultForFunction]) -> HashMap<String, BlockCoverage> {
+    merge_block_coverage(results.iter().map(|result| &result.block_coverage))
+}
+
+/// Like `merge_coverage()`, but takes the `block_coverage` maps directly
+pub(crate) fn merge_block_coverage<'a>(block_coverages: impl IntoIterator<Item = &'a HashMap<String, BlockCoverage>>) -> HashMap<String, BlockCoverage> {
+    let mut merged: HashMap<String, BlockCoverage> = HashMap::new();
+    for block_coverage in block_coverages {
+        for (funcname, coverage) in block_coverage {
+            match merged.get_mut(funcname) {
+                None => {
+                    merged.insert(funcname.clone(), coverage.clone());
+                },
+                Some(merged_coverage) => {
+                    merged_coverage.seen_blocks.extend(coverage.seen_blocks.iter().cloned());
+                    let seen_blocks = &merged_coverage.seen_blocks;
+                    merged_coverage.missed_blocks.retain(|bbname| !seen_blocks.contains(bbname));
+                    merged_coverage.percentage = merged_coverage.seen_blocks.len() as f64 / (merged_coverage.seen_blocks.len() + merged_coverage.missed_blocks.len()) as f64;
+                },
+            }
+        }
+    }
+    merged
+}
+
+/// Records which functions were handled by a function hook (either a custom
+/// hook or the default hook), rather than symbolically executed, and how many
+/// times each was called.
+#[derive(Clone)]
+pub struct HookedFunctions(Rc<RefCell<HashMap<String, usize>>>);
+
+impl HookedFunctions {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(HashMap::new())))
+    }
+
+    /// Add the instruction and terminator callbacks which record calls of
+    /// hooked functions into this `HookedFunctions`
+    pub fn add_callbacks<'p, B: Backend>(&self, config: &mut Config<'p, B>) {
+        let hooked = self.clone();
+        config.callbacks.add_instruction_callback(move |inst: &Instruction, state: &State<B>| {
+            if let Instruction::Call(call) = inst {
+                hooked.record_call(&call.function, state);
+            }
+            Ok(())
+        });
+        let hooked = self.clone();
+        config.callbacks.add_terminator_callback(move |term: &Terminator, state: &State<B>| {
+            if let Terminator::Invoke(invoke) = term {
+                hooked.record_call(&invoke.function, state);
+            }
+            Ok(())
+        });
+    }
+
+    /// Record the call of the given function, if it is going to be handled by
+    /// a function hook
+    fn record_call<B: Backend>(&self, function: &Either<InlineAssembly, Operand>, state: &State<B>) {
+        let funcname = match function {
+            Either::Right(Operand::ConstantOperand(cref)) => match cref.as_ref() {
+                Constant::GlobalReference { name: Name::Name(name), .. } => name,
+                _ => return,  // we don't attempt to record calls through constant function pointers
+            },
+            _ => return,  // we don't attempt to record inline assembly or calls through function pointers
+        };
+        // functions without an LLVM definition will be handled by the default hook
+        if state.config.function_hooks.is_hooked(funcname) || state.proj.get_func_by_name(funcname).is_none() {
+            *self.0.borrow_mut().entry(funcname.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// Returns a map from (mangled) function names to the number of times that
+    /// function was called and handled by a function hook
+    pub fn counts(&self) -> HashMap<String, usize> {
+        self.0.borrow().clone()
+    }
+}
+
+/// This struct describes line coverage of a single source file.
+///
+/// A source line is considered covered if any instruction from that line was
+/// seen on any path. In a block where a path ended with an error or violation,
+/// only the instructions up to (and including) the one where the error or
+/// violation occurred are considered seen.
+#[derive(Clone, Default)]
+pub struct LineCoverage {
+    /// The percentage of source lines in the file which were covered. Will be
+    /// in the range [0,1].
+    pub percentage: f64,
+
+    /// The line numbers which were covered
+    pub covered_lines: BTreeSet<u32>,
+
+    /// The line numbers which were not covered
+    pub missed_lines: BTreeSet<u32>,
+}
+
+/// Get the source lines (filename and line number) of the instructions and
+/// terminator in the given block, according to the debug info
+fn source_lines_of_block(bb: &BasicBlock) -> BTreeSet<(String, u32)> {
+    bb.instrs
+        .iter()
+        .map(|inst| inst.get_debug_loc())
+        .chain(std::iter::once(bb.term.get_debug_loc()))
+        .filter_map(|debugloc| debugloc.as_ref().map(source_line))
+        .collect()
+}
+
+/// Get the source location (filename and line number) of the first
+/// instruction (or terminator) in the given block which has debug info
+fn source_location_of_block(bb: &BasicBlock) -> Option<(String, u32)> {
+    bb.instrs
+        .iter()
+        .map(|inst| inst.get_debug_loc())
+        .chain(std::iter::once(bb.term.get_debug_loc()))
+        .find_map(|debugloc| debugloc.as_ref().map(source_line))
+}
+
+/// Get the source file path and line number of the given `DebugLoc`
+pub(crate) fn source_line(debugloc: &DebugLoc) -> (String, u32) {
+    let filename = match &debugloc.directory {
+        Some(dir) if !Path::new(&debugloc.filename).is_absolute() => Path::new(dir).join(&debugloc.filename).to_string_lossy().into_owned(),
+        _ => debugloc.filename.clone(),
+    };
+    (filename, debugloc.line)
+}
+
+/// Write the given block coverage (e.g., the `block_coverage` field of a
+/// `ConstantTimeResultForFunction`) to `path` in LCOV format, so that it can
+/// be viewed with standard coverage tooling.
+///
+/// Each source line is reported (with a `DA` record) as covered if any block
+/// containing an instruction from that line was covered, or as not covered
+/// otherwise. Blocks with no debug info are not reported at the line level;
+/// functions with no debug info at all are instead reported only with `FN` and
+/// `FNDA` records, under their LLVM module's name.
+pub fn write_lcov(block_coverage: &HashMap<String, BlockCoverage>, path: impl AsRef<Path>) -> io::Result<()> {
+    // map from source file to (map from line number to whether that line was covered)
+    let mut lines: BTreeMap<String, BTreeMap<u32, bool>> = BTreeMap::new();
+    // map from source file (or module name) to the functions which we report in that file, with the first line of each (if known)
+    let mut funcs: BTreeMap<String, BTreeMap<&str, Option<u32>>> = BTreeMap::new();
+    for (funcname, coverage) in block_coverage {
+        if coverage.block_source_lines.is_empty() {
+            funcs.entry(coverage.module_name.clone()).or_default().insert(funcname, None);
+            continue;
+        }
+        for (bbname, bb_lines) in &coverage.block_source_lines {
+            let covered = coverage.seen_blocks.contains(bbname);
+            for (file, line) in bb_lines {
+                *lines.entry(file.clone()).or_default().entry(*line).or_default() |= covered;
+            }
+        }
+        let (file, first_line) = coverage.block_source_lines.values().flatten().min_by_key(|(_, line)| *line).unwrap();
+        funcs.entry(file.clone()).or_default().insert(funcname, Some(*first_line));
+    }
+
+    let mut w = BufWriter::new(File::create(path)?);
+    let files: BTreeSet<&String> = lines.keys().chain(funcs.keys()).collect();
+    for file in files {
+        writeln!(w, "TN:")?;
+        writeln!(w, "SF:{}", file)?;
+        if let Some(funcs) = funcs.get(file) {
+            for (funcname, first_line) in funcs {
+                writeln!(w, "FN:{},{}", first_line.unwrap_or(0), funcname)?;
+            }
+            for funcname in funcs.keys() {
+                // every function in `block_coverage` was executed at least once
+                writeln!(w, "FNDA:1,{}", funcname)?;
+            }
+            writeln!(w, "FNF:{}", funcs.len())?;
+            writeln!(w, "FNH:{}", funcs.len())?;
+        }
+        if let Some(lines) = lines.get(file) {
+            for (line, covered) in lines {
+                writeln!(w, "DA:{},{}", line, if *covered { 1 } else { 0 })?;
+            }
+            writeln!(w, "LF:{}", lines.len())?;
+            writeln!(w, "LH:{}", lines.values().filter(|covered| **covered).count())?;
+        }
+        writeln!(w, "end_of_record")?;
+    }
+    w.flush()
 }
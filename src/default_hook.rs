@@ -1,15 +1,111 @@
-use crate::secret;
+use crate::{hooks, secret};
 use either::Either;
 use haybale::{Error, Result, ReturnValue, State};
 use haybale::backend::BV;
 use haybale::function_hooks::IsCall;
 use llvm_ir::{Constant, Name, Operand, Type};
 use llvm_ir::types::NamedStructDef;
-use log::info;
+use log::{info, warn};
+use regex::Regex;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
 
-pub fn pitchfork_default_hook(
-    state: &mut State<secret::Backend>,
-    call: &dyn IsCall,
+/// The type of the function hooks which can be used in a `PatternHook`. Any
+/// function with the same signature as the hooks in
+/// [`hooks`](hooks/index.html), such as `hooks::return_secret`, can be used.
+pub type PatternHookFn = dyn for<'p> Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> + Send + Sync;
+
+/// A function hook which applies to all called functions whose names match a
+/// regular expression. See notes on `pattern_hooks` in `PitchforkConfig`.
+#[derive(Clone)]
+pub struct PatternHook {
+    pattern: Regex,
+    match_demangled: bool,
+    hook: Arc<PatternHookFn>,
+}
+
+impl PatternHook {
+    /// A `PatternHook` which applies to functions whose (mangled) names match
+    /// the regular expression `pattern` (anywhere in the name, unless anchored
+    /// with `^` or `$`)
+    pub fn new(pattern: &str, hook: impl for<'p> Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> + Send + Sync + 'static) -> std::result::Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: Regex::new(pattern)?,
+            match_demangled: false,
+            hook: Arc::new(hook),
+        })
+    }
+
+    /// Like `new()`, but `pattern` is matched against the demangled function
+    /// names (for C++ or Rust), e.g., `^std::__1::vector<.*>::push_back` to
+    /// hook all instantiations of a template method
+    pub fn new_demangled(pattern: &str, hook: impl for<'p> Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> + Send + Sync + 'static) -> std::result::Result<Self, regex::Error> {
+        Ok(Self {
+            match_demangled: true,
+            ..Self::new(pattern, hook)?
+        })
+    }
+
+    /// Does this `PatternHook` apply to the function with the given (mangled)
+    /// name
+    fn applies_to(&self, funcname: &str, state: &State<secret::Backend>) -> bool {
+        if self.match_demangled {
+            self.pattern.is_match(&state.demangle(funcname))
+        } else {
+            self.pattern.is_match(funcname)
+        }
+    }
+}
+
+impl fmt::Debug for PatternHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PatternHook")
+            .field("pattern", &self.pattern)
+            .field("match_demangled", &self.match_demangled)
+            .finish()
+    }
+}
+
+thread_local! {
+    /// Whether the `auto_stub_secret_functions` setting is active for the
+    /// current analysis
+    static AUTO_STUB_SECRET_FUNCTIONS: Cell<bool> = Cell::new(false);
+
+    /// With `auto_stub_secret_functions`, the (pretty) names of the functions
+    /// which we have already warned about stubbing
+    static AUTO_STUBBED_FUNCTIONS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+
+    /// The `pattern_hooks` for the current analysis
+    static PATTERN_HOOKS: RefCell<Vec<PatternHook>> = RefCell::new(Vec::new());
+}
+
+/// Set the `pattern_hooks` for the current analysis
+pub(crate) fn set_pattern_hooks(pattern_hooks: Vec<PatternHook>) {
+    PATTERN_HOOKS.with(|hooks| *hooks.borrow_mut() = pattern_hooks);
+}
+
+/// Set whether the `auto_stub_secret_functions` setting is active. This also
+/// forgets which functions we have already warned about.
+pub(crate) fn set_auto_stub_secret_functions(auto_stub: bool) {
+    AUTO_STUB_SECRET_FUNCTIONS.with(|a| a.set(auto_stub));
+    AUTO_STUBBED_FUNCTIONS.with(|funcs| funcs.borrow_mut().clear());
+}
+
+/// Log a warning that we're auto-stubbing the given function, unless we
+/// already have
+fn warn_auto_stubbed(pretty_funcname: &str, reason: &str) {
+    AUTO_STUBBED_FUNCTIONS.with(|funcs| {
+        if funcs.borrow_mut().insert(pretty_funcname.to_owned()) {
+            warn!("Stubbing out {}, which has no LLVM definition or function hook, even though {}, because `auto_stub_secret_functions` is set", pretty_funcname, reason);
+        }
+    })
+}
+
+pub fn pitchfork_default_hook<'p>(
+    state: &mut State<'p, secret::Backend>,
+    call: &'p dyn IsCall,
 ) -> Result<ReturnValue<secret::BV>> {
     let called_funcname = match call.get_called_func() {
         Either::Left(_) => panic!("invoked default hook for an inline assembly call"),  // this shouldn't happen
@@ -24,12 +120,32 @@ pub fn pitchfork_default_hook(
         Some(funcname) => format!("a function named {:?}", state.demangle(funcname)),
         None => "a function pointer".into(),
     };
+
+    if let Some(funcname) = called_funcname {
+        let hook = PATTERN_HOOKS.with(|hooks| {
+            hooks.borrow().iter().find(|hook| hook.applies_to(funcname, state)).map(|hook| hook.hook.clone())
+        });
+        if let Some(hook) = hook {
+            info!("Using pattern hook for {}", pretty_funcname);
+            return hook(state, call);
+        }
+    }
+
     info!("Using Pitchfork default hook for {}", pretty_funcname);
 
+    let auto_stub = AUTO_STUB_SECRET_FUNCTIONS.with(|a| a.get());
     for (i, arg) in call.get_arguments().iter().map(|(arg, _)| arg).enumerate() {
         // if the arg is secret, or points to any secret data, then raise an error and require a manually-specified hook or LLVM definition
         let arg_bv = state.operand_to_bv(arg)?;
         match is_or_points_to_secret(state, &arg_bv, &state.type_of(arg))? {
+            ArgumentKind::Secret if auto_stub => {
+                warn_auto_stubbed(&pretty_funcname, &format!("its argument #{} (zero-indexed) may refer to secret data", i));
+                return hooks::return_secret(state, call);
+            },
+            ArgumentKind::Unknown if auto_stub => {
+                // as in `hooks::propagate_taint`, assume the opaque struct doesn't contain secret data
+                warn_auto_stubbed(&pretty_funcname, &format!("its argument #{} (zero-indexed) involves an opaque struct type", i));
+            },
             ArgumentKind::Secret => match called_funcname {
                 Some(funcname) => {
                     let demangled = state.demangle(funcname);
@@ -85,7 +201,7 @@ pub(crate) fn is_or_points_to_secret(state: &mut State<secret::Backend>, bv: &se
                     Some(size) => size,
                 };
                 let mut need_pop = false;
-                if state.bvs_can_be_equal(&bv, &state.zero(bv.get_width()))? {
+                if secret::time_solver_query(secret::SolverQueryKind::Other, || state.bvs_can_be_equal(&bv, &state.zero(bv.get_width())))? {
                     // If the pointer is NULL then it clearly doesn't point to secret.
                     // So we only need to investigate the case where it's not NULL.
                     // We also need to temporarily constrain it to be not-NULL in order
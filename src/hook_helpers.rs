@@ -1,10 +1,14 @@
 //! This module contains helper functions that may be useful in writing function hooks.
 
-use crate::{allocation, secret, AbstractData, StructDescriptions};
+use crate::{allocation, secret, AbstractData, PitchforkConfig, StructDescriptions};
+use crate::default_hook::{ArgumentKind, is_or_points_to_secret};
+use crate::violation::{report_violation, CTViolationKind};
 use either::Either;
-use haybale::{Error, Project, Result, State};
+use haybale::{Error, Project, Result, ReturnValue, State};
+use haybale::function_hooks::IsCall;
 use haybale::backend::*;
 use llvm_ir::*;
+use std::convert::TryFrom;
 
 /// Fills a buffer with unconstrained data, and also outputs the number of bytes written.
 ///
@@ -116,6 +120,10 @@ pub fn fill_secret_with_length(
 
 /// Allocates space for the given `AbstractData`, initializes it, and returns a
 /// pointer to the newly-allocated space.
+///
+/// Any `AbstractData::default()`s are expanded according to the default
+/// `PitchforkConfig`; see `allocate_and_init_abstractdata_with_config()` to
+/// use the `PitchforkConfig` the analysis is using instead.
 pub fn allocate_and_init_abstractdata<'p>(
     proj: &'p Project,
     state: &mut State<'p, secret::Backend>,
@@ -123,21 +131,53 @@ pub fn allocate_and_init_abstractdata<'p>(
     ty: &Type,  // Type of the AbstractData
     sd: &'p StructDescriptions,
 ) -> Result<secret::BV> {
-    let ad = ad.to_complete(ty, proj, sd);
+    allocate_and_init_abstractdata_with_config(proj, state, ad, ty, sd, &PitchforkConfig::default())
+}
+
+/// Like `allocate_and_init_abstractdata()`, but `pitchfork_config` should be
+/// the `PitchforkConfig` the analysis is using, so that any
+/// `AbstractData::default()`s are expanded the same way as for the function
+/// parameters (e.g., with the same `default_array_length`).
+pub fn allocate_and_init_abstractdata_with_config<'p>(
+    proj: &'p Project,
+    state: &mut State<'p, secret::Backend>,
+    ad: AbstractData,
+    ty: &Type,  // Type of the AbstractData
+    sd: &'p StructDescriptions,
+    pitchfork_config: &PitchforkConfig,
+) -> Result<secret::BV> {
+    let ad = ad.to_complete(ty, proj, sd, pitchfork_config);
     let ptr = state.allocate(ad.size_in_bits() as u64);
-    let mut allocationctx = allocation::Context::new(proj, state, sd);
+    let mut allocationctx = allocation::Context::new(proj, state, sd, pitchfork_config);
     allocation::InitializationContext::blank().initialize_cad_in_memory(&mut allocationctx, &ptr, &ad, Some(ty))?;
     Ok(ptr)
 }
 
 /// Reinitializes whatever is pointed to by the given pointer, according to the
 /// given `AbstractData`.
+///
+/// As with `allocate_and_init_abstractdata()`, this uses the default
+/// `PitchforkConfig`; see `reinitialize_pointee_with_config()`.
 pub fn reinitialize_pointee<'p>(
     proj: &'p Project,
     state: &mut State<'p, secret::Backend>,
     pointer: &Operand,  // we'll reinitialize the [struct, array, whatever] that this points to
     ad: AbstractData,  // `AbstractData` describing the _pointee_ (not the pointer) and how to reinitialize it
     sd: &'p StructDescriptions,
+) -> Result<()> {
+    reinitialize_pointee_with_config(proj, state, pointer, ad, sd, &PitchforkConfig::default())
+}
+
+/// Like `reinitialize_pointee()`, but `pitchfork_config` should be the
+/// `PitchforkConfig` the analysis is using, as for
+/// `allocate_and_init_abstractdata_with_config()`.
+pub fn reinitialize_pointee_with_config<'p>(
+    proj: &'p Project,
+    state: &mut State<'p, secret::Backend>,
+    pointer: &Operand,  // we'll reinitialize the [struct, array, whatever] that this points to
+    ad: AbstractData,  // `AbstractData` describing the _pointee_ (not the pointer) and how to reinitialize it
+    sd: &'p StructDescriptions,
+    pitchfork_config: &PitchforkConfig,
 ) -> Result<()> {
     let ptr = state.operand_to_bv(pointer)?;
     let pointer_ty = state.type_of(pointer);
@@ -145,7 +185,276 @@ pub fn reinitialize_pointee<'p>(
         Type::PointerType { pointee_type, .. } => pointee_type,
         ty => return Err(Error::OtherError(format!("reinitialize_pointee: expected `pointer` to be a pointer, got {:?}", ty))),
     };
-    let mut allocationctx = allocation::Context::new(proj, state, sd);
+    let mut allocationctx = allocation::Context::new(proj, state, sd, pitchfork_config);
     allocation::InitializationContext::blank().initialize_data_in_memory(&mut allocationctx, &ptr, ad, &pointee_ty)?;
     Ok(())
 }
+
+/// A replacement for `haybale`'s built-in `memcpy` hook which first checks
+/// whether the length argument is secret, and if so, reports a constant-time
+/// violation (of kind `CTViolationKind::MemoryAddress`) naming the called
+/// function. Otherwise, this behaves exactly like the built-in hook.
+///
+/// Without this, a `memcpy` with a secret length generally produces an opaque
+/// error from deep inside the symbolic execution of the copy.
+///
+/// This is not used by default. To use it, register it for `memcpy`, and/or
+/// `memmove`, and/or the appropriate `llvm.memcpy.*` or `llvm.memmove.*`
+/// intrinsics, in `Config.function_hooks`.
+pub fn memcpy_checking_secret_length<'p>(
+    state: &mut State<'p, secret::Backend>,
+    call: &'p dyn IsCall,
+) -> Result<ReturnValue<secret::BV>> {
+    check_length_not_secret(state, call)?;
+    haybale::hooks::intrinsics::symex_memcpy(state, call)
+}
+
+/// A replacement for `haybale`'s built-in `memset` hook which first checks
+/// whether the length argument is secret, and if so, reports a constant-time
+/// violation (of kind `CTViolationKind::MemoryAddress`) naming the called
+/// function. Otherwise, this behaves exactly like the built-in hook.
+///
+/// As with `memcpy_checking_secret_length`, this is not used by default; to
+/// use it, register it for `memset` and/or the appropriate `llvm.memset.*`
+/// intrinsics in `Config.function_hooks`.
+pub fn memset_checking_secret_length<'p>(
+    state: &mut State<'p, secret::Backend>,
+    call: &'p dyn IsCall,
+) -> Result<ReturnValue<secret::BV>> {
+    check_length_not_secret(state, call)?;
+    haybale::hooks::intrinsics::symex_memset(state, call)
+}
+
+/// For `memcpy`, `memmove`, and `memset` (and their intrinsic versions), the
+/// length is the third argument. Report a constant-time violation if it is
+/// (even partially) secret.
+fn check_length_not_secret(state: &State<secret::Backend>, call: &dyn IsCall) -> Result<()> {
+    let funcname = called_function_name(call);
+    let num_bytes = match call.get_arguments().get(2) {
+        Some((num_bytes, _)) => num_bytes,
+        None => return Err(Error::OtherError(format!("Call to {} has fewer than 3 arguments", funcname))),
+    };
+    if state.operand_to_bv(num_bytes)?.is_secret() {
+        Err(report_violation(CTViolationKind::MemoryAddress, format!("call to {} with a secret length", funcname)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Get a printable name for the function called by `call`
+fn called_function_name(call: &dyn IsCall) -> String {
+    match call.get_called_func() {
+        Either::Right(Operand::ConstantOperand(cref)) => match cref.as_ref() {
+            Constant::GlobalReference { name: Name::Name(name), .. } => format!("{:?}", name),
+            _ => "a function pointer".into(),
+        },
+        _ => "a function pointer".into(),
+    }
+}
+
+/// Get a hook which returns a secret value of `return_width` bits if any of
+/// the arguments are secret, or if any of the arguments contain a pointer to
+/// any secret data. Otherwise, the hook returns an unconstrained public value
+/// of `return_width` bits. If `return_width` is 0, the hook returns void.
+///
+/// This is similar to `hooks::propagate_taint`, but uses the given
+/// `return_width` rather than the return type of the call. Like that hook, it
+/// assumes that opaque struct types do not contain secret data or pointers to
+/// secret data.
+///
+/// This can be used to stub out opaque library functions, e.g.,
+/// `config.function_hooks.add("some_primitive", &taint_propagating_stub_hook(32))`.
+pub fn taint_propagating_stub_hook<'p>(
+    return_width: u32,
+) -> impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> {
+    move |state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall| {
+        let any_secret = any_arg_is_or_points_to_secret(state, call)?;
+        if return_width == 0 {
+            Ok(ReturnValue::ReturnVoid)
+        } else if any_secret {
+            Ok(ReturnValue::Return(secret::BV::Secret {
+                btor: state.solver.clone(),
+                width: return_width,
+                symbol: Some("taint_propagating_stub_retval".into()),
+            }))
+        } else {
+            Ok(ReturnValue::Return(state.new_bv_with_name(Name::from("taint_propagating_stub_retval"), return_width)?))
+        }
+    }
+}
+
+/// Get a hook which classifies the call's arguments as `hooks::propagate_taint`
+/// and `taint_propagating_stub_hook` do, and then dispatches to `secret_hook`
+/// if any of the arguments are secret or contain a pointer to any secret data,
+/// or to `public_hook` otherwise. Like those hooks, this assumes that opaque
+/// struct types do not contain secret data or pointers to secret data.
+///
+/// Note that a function hook always replaces the function it hooks, so there
+/// is no way for `public_hook` to fall back on executing the function's LLVM
+/// definition. `public_hook` must instead model the function's behavior on
+/// public arguments, e.g., with `haybale::function_hooks::generic_stub_hook`
+/// or a hook written for the purpose.
+///
+/// For example, to stub out `some_primitive` as returning a secret only when
+/// it is given secret data:
+/// `config.function_hooks.add("some_primitive", &conditional_on_secret(generic_stub_hook, hooks::return_secret))`.
+pub fn conditional_on_secret<'p>(
+    public_hook: impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>>,
+    secret_hook: impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>>,
+) -> impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> {
+    move |state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall| {
+        if any_arg_is_or_points_to_secret(state, call)? {
+            secret_hook(state, call)
+        } else {
+            public_hook(state, call)
+        }
+    }
+}
+
+/// Is any argument of the `call` secret, or does it contain a pointer to any
+/// secret data. Arguments involving opaque struct types are assumed not to.
+fn any_arg_is_or_points_to_secret<'p>(state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall) -> Result<bool> {
+    for arg in call.get_arguments().iter().map(|(arg, _)| arg) {
+        let arg_bv = state.operand_to_bv(arg)?;
+        match is_or_points_to_secret(state, &arg_bv, &state.type_of(arg))? {
+            ArgumentKind::Public | ArgumentKind::Unknown => {},
+            ArgumentKind::Secret => return Ok(true),
+        }
+    }
+    Ok(false)
+}
+
+/// Get a hook which models a randomness source (e.g., a DRBG or entropy
+/// function which fills a caller-provided buffer) without executing it.
+///
+/// The hook fills the buffer pointed to by the call's `ptr_arg_index`th
+/// argument (counting from 0) with fresh unconstrained public bytes. The
+/// number of bytes is given by the `len_arg_index`th argument, which must have
+/// a single possible value; a secret length is reported as a constant-time
+/// violation (of kind `CTViolationKind::MemoryAddress`). The hook returns 0
+/// (the usual success value), or void for void-typed functions.
+///
+/// For example, for `int mbedtls_ctr_drbg_random(void *p_rng, unsigned char *output, size_t output_len)`:
+/// `config.function_hooks.add("mbedtls_ctr_drbg_random", &fill_buffer_with_public_symbolic(1, 2))`.
+pub fn fill_buffer_with_public_symbolic<'p>(
+    ptr_arg_index: usize,
+    len_arg_index: usize,
+) -> impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> {
+    move |state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall| {
+        let funcname = called_function_name(call);
+        let arg = |idx: usize| call.get_arguments().get(idx).map(|(arg, _)| arg).ok_or_else(|| Error::OtherError(format!(
+            "fill_buffer_with_public_symbolic: call to {} has {} arguments, but expected an argument at index {}",
+            funcname,
+            call.get_arguments().len(),
+            idx,
+        )));
+        let ptr = state.operand_to_bv(arg(ptr_arg_index)?)?;
+        let len = state.operand_to_bv(arg(len_arg_index)?)?;
+        if len.is_secret() {
+            return Err(report_violation(CTViolationKind::MemoryAddress, format!("call to {} with a secret length", funcname)));
+        }
+        let len = match len.as_u64() {
+            Some(len) => len,
+            None => match state.get_a_solution_for_bv(&len)?.and_then(|solution| solution.as_u64()) {
+                Some(candidate) if state.bvs_must_be_equal(&len, &state.bv_from_u64(candidate, len.get_width()))? => candidate,
+                _ => return Err(Error::OtherError(format!("fill_buffer_with_public_symbolic: call to {} with a length which may have more than one value", funcname))),
+            },
+        };
+        if len > 0 {
+            let bits = len.checked_mul(8).and_then(|bits| u32::try_from(bits).ok())
+                .ok_or_else(|| Error::OtherError(format!("fill_buffer_with_public_symbolic: call to {} with a length of {} bytes, which is too large", funcname, len)))?;
+            let bytes = state.new_bv_with_name(Name::from(format!("{}_output", funcname.trim_matches('"'))), bits)?;
+            state.write(&ptr, bytes)?;
+        }
+        match state.type_of(call).as_ref() {
+            Type::VoidType => Ok(ReturnValue::ReturnVoid),
+            ty => {
+                let width = state.size_in_bits(&ty)
+                    .ok_or_else(|| Error::OtherError("Call return type is an opaque struct type".into()))?;
+                Ok(ReturnValue::Return(state.zero(width)))
+            },
+        }
+    }
+}
+
+/// Get a hook for `malloc()` (or similar functions taking the size in bytes as
+/// their first argument and returning a pointer to fresh memory) which
+/// allocates the memory with `state.allocate()`, rather than executing an
+/// allocator implementation.
+///
+/// If the size is not constant, it is constrained to be at most `max_bytes`,
+/// and `max_bytes` bytes are allocated. A constant size greater than
+/// `max_bytes` is an error. A secret size is reported as a constant-time
+/// violation (of kind `CTViolationKind::MemoryAddress`).
+///
+/// The allocated memory is left uninitialized.
+///
+/// For example, `config.function_hooks.add("malloc", &malloc_hook(4096))`.
+pub fn malloc_hook<'p>(
+    max_bytes: u64,
+) -> impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> {
+    move |state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall| {
+        let size = match call.get_arguments().get(0) {
+            Some((size, _)) => state.operand_to_bv(size)?,
+            None => return Err(Error::OtherError(format!("malloc_hook: call to {} has no arguments", called_function_name(call)))),
+        };
+        let (addr, _) = allocate_bounded(state, call, &size, max_bytes)?;
+        Ok(ReturnValue::Return(addr))
+    }
+}
+
+/// Like `malloc_hook()`, but for `calloc()`: the size is the product of the
+/// first two arguments, and the allocated memory is zero-initialized.
+///
+/// For example, `config.function_hooks.add("calloc", &calloc_hook(4096))`.
+pub fn calloc_hook<'p>(
+    max_bytes: u64,
+) -> impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> {
+    move |state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall| {
+        let (num, size) = match call.get_arguments().as_slice() {
+            [(num, _), (size, _), ..] => (state.operand_to_bv(num)?, state.operand_to_bv(size)?),
+            _ => return Err(Error::OtherError(format!("calloc_hook: call to {} has fewer than 2 arguments", called_function_name(call)))),
+        };
+        let (addr, bytes) = allocate_bounded(state, call, &num.mul(&size), max_bytes)?;
+        if bytes > 0 {
+            let bits = u32::try_from(bytes * 8).map_err(|_| Error::OtherError(format!("calloc_hook: allocation of {} bytes is too large", bytes)))?;
+            let zeroes = state.zero(bits);
+            state.write(&addr, zeroes)?;
+        }
+        Ok(ReturnValue::Return(addr))
+    }
+}
+
+/// A hook for `free()` which does nothing. (Memory allocated with
+/// `malloc_hook()` or `calloc_hook()` is never reused, so it doesn't need to
+/// be freed.)
+pub fn free_hook(
+    _state: &mut State<secret::Backend>,
+    _call: &dyn IsCall,
+) -> Result<ReturnValue<secret::BV>> {
+    Ok(ReturnValue::ReturnVoid)
+}
+
+/// Allocate memory for an allocation of `size` bytes, as described in the
+/// docs on `malloc_hook()`. Returns the address, and the number of bytes
+/// actually allocated.
+fn allocate_bounded(state: &mut State<secret::Backend>, call: &dyn IsCall, size: &secret::BV, max_bytes: u64) -> Result<(secret::BV, u64)> {
+    let funcname = called_function_name(call);
+    if size.is_secret() {
+        return Err(report_violation(CTViolationKind::MemoryAddress, format!("call to {} with a secret size", funcname)));
+    }
+    let bytes = match size.as_u64() {
+        Some(bytes) if bytes > max_bytes => return Err(Error::OtherError(format!(
+            "Call to {} requested {} bytes, which is more than the maximum of {} bytes for this hook",
+            funcname, bytes, max_bytes,
+        ))),
+        Some(bytes) => bytes,
+        None => {
+            size.ulte(&state.bv_from_u64(max_bytes, size.get_width())).assert()?;
+            max_bytes
+        },
+    };
+    // allocate at least one byte, so that each allocation gets a distinct address
+    let addr = state.allocate(std::cmp::max(bytes, 1) * 8);
+    Ok((addr, bytes))
+}
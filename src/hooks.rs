@@ -3,8 +3,9 @@
 
 use crate::default_hook::{ArgumentKind, is_or_points_to_secret};
 use crate::secret;
+use haybale::backend::BV;
 use haybale::function_hooks::{IsCall, generic_stub_hook};
-use haybale::{Error, Result, ReturnValue, State};
+use haybale::{Config, Error, Result, ReturnValue, State};
 use llvm_ir::Type;
 
 /// This hook will ignore all of the function arguments and simply return an
@@ -61,3 +62,163 @@ pub fn propagate_taint(
     // if we got here, we didn't find any secret data
     return_public_unconstrained(state, call)
 }
+
+/// Get a hook for a constant-time comparison function in the style of
+/// `memcmp`, such as `CRYPTO_memcmp` or `consttime_memequal`, which compares
+/// `len` bytes.
+///
+/// The hook assumes the first two arguments to the hooked function are
+/// pointers to the two buffers to compare. It reads `len` bytes from each
+/// buffer (so the pointers themselves must not be secret), and returns a secret
+/// value of the appropriate size if either buffer contains any secret data, or
+/// an unconstrained public value otherwise. In particular, it never branches
+/// on the contents of the buffers.
+///
+/// Note that this hook _trusts_ that the hooked function is genuinely
+/// constant-time: the function itself is never analyzed. It is meant for
+/// cutting off analysis at a comparison primitive which is already known to be
+/// constant-time, e.g., because its internal loop bounds would otherwise
+/// produce spurious violations or errors.
+///
+/// To use this, register it for the appropriate function, e.g.,
+/// `config.function_hooks.add("CRYPTO_memcmp", &ct_memcmp_hook(32))`.
+pub fn ct_memcmp_hook<'p>(
+    len: u32,
+) -> impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> {
+    move |state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall| {
+        let args = call.get_arguments();
+        if args.len() < 2 {
+            return Err(Error::OtherError(format!("ct_memcmp_hook: expected at least 2 arguments, got {}", args.len())));
+        }
+        let mut any_secret = false;
+        for (arg, _) in &args[.. 2] {
+            match state.type_of(arg).as_ref() {
+                Type::PointerType { .. } => {},
+                ty => return Err(Error::OtherError(format!("ct_memcmp_hook: expected the first two arguments to be pointers, got {:?}", ty))),
+            }
+            if len > 0 {
+                let ptr = state.operand_to_bv(arg)?;
+                if state.read(&ptr, len * 8)?.is_secret() {
+                    any_secret = true;
+                }
+            }
+        }
+        if any_secret {
+            return_secret(state, call)
+        } else {
+            return_public_unconstrained(state, call)
+        }
+    }
+}
+
+/// Names of some common constant-time select helpers, for use with
+/// `add_ct_select_hooks()`. Each takes a condition (or mask) as its first
+/// argument and the two values to select between as its second and third
+/// arguments, and returns the selected value; see `ct_select()`.
+pub const CT_SELECT_FUNCTION_NAMES: &[&str] = &[
+    "constant_time_select",
+    "constant_time_select_8",
+    "constant_time_select_32",
+    "constant_time_select_64",
+    "constant_time_select_int",
+    "constant_time_select_w",
+    "ct_select",
+    "ct_select_u8",
+    "ct_select_u32",
+    "ct_select_u64",
+    "ct_select_size_t",
+];
+
+/// This hook models a trusted constant-time select helper, such as OpenSSL's
+/// `constant_time_select(mask, a, b)`: it returns the second argument if the
+/// first argument is nonzero, or the third argument otherwise.
+///
+/// Unlike an LLVM `select` instruction, a secret condition is never considered
+/// a violation (regardless of the `secret_select_is_violation` setting in
+/// `PitchforkConfig`), nor warned about: the helper is trusted to be
+/// constant-time, and the function itself is never analyzed. The result is
+/// secret if the condition or either value is secret.
+///
+/// Helpers taking a mask which is all-zeroes or all-ones (rather than an
+/// arbitrary nonzero condition) are modeled correctly for those masks.
+///
+/// To use this, register it for the appropriate functions, e.g. with
+/// `add_ct_select_hooks()`.
+pub fn ct_select(
+    state: &mut State<secret::Backend>,
+    call: &dyn IsCall,
+) -> Result<ReturnValue<secret::BV>> {
+    let args = call.get_arguments();
+    if args.len() != 3 {
+        return Err(Error::OtherError(format!("ct_select: expected 3 arguments, got {}", args.len())));
+    }
+    let cond = state.operand_to_bv(&args[0].0)?;
+    let truebv = state.operand_to_bv(&args[1].0)?;
+    let falsebv = state.operand_to_bv(&args[2].0)?;
+    if truebv.get_width() != falsebv.get_width() {
+        return Err(Error::OtherError(format!("ct_select: expected the two values to have the same width, got {} and {} bits", truebv.get_width(), falsebv.get_width())));
+    }
+    let cond = cond._ne(&state.zero(cond.get_width()));
+    Ok(ReturnValue::Return(trusted_select(&cond, &truebv, &falsebv)))
+}
+
+/// Get a hook for a trusted constant-time conditional swap helper which swaps
+/// two buffers of `len` bytes, such as `BN_consttime_swap` or a `cswap` in an
+/// elliptic-curve ladder.
+///
+/// The hook assumes the first argument to the hooked function is the condition
+/// (or mask), and the second and third arguments are pointers to the two
+/// buffers (so the pointers themselves must not be secret). If the condition is
+/// nonzero, the contents of the buffers are swapped. As with `ct_select()`, a
+/// secret condition is not considered a violation; it just makes the contents
+/// of both buffers secret. Any return value is an unconstrained public value.
+///
+/// To use this, register it for the appropriate function, e.g.,
+/// `config.function_hooks.add("x25519_cswap", &ct_swap_hook(32))`.
+pub fn ct_swap_hook<'p>(
+    len: u32,
+) -> impl Fn(&mut State<'p, secret::Backend>, &'p dyn IsCall) -> Result<ReturnValue<secret::BV>> {
+    move |state: &mut State<'p, secret::Backend>, call: &'p dyn IsCall| {
+        let args = call.get_arguments();
+        if args.len() < 3 {
+            return Err(Error::OtherError(format!("ct_swap_hook: expected at least 3 arguments, got {}", args.len())));
+        }
+        if len > 0 {
+            let cond = state.operand_to_bv(&args[0].0)?;
+            let cond = cond._ne(&state.zero(cond.get_width()));
+            let a_ptr = state.operand_to_bv(&args[1].0)?;
+            let b_ptr = state.operand_to_bv(&args[2].0)?;
+            let a = state.read(&a_ptr, len * 8)?;
+            let b = state.read(&b_ptr, len * 8)?;
+            state.write(&a_ptr, trusted_select(&cond, &b, &a))?;
+            state.write(&b_ptr, trusted_select(&cond, &a, &b))?;
+        }
+        return_public_unconstrained(state, call)
+    }
+}
+
+/// Register `ct_select()` as the hook for each of the given function names.
+/// `CT_SELECT_FUNCTION_NAMES` contains some common names; users can of course
+/// pass their own names instead, or in addition, e.g.
+///
+/// ```ignore
+/// add_ct_select_hooks(&mut config, CT_SELECT_FUNCTION_NAMES.iter().copied().chain(vec!["my_select"]));
+/// ```
+pub fn add_ct_select_hooks<'a>(
+    config: &mut Config<secret::Backend>,
+    funcnames: impl IntoIterator<Item = &'a str>,
+) {
+    for funcname in funcnames {
+        config.function_hooks.add(funcname, &ct_select);
+    }
+}
+
+/// Like `cond.cond_bv(truebv, falsebv)`, but without warning about (or
+/// otherwise treating specially) a secret `cond`
+fn trusted_select(cond: &secret::BV, truebv: &secret::BV, falsebv: &secret::BV) -> secret::BV {
+    match cond {
+        secret::BV::Public(_) => cond.cond_bv(truebv, falsebv),
+        secret::BV::Secret { btor, .. } => secret::BV::Secret { btor: btor.clone(), width: truebv.get_width(), symbol: None },
+        secret::BV::PartiallySecret { data, .. } => secret::BV::Secret { btor: data.get_btor().clone().into(), width: truebv.get_width(), symbol: None },
+    }
+}
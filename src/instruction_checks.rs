@@ -0,0 +1,103 @@
+//! Optional checks for constant-time violations which can't be detected by the
+//! `secret::Backend` alone, because they depend on which LLVM instruction is
+//! being executed. These are implemented as `haybale` instruction and
+//! terminator callbacks, which run before each instruction (or terminator) is
+//! executed.
+
+use crate::{secret, PitchforkConfig, VariableTimeOpcode};
+use crate::violation::{record_secret_branch, report_violation, CTViolationKind};
+use haybale::{Config, Result, State};
+use llvm_ir::{Instruction, Operand, Terminator};
+use log::warn;
+use std::collections::HashSet;
+
+/// Add the instruction and terminator callbacks required by the given
+/// `PitchforkConfig` to the given `Config`
+pub(crate) fn add_instruction_checks(config: &mut Config<secret::Backend>, pitchfork_config: &PitchforkConfig) {
+    config.callbacks.add_instruction_callback(check_vector_element_index);
+    if pitchfork_config.secret_select_is_violation {
+        config.callbacks.add_instruction_callback(check_select_condition);
+    }
+    if !pitchfork_config.variable_time_opcodes.is_empty() {
+        let opcodes = pitchfork_config.variable_time_opcodes.clone();
+        config.callbacks.add_instruction_callback(move |inst: &Instruction, state: &State<secret::Backend>| {
+            check_variable_time_operands(&opcodes, inst, state)
+        });
+    }
+    if pitchfork_config.continue_past_secret_branches {
+        config.callbacks.add_terminator_callback(record_secret_branches);
+    }
+}
+
+/// For use with `continue_past_secret_branches`: since secret branch
+/// conditions won't produce errors in that mode, record each branch on a
+/// secret condition here instead
+fn record_secret_branches(term: &Terminator, state: &State<secret::Backend>) -> Result<()> {
+    let condition = match term {
+        Terminator::CondBr(condbr) => &condbr.condition,
+        Terminator::Switch(switch) => &switch.operand,
+        _ => return Ok(()),
+    };
+    if state.operand_to_bv(condition)?.is_secret() {
+        warn!("Branch on a secret condition in {:?}, block {}; continuing past it because `continue_past_secret_branches` is set", state.cur_loc.func.name, state.cur_loc.bb.name);
+        record_secret_branch(state);
+    }
+    Ok(())
+}
+
+/// Report a constant-time violation for any `select` instruction whose
+/// condition is secret
+fn check_select_condition(inst: &Instruction, state: &State<secret::Backend>) -> Result<()> {
+    if let Instruction::Select(select) = inst {
+        let condition = state.operand_to_bv(&select.condition)?;
+        if condition.is_secret() {
+            return Err(report_violation(CTViolationKind::Select, "'select' operation with a secret condition"));
+        }
+    }
+    Ok(())
+}
+
+/// Report a constant-time violation for any `extractelement` or
+/// `insertelement` instruction whose element index is (even partially)
+/// secret. Selecting a vector lane by a secret index is typically compiled to
+/// a memory access at a secret-dependent address, so we report it as such.
+///
+/// (`shufflevector` needs no check, as its mask is always a constant.)
+fn check_vector_element_index(inst: &Instruction, state: &State<secret::Backend>) -> Result<()> {
+    let (opname, index) = match inst {
+        Instruction::ExtractElement(ee) => ("extractelement", &ee.index),
+        Instruction::InsertElement(ie) => ("insertelement", &ie.index),
+        _ => return Ok(()),
+    };
+    if state.operand_to_bv(index)?.is_secret() {
+        return Err(report_violation(
+            CTViolationKind::MemoryAddress,
+            format!("'{}' operation with a secret element index", opname),
+        ));
+    }
+    Ok(())
+}
+
+/// Report a constant-time violation for any instruction in `opcodes` with an
+/// operand which is (even partially) secret
+fn check_variable_time_operands(opcodes: &HashSet<VariableTimeOpcode>, inst: &Instruction, state: &State<secret::Backend>) -> Result<()> {
+    let (opcode, opname, operand0, operand1): (_, _, &Operand, &Operand) = match inst {
+        Instruction::Mul(mul) => (VariableTimeOpcode::Mul, "mul", &mul.operand0, &mul.operand1),
+        Instruction::UDiv(udiv) => (VariableTimeOpcode::UDiv, "udiv", &udiv.operand0, &udiv.operand1),
+        Instruction::SDiv(sdiv) => (VariableTimeOpcode::SDiv, "sdiv", &sdiv.operand0, &sdiv.operand1),
+        Instruction::URem(urem) => (VariableTimeOpcode::URem, "urem", &urem.operand0, &urem.operand1),
+        Instruction::SRem(srem) => (VariableTimeOpcode::SRem, "srem", &srem.operand0, &srem.operand1),
+        _ => return Ok(()),
+    };
+    if opcodes.contains(&opcode) {
+        for operand in &[operand0, operand1] {
+            if state.operand_to_bv(operand)?.is_secret() {
+                return Err(report_violation(
+                    CTViolationKind::VariableTimeOperation,
+                    format!("'{}' operation, which is configured as variable-time, with a secret operand", opname),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use crate::{ConstantTimeResultForFunction, ConstantTimeResultForPath};
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Write the given results to `path` as a JSON array, with one object per
+/// analyzed function, for consumption by automated tooling.
+///
+/// Each object contains the function name; whether the function was found to
+/// be constant-time; the `PathStatistics`; a description of each constant-time
+/// violation and each other error encountered; the block coverage percentage
+/// of each function encountered; and the number of calls of each hooked
+/// function.
+pub fn write_json(results: &[ConstantTimeResultForFunction], path: impl AsRef<Path>) -> io::Result<()> {
+    let jsons: Vec<Value> = results.iter().map(result_to_json).collect();
+    write_json_array(&jsons, path)
+}
+
+/// Write the given JSON values (as produced by `result_to_json()`) to `path` as
+/// a JSON array
+pub(crate) fn write_json_array(jsons: &[Value], path: impl AsRef<Path>) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut w, jsons)?;
+    writeln!(w)?;
+    w.flush()
+}
+
+/// Get a JSON object describing the given result. See `write_json()`.
+pub(crate) fn result_to_json(result: &ConstantTimeResultForFunction) -> Value {
+    let path_stats = result.path_statistics();
+    let violations: Vec<Value> = result.path_results.iter().filter_map(|path_result| match path_result {
+        ConstantTimeResultForPath::NotConstantTime { violation } => Some(json!({
+            "kind": format!("{:?}", violation.kind),
+            "message": violation.msg,
+            "function": violation.funcname,
+            "block": violation.bbname.to_string(),
+            "source_location": violation.source_loc.as_ref().map(|source_loc| source_loc.to_string()),
+            "call_stack": violation.call_stack.iter().map(|frame| frame.funcname.as_str()).collect::<Vec<&str>>(),
+            "post_violation": violation.post_violation,
+        })),
+        _ => None,
+    }).collect();
+    let errors: Vec<String> = result.path_results.iter().filter_map(|path_result| match path_result {
+        ConstantTimeResultForPath::OtherError { error, .. } => Some(error.to_string()),
+        ConstantTimeResultForPath::TimedOut { timeout } => Some(format!("analysis timed out after {:?}", timeout)),
+        _ => None,
+    }).collect();
+    // percentages, rounded to one decimal place
+    let block_coverage: Map<String, Value> = result.block_coverage.iter()
+        .map(|(funcname, coverage)| (funcname.clone(), json!((1000.0 * coverage.percentage).round() / 10.0)))
+        .collect();
+    let counts = [
+        ("num_ct_paths", path_stats.num_ct_paths),
+        ("num_ct_violations", path_stats.num_ct_violations),
+        ("num_secret_branches", path_stats.num_secret_branches),
+        ("num_secret_memory_addresses", path_stats.num_secret_memory_addresses),
+        ("num_secret_selects", path_stats.num_secret_selects),
+        ("num_variable_time_operations", path_stats.num_variable_time_operations),
+        ("num_secret_loop_bounds", path_stats.num_secret_loop_bounds),
+        ("num_hook_violations", path_stats.num_hook_violations),
+        ("num_unsats", path_stats.num_unsats),
+        ("num_loop_bound_exceeded", path_stats.num_loop_bound_exceeded),
+        ("num_null_ptr_deref", path_stats.num_null_ptr_deref),
+        ("num_function_not_found", path_stats.num_function_not_found),
+        ("num_solver_errors", path_stats.num_solver_errors),
+        ("num_solver_timeouts", path_stats.num_solver_timeouts),
+        ("num_unsupported_instruction", path_stats.num_unsupported_instruction),
+        ("num_malformed_instruction", path_stats.num_malformed_instruction),
+        ("num_unreachable_instruction", path_stats.num_unreachable_instruction),
+        ("num_failed_resolve_fptr", path_stats.num_failed_resolve_fptr),
+        ("num_hook_retval_mismatch", path_stats.num_hook_retval_mismatch),
+        ("num_other_errors", path_stats.num_other_errors),
+        ("num_timeouts", path_stats.num_timeouts),
+        ("total_instructions", path_stats.total_instructions),
+    ];
+    let path_statistics: Map<String, Value> = counts.iter().map(|(name, count)| (name.to_string(), json!(count))).collect();
+
+    json!({
+        "funcname": result.funcname,
+        "is_constant_time": result.path_results.len() == path_stats.num_ct_paths,
+        "timed_out": result.timed_out(),
+        "exhaustive": result.exhaustive,
+        "path_statistics": path_statistics,
+        "violations": violations,
+        "errors": errors,
+        "block_coverage_percent": block_coverage,
+        "hooked_functions": result.hooked_functions,
+    })
+}
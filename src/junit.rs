@@ -0,0 +1,70 @@
+use crate::{ConstantTimeResultForFunction, ConstantTimeResultForPath};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Write the given results to `path` as a JUnit-style XML report, so that they
+/// can be displayed by CI systems which understand that format.
+///
+/// The report consists of a single `testsuite`, with one `testcase` per
+/// analyzed function:
+///
+/// - if any constant-time violation was found in the function, the testcase
+///   contains a `failure` with the message from `first_ct_violation()`;
+/// - otherwise, if any other error was encountered, the testcase contains an
+///   `error` with the message of the first such error;
+/// - otherwise, the testcase passes.
+///
+/// Each testcase also has `properties` giving the number of paths analyzed and
+/// the block coverage of the toplevel function.
+pub fn write_junit_xml(results: &[ConstantTimeResultForFunction], path: impl AsRef<Path>) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    let num_failures = results.iter().filter(|result| result.first_ct_violation().is_some()).count();
+    let num_errors = results.iter()
+        .filter(|result| result.first_ct_violation().is_none() && result.first_error_or_violation().is_some())
+        .count();
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<testsuite name="pitchfork" tests="{}" failures="{}" errors="{}">"#, results.len(), num_failures, num_errors)?;
+    for result in results {
+        writeln!(w, r#"  <testcase name="{}" classname="pitchfork">"#, escape(result.funcname))?;
+
+        writeln!(w, "    <properties>")?;
+        writeln!(w, r#"      <property name="paths" value="{}"/>"#, result.path_results.len())?;
+        if let Some(coverage) = result.block_coverage.get(result.mangled_funcname) {
+            writeln!(w, r#"      <property name="block_coverage_percent" value="{:.1}"/>"#, 100.0 * coverage.percentage)?;
+        }
+        writeln!(w, "    </properties>")?;
+
+        if let Some(violation) = result.first_ct_violation() {
+            writeln!(w, r#"    <failure message="{}" type="ConstantTimeViolation">{}</failure>"#,
+                escape(&format!("{} is not constant-time: {}", result.funcname, violation.kind)),
+                escape(&violation.full_message),
+            )?;
+        } else if let Some(ConstantTimeResultForPath::OtherError { error, full_message, .. }) = result.first_error_or_violation() {
+            writeln!(w, r#"    <error message="{}">{}</error>"#, escape(&error.to_string()), escape(full_message))?;
+        } else if let Some(ConstantTimeResultForPath::TimedOut { timeout }) = result.first_error_or_violation() {
+            writeln!(w, r#"    <error message="{}" type="TimedOut"/>"#, escape(&format!("analysis timed out after {:?}", timeout)))?;
+        }
+
+        writeln!(w, "  </testcase>")?;
+    }
+    writeln!(w, "</testsuite>")?;
+    w.flush()
+}
+
+/// Escape `s` for use in XML text or attribute values
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -10,47 +10,97 @@
 
 mod abstractdata;
 pub use abstractdata::*;
+#[cfg(feature = "serde")]
+mod abstractdata_serde;
 mod allocation;
+mod args_file;
+pub use allocation::AllocatedArg;
 mod coverage;
 use coverage::*;
+pub use coverage::{merge_coverage, write_lcov};
 mod default_hook;
 use default_hook::pitchfork_default_hook;
+pub use default_hook::{PatternHook, PatternHookFn};
 pub mod hooks;
 pub mod hook_helpers;
+mod instruction_checks;
 pub mod secret;
+mod json;
+pub use json::write_json;
+mod junit;
+pub use junit::write_junit_xml;
 mod path_statistics;
-pub use path_statistics::PathStatistics;
+pub use path_statistics::{PathMetrics, PathStatistics};
 mod pitchfork_config;
-pub use pitchfork_config::PitchforkConfig;
+pub use pitchfork_config::{PitchforkConfig, VariableTimeOpcode};
+mod project_cache;
+pub use project_cache::cached_project;
+mod result_diff;
+pub use result_diff::{diff_results, FunctionChange, FunctionDiff, ResultDiff};
+mod violation;
+pub use violation::{ArgCounterexample, CallFrame, CTViolation, CTViolationKind, PathErrorSummary};
+mod scenarios;
+pub use scenarios::{check_for_ct_violation_multi, MultiScenarioResult};
+mod secret_reads;
+use secret_reads::SecretArgReads;
+mod watchpoint_hits;
+use watchpoint_hits::WatchpointHits;
+pub use watchpoint_hits::{WatchpointAccess, WatchpointHit};
 mod logging;
 mod progress;
 mod main_func;
-pub use main_func::main_func;
+pub use main_func::{main_func, main_func_with_status, EXIT_ERROR, EXIT_SUCCESS, EXIT_VIOLATION};
 
 use colored::*;
-use haybale::{symex_function, backend::Backend, ExecutionManager, State, ReturnValue};
+use haybale::{symex_function, backend::{Backend, BV}, ExecutionManager, State, ReturnValue};
 use haybale::{Error, Result};
 pub use haybale::{Config, Project};
+use llvm_ir::Name;
 use haybale::function_hooks::IsCall;
 use lazy_static::lazy_static;
 use log::{debug, info, warn};
-use std::collections::{HashMap, HashSet};
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 /// Holds information about the results of a constant-time analysis of a single
 /// path.
 #[derive(Clone, Debug)]
 pub enum ConstantTimeResultForPath {
-    IsConstantTime,
+    IsConstantTime {
+        /// Whether the value returned on this path was secret, partially
+        /// secret, or public; or `None` if no value was returned (e.g., the
+        /// function returns `void`).
+        return_secrecy: Option<secret::Secrecy>,
+    },
+    /// A constant-time violation. Besides the violations `pitchfork` detects
+    /// itself, this includes errors returned by user-written function hooks
+    /// whose message begins with "Constant-time violation:"; these have kind
+    /// `CTViolationKind::Hook`.
     NotConstantTime {
-        /// A `String` describing the violation found on this path.
-        violation_message: String,
+        /// Describes the violation found on this path.
+        violation: CTViolation,
     },
     OtherError {
         /// The `Error` encountered on this path.
         error: Error,
         /// The full error message with "rich context" (backtrace, full path, etc)
         full_message: String,
+        /// `true` if this path was explored past a secret branch; see notes on
+        /// `post_violation` in `CTViolation`.
+        post_violation: bool,
+        /// A short summary of where and how the path failed. `None` if the
+        /// analysis panicked, in which case no details are available.
+        summary: Option<PathErrorSummary>,
+    },
+    /// Not actually the result of a path: indicates that the
+    /// `per_function_timeout` in `PitchforkConfig` expired, so we stopped
+    /// analyzing the function. This is always the last result for the function.
+    TimedOut {
+        /// The timeout which expired
+        timeout: Duration,
     },
 }
 
@@ -69,15 +119,45 @@ pub struct ConstantTimeResultForFunction<'a> {
     /// We simply have no way of knowing how many more paths there might be
     /// beyond one of these errors.
     pub path_results: Vec<ConstantTimeResultForPath>,
+    /// `true` if the analysis ran to completion, i.e., it explored every path
+    /// it could, rather than stopping at the first error or violation (see
+    /// `keep_going` in `PitchforkConfig`) or because the `per_function_timeout`
+    /// expired. In that case, `path_results` has one entry for every path
+    /// through the function -- except that, as noted above, paths ending in
+    /// an error or violation may have hidden further paths.
+    ///
+    /// So a function is fully verified only if this is `true` and every path
+    /// is `ConstantTimeResultForPath::IsConstantTime`.
+    pub exhaustive: bool,
+    /// The `PathMetrics` for each path in `path_results` (in the same order,
+    /// and not including any `ConstantTimeResultForPath::TimedOut`)
+    pub path_metrics: Vec<PathMetrics>,
+    /// For each path in `path_results` (in the same order, and not including
+    /// any `ConstantTimeResultForPath::TimedOut`), the accesses to memory
+    /// covered by watchpoints (see `AbstractData::with_watchpoint()`, and
+    /// `bounds_check_pointees` in `PitchforkConfig`) on that path. Only loads
+    /// and stores at constant addresses are detected; in particular, accesses
+    /// made by function hooks (e.g., for `memcpy`) are not included.
+    pub watchpoint_hits: Vec<Vec<WatchpointHit>>,
     /// Map from function names to statistics on the block coverage of those
     /// functions. Functions not appearing in the map were not encountered on
-    /// any path, or were hooked.
+    /// any path, or were hooked (see `hooked_functions`).
     ///
     /// Note that in the case of `ConstantTimeResultForPath::NotConstantTime` or
     /// `ConstantTimeResultForPath::OtherError`, the coverage stats consider the
     /// block in which the error occurred to be covered, even if the portion of
     /// the block after where the error occurred was not covered.
     pub block_coverage: HashMap<String, BlockCoverage>,
+    /// Map from source file names to statistics on the line coverage of those
+    /// files, according to the debug info. Only files containing functions which
+    /// appear in `block_coverage` are included.
+    ///
+    /// Unlike `block_coverage`, in the case of
+    /// `ConstantTimeResultForPath::NotConstantTime` or
+    /// `ConstantTimeResultForPath::OtherError`, lines after the instruction where
+    /// the error occurred are not considered covered (unless they were covered on
+    /// some other path).
+    pub line_coverage: HashMap<String, LineCoverage>,
     /// If we logged all the detailed error messages, then this is the name of
     /// the file they were logged to.
     /// Otherwise, if this is `None`, we did not log the detailed error messages.
@@ -91,40 +171,124 @@ pub struct ConstantTimeResultForFunction<'a> {
     /// (In either case, coverage stats are available in the `block_coverage`
     /// field above.)
     pub coverage_filename: Option<String>,
+    /// LLVM struct names of the structs which were entirely opaque (no
+    /// definition anywhere in the `Project`) and not described in the
+    /// `StructDescriptions`, and for which we therefore allocated
+    /// `opaque_struct_size_bytes` unconstrained bytes while allocating the
+    /// function parameters. See notes on `opaque_struct_size_bytes` in
+    /// `PitchforkConfig`.
+    pub opaque_structs: BTreeSet<String>,
+    /// Map from parameter name to the value we allocated for that parameter of
+    /// the toplevel function (and, for pointers, the base address of the memory
+    /// we allocated for the pointee). These can be used to correlate a
+    /// reported violation with the input values it involved.
+    pub allocated_args: HashMap<Name, AllocatedArg>,
+    /// Names of the parameters of the toplevel function which point to memory
+    /// containing secret data, but whose memory was never read on any path
+    /// analyzed. Those secrets can't have influenced the behavior of the
+    /// function, which may indicate a mistake in the `AbstractData` given for
+    /// the arguments. Only the memory directly pointed to by each parameter is
+    /// considered, and passing a pointer into that memory to any function
+    /// call counts as reading it.
+    pub unread_secret_args: Vec<Name>,
+    /// Map from (mangled) names of functions which were handled by a function
+    /// hook (either a custom hook or the default hook) rather than symbolically
+    /// executed, to the number of times each was called, over all the paths
+    /// analyzed. This can be used to check that no function was unexpectedly
+    /// stubbed out. Calls through function pointers are not included.
+    pub hooked_functions: HashMap<String, usize>,
+    /// Constant-time violations which were found, but not reported in
+    /// `path_results` because their source locations were excluded by the
+    /// `violation_source_prefixes` or `ignored_violation_source_prefixes`
+    /// settings in `PitchforkConfig`. The paths ending in these violations are
+    /// also omitted from `path_metrics` and `watchpoint_hits`.
+    pub filtered_violations: Vec<CTViolation>,
+    /// Whether the `Display` for this `ConstantTimeResultForFunction` includes
+    /// coverage stats (when the function was verified constant-time). This is
+    /// set from the `display_coverage_stats` setting in `PitchforkConfig`.
+    pub display_coverage_stats: bool,
+}
+
+impl ConstantTimeResultForPath {
+    /// If this is a `NotConstantTime` result, return the kind of violation
+    pub fn violation_kind(&self) -> Option<CTViolationKind> {
+        match self {
+            ConstantTimeResultForPath::IsConstantTime { .. } => None,
+            ConstantTimeResultForPath::NotConstantTime { violation } => Some(violation.kind),
+            ConstantTimeResultForPath::OtherError { .. } => None,
+            ConstantTimeResultForPath::TimedOut { .. } => None,
+        }
+    }
 }
 
 impl<'a> ConstantTimeResultForFunction<'a> {
-    /// Return the `violation_message` for the first `NotConstantTime` result
+    /// Return the `CTViolation` for the first `NotConstantTime` result
     /// encountered, if there is one.
-    pub fn first_ct_violation(&self) -> Option<&str> {
+    pub fn first_ct_violation(&self) -> Option<&CTViolation> {
         self.path_results.iter().find_map(|path_result| match path_result {
-            ConstantTimeResultForPath::IsConstantTime => None,
-            ConstantTimeResultForPath::NotConstantTime { violation_message } => Some(violation_message as &str),
+            ConstantTimeResultForPath::IsConstantTime { .. } => None,
+            ConstantTimeResultForPath::NotConstantTime { violation } => Some(violation),
             ConstantTimeResultForPath::OtherError { .. } => None,
+            ConstantTimeResultForPath::TimedOut { .. } => None,
         })
     }
 
-    /// Return the first `NotConstantTime` or `OtherError` result encountered,
-    /// if there is one.
+    /// Return the first `NotConstantTime`, `OtherError`, or `TimedOut` result
+    /// encountered, if there is one.
     pub fn first_error_or_violation(&self) -> Option<&ConstantTimeResultForPath> {
         self.path_results.iter().find(|path_result| match path_result {
-            ConstantTimeResultForPath::IsConstantTime => false,
+            ConstantTimeResultForPath::IsConstantTime { .. } => false,
             ConstantTimeResultForPath::NotConstantTime { .. } => true,
             ConstantTimeResultForPath::OtherError { .. } => true,
+            ConstantTimeResultForPath::TimedOut { .. } => true,
         })
     }
 
+    /// The `CTViolation`s for all `NotConstantTime` results, grouped by the
+    /// (mangled) name of the function containing the violation, i.e., the
+    /// innermost function in the violation's `call_stack`. This can be used
+    /// to see which primitives the violations occur in.
+    pub fn violations_by_function(&self) -> BTreeMap<&str, Vec<&CTViolation>> {
+        let mut groups: BTreeMap<&str, Vec<&CTViolation>> = BTreeMap::new();
+        for path_result in &self.path_results {
+            if let ConstantTimeResultForPath::NotConstantTime { violation } = path_result {
+                groups.entry(violation.funcname.as_str()).or_default().push(violation);
+            }
+        }
+        groups
+    }
+
+    /// Did the analysis stop early because the `per_function_timeout` in
+    /// `PitchforkConfig` expired
+    pub fn timed_out(&self) -> bool {
+        match self.path_results.last() {
+            Some(ConstantTimeResultForPath::TimedOut { .. }) => true,
+            _ => false,
+        }
+    }
+
     pub fn path_statistics(&self) -> PathStatistics {
         let mut path_stats = PathStatistics::new();
+        path_stats.exhaustive = self.exhaustive;
         for result in &self.path_results {
             path_stats.add_path_result(result);
         }
+        for metrics in &self.path_metrics {
+            path_stats.add_path_metrics(metrics);
+        }
         path_stats
     }
 }
 
 /// Produces a pretty (even colored!) description of the
 /// `ConstantTimeResultForFunction`, including selected coverage statistics
+/// (see `display_coverage_stats`).
+///
+/// The description depends only on the fields of the
+/// `ConstantTimeResultForFunction`, not on any environment variables. Colors
+/// are omitted if stdout isn't a terminal, or if the `PITCHFORK_NO_COLOR`
+/// environment variable was set (to any value) when the analysis ran; see
+/// `respect_no_color_env_var()`.
 impl<'a> fmt::Display for ConstantTimeResultForFunction<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "\nResults for {}:\n", self.funcname)?;
@@ -136,13 +300,20 @@ impl<'a> fmt::Display for ConstantTimeResultForFunction<'a> {
 
         let path_stats = self.path_statistics();
         path_stats.fmt(f)?;
+        if self.exhaustive {
+            writeln!(f, "analysis exhaustive: explored {} path(s)", path_stats.num_paths())?;
+        } else if self.timed_out() {
+            writeln!(f, "analysis truncated by the timeout after {} path(s)", path_stats.num_paths())?;
+        } else {
+            writeln!(f, "analysis truncated at an error or violation, after {} path(s)", path_stats.num_paths())?;
+        }
         writeln!(f)?;
 
         // is the function entirely verified (no CT violations or other errors)?
         let is_ct = self.path_results.len() == path_stats.num_ct_paths;
 
         // if the function was entirely verified, show coverage stats here directly.
-        if is_ct {
+        if is_ct && self.display_coverage_stats {
             write!(f, "{}", pretty_coverage_stats(&self.funcname, &self.mangled_funcname, &self.block_coverage)?)?;
             writeln!(f)?;
         }
@@ -150,20 +321,20 @@ impl<'a> fmt::Display for ConstantTimeResultForFunction<'a> {
         if path_stats.num_ct_violations > 0 {
             match self.first_ct_violation() {
                 None => panic!("we counted a ct violation, but now can't find one"),
-                Some(violation_message) => {
+                Some(violation) => {
                     writeln!(f, "{} {}", self.funcname, "is not constant-time".red())?;
                     if let Some(filename) = &self.error_filename {
                         writeln!(f, "All errors and violations have been logged to {}", filename)?;
-                        writeln!(f, "  and the first constant-time violation is described below:\n\n{}", violation_message)?;
+                        writeln!(f, "  and the first constant-time violation is described below:\n\n{}", violation)?;
                     } else {
-                        writeln!(f, "First constant-time violation encountered:\n\n{}", violation_message)?;
+                        writeln!(f, "First constant-time violation encountered:\n\n{}", violation)?;
                     }
                 },
             }
         } else if !is_ct {
             match self.first_error_or_violation() {
                 None => panic!("we counted a non-ct path, but now can't find one"),
-                Some(ConstantTimeResultForPath::IsConstantTime) => panic!("first_error_or_violation shouldn't return an IsConstantTime"),
+                Some(ConstantTimeResultForPath::IsConstantTime { .. }) => panic!("first_error_or_violation shouldn't return an IsConstantTime"),
                 Some(ConstantTimeResultForPath::NotConstantTime { .. }) => panic!("we counted no ct violations, but now somehow found one"),
                 Some(ConstantTimeResultForPath::OtherError { full_message, .. }) => {
                     if let Some(filename) = &self.error_filename {
@@ -173,15 +344,59 @@ impl<'a> fmt::Display for ConstantTimeResultForFunction<'a> {
                         writeln!(f, "First error encountered:\n\n{}", full_message)?;
                     }
                 },
+                Some(ConstantTimeResultForPath::TimedOut { timeout }) => {
+                    writeln!(f, "{} after {:?}, before all paths were analyzed", "Analysis timed out".red(), timeout)?;
+                },
             }
         } else {
             writeln!(f, "{} {}", self.funcname, "is constant-time".green())?;
         }
 
+        let num_secret_returns = self.path_results.iter()
+            .filter(|path_result| match path_result {
+                ConstantTimeResultForPath::IsConstantTime { return_secrecy: Some(secrecy) } => *secrecy != secret::Secrecy::Public,
+                _ => false,
+            })
+            .count();
+        if num_secret_returns > 0 {
+            writeln!(f, "\nnote: {} path(s) returned a secret or partially secret value", num_secret_returns)?;
+        }
+
+        let num_out_of_bounds_paths = self.watchpoint_hits.iter()
+            .filter(|hits| hits.iter().any(|hit| hit.name.starts_with(allocation::Context::POINTEE_GUARD_PREFIX)))
+            .count();
+        if num_out_of_bounds_paths > 0 {
+            writeln!(f, "\nnote: {} path(s) accessed memory just outside the memory allocated for a pointee; see the watchpoint hits for details", num_out_of_bounds_paths)?;
+        }
+
+        if !self.filtered_violations.is_empty() {
+            writeln!(f, "\nnote: {} constant-time violation(s) outside of the selected source files were not reported; see `filtered_violations`", self.filtered_violations.len())?;
+        }
+
+        if !self.unread_secret_args.is_empty() {
+            let names: Vec<String> = self.unread_secret_args.iter().map(|name| name.to_string()).collect();
+            writeln!(f, "\nnote: the secret data pointed to by these parameters was never read, so it can't have influenced the function's behavior: {}", names.join(", "))?;
+        }
+
         Ok(())
     }
 }
 
+/// If the `PITCHFORK_NO_COLOR` environment variable is set (to any value),
+/// turn off colors in all of our output, for the rest of the process.
+///
+/// This is called when results are produced (rather than when they are
+/// formatted), so that our `Display` impls don't read the environment.
+///
+/// Without it, the `colored` crate already omits colors when stdout isn't a
+/// terminal (or when `NO_COLOR` is set), but output may still be colored when
+/// it's written somewhere other than stdout, e.g. to a log file.
+pub(crate) fn respect_no_color_env_var() {
+    if std::env::var_os("PITCHFORK_NO_COLOR").is_some() {
+        colored::control::set_override(false);
+    }
+}
+
 /// Get a formatted version of the coverage results as a `String`.
 ///
 /// `funcname`, `mangled_funcname`: name of the top-level function, unmangled and mangled respectively
@@ -191,10 +406,20 @@ pub fn pretty_coverage_stats(funcname: &str, mangled_funcname: &str, block_cover
     use std::fmt::Write;
     let mut s = String::new();
     writeln!(&mut s, "Coverage stats:\n")?;
-    let toplevel_coverage = block_coverage.get(mangled_funcname).unwrap();
-    writeln!(&mut s, "  Block coverage of toplevel function ({}): {:.1}%", funcname, 100.0 * toplevel_coverage.percentage)?;
-    if toplevel_coverage.percentage < 1.0 {
-        writeln!(&mut s, "  Missed blocks in toplevel function: {:?}", toplevel_coverage.missed_blocks.iter())?;
+    match block_coverage.get(mangled_funcname) {
+        Some(toplevel_coverage) => {
+            writeln!(&mut s, "  Block coverage of toplevel function ({}): {:.1}%", funcname, 100.0 * toplevel_coverage.percentage)?;
+            if toplevel_coverage.percentage < 1.0 {
+                writeln!(&mut s, "  Missed blocks in toplevel function:")?;
+                for description in toplevel_coverage.missed_block_descriptions() {
+                    writeln!(&mut s, "    {}", description)?;
+                }
+            }
+        },
+        None => {
+            // the coverage only includes functions which were entered, and hooked functions are never entered
+            writeln!(&mut s, "  Toplevel function ({}) was not executed (was it hooked?)", funcname)?;
+        },
     }
     writeln!(&mut s)?;
     for (fname, coverage) in block_coverage {
@@ -250,145 +475,338 @@ pub fn check_for_ct_violation_in_inputs<'p>(
 /// structs. These will be used whenever a struct of the appropriate type is
 /// found while processing an `AbstractData::default()`; for more details, see
 /// [docs on `AbstractData::default()`](struct.AbstractData.html#method.default).
+/// Struct names which aren't exactly LLVM struct names may also be given as
+/// source-level names, e.g. `foo` for `struct.foo`, or the C++ class name
+/// (with all template arguments) for `class.std::__1::...`.
 ///
 /// `pitchfork_config`: see [docs on `PitchforkConfig`](struct.PitchforkConfig.html).
 ///
 /// Other arguments are the same as for
 /// [`haybale::symex_function()`](https://PLSysSec.github.io/haybale/haybale/fn.symex_function.html).
+///
+/// If the analysis of a path panics, the panic is recorded as a
+/// `ConstantTimeResultForPath::OtherError` and the analysis stops, returning
+/// the results of the paths analyzed so far. (Panics while allocating the
+/// function parameters, before any paths are analyzed, are not caught.)
 pub fn check_for_ct_violation<'p>(
+    funcname: &'p str,
+    project: &'p Project,
+    args: Option<Vec<AbstractData>>,
+    sd: &StructDescriptions,
+    config: Config<'p, secret::Backend>,
+    pitchfork_config: &PitchforkConfig,
+) -> ConstantTimeResultForFunction<'p> {
+    check_for_ct_violation_with_setup(funcname, project, args, sd, config, pitchfork_config, |_| {})
+}
+
+/// Like [`check_for_ct_violation()`](fn.check_for_ct_violation.html), but
+/// `setup` is called with the initial `State` after the function parameters
+/// have been allocated, and before any paths are analyzed.
+///
+/// `setup` can be used to initialize global state, e.g., by writing to memory
+/// or adding constraints. To allocate and initialize additional memory
+/// according to an `AbstractData`, see the functions in
+/// [`hook_helpers`](hook_helpers/index.html), such as
+/// `allocate_and_init_abstractdata_with_config()`.
+pub fn check_for_ct_violation_with_setup<'p>(
     funcname: &'p str,
     project: &'p Project,
     args: Option<Vec<AbstractData>>,
     sd: &StructDescriptions,
     mut config: Config<'p, secret::Backend>,
     pitchfork_config: &PitchforkConfig,
+    setup: impl FnOnce(&mut State<'p, secret::Backend>),
 ) -> ConstantTimeResultForFunction<'p> {
-    // add our uninitialized-function-pointer hook, but don't override the user
-    // if they provided a different uninitialized-function-pointer hook
-    if !config.function_hooks.is_hooked("hook_uninitialized_function_pointer") {
-        config.function_hooks.add("hook_uninitialized_function_pointer", &hook_uninitialized_function_pointer);
+    run_ct_analysis(funcname, pitchfork_config, |add_progress_callbacks| {
+        add_progress_callbacks(&mut config);
+        CtAnalysis::new_with_setup(funcname, project, args, sd, config, pitchfork_config, setup)
+    })
+}
+
+/// Like [`check_for_ct_violation_with_setup()`](fn.check_for_ct_violation_with_setup.html),
+/// but using an already-constructed `ExecutionManager` rather than creating
+/// one from a `Config`. This gives the caller control over how the
+/// `ExecutionManager` is created; e.g., several argument specifications can
+/// be checked against the same function by calling
+/// [`haybale::symex_function()`](https://PLSysSec.github.io/haybale/haybale/fn.symex_function.html)
+/// once for each, with no need to re-parse the bitcode.
+///
+/// `em` must have been created for the function `funcname` in `project`, and
+/// must not have been used to analyze any paths yet. See
+/// [`CtAnalysis::from_execution_manager()`](struct.CtAnalysis.html#method.from_execution_manager)
+/// for other caveats.
+pub fn check_for_ct_violation_with_execution_manager<'p>(
+    funcname: &'p str,
+    project: &'p Project,
+    mut em: ExecutionManager<'p, secret::Backend>,
+    args: Option<Vec<AbstractData>>,
+    sd: &StructDescriptions,
+    pitchfork_config: &PitchforkConfig,
+    setup: impl FnOnce(&mut State<'p, secret::Backend>),
+) -> ConstantTimeResultForFunction<'p> {
+    run_ct_analysis(funcname, pitchfork_config, |add_progress_callbacks| {
+        add_progress_callbacks(&mut em.mut_state().config);
+        CtAnalysis::from_execution_manager(funcname, project, em, args, sd, pitchfork_config, setup)
+    })
+}
+
+/// A `Config` with the function hooks which Pitchfork would otherwise add
+/// when the analysis starts: the default function hook (which handles calls
+/// to functions with no LLVM definition or hook, such as by stubbing them out
+/// when that's safe) and the hook for calls through uninitialized function
+/// pointers, which falls back to the default hook. Otherwise, this is the
+/// same as `Config::default()`.
+///
+/// This gives a complete baseline to customize (e.g., by adding more function
+/// hooks) or inspect. It's not necessary to start from this `Config`: the
+/// `check_for_ct_violation` functions add these hooks to any `Config` which
+/// doesn't already have them, without replacing hooks the user provided.
+pub fn pitchfork_config_defaults<'p>() -> Config<'p, secret::Backend> {
+    let mut config = Config::default();
+    add_pitchfork_hooks(&mut config);
+    config
+}
+
+/// Describe how each parameter of the function `funcname` would be
+/// initialized for the analysis, without performing any symbolic execution or
+/// solving. That is, lower each `AbstractData` in `args` to the
+/// `CompleteAbstractData` it would be expanded to, and pretty-print the result.
+/// This can be used to check a harness (e.g., that arrays have the intended
+/// lengths, or that structs were matched to the intended types) before
+/// starting a long analysis.
+///
+/// `args`, `sd`, and `pitchfork_config` are interpreted just as for
+/// [`check_for_ct_violation()`](fn.check_for_ct_violation.html).
+///
+/// Returns an `Err` if the function isn't found or `args` has the wrong
+/// number of elements. As in the analysis itself, a mismatch between an
+/// `AbstractData` and the LLVM type it describes results in a panic, with a
+/// message describing the mismatch.
+pub fn describe_args(
+    funcname: &str,
+    args: Option<Vec<AbstractData>>,
+    sd: &StructDescriptions,
+    project: &Project,
+    pitchfork_config: &PitchforkConfig,
+) -> std::result::Result<String, String> {
+    use std::fmt::Write;
+    let (func, _) = project.get_func_by_name(funcname)
+        .ok_or_else(|| format!("Failed to find function {:?}", funcname))?;
+    let args = match args {
+        Some(args) if args.len() != func.parameters.len() => {
+            return Err(format!("Function {:?} has {} parameters, but we received {} argument `AbstractData`s", funcname, func.parameters.len(), args.len()));
+        },
+        Some(args) => args,
+        None => vec![AbstractData::default(); func.parameters.len()],
+    };
+    let sd = abstractdata::resolve_struct_description_names(sd, project);
+    let mut s = String::new();
+    writeln!(&mut s, "Parameters of {}:", funcname).unwrap();
+    for (param, arg) in func.parameters.iter().zip(args) {
+        let data = arg.to_complete(&param.ty, project, &sd, pitchfork_config);
+        writeln!(&mut s, "\n  {} ({} bits): {}", param.name, data.size_in_bits(), data).unwrap();
+        for line in data.to_tree_string().lines() {
+            writeln!(&mut s, "    {}", line).unwrap();
+        }
     }
+    Ok(s)
+}
 
-    // insert the `pitchfork_default_hook` as the default function hook, but
-    // don't override the user if they provided a different default function hook
-    if !config.function_hooks.has_default_hook() {
-        config.function_hooks.add_default_hook(&pitchfork_default_hook);
+/// Make `s` safe to use as a single path component on any platform, by
+/// replacing each character other than ASCII letters, digits, `-`, `_`, and
+/// `.` with `_`. This is needed for function names, which (e.g., for C++ or
+/// Rust) may contain characters such as `:`, `<`, or `/`.
+///
+/// Very long names are truncated to stay within filename length limits, with a
+/// hash of the full name appended to keep them distinct.
+fn sanitize_path_component(s: &str) -> String {
+    const MAX_LEN: usize = 200;
+    let sanitized: String = s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    if sanitized.len() > MAX_LEN {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        s.hash(&mut hasher);
+        format!("{}_{:016x}", &sanitized[.. MAX_LEN], hasher.finish())  // `sanitized` is all ASCII, so this slice is on a char boundary
+    } else if sanitized.is_empty() || sanitized.chars().all(|c| c == '.') {
+        // "", ".", and ".." aren't usable as directory names
+        sanitized.replace('.', "_") + "_"
+    } else {
+        sanitized
     }
+}
 
-    let (log_filename, error_filename, coverage_filename) = {
+/// Create a `CtAnalysis` with `make_analysis`, and drive it to completion (or
+/// to the first error or violation, unless `keep_going`; or to the
+/// `max_violations`th violation), doing any logging
+/// to files and progress updates according to the `PitchforkConfig`.
+///
+/// `make_analysis` must call the function it is given with the `Config` the
+/// analysis will use, before creating the `CtAnalysis`. That adds the
+/// callbacks for progress updates, and also initializes logging to file if
+/// appropriate, so that the parameter allocation is logged too.
+fn run_ct_analysis<'p>(
+    funcname: &'p str,
+    pitchfork_config: &PitchforkConfig,
+    make_analysis: impl FnOnce(&mut dyn FnMut(&mut Config<'p, secret::Backend>)) -> CtAnalysis<'p>,
+) -> ConstantTimeResultForFunction<'p> {
+    respect_no_color_env_var();
+    let (log_filename, error_filename, mut error_file, coverage_filename) = {
         use chrono::prelude::Local;
-        let time = Local::now().format("%Y-%m-%d_%H:%M:%S").to_string();
-        let dir = format!("logs/{}", funcname);
+        use std::fs::File;
+        // no colons in the timestamp, as they aren't allowed in filenames on Windows
+        let time = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let dir = pitchfork_config.log_dir.as_deref().unwrap_or_else(|| Path::new("logs")).join(sanitize_path_component(funcname));
+        // The analysis doesn't need any of these files, so failing to create
+        // one isn't fatal: we just warn and skip that particular output
+        let create_in_dir = |filename: String, purpose: &str| -> Option<(String, File)> {
+            let path = dir.join(filename);
+            match std::fs::create_dir_all(&dir).and_then(|()| File::create(&path)) {
+                Ok(file) => Some((path.display().to_string(), file)),
+                Err(e) => {
+                    warn!("Failed to create {} for {}, so it will be skipped: {}", path.display(), purpose, e);
+                    None
+                },
+            }
+        };
         let log_filename = if pitchfork_config.progress_updates {
-            std::fs::create_dir_all(&dir).unwrap();
-            Some(format!("{}/log_{}.log", dir, time))
+            create_in_dir(format!("log_{}.log", time), "the log and progress updates").map(|(filename, _)| filename)
         } else {
             None
         };
-        let error_filename = if pitchfork_config.keep_going && pitchfork_config.dump_errors {
-            std::fs::create_dir_all(&dir).unwrap();
-            Some(format!("{}/errors_{}.log", dir, time))
+        let (error_filename, error_file) = if pitchfork_config.keep_going && pitchfork_config.dump_errors {
+            match create_in_dir(format!("errors_{}.log", time), "dumping errors") {
+                Some((filename, file)) => (Some(filename), Some(file)),
+                None => (None, None),
+            }
         } else {
-            None
+            (None, None)
         };
         let coverage_filename = if pitchfork_config.dump_coverage_stats {
-            std::fs::create_dir_all(&dir).unwrap();
-            Some(format!("{}/coverage_{}.txt", dir, time))
+            create_in_dir(format!("coverage_{}.txt", time), "dumping coverage stats").map(|(filename, _)| filename)
         } else {
             None
         };
-        (log_filename, error_filename, coverage_filename)
+        (log_filename, error_filename, error_file, coverage_filename)
     };
 
-    let mut progress_updater: Box<dyn ProgressUpdater<secret::Backend>> = if pitchfork_config.progress_updates {
-        Box::new(initialize_progress_updater(log_filename.as_ref().unwrap(), funcname, &mut config, pitchfork_config.debug_logging))
-    } else {
-        Box::new(NullProgressUpdater { })
-    };
-
-    // first sanity-check the StructDescriptions, ensure that all its struct names are valid
-    let sd_names: HashSet<_> = sd.iter().map(|(name, _)| name).collect();
-    let proj_names: HashSet<_> = project.all_named_struct_types().map(|(name, _, _)| name).collect();
-    for name in sd_names.difference(&proj_names) {
-        panic!("Struct name {:?} appears in StructDescriptions but not found in the Project", name);
-    }
-
-    info!("Checking function {:?} for ct violations", funcname);
-    let mut em: ExecutionManager<secret::Backend> = symex_function(funcname, project, config, None).unwrap();
-
-    info!("Allocating memory for function parameters");
-    let params = em.state().cur_loc.func.parameters.iter();
-    match args {
-        Some(args) => {
-            assert_eq!(params.len(), args.len(), "Function {:?} has {} parameters, but we received only {} argument `AbstractData`s", funcname, params.len(), args.len());
-            allocation::allocate_args(project, em.mut_state(), sd, params.zip(args.into_iter())).unwrap();
-        },
-        None => {
-            allocation::allocate_args(project, em.mut_state(), sd, params.zip(std::iter::repeat(AbstractData::default()))).unwrap();
-        },
-    }
-    debug!("Done allocating memory for function parameters");
+    let mut progress_updater: Option<Box<dyn ProgressUpdater<secret::Backend>>> = None;
+    let mut analysis = make_analysis(&mut |config| {
+        // `log_filename` is `Some` if and only if `progress_updates` is set and we could create the log file
+        let new_updater: Box<dyn ProgressUpdater<secret::Backend>> = match &log_filename {
+            Some(log_filename) => Box::new(initialize_progress_updater(log_filename, funcname, config, pitchfork_config.debug_logging)),
+            None => Box::new(NullProgressUpdater { }),
+        };
+        progress_updater = Some(new_updater);
+    });
+    let mut progress_updater = progress_updater.expect("make_analysis should have added the progress callbacks");
 
-    let mut blocks_seen = BlocksSeen::new();
-    let mangled_funcname = {
-        let (func, _) = project.get_func_by_name(funcname).unwrap();
-        &func.name
-    };
     let mut path_results = Vec::new();
-    let mut error_file = error_filename.as_ref().map(|filename| {
-        use std::fs::File;
-        use std::path::Path;
-        File::create(&Path::new(filename))
-            .unwrap_or_else(|e| panic!("Failed to open file {} to dump errors: {}", filename, e))
-    });
 
+    let start_time = Instant::now();
+    let mut exhaustive = false;
+    let mut num_violations = 0;
+    let mut filtered_violations = Vec::new();
+    let mut filtered_path_indices = Vec::new();  // indices into `analysis.path_metrics` of the paths ending in `filtered_violations`
     loop {
-        match em.next() {
-            Some(Ok(_)) => {
-                info!("Finished a path with no errors or violations");
-                blocks_seen.update_with_current_path(&em);
-                let path_result = ConstantTimeResultForPath::IsConstantTime;
-                progress_updater.update_path_result(&path_result);
-                path_results.push(path_result);
+        if let Some(timeout) = pitchfork_config.per_function_timeout {
+            if start_time.elapsed() >= timeout {
+                info!("Analysis of {:?} timed out after {:?}", funcname, timeout);
+                path_results.push(ConstantTimeResultForPath::TimedOut { timeout });
+                break;
+            }
+        }
+        let path_start_time = Instant::now();
+        let path_result = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| analysis.next())) {
+            Ok(Some(path_result)) => path_result,
+            Ok(None) => {
+                exhaustive = true;
+                break;
             },
-            Some(Err(error)) => {
-                blocks_seen.update_with_current_path(&em);
-                let mut full_message = em.state().full_error_message_with_context(error.clone());
-                if full_message.contains("debug-level logging messages") {
-                    // add our own Pitchfork-specific logging advice
-                    full_message.push_str("note: To enable debug-level logging messages when `progress_updates` is\n");
-                    full_message.push_str("      enabled in `PitchforkConfig`, use the `debug_logging` setting\n");
-                }
+            Err(payload) => {
+                // The analysis state may be inconsistent after a panic, so we
+                // record the panic as an error on this path and stop, returning
+                // the results we have so far
+                let msg = panic_message(&*payload);
+                warn!("Analysis of {:?} panicked: {}", funcname, msg);
+                let error = Error::OtherError(format!("Panic during analysis: {}", msg));
+                let full_message = format!("{}\n\nThe analysis panicked, so no further paths were analyzed, and no details of this path are available.\n", error);
                 if let Some(ref mut file) = error_file {
                     use std::io::Write;
                     write!(file, "==================\n\n{}\n\n", full_message)
                         .unwrap_or_else(|e| warn!("Failed to write an error message to file: {}", e));
                 }
-                let path_result = if full_message.contains("Constant-time violation:") {
-                    info!("Found a constant-time violation on this path");
-                    ConstantTimeResultForPath::NotConstantTime { violation_message: full_message }
-                } else {
-                    info!("Encountered an error (other than a constant-time violation) on this path: {}", error);
-                    ConstantTimeResultForPath::OtherError { error, full_message }
-                };
+                // keep `path_metrics` and `path_watchpoint_hits` in step with `path_results`
+                analysis.path_metrics.push(PathMetrics {
+                    num_blocks: 0,
+                    num_instructions: 0,
+                    duration: path_start_time.elapsed(),
+                    solver_duration: secret::take_solver_duration(),
+                    solver_queries: secret::take_solver_query_counts(),
+                });
+                analysis.path_watchpoint_hits.push(Vec::new());
+                let path_result = ConstantTimeResultForPath::OtherError { error, full_message, post_violation: false, summary: None };
                 progress_updater.update_path_result(&path_result);
                 path_results.push(path_result);
-                if !pitchfork_config.keep_going {
-                    break;
+                break;
+            },
+        };
+        let path_result = match path_result {
+            ConstantTimeResultForPath::NotConstantTime { violation } if !violation.passes_source_filter(pitchfork_config) => {
+                warn!("Not reporting a constant-time violation outside of the selected source files: {}{}",
+                    violation.msg,
+                    match &violation.source_loc {
+                        Some(source_loc) => format!(" ({})", source_loc),
+                        None => String::new(),
+                    },
+                );
+                filtered_violations.push(violation);
+                filtered_path_indices.push(analysis.path_metrics.len() - 1);
+                continue;
+            },
+            path_result => path_result,
+        };
+        progress_updater.update_path_result(&path_result);
+        let is_error = match &path_result {
+            ConstantTimeResultForPath::IsConstantTime { .. } => false,
+            ConstantTimeResultForPath::TimedOut { .. } => unreachable!("CtAnalysis doesn't produce TimedOut results"),
+            ConstantTimeResultForPath::NotConstantTime { violation: CTViolation { full_message, .. } }
+            | ConstantTimeResultForPath::OtherError { full_message, .. } => {
+                if let Some(ref mut file) = error_file {
+                    use std::io::Write;
+                    write!(file, "==================\n\n{}\n\n", full_message)
+                        .unwrap_or_else(|e| warn!("Failed to write an error message to file: {}", e));
                 }
+                true
             },
-            None => break,
+        };
+        if let ConstantTimeResultForPath::NotConstantTime { .. } = path_result {
+            num_violations += 1;
+        }
+        path_results.push(path_result);
+        if is_error && !pitchfork_config.keep_going {
+            break;
+        }
+        if pitchfork_config.max_violations.map_or(false, |max| num_violations >= max) {
+            info!("Stopping analysis of {:?} after finding {} constant-time violations", funcname, num_violations);
+            break;
         }
     }
 
-    let block_coverage = blocks_seen.full_coverage_stats();
-    info!("Block coverage of toplevel function ({:?}): {:.1}%", funcname, 100.0 * block_coverage.get(mangled_funcname).unwrap().percentage);
+    let mangled_funcname = analysis.mangled_funcname();
+    let block_coverage = analysis.block_coverage();
+    let line_coverage = analysis.line_coverage();
+    match block_coverage.get(mangled_funcname) {
+        Some(coverage) => info!("Block coverage of toplevel function ({:?}): {:.1}%", funcname, 100.0 * coverage.percentage),
+        None => warn!("Toplevel function ({:?}) was not executed (was it hooked?)", funcname),
+    }
 
     if let Some(filename) = &coverage_filename {
         debug!("Analysis finished. Dumping coverage stats to {}", filename);
         use std::fs::File;
         use std::io::Write;
-        use std::path::Path;
         match File::create(&Path::new(filename)) {
             Err(e) => warn!("Failed to open file {} to dump coverage stats: {}", filename, e),
             Ok(mut file) => {
@@ -406,19 +824,708 @@ pub fn check_for_ct_violation<'p>(
 
     progress_updater.finalize();
 
+    let mut path_metrics = analysis.path_metrics;
+    let mut watchpoint_hits = analysis.path_watchpoint_hits;
+    for &idx in filtered_path_indices.iter().rev() {
+        path_metrics.remove(idx);
+        watchpoint_hits.remove(idx);
+    }
+    if pitchfork_config.sort_path_results {
+        sort_path_results(&mut path_results, &mut path_metrics, &mut watchpoint_hits);
+    }
+
     ConstantTimeResultForFunction {
         funcname,
         mangled_funcname,
         path_results,
+        exhaustive,
+        path_metrics,
+        watchpoint_hits,
         block_coverage,
+        line_coverage,
         error_filename,
         coverage_filename,
+        hooked_functions: analysis.hooked_functions(),
+        unread_secret_args: analysis.unread_secret_args(),
+        opaque_structs: analysis.opaque_structs,
+        allocated_args: analysis.allocated_args,
+        filtered_violations,
+        display_coverage_stats: pitchfork_config.display_coverage_stats,
+    }
+}
+
+/// Get the message from the payload of a panic, as caught by `catch_unwind()`
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "<unknown panic payload>".to_owned()
+    }
+}
+
+/// Sort `path_results` in the canonical order described in the docs on
+/// `sort_path_results` in `PitchforkConfig`, keeping `path_metrics` and
+/// `watchpoint_hits` (which have one entry for each result other than a
+/// `TimedOut`) in the same order. A `TimedOut` result stays last.
+fn sort_path_results(path_results: &mut Vec<ConstantTimeResultForPath>, path_metrics: &mut Vec<PathMetrics>, watchpoint_hits: &mut Vec<Vec<WatchpointHit>>) {
+    let timed_out = match path_results.last() {
+        Some(ConstantTimeResultForPath::TimedOut { .. }) => path_results.pop(),
+        _ => None,
+    };
+    let mut paths: Vec<_> = path_results.drain(..).zip(path_metrics.drain(..)).zip(watchpoint_hits.drain(..)).collect();
+    paths.sort_by_key(|((path_result, _), _)| canonical_sort_key(path_result));  // stable, so equal keys keep the order they were found in
+    for ((path_result, metrics), hits) in paths {
+        path_results.push(path_result);
+        path_metrics.push(metrics);
+        watchpoint_hits.push(hits);
+    }
+    path_results.extend(timed_out);
+}
+
+fn canonical_sort_key(path_result: &ConstantTimeResultForPath) -> (u8, String, String, String, String) {
+    match path_result {
+        ConstantTimeResultForPath::NotConstantTime { violation } => (
+            0,
+            violation.source_loc.as_ref().map(|source_loc| source_loc.to_string()).unwrap_or_default(),
+            violation.funcname.clone(),
+            violation.bbname.to_string(),
+            violation.msg.clone(),
+        ),
+        ConstantTimeResultForPath::OtherError { error, .. } => (1, String::new(), String::new(), String::new(), error.to_string()),
+        ConstantTimeResultForPath::IsConstantTime { .. } => (2, String::new(), String::new(), String::new(), String::new()),
+        ConstantTimeResultForPath::TimedOut { .. } => (3, String::new(), String::new(), String::new(), String::new()),
+    }
+}
+
+/// A constant-time analysis of a single function, which can be driven
+/// path-by-path.
+///
+/// `CtAnalysis` is an `Iterator` over `ConstantTimeResultForPath`s, one for
+/// each path analyzed. This allows callers to react to results as they come
+/// in, or to stop the analysis based on their own criteria.
+/// [`check_for_ct_violation()`](fn.check_for_ct_violation.html) is
+/// implemented by consuming a `CtAnalysis`, stopping after the first error or
+/// violation unless `keep_going` is set in the `PitchforkConfig`.
+///
+/// Note that a `CtAnalysis` does none of the logging to files, or progress
+/// updates, that `check_for_ct_violation()` may do according to the
+/// `PitchforkConfig`.
+///
+/// At most one `CtAnalysis` may exist on each thread at a time, because some
+/// of the per-analysis settings and records are kept per-thread. Creating a
+/// `CtAnalysis` while another one still exists on the same thread panics.
+/// Analyses on different threads are independent.
+pub struct CtAnalysis<'p> {
+    /// Name of the toplevel function we are analyzing
+    funcname: &'p str,
+    /// Mangled name of the toplevel function we are analyzing
+    mangled_funcname: &'p str,
+    em: ExecutionManager<'p, secret::Backend>,
+    blocks_seen: BlocksSeen<'p>,
+    hooked_functions: HookedFunctions,
+    path_metrics: Vec<PathMetrics>,
+    watchpoint_hits: WatchpointHits,
+    path_watchpoint_hits: Vec<Vec<WatchpointHit>>,
+    secret_arg_reads: SecretArgReads,
+    opaque_structs: BTreeSet<String>,
+    allocated_args: HashMap<Name, AllocatedArg>,
+    /// With the `counterexamples` setting, the contents of the memory
+    /// allocated for each pointer parameter after the parameters were
+    /// allocated, as one `BV` per byte
+    initial_pointee_bytes: Option<HashMap<Name, Vec<secret::BV>>>,
+    /// See `counterexample_num_values` in `PitchforkConfig`
+    counterexample_num_values: usize,
+    /// Ensures this is the only `CtAnalysis` on this thread
+    _guard: AnalysisGuard,
+}
+
+thread_local! {
+    /// Whether a `CtAnalysis` currently exists on this thread
+    static ANALYSIS_EXISTS: Cell<bool> = Cell::new(false);
+}
+
+/// Held by each `CtAnalysis`, to enforce that at most one exists per thread.
+/// The per-analysis settings in thread-locals (e.g., in the `secret` and
+/// `violation` modules) are reset when an analysis is created, which would
+/// silently change the behavior of any other analysis on the same thread.
+struct AnalysisGuard { }
+
+impl AnalysisGuard {
+    fn acquire() -> Self {
+        if ANALYSIS_EXISTS.with(|exists| exists.replace(true)) {
+            panic!("Can't create a CtAnalysis while another one still exists on the same thread; finish or drop the other one first");
+        }
+        AnalysisGuard { }
+    }
+}
+
+impl Drop for AnalysisGuard {
+    fn drop(&mut self) {
+        ANALYSIS_EXISTS.with(|exists| exists.set(false));
+    }
+}
+
+impl<'p> CtAnalysis<'p> {
+    /// Set up a constant-time analysis of the given function, including
+    /// allocating its parameters. No paths are analyzed until the first call
+    /// to `next()`.
+    ///
+    /// Arguments are the same as for
+    /// [`check_for_ct_violation()`](fn.check_for_ct_violation.html).
+    pub fn new(
+        funcname: &'p str,
+        project: &'p Project,
+        args: Option<Vec<AbstractData>>,
+        sd: &StructDescriptions,
+        config: Config<'p, secret::Backend>,
+        pitchfork_config: &PitchforkConfig,
+    ) -> Self {
+        Self::new_with_setup(funcname, project, args, sd, config, pitchfork_config, |_| {})
+    }
+
+    /// Like `new()`, but `setup` is called with the initial `State` after the
+    /// function parameters have been allocated. See
+    /// [`check_for_ct_violation_with_setup()`](fn.check_for_ct_violation_with_setup.html).
+    pub fn new_with_setup(
+        funcname: &'p str,
+        project: &'p Project,
+        args: Option<Vec<AbstractData>>,
+        sd: &StructDescriptions,
+        mut config: Config<'p, secret::Backend>,
+        pitchfork_config: &PitchforkConfig,
+        setup: impl FnOnce(&mut State<'p, secret::Backend>),
+    ) -> Self {
+        let guard = AnalysisGuard::acquire();
+        // `strict_uninitialized_memory` and `byte_granularity_secrecy` must be
+        // set before the `ExecutionManager` (and thus its `Memory`) is created
+        secret::set_strict_uninitialized_memory(pitchfork_config.strict_uninitialized_memory);
+        secret::set_byte_granularity_secrecy(pitchfork_config.byte_granularity_secrecy);
+        warn_about_hooked_definitions(project, &config);
+        let recorders = Self::add_hooks_and_callbacks(&mut config, pitchfork_config);
+
+        info!("Checking function {:?} for ct violations", funcname);
+        let em: ExecutionManager<secret::Backend> = symex_function(funcname, project, config, None).unwrap();
+
+        Self::with_execution_manager(funcname, project, em, args, sd, pitchfork_config, setup, recorders, guard)
+    }
+
+    /// Like `new_with_setup()`, but using an already-constructed
+    /// `ExecutionManager` rather than creating one from a `Config`. See
+    /// [`check_for_ct_violation_with_execution_manager()`](fn.check_for_ct_violation_with_execution_manager.html).
+    ///
+    /// `em` must have been created for the function `funcname` in `project`,
+    /// and must not have been used to analyze any paths yet. Our hooks and
+    /// callbacks are added to its `Config`, just as `new()` adds them to the
+    /// `Config` it is given.
+    ///
+    /// The `strict_uninitialized_memory` and `byte_granularity_secrecy`
+    /// settings in the `PitchforkConfig` are not supported here, as they have
+    /// to be in effect when the `ExecutionManager` is created.
+    pub fn from_execution_manager(
+        funcname: &'p str,
+        project: &'p Project,
+        mut em: ExecutionManager<'p, secret::Backend>,
+        args: Option<Vec<AbstractData>>,
+        sd: &StructDescriptions,
+        pitchfork_config: &PitchforkConfig,
+        setup: impl FnOnce(&mut State<'p, secret::Backend>),
+    ) -> Self {
+        let guard = AnalysisGuard::acquire();
+        let (func, _) = project.get_func_by_name(funcname).unwrap_or_else(|| panic!("Failed to find function {:?}", funcname));
+        assert_eq!(em.state().cur_loc.func.name, func.name, "ExecutionManager was created for a different function than {:?}", funcname);
+        if pitchfork_config.strict_uninitialized_memory {
+            warn!("strict_uninitialized_memory is not supported with a pre-built ExecutionManager; ignoring it");
+        }
+        secret::set_strict_uninitialized_memory(false);
+        if pitchfork_config.byte_granularity_secrecy {
+            warn!("byte_granularity_secrecy is not supported with a pre-built ExecutionManager; ignoring it");
+        }
+        secret::set_byte_granularity_secrecy(false);
+        warn_about_hooked_definitions(project, &em.state().config);
+        let recorders = Self::add_hooks_and_callbacks(&mut em.mut_state().config, pitchfork_config);
+
+        info!("Checking function {:?} for ct violations", funcname);
+        Self::with_execution_manager(funcname, project, em, args, sd, pitchfork_config, setup, recorders, guard)
+    }
+
+    /// Add our hooks and callbacks to the given `Config`, and apply the
+    /// per-analysis settings from the `PitchforkConfig`. Returns the recorders
+    /// which the callbacks report to.
+    fn add_hooks_and_callbacks(
+        config: &mut Config<'p, secret::Backend>,
+        pitchfork_config: &PitchforkConfig,
+    ) -> (HookedFunctions, WatchpointHits, SecretArgReads) {
+        add_pitchfork_hooks(config);
+        instruction_checks::add_instruction_checks(config, pitchfork_config);
+        violation::set_continue_past_secret_branches(pitchfork_config.continue_past_secret_branches);
+        default_hook::set_auto_stub_secret_functions(pitchfork_config.auto_stub_secret_functions);
+        default_hook::set_pattern_hooks(pitchfork_config.pattern_hooks.clone());
+        secret::set_oblivious_access_hook(pitchfork_config.oblivious_access_hook.clone());
+        let hooked_functions = HookedFunctions::new();
+        hooked_functions.add_callbacks(config);
+        let watchpoint_hits = WatchpointHits::new();
+        watchpoint_hits.add_callbacks(config);
+        let secret_arg_reads = SecretArgReads::new();
+        secret_arg_reads.add_callbacks(config);
+        (hooked_functions, watchpoint_hits, secret_arg_reads)
+    }
+
+    /// Allocate the function parameters in the given `ExecutionManager`, run
+    /// `setup`, and finish setting up the `CtAnalysis`
+    #[allow(clippy::too_many_arguments)]
+    fn with_execution_manager(
+        funcname: &'p str,
+        project: &'p Project,
+        mut em: ExecutionManager<'p, secret::Backend>,
+        args: Option<Vec<AbstractData>>,
+        sd: &StructDescriptions,
+        pitchfork_config: &PitchforkConfig,
+        setup: impl FnOnce(&mut State<'p, secret::Backend>),
+        (hooked_functions, watchpoint_hits, secret_arg_reads): (HookedFunctions, WatchpointHits, SecretArgReads),
+        guard: AnalysisGuard,
+    ) -> Self {
+        // first sanity-check the StructDescriptions, ensure that all its struct names are valid
+        // (translating source-level struct names to LLVM struct names as necessary)
+        let sd = abstractdata::resolve_struct_description_names(sd, project);
+        let sd = sd.as_ref();
+
+        // and sanity-check the `AbstractValue::Named` values, so that cycles or
+        // references to undefined names are reported up front rather than as
+        // confusing failures during allocation or analysis
+        let all_data = args.iter().flatten().chain(pitchfork_config.global_variables.values()).chain(sd.values());
+        if let Err(e) = abstractdata::check_named_values(all_data) {
+            panic!("Invalid named values in the AbstractData for function {:?}: {}", funcname, e);
+        }
+
+        info!("Allocating memory for function parameters");
+        let params = em.state().cur_loc.func.parameters.iter();
+        let (opaque_structs, allocated_args) = {
+            let mut allocationctx = allocation::Context::new(project, em.mut_state(), sd, pitchfork_config);
+            match args {
+                Some(args) => {
+                    assert_eq!(params.len(), args.len(), "Function {:?} has {} parameters, but we received only {} argument `AbstractData`s", funcname, params.len(), args.len());
+                    allocationctx.allocate_args(params.zip(args.into_iter())).unwrap();
+                },
+                None => {
+                    allocationctx.allocate_args(params.zip(std::iter::repeat(AbstractData::default()))).unwrap();
+                },
+            }
+            if !pitchfork_config.global_variables.is_empty() {
+                info!("Initializing global variables: {:?}", pitchfork_config.global_variables.keys().collect::<Vec<_>>());
+                allocationctx.initialize_globals(&pitchfork_config.global_variables).unwrap();
+            }
+            watchpoint_hits.set_watchpoints(allocationctx.watchpoints().to_vec());
+            (allocationctx.opaque_structs().clone(), allocationctx.allocated_args().clone())
+        };
+        if !opaque_structs.is_empty() {
+            info!("Allocated {} bytes for each of these entirely opaque structs: {:?}", pitchfork_config.opaque_struct_size_bytes, opaque_structs);
+        }
+        debug!("Done allocating memory for function parameters");
+        secret_arg_reads.set_regions(secret_arg_regions(em.state(), &allocated_args));
+
+        setup(em.mut_state());
+
+        let initial_pointee_bytes = if pitchfork_config.counterexamples {
+            Some(pointee_bytes(em.state(), &allocated_args))
+        } else {
+            None
+        };
+
+        let mangled_funcname = {
+            let (func, _) = project.get_func_by_name(funcname).unwrap();
+            &func.name
+        };
+
+        Self {
+            funcname,
+            mangled_funcname,
+            em,
+            blocks_seen: BlocksSeen::new(),
+            hooked_functions,
+            path_metrics: Vec::new(),
+            watchpoint_hits,
+            path_watchpoint_hits: Vec::new(),
+            secret_arg_reads,
+            opaque_structs,
+            allocated_args,
+            initial_pointee_bytes,
+            counterexample_num_values: pitchfork_config.counterexample_num_values,
+            _guard: guard,
+        }
+    }
+
+    /// Name of the toplevel function being analyzed
+    pub fn funcname(&self) -> &'p str {
+        self.funcname
+    }
+
+    /// Mangled name of the toplevel function being analyzed
+    /// (this may be the same as `funcname`, e.g. for C code)
+    pub fn mangled_funcname(&self) -> &'p str {
+        self.mangled_funcname
+    }
+
+    /// Map from function names to statistics on the block coverage of those
+    /// functions, over all the paths analyzed so far. See notes on
+    /// `block_coverage` in `ConstantTimeResultForFunction`.
+    pub fn block_coverage(&self) -> HashMap<String, BlockCoverage> {
+        self.blocks_seen.full_coverage_stats()
+    }
+
+    /// Map from source file names to statistics on the line coverage of those
+    /// files, over all the paths analyzed so far. See notes on `line_coverage`
+    /// in `ConstantTimeResultForFunction`.
+    pub fn line_coverage(&self) -> HashMap<String, LineCoverage> {
+        self.blocks_seen.full_line_coverage_stats()
+    }
+
+    /// Map from (mangled) names of functions which were handled by a function
+    /// hook, to the number of times each was called, over all the paths
+    /// analyzed so far. See notes on `hooked_functions` in
+    /// `ConstantTimeResultForFunction`.
+    pub fn hooked_functions(&self) -> HashMap<String, usize> {
+        self.hooked_functions.counts()
+    }
+
+    /// LLVM struct names of the entirely opaque structs we allocated space for
+    /// while allocating the function parameters. See notes on `opaque_structs`
+    /// in `ConstantTimeResultForFunction`.
+    pub fn opaque_structs(&self) -> &BTreeSet<String> {
+        &self.opaque_structs
+    }
+
+    /// Map from parameter name to the value we allocated for that parameter.
+    /// See notes on `allocated_args` in `ConstantTimeResultForFunction`.
+    pub fn allocated_args(&self) -> &HashMap<Name, AllocatedArg> {
+        &self.allocated_args
+    }
+
+    /// Names of the parameters pointing to secret data which hasn't been read
+    /// on any path analyzed so far. See notes on `unread_secret_args` in
+    /// `ConstantTimeResultForFunction`.
+    pub fn unread_secret_args(&self) -> Vec<Name> {
+        self.secret_arg_reads.unread()
+    }
+
+    /// The `PathMetrics` for each path analyzed so far
+    pub fn path_metrics(&self) -> &[PathMetrics] {
+        &self.path_metrics
+    }
+
+    /// The watchpoint hits on each path analyzed so far. See notes on
+    /// `watchpoint_hits` in `ConstantTimeResultForFunction`.
+    pub fn watchpoint_hits(&self) -> &[Vec<WatchpointHit>] {
+        &self.path_watchpoint_hits
+    }
+
+    /// The `State` at the end of the most recently analyzed path (or, before
+    /// the first call to `next()`, the initial `State`)
+    pub fn state(&self) -> &State<'p, secret::Backend> {
+        self.em.state()
+    }
+
+    /// With the `counterexamples` setting, add a counterexample to the given
+    /// violation, which was found on the current path
+    fn with_counterexample(&self, mut violation: CTViolation) -> CTViolation {
+        if let Some(initial_pointee_bytes) = &self.initial_pointee_bytes {
+            match find_counterexample(self.em.state(), &self.allocated_args, initial_pointee_bytes, self.counterexample_num_values) {
+                Ok(counterexample) => {
+                    violation.full_message.push_str(&describe_counterexample(&counterexample));
+                    violation.counterexample = Some(counterexample);
+                },
+                Err(e) => warn!("Failed to find a counterexample for a constant-time violation: {}", e),
+            }
+        }
+        violation
+    }
+}
+
+impl<'p> Iterator for CtAnalysis<'p> {
+    type Item = ConstantTimeResultForPath;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start_time = Instant::now();
+        secret::take_solver_duration();  // discard any solver time from before this path
+        secret::take_solver_query_counts();  // and likewise any solver queries
+        secret::take_constraints_asserted();  // and constraints
+        let path_result = self.em.next()?;
+        let constraints_asserted = secret::take_constraints_asserted();
+        let path_ended_in_error = path_result.is_err();
+        self.path_metrics.push(PathMetrics::of_current_path(self.em.state(), path_ended_in_error, start_time.elapsed(), secret::take_solver_duration(), secret::take_solver_query_counts()));
+        self.path_watchpoint_hits.push(self.watchpoint_hits.hits_for_completed_path(self.em.state()));
+        self.blocks_seen.update_with_current_path(&self.em, path_ended_in_error);
+        match path_result {
+            Ok(retval) => {
+                match violation::first_secret_branch_on_path(self.em.state()) {
+                    None => {
+                        info!("Finished a path with no errors or violations");
+                        let return_secrecy = match retval {
+                            ReturnValue::Return(bv) => Some(bv.secrecy()),
+                            _ => None,
+                        };
+                        Some(ConstantTimeResultForPath::IsConstantTime { return_secrecy })
+                    },
+                    Some((func, bb)) => {
+                        info!("Finished a path which continued past a secret branch");
+                        let violation = self.with_counterexample(CTViolation::at_secret_branch(func, bb, self.em.state()));
+                        Some(ConstantTimeResultForPath::NotConstantTime { violation })
+                    },
+                }
+            },
+            Err(error) => {
+                let mut full_message = self.em.state().full_error_message_with_context(error.clone());
+                if full_message.contains("debug-level logging messages") {
+                    // add our own Pitchfork-specific logging advice
+                    full_message.push_str("note: To enable debug-level logging messages when `progress_updates` is\n");
+                    full_message.push_str("      enabled in `PitchforkConfig`, use the `debug_logging` setting\n");
+                }
+                let post_violation = violation::first_secret_branch_on_path(self.em.state()).is_some();
+                let reported_violation = violation::take_reported_violation(&error);
+                if let Error::LoopBoundExceeded(_) = error {
+                    match violation::secret_loop_branch_on_path(self.em.state()) {
+                        Some((func, bb)) => {
+                            info!("Reached the loop bound in a loop with a secret exit condition");
+                            let violation = self.with_counterexample(CTViolation::at_secret_loop_bound(func, bb, &error, &full_message, self.em.state()));
+                            return Some(ConstantTimeResultForPath::NotConstantTime { violation });
+                        },
+                        None => {
+                            full_message.push_str("note: No exit condition of the loop depended on secret data, so this is a limit\n");
+                            full_message.push_str("      of the analysis (see `loop_bound` in `Config`), not a constant-time violation\n");
+                        },
+                    }
+                }
+                match reported_violation {
+                    Some(kind) => {
+                        info!("Found a constant-time violation on this path: {}", kind);
+                        if let Some(description) = describe_argument_taint(self.em.state(), &self.allocated_args) {
+                            full_message.push_str(&description);
+                        }
+                        let violation = self.with_counterexample(CTViolation::new(kind, &error, full_message, self.em.state(), post_violation));
+                        Some(ConstantTimeResultForPath::NotConstantTime { violation })
+                    },
+                    None => {
+                        info!("Encountered an error (other than a constant-time violation) on this path: {}", error);
+                        let summary = PathErrorSummary::new(&error, self.em.state(), constraints_asserted);
+                        Some(ConstantTimeResultForPath::OtherError { error, full_message, post_violation, summary: Some(summary) })
+                    },
+                }
+            },
+        }
+    }
+}
+
+/// Read the memory allocated for each of the given arguments, one byte at a
+/// time. Arguments whose memory can't be read (e.g., because some of it is
+/// uninitialized, with `strict_uninitialized_memory`) are omitted.
+fn pointee_bytes(state: &State<secret::Backend>, allocated_args: &HashMap<Name, AllocatedArg>) -> HashMap<Name, Vec<secret::BV>> {
+    allocated_args.iter()
+        .filter_map(|(name, arg)| {
+            let base_address = arg.base_address.as_ref()?;
+            let addr = base_address.as_u64()?;
+            let bytes = arg.allocation_bits? / 8;
+            let contents = (0 .. bytes)
+                .map(|offset| state.read(&state.bv_from_u64(addr + offset, base_address.get_width()), 8))
+                .collect::<Result<Vec<secret::BV>>>();
+            match contents {
+                Ok(contents) => Some((name.clone(), contents)),
+                Err(e) => {
+                    warn!("Failed to read the memory pointed to by parameter {} for counterexamples: {}", name, e);
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+/// Get concrete values for the public parts of the given arguments, and of
+/// the given `initial_pointee_bytes`, under a single satisfying assignment of
+/// the current constraints in `state`; and also up to `num_values` distinct
+/// values of each public argument. See notes on `counterexample` in
+/// `CTViolation`.
+fn find_counterexample(
+    state: &State<secret::Backend>,
+    allocated_args: &HashMap<Name, AllocatedArg>,
+    initial_pointee_bytes: &HashMap<Name, Vec<secret::BV>>,
+    num_values: usize,
+) -> Result<HashMap<Name, ArgCounterexample>> {
+    // all the solutions below come from the model found by this one query
+    if !state.sat()? {
+        return Err(Error::Unsat);
+    }
+    let mut counterexample: HashMap<Name, ArgCounterexample> = allocated_args.iter()
+        .map(|(name, arg)| {
+            let value = match &arg.value {
+                bv @ secret::BV::Public(_) => {
+                    let solution = bv.get_a_solution()?;
+                    Some(match solution.as_u64() {
+                        Some(value) => format!("{:#x}", value),
+                        None => solution.as_01x_str().to_owned(),
+                    })
+                },
+                _ => None,
+            };
+            let pointee_bytes = match initial_pointee_bytes.get(name) {
+                None => None,
+                Some(bytes) => Some(bytes.iter()
+                    .map(|byte| match byte {
+                        secret::BV::Public(_) => Ok(byte.get_a_solution()?.as_u64().map(|byte| byte as u8)),
+                        _ => Ok(None),
+                    })
+                    .collect::<Result<Vec<Option<u8>>>>()?),
+            };
+            Ok((name.clone(), ArgCounterexample { value, pointee_bytes, possible_values: Vec::new(), all_possible_values: false }))
+        })
+        .collect::<Result<_>>()?;
+    // these further queries replace the model, so they must come after all
+    // of the above
+    if num_values > 0 {
+        for (name, arg) in allocated_args {
+            if let secret::BV::Public(_) = &arg.value {
+                let (values, all) = possible_public_values(state, &arg.value, num_values)?;
+                let arg_counterexample = counterexample.get_mut(name).expect("every allocated arg has an ArgCounterexample");
+                arg_counterexample.possible_values = values;
+                arg_counterexample.all_possible_values = all;
+            }
+        }
     }
+    Ok(counterexample)
+}
+
+/// Find up to `n` distinct values of the public `bv` under the current
+/// constraints in `state`, by repeatedly solving and excluding the previous
+/// solution. Also returns `true` if these are all of its possible values.
+///
+/// Values wider than 64 bits are not excluded after being found, so for them
+/// this returns at most one value.
+fn possible_public_values(state: &State<secret::Backend>, bv: &secret::BV, n: usize) -> Result<(Vec<String>, bool)> {
+    state.solver.push(1);
+    let result = (|| {
+        let mut values = Vec::new();
+        while values.len() < n {
+            if !state.sat()? {
+                return Ok((values, true));
+            }
+            let solution = bv.get_a_solution()?;
+            match solution.as_u64() {
+                Some(value) => {
+                    values.push(format!("{:#x}", value));
+                    bv._ne(&state.bv_from_u64(value, bv.get_width())).assert()?;
+                },
+                None => {
+                    values.push(solution.as_01x_str().to_owned());
+                    return Ok((values, false));
+                },
+            }
+        }
+        Ok((values, !state.sat()?))
+    })();
+    state.solver.pop(1);
+    result
+}
+
+/// Describe the given counterexample, for inclusion in a violation's full
+/// message
+fn describe_counterexample(counterexample: &HashMap<Name, ArgCounterexample>) -> String {
+    let mut lines: Vec<String> = counterexample.iter()
+        .map(|(name, arg)| {
+            let mut line = format!("  parameter {}: {}\n", name, arg.value.as_deref().unwrap_or("<secret>"));
+            if let Some(bytes) = &arg.pointee_bytes {
+                let bytes: Vec<String> = bytes.iter()
+                    .map(|byte| match byte {
+                        Some(byte) => format!("{:02x}", byte),
+                        None => "??".to_owned(),
+                    })
+                    .collect();
+                line.push_str(&format!("    initial contents of the memory it points to (?? = secret): {}\n", bytes.join(" ")));
+            }
+            if arg.possible_values.len() > 1 {
+                line.push_str(&format!("    {}: {}\n",
+                    if arg.all_possible_values { "all values leading to this violation" } else { "values leading to this violation include" },
+                    arg.possible_values.join(", "),
+                ));
+            }
+            line
+        })
+        .collect();
+    lines.sort();
+    format!("note: These public inputs lead to this violation:\n{}", lines.concat())
 }
 
-fn hook_uninitialized_function_pointer(
-    state: &mut State<secret::Backend>,
-    call: &dyn IsCall,
+/// Describe which of the memory regions allocated for the given arguments
+/// currently contain secret data, or `None` if no memory was allocated for any
+/// of the arguments
+fn describe_argument_taint(state: &State<secret::Backend>, allocated_args: &HashMap<Name, AllocatedArg>) -> Option<String> {
+    let mut lines: Vec<String> = allocated_args.iter()
+        .filter_map(|(name, arg)| {
+            let addr = arg.base_address.as_ref()?.as_u64()?;
+            let bytes = arg.allocation_bits? / 8;
+            let taint = secret::taint_of_region(|addr, bits| state.read(addr, bits), &state.solver, addr, bytes);
+            Some(match taint {
+                Ok(taint) => format!("  memory pointed to by parameter {}: {}\n", name, taint),
+                Err(e) => format!("  memory pointed to by parameter {}: unknown ({})\n", name, e),
+            })
+        })
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    lines.sort();
+    Some(format!("note: At the time of the violation, the contents of argument memory were:\n{}", lines.concat()))
+}
+
+/// The memory directly pointed to by each of the `allocated_args` which
+/// contains any secret data, as (parameter name, address, size in bytes)
+fn secret_arg_regions(state: &State<secret::Backend>, allocated_args: &HashMap<Name, AllocatedArg>) -> Vec<(Name, u64, u64)> {
+    allocated_args.iter()
+        .filter_map(|(name, arg)| {
+            let addr = arg.base_address.as_ref()?.as_u64()?;
+            let bytes = arg.allocation_bits? / 8;
+            match secret::taint_of_region(|addr, bits| state.read(addr, bits), &state.solver, addr, bytes) {
+                Ok(secret::RegionTaint::Public) => None,
+                Ok(_) => Some((name.clone(), addr, bytes)),
+                Err(e) => {
+                    warn!("Failed to determine whether the memory pointed to by parameter {} contains secrets: {}", name, e);
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+/// Log a warning for each function which has an LLVM definition in the
+/// `Project` but also has a function hook in the `Config`. The hook takes
+/// precedence, so the definition won't be analyzed; this is easy to forget.
+fn warn_about_hooked_definitions(project: &Project, config: &Config<secret::Backend>) {
+    let hooked: BTreeSet<&str> = project.all_functions()
+        .map(|(func, _)| func.name.as_str())
+        .filter(|funcname| config.function_hooks.is_hooked(funcname))
+        .collect();
+    for funcname in hooked {
+        warn!("Function {:?} has an LLVM definition, but also a function hook; the hook will take precedence, so the definition won't be analyzed", funcname);
+    }
+}
+
+/// Add Pitchfork's uninitialized-function-pointer hook and default function
+/// hook to the `Config`, except where the user has already provided their own
+fn add_pitchfork_hooks(config: &mut Config<'_, secret::Backend>) {
+    // add our uninitialized-function-pointer hook, but don't override the user
+    // if they provided a different uninitialized-function-pointer hook
+    if !config.function_hooks.is_hooked("hook_uninitialized_function_pointer") {
+        config.function_hooks.add("hook_uninitialized_function_pointer", &hook_uninitialized_function_pointer);
+    }
+
+    // insert the `pitchfork_default_hook` as the default function hook, but
+    // don't override the user if they provided a different default function hook
+    if !config.function_hooks.has_default_hook() {
+        config.function_hooks.add_default_hook(&pitchfork_default_hook);
+    }
+}
+
+fn hook_uninitialized_function_pointer<'p>(
+    state: &mut State<'p, secret::Backend>,
+    call: &'p dyn IsCall,
 ) -> Result<ReturnValue<secret::BV>> {
     info!("Function pointer is uninitialized; trying Pitchfork default hook");
     default_hook::pitchfork_default_hook(state, call)
@@ -452,3 +1559,101 @@ fn initialize_progress_updater<B: Backend>(log_filename: &str, funcname: &str, c
 fn initialize_progress_updater<B: Backend>(_log_filename: &str, _funcname: &str, _config: &mut Config<B>, _debug_logging: bool) -> NullProgressUpdater {
     NullProgressUpdater { }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_path_components() {
+        assert_eq!(sanitize_path_component("ct_simple"), "ct_simple");
+        assert_eq!(sanitize_path_component("_ZN3foo3barEv.llvm.123"), "_ZN3foo3barEv.llvm.123");
+        assert_eq!(sanitize_path_component("std::vector<int>::push_back"), "std__vector_int___push_back");
+        assert_eq!(sanitize_path_component("a/b\\c d"), "a_b_c_d");
+        assert_eq!(sanitize_path_component(".."), "___");
+        assert_eq!(sanitize_path_component(""), "_");
+        let long = "x".repeat(1000);
+        let sanitized = sanitize_path_component(&long);
+        assert!(sanitized.len() < 255);
+        assert!(sanitized.starts_with(&"x".repeat(200)));
+        assert_ne!(sanitized, sanitize_path_component(&"x".repeat(1001)));
+    }
+
+    #[test]
+    fn error_kinds_in_path_statistics() {
+        let other_error = |error: Error| ConstantTimeResultForPath::OtherError { error, full_message: String::new(), post_violation: false, summary: None };
+        let mut path_stats = PathStatistics::new();
+        path_stats.add_path_result(&other_error(Error::SolverError("Query timed out".to_owned())));
+        path_stats.add_path_result(&other_error(Error::SolverError("The solver returned an error".to_owned())));
+        path_stats.add_path_result(&other_error(Error::Unsat));
+        path_stats.add_path_result(&other_error(Error::Unsat));
+        assert_eq!(path_stats.num_solver_timeouts, 1);
+        assert_eq!(path_stats.num_solver_errors, 1);
+        assert_eq!(path_stats.num_unsats, 2);
+        assert_eq!(path_stats.num_errors(), 4);
+        assert_eq!(path_stats.num_distinct_error_kinds(), 3);
+        let display = path_stats.to_string();
+        assert!(display.contains("paths with a solver timeout"), "{}", display);
+        assert!(display.contains("other solver errors"), "{}", display);
+    }
+
+    #[test]
+    fn violations_reported_by_hooks() {
+        // violations reported with `report_violation()` keep their kind
+        let error = violation::report_violation(CTViolationKind::MemoryAddress, "call to \"memcpy\" with a secret length");
+        assert_eq!(violation::take_reported_violation(&error), Some(CTViolationKind::MemoryAddress));
+        // errors from user-written hooks are recognized by their message
+        let error = Error::OtherError("Constant-time violation: secret argument to \"my_primitive\"".to_owned());
+        assert_eq!(violation::take_reported_violation(&error), Some(CTViolationKind::Hook));
+        assert_eq!(violation::take_reported_violation(&Error::OtherError("some other error".to_owned())), None);
+    }
+
+    #[test]
+    fn secret_branches_are_per_path() {
+        let block = |funcname: &str, bbname: &str| (funcname.to_owned(), Name::from(bbname));
+        let mut branches = violation::SecretBranches::default();
+        assert!(branches.indices_on_path(&[block("f", "entry"), block("f", "merge")]).is_empty());
+        // on one path to `merge`, its branch condition was secret
+        branches.record(vec![block("f", "entry"), block("f", "secret"), block("f", "merge")]);
+        assert_eq!(branches.indices_on_path(&[block("f", "entry"), block("f", "secret"), block("f", "merge"), block("f", "exit")]), vec![2]);
+        // but another path crosses `merge` with a public branch condition
+        assert!(branches.indices_on_path(&[block("f", "entry"), block("f", "public"), block("f", "merge"), block("f", "exit")]).is_empty());
+    }
+
+    #[test]
+    fn call_stack_with_recursion() {
+        use violation::PathStep::{Branch, Call, Return};
+        // `f` branches, calls itself, and the inner `f` calls `g`
+        let steps = [Call, Branch, Call, Call];
+        assert_eq!(violation::call_stack_indices(&steps), vec![(0, 1), (2, 2), (3, 3)]);
+        // `g` returns to the inner `f`, which branches
+        let steps = [Call, Branch, Call, Call, Return, Branch];
+        assert_eq!(violation::call_stack_indices(&steps), vec![(0, 1), (2, 5)]);
+        // the inner `f` returns to the outer `f`, though both are in the same function
+        let steps = [Call, Branch, Call, Call, Return, Branch, Return, Branch];
+        assert_eq!(violation::call_stack_indices(&steps), vec![(0, 7)]);
+    }
+
+    #[test]
+    fn check_named_values() {
+        let check = |args: Vec<AbstractData>| abstractdata::check_named_values(args.iter());
+        // a name may be defined more than once, and referenced from inside a struct
+        assert_eq!(check(vec![
+            AbstractData::pub_i32(AbstractValue::named("n", AbstractValue::Range(0, 20))),
+            AbstractData::pub_pointer_to(AbstractData::_struct("s", vec![
+                AbstractData::pub_i32(AbstractValue::named("n", AbstractValue::Unconstrained)),
+                AbstractData::pub_i32(AbstractValue::All(vec![AbstractValue::NonZero, AbstractValue::UnsignedLessThan("n".to_owned())])),
+            ])),
+        ]), Ok(()));
+        // undefined name
+        assert!(check(vec![AbstractData::pub_i32(AbstractValue::EqualTo("n".to_owned()))]).unwrap_err().contains("[\"n\"]"));
+        // a value can't be constrained in terms of itself, even indirectly
+        assert!(check(vec![
+            AbstractData::pub_i32(AbstractValue::named("a", AbstractValue::UnsignedLessThan("a".to_owned()))),
+        ]).unwrap_err().contains("cycle: a -> a"));
+        assert!(check(vec![
+            AbstractData::pub_i32(AbstractValue::named("a", AbstractValue::named("b", AbstractValue::EqualTo("c".to_owned())))),
+            AbstractData::pub_i32(AbstractValue::named("c", AbstractValue::All(vec![AbstractValue::Range(0, 5), AbstractValue::SignedGreaterThan("b".to_owned())]))),
+        ]).unwrap_err().contains("cycle: c -> b -> c"));
+    }
+}
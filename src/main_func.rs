@@ -1,10 +1,16 @@
-use crate::check_for_ct_violation;
-use crate::{AbstractData, PitchforkConfig, StructDescriptions};
+use crate::args_file;
+use crate::{check_for_ct_violation, describe_args};
+use crate::json;
+use crate::respect_no_color_env_var;
+use crate::{AbstractData, PathStatistics, PitchforkConfig, StructDescriptions};
 use crate::secret;
 
 use colored::*;
 use haybale::{Config, Project};
+use haybale::demangling::Demangling;
 use itertools::Itertools;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
 fn usage() {
@@ -19,6 +25,11 @@ fn usage() {
     println!();
     println!("  --list-functions: list all functions defined in the LLVM bitcode and exit");
     println!();
+    println!("  --describe-args: instead of analyzing the specified functions, print the");
+    println!("      complete description of how each of their parameters would be");
+    println!("      initialized, and exit. This performs no symbolic execution, so it can be");
+    println!("      used to quickly check the AbstractData for each function.");
+    println!();
     println!("  --loop-bound <n>: Use <n> as the value for the similarly named option in");
     println!("      `haybale::Config`; see docs there.");
     println!();
@@ -44,10 +55,48 @@ fn usage() {
     println!("      be printed directly to stderr. You may redirect stderr if you still want");
     println!("      log messages recorded in a file.");
     println!();
+    println!("  --log-dir <path>: write log files, error dumps, and coverage stats to");
+    println!("      subdirectories of <path> rather than of ./logs.");
+    println!();
+    println!("  --jobs <n>: analyze up to <n> functions concurrently, each in its own");
+    println!("      thread. If <n> is greater than 1, this implies --no-progress-updates.");
+    println!();
+    println!("  --max-violations <n>: stop analyzing each function once <n> constant-time");
+    println!("      violations have been found in it, rather than exploring all paths.");
+    println!();
+    println!("  --json-output <path>: after analyzing all the functions, write the results");
+    println!("      to <path> in JSON format. With this option, the process exits with a");
+    println!("      nonzero status if any constant-time violation or other error was found.");
+    println!();
+    println!("  --quiet: don't print the results for each function, or the summary, to");
+    println!("      stdout. This is most useful with --json-output.");
+    println!();
+    println!("  --args-file <path>: read the AbstractData to use for function arguments from");
+    println!("      the JSON file at <path>, which maps function names to either an array of");
+    println!("      argument specs, or an object mapping \"param<n>\" to argument specs.");
+    println!("      Argument specs are strings naming AbstractData constructors, e.g.");
+    println!("      \"sec_i32\" or \"pub_pointer_to(array_of(sec_i8, 32))\". Unspecified");
+    println!("      arguments get AbstractData::default(). For functions in the file, this");
+    println!("      takes precedence over the AbstractData provided by the program.");
+    println!();
     println!("  --prefix: instead of each non-option argument being a function name, it will");
     println!("      indicate a prefix, and all functions defined in the LLVM bitcode which");
     println!("      have names beginning with that prefix will be checked for constant-time");
     println!("      violations.");
+    println!();
+    println!("  --regex <pattern>: all functions defined in the LLVM bitcode which have names");
+    println!("      matching the regular expression <pattern> (anywhere in the name, unless");
+    println!("      anchored with ^ or $) will be checked for constant-time violations, in");
+    println!("      addition to any functions given as non-option arguments. Can't be used");
+    println!("      with --prefix.");
+    println!();
+    println!("  --demangle-match: with --regex, match the pattern against the demangled");
+    println!("      function names (for C++ or Rust) rather than the mangled names.");
+    println!();
+    println!("Environment variables:");
+    println!();
+    println!("  PITCHFORK_NO_COLOR: if set (to any value), don't use colors in the output.");
+    println!("      Colors are also omitted whenever stdout isn't a terminal.");
 }
 
 /// A struct which represents the options the user specified at the command-line
@@ -66,7 +115,22 @@ struct CommandLineOptions {
     /// `None` means not specified / don't override
     solver_timeout: Option<Duration>,
 
+    /// `None` means don't write JSON output
+    json_output: Option<String>,
+
+    quiet: bool,
+
+    /// `None` means no args file was specified
+    args_file: Option<String>,
+
     prefix: bool,
+
+    /// `None` means no regex was specified
+    regex: Option<String>,
+
+    demangle_match: bool,
+
+    describe_args: bool,
 }
 
 impl Default for CommandLineOptions {
@@ -85,7 +149,13 @@ impl Default for CommandLineOptions {
             max_callstack_depth: None,
             max_memcpy_length: None,
             solver_timeout: None,
+            json_output: None,
+            quiet: false,
+            args_file: None,
             prefix: false,
+            regex: None,
+            demangle_match: false,
+            describe_args: false,
         }
     }
 }
@@ -104,12 +174,61 @@ impl Default for CommandLineOptions {
 ///   - `get_config`: a closure which, when called, produces the `Config` you
 ///         want to use. Note that some parts of the `Config` may be overridden by
 ///         command-line arguments.
+///
+/// `get_args_for_funcname` and `get_config` must be `Sync`, as they may be
+/// called from multiple threads if the `--jobs` option is used.
+///
+/// To get an exit status indicating whether any violations were found (e.g.,
+/// for use in CI), see `main_func_with_status()`.
 pub fn main_func<F>(
     get_project: impl FnOnce() -> Project,
     get_struct_descriptions: impl FnOnce() -> StructDescriptions,
-    get_args_for_funcname: impl Fn(&str) -> Option<Vec<AbstractData>>,
+    get_args_for_funcname: impl Fn(&str) -> Option<Vec<AbstractData>> + Sync,
     get_config: F,
-) where for<'p> F: Fn(&'p Project) -> Config<'p, secret::Backend> {
+) where for<'p> F: Fn(&'p Project) -> Config<'p, secret::Backend> + Sync {
+    let (status, json_output) = run(get_project, get_struct_descriptions, get_args_for_funcname, get_config);
+    if json_output && status != EXIT_SUCCESS {
+        std::process::exit(status);
+    }
+}
+
+/// Exit status from `main_func_with_status()` indicating that all the analyzed
+/// functions were verified to be constant-time
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit status from `main_func_with_status()` indicating that a constant-time
+/// violation was found in at least one analyzed function
+pub const EXIT_VIOLATION: i32 = 1;
+/// Exit status from `main_func_with_status()` indicating that no constant-time
+/// violations were found, but some other error was encountered (or the
+/// analysis timed out) in at least one analyzed function, or the command-line
+/// arguments were invalid
+pub const EXIT_ERROR: i32 = 2;
+
+/// Like `main_func()`, but returns an exit status: `EXIT_SUCCESS`,
+/// `EXIT_VIOLATION`, or `EXIT_ERROR`. This can be used in your `main()` like
+/// `std::process::exit(main_func_with_status(...))`, so that, e.g., a CI job
+/// fails if any violation is found.
+///
+/// `--help` and `--list-functions` produce `EXIT_SUCCESS`, as does
+/// `--describe-args` unless a function can't be described.
+pub fn main_func_with_status<F>(
+    get_project: impl FnOnce() -> Project,
+    get_struct_descriptions: impl FnOnce() -> StructDescriptions,
+    get_args_for_funcname: impl Fn(&str) -> Option<Vec<AbstractData>> + Sync,
+    get_config: F,
+) -> i32 where for<'p> F: Fn(&'p Project) -> Config<'p, secret::Backend> + Sync {
+    run(get_project, get_struct_descriptions, get_args_for_funcname, get_config).0
+}
+
+/// Shared implementation of `main_func()` and `main_func_with_status()`.
+/// Returns the exit status, and whether JSON output was requested.
+fn run<F>(
+    get_project: impl FnOnce() -> Project,
+    get_struct_descriptions: impl FnOnce() -> StructDescriptions,
+    get_args_for_funcname: impl Fn(&str) -> Option<Vec<AbstractData>> + Sync,
+    get_config: F,
+) -> (i32, bool) where for<'p> F: Fn(&'p Project) -> Config<'p, secret::Backend> + Sync {
+    respect_no_color_env_var();
     let mut cmdlineoptions = CommandLineOptions::default();
 
     let mut args = std::env::args().skip(1);
@@ -117,7 +236,7 @@ pub fn main_func<F>(
         match arg.as_str() {
             "-h" | "--help" => {
                 usage();
-                return ();
+                return (EXIT_SUCCESS, false);
             },
             "--list-functions" => {
                 let proj = get_project();
@@ -125,7 +244,7 @@ pub fn main_func<F>(
                 for funcname in proj.all_functions().map(|(func, _)| &func.name).sorted() {
                     println!("{}", funcname);
                 }
-                return ();
+                return (EXIT_SUCCESS, false);
             },
             "--loop-bound" => {
                 cmdlineoptions.loop_bound = Some(args.next().expect("--loop-bound argument requires a value").parse().unwrap());
@@ -145,34 +264,96 @@ pub fn main_func<F>(
             "--no-progress-updates" => {
                 cmdlineoptions.pitchfork_config.progress_updates = false;
             },
+            "--log-dir" => {
+                cmdlineoptions.pitchfork_config.log_dir = Some(args.next().expect("--log-dir argument requires a value").into());
+            },
+            "--jobs" => {
+                cmdlineoptions.pitchfork_config.jobs = args.next().expect("--jobs argument requires a value").parse().unwrap();
+            },
+            "--max-violations" => {
+                cmdlineoptions.pitchfork_config.max_violations = Some(args.next().expect("--max-violations argument requires a value").parse().unwrap());
+            },
+            "--json-output" => {
+                cmdlineoptions.json_output = Some(args.next().expect("--json-output argument requires a value"));
+            },
+            "--quiet" => {
+                cmdlineoptions.quiet = true;
+            },
+            "--args-file" => {
+                cmdlineoptions.args_file = Some(args.next().expect("--args-file argument requires a value"));
+            },
             "--prefix" => {
                 cmdlineoptions.prefix = true;
             },
+            "--regex" => {
+                cmdlineoptions.regex = Some(args.next().expect("--regex argument requires a value"));
+            },
+            "--describe-args" => {
+                cmdlineoptions.describe_args = true;
+            },
+            "--demangle-match" => {
+                cmdlineoptions.demangle_match = true;
+            },
             s if s.starts_with("--") || s.starts_with("-") => {
                 eprintln!("error: unrecognized option {}", s);
-                return ();
+                return (EXIT_ERROR, false);
             },
             funcname => {
-                process_nonoption_args(std::iter::once(funcname.into()).chain(args), cmdlineoptions, get_project, get_struct_descriptions, get_args_for_funcname, get_config);
-                return ();
+                let json_output = cmdlineoptions.json_output.is_some();
+                let status = process_nonoption_args(std::iter::once(funcname.into()).chain(args), cmdlineoptions, get_project, get_struct_descriptions, get_args_for_funcname, get_config);
+                return (status, json_output);
             },
         }
     }
     // if we got here, we didn't get any nonoption arguments, or -h, --help, or --list-functions
+    if cmdlineoptions.regex.is_some() {
+        // the regex is enough to specify the functions
+        let json_output = cmdlineoptions.json_output.is_some();
+        let status = process_nonoption_args(std::iter::empty(), cmdlineoptions, get_project, get_struct_descriptions, get_args_for_funcname, get_config);
+        return (status, json_output);
+    }
     println!("Error: No functions specified");
     println!();
     usage();
+    (EXIT_ERROR, false)
 }
 
+/// Returns the exit status; see `main_func_with_status()`
 fn process_nonoption_args<F>(
     nonoption_args: impl Iterator<Item = String>,
-    cmdlineoptions: CommandLineOptions,
+    mut cmdlineoptions: CommandLineOptions,
     get_project: impl FnOnce() -> Project,
     get_struct_descriptions: impl FnOnce() -> StructDescriptions,
-    get_args_for_funcname: impl Fn(&str) -> Option<Vec<AbstractData>>,
+    get_args_for_funcname: impl Fn(&str) -> Option<Vec<AbstractData>> + Sync,
     get_config: F,
-) where for<'p> F: Fn(&'p Project) -> Config<'p, secret::Backend> {
-    let mut results = Vec::new();
+) -> i32 where for<'p> F: Fn(&'p Project) -> Config<'p, secret::Backend> + Sync {
+    if cmdlineoptions.prefix && cmdlineoptions.regex.is_some() {
+        eprintln!("error: --prefix and --regex can't be used together. Use --help for more information.");
+        return EXIT_ERROR;
+    }
+    let regex = match cmdlineoptions.regex.as_ref().map(|pattern| regex::Regex::new(pattern)) {
+        None => None,
+        Some(Ok(regex)) => Some(regex),
+        Some(Err(e)) => {
+            eprintln!("error: invalid --regex pattern: {}", e);
+            return EXIT_ERROR;
+        },
+    };
+    let args_from_file = match &cmdlineoptions.args_file {
+        None => args_file::ArgsFile::new(),
+        Some(path) => match args_file::read_args_file(path) {
+            Ok(args_from_file) => args_from_file,
+            Err(e) => {
+                eprintln!("error: invalid --args-file: {}", e);
+                return EXIT_ERROR;
+            },
+        },
+    };
+    let jobs = cmdlineoptions.pitchfork_config.jobs;
+    if jobs > 1 {
+        // the progress-updates UI can't display multiple analyses at once
+        cmdlineoptions.pitchfork_config.progress_updates = false;
+    }
     if !cmdlineoptions.pitchfork_config.progress_updates || cfg!(not(feature = "progress-updates")) {
         use env_logger::Env;
         if cmdlineoptions.pitchfork_config.debug_logging {
@@ -183,56 +364,151 @@ fn process_nonoption_args<F>(
     }
     let proj = get_project();
     let struct_descriptions = get_struct_descriptions();
-    let nonoption_args = nonoption_args.collect::<Vec<_>>();  // collecting here shouldn't be necessary, but ensures that all the args outlive the for loop so that `results` can be used after it
+    let nonoption_args = nonoption_args.collect::<Vec<_>>();  // collecting here shouldn't be necessary, but ensures that all the args outlive the analyses so that the function names can be borrowed from them
+    let mut funcnames: Vec<&str> = Vec::new();
     for funcname in nonoption_args.iter() {
         if funcname.starts_with("--") || funcname.starts_with("-") {
             eprintln!("error: options must come before non-option arguments. Use --help for more information.");
-            return ();
+            return EXIT_ERROR;
         }
         if cmdlineoptions.prefix {
-            for full_funcname in proj.all_functions().map(|(func, _)| &func.name).filter(|proj_funcname| proj_funcname.starts_with(funcname)) {
-                let mut config = get_config(&proj);
-                set_cmdline_overrides(&mut config, &cmdlineoptions);
-                let result = check_for_ct_violation(
-                    full_funcname,
-                    &proj,
-                    get_args_for_funcname(full_funcname),
-                    &struct_descriptions,
-                    config,
-                    &cmdlineoptions.pitchfork_config,
-                );
-                println!("{}", result);
-                results.push(result);
-            }
+            funcnames.extend(proj.all_functions().map(|(func, _)| func.name.as_str()).filter(|proj_funcname| proj_funcname.starts_with(funcname.as_str())));
         } else {
-            let mut config = get_config(&proj);
-            set_cmdline_overrides(&mut config, &cmdlineoptions);
-            let result = check_for_ct_violation(
-                funcname,
-                &proj,
-                get_args_for_funcname(funcname),
-                &struct_descriptions,
-                config,
-                &cmdlineoptions.pitchfork_config,
-            );
+            funcnames.push(funcname);
+        }
+    }
+    if let Some(regex) = &regex {
+        let demangling = if cmdlineoptions.demangle_match {
+            get_config(&proj).demangling
+        } else {
+            Some(Demangling::NoDemangling)
+        };
+        let matching_funcs = proj.all_functions()
+            .map(|(func, _)| func.name.as_str())
+            .filter(|proj_funcname| regex.is_match(&demangle(proj_funcname, demangling)));
+        funcnames.extend(matching_funcs);
+    }
+
+    let args_for = |funcname: &str| match args_from_file.get(funcname) {
+        Some(specs) => {
+            let num_params = proj.get_func_by_name(funcname).map(|(func, _)| func.parameters.len()).unwrap_or(0);
+            Some(args_file::args_for_function(specs, num_params))
+        },
+        None => get_args_for_funcname(funcname),
+    };
+
+    if cmdlineoptions.describe_args {
+        let mut status = EXIT_SUCCESS;
+        for funcname in &funcnames {
+            match describe_args(funcname, args_for(funcname), &struct_descriptions, &proj, &cmdlineoptions.pitchfork_config) {
+                Ok(description) => println!("{}", description),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    status = EXIT_ERROR;
+                },
+            }
+        }
+        return status;
+    }
+
+    let analyze = |funcname: &str| {
+        let mut config = get_config(&proj);
+        set_cmdline_overrides(&mut config, &cmdlineoptions);
+        let result = check_for_ct_violation(
+            funcname,
+            &proj,
+            args_for(funcname),
+            &struct_descriptions,
+            config,
+            &cmdlineoptions.pitchfork_config,
+        );
+        if !cmdlineoptions.quiet {
             println!("{}", result);
-            results.push(result);
         }
+        FunctionSummary {
+            funcname: funcname.to_owned(),
+            num_paths: result.path_results.len(),
+            path_stats: result.path_statistics(),
+            json: cmdlineoptions.json_output.as_ref().map(|_| json::result_to_json(&result)),
+        }
+    };
+    let summaries: Vec<FunctionSummary> = if jobs > 1 {
+        // `ConstantTimeResultForFunction`s can't be sent between threads, so
+        // each thread reports just the `FunctionSummary`s
+        let next_func = AtomicUsize::new(0);
+        let summaries = Mutex::new(Vec::with_capacity(funcnames.len()));
+        std::thread::scope(|scope| {
+            for _ in 0 .. jobs {
+                scope.spawn(|| loop {
+                    let i = next_func.fetch_add(1, Ordering::SeqCst);
+                    let funcname = match funcnames.get(i) {
+                        Some(funcname) => funcname,
+                        None => break,
+                    };
+                    let summary = analyze(funcname);
+                    summaries.lock().unwrap().push((i, summary));
+                });
+            }
+        });
+        summaries.into_inner().unwrap().into_iter().sorted_by_key(|(i, _)| *i).map(|(_, summary)| summary).collect()
+    } else {
+        funcnames.iter().map(|funcname| analyze(funcname)).collect()
+    };
+
+    if let Some(path) = &cmdlineoptions.json_output {
+        let jsons: Vec<serde_json::Value> = summaries.iter().filter_map(|summary| summary.json.clone()).collect();
+        json::write_json_array(&jsons, path)
+            .unwrap_or_else(|e| eprintln!("error: failed to write JSON output to {}: {}", path, e));
     }
-    if results.len() > 1 {
+
+    let status = if summaries.iter().any(|summary| summary.path_stats.num_ct_violations > 0) {
+        EXIT_VIOLATION
+    } else if summaries.iter().any(|summary| summary.num_paths > summary.path_stats.num_ct_paths) {
+        EXIT_ERROR
+    } else {
+        EXIT_SUCCESS
+    };
+    if summaries.len() > 1 && !cmdlineoptions.quiet {
         println!("\n=======\n\nSummary of results:\n");
-        for result in results {
-            let path_stats = result.path_statistics();
-            let is_ct = result.path_results.len() == path_stats.num_ct_paths;
-            let have_violation = path_stats.num_ct_violations > 0;
-            println!("{} {}", result.funcname,
+        for summary in summaries {
+            let is_ct = summary.num_paths == summary.path_stats.num_ct_paths;
+            let have_violation = summary.path_stats.num_ct_violations > 0;
+            println!("{} {}", summary.funcname,
                 if is_ct { "is constant-time".green() }
                 else if have_violation { "is not constant-time".red() }
                 else { "encountered errors".red() }
             );
-            println!("{}", path_stats);
+            println!("{}", summary.path_stats);
         }
     }
+    status
+}
+
+/// The parts of a `ConstantTimeResultForFunction` needed for the summary
+/// printed at the end of `main_func()`
+struct FunctionSummary {
+    funcname: String,
+    /// Number of entries in `path_results`
+    num_paths: usize,
+    path_stats: PathStatistics,
+    /// The result as a JSON value, if we're producing JSON output
+    json: Option<serde_json::Value>,
+}
+
+/// Demangle `funcname` according to `demangling`. `None` means to try Rust
+/// and then C++ demangling.
+fn demangle(funcname: &str, demangling: Option<Demangling>) -> String {
+    match demangling {
+        Some(demangling) => demangling.maybe_demangle(funcname),
+        None => {
+            let demangled = Demangling::Rust.maybe_demangle(funcname);
+            if demangled != funcname {
+                demangled
+            } else {
+                Demangling::CPP.maybe_demangle(funcname)
+            }
+        },
+    }
 }
 
 fn set_cmdline_overrides(config: &mut Config<secret::Backend>, cmdlineoptions: &CommandLineOptions) {
@@ -1,16 +1,92 @@
 use colored::*;
-use crate::ConstantTimeResultForPath;
-use haybale::Error;
+use crate::{secret, CTViolation, CTViolationKind, ConstantTimeResultForPath};
+use crate::violation::is_solver_timeout;
+use haybale::{Error, State};
+use haybale::state::BBInstrIndex;
 use std::fmt;
+use std::time::Duration;
+
+/// Some measurements of a single path, recorded while analyzing it
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PathMetrics {
+    /// Number of basic blocks on the path (counting a block multiple times if
+    /// it was entered multiple times)
+    pub num_blocks: usize,
+    /// Number of LLVM instructions (including terminators) executed on the
+    /// path. This counts each block on the path from the instruction where the
+    /// path entered the block, through the terminator (or through the
+    /// instruction where the path ended with an error or violation).
+    pub num_instructions: usize,
+    /// Wall-clock time taken to analyze the path
+    pub duration: Duration,
+    /// Of `duration`, the wall-clock time spent in the solver queries Pitchfork
+    /// makes itself, mostly when reading memory (to determine whether the data
+    /// read is secret). Queries made inside haybale, e.g. to determine which
+    /// directions a branch can go, are not included.
+    pub solver_duration: Duration,
+    /// Numbers of the solver queries counted in `solver_duration`, by category
+    pub solver_queries: secret::SolverQueryCounts,
+}
+
+impl PathMetrics {
+    /// Compute the `PathMetrics` for the path which was just analyzed in
+    /// `state`, taking `duration`, of which `solver_duration` was spent in
+    /// the `solver_queries`.
+    ///
+    /// `path_ended_in_error`: if `true`, then the path ended with an error or
+    /// violation at the current location in `state`
+    pub(crate) fn of_current_path(state: &State<secret::Backend>, path_ended_in_error: bool, duration: Duration, solver_duration: Duration, solver_queries: secret::SolverQueryCounts) -> Self {
+        let path = state.get_path();
+        let num_instructions = path.iter().enumerate().map(|(i, pathentry)| {
+            let bb = pathentry.0.bb;
+            let start = match pathentry.0.instr {
+                BBInstrIndex::Instr(idx) => idx,
+                BBInstrIndex::Terminator => bb.instrs.len(),
+            };
+            let end = if path_ended_in_error && i == path.len() - 1 {
+                match state.cur_loc.instr {
+                    BBInstrIndex::Instr(idx) => idx + 1,
+                    BBInstrIndex::Terminator => bb.instrs.len() + 1,
+                }
+            } else {
+                bb.instrs.len() + 1
+            };
+            end.saturating_sub(start)
+        }).sum();
+        Self {
+            num_blocks: path.len(),
+            num_instructions,
+            duration,
+            solver_duration,
+            solver_queries,
+        }
+    }
+}
 
 /// Some statistics which can be computed from a
 /// [`ConstantTimeResultForFunction`](struct.ConstantTimeResultForFunction.html).
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct PathStatistics {
+    /// Did the analysis run to completion, exploring every path it could? See
+    /// notes on `exhaustive` in `ConstantTimeResultForFunction`. This is
+    /// `false` for statistics of an analysis still in progress.
+    pub exhaustive: bool,
     /// How many paths "passed", that is, had no error or constant-time violation
     pub num_ct_paths: usize,
     /// How many constant-time violations did we find
     pub num_ct_violations: usize,
+    /// Of the constant-time violations, how many were `CTViolationKind::Branch`
+    pub num_secret_branches: usize,
+    /// Of the constant-time violations, how many were `CTViolationKind::MemoryAddress`
+    pub num_secret_memory_addresses: usize,
+    /// Of the constant-time violations, how many were `CTViolationKind::Select`
+    pub num_secret_selects: usize,
+    /// Of the constant-time violations, how many were `CTViolationKind::VariableTimeOperation`
+    pub num_variable_time_operations: usize,
+    /// Of the constant-time violations, how many were `CTViolationKind::LoopBound`
+    pub num_secret_loop_bounds: usize,
+    /// Of the constant-time violations, how many were `CTViolationKind::Hook`
+    pub num_hook_violations: usize,
     /// How many Unsat errors did we find
     pub num_unsats: usize,
     /// How many LoopBoundExceeded errors did we find
@@ -19,8 +95,11 @@ pub struct PathStatistics {
     pub num_null_ptr_deref: usize,
     /// How many FunctionNotFound errors did we find
     pub num_function_not_found: usize,
-    /// How many solver errors (including timeouts) did we find
+    /// How many solver errors (other than timeouts) did we find
     pub num_solver_errors: usize,
+    /// How many solver queries timed out (see `solver_query_timeout` in
+    /// `Config`)
+    pub num_solver_timeouts: usize,
     /// How many UnsupportedInstruction errors did we find
     pub num_unsupported_instruction: usize,
     /// How many MalformedInstruction errors did we find
@@ -33,36 +112,181 @@ pub struct PathStatistics {
     pub num_hook_retval_mismatch: usize,
     /// How many other errors did we find
     pub num_other_errors: usize,
+    /// How many times did the analysis time out (see `per_function_timeout` in
+    /// `PitchforkConfig`)
+    pub num_timeouts: usize,
+    /// Total number of LLVM instructions executed, over all paths for which
+    /// `PathMetrics` were recorded. See notes on `num_instructions` in
+    /// `PathMetrics`.
+    pub total_instructions: usize,
+    /// The number of basic blocks on each path for which `PathMetrics` were
+    /// recorded
+    pub path_lengths: Vec<usize>,
+    /// The wall-clock time taken to analyze each path for which `PathMetrics`
+    /// were recorded
+    pub path_durations: Vec<Duration>,
+    /// The wall-clock time spent in solver queries while analyzing each path
+    /// for which `PathMetrics` were recorded. See notes on `solver_duration`
+    /// in `PathMetrics`.
+    pub path_solver_durations: Vec<Duration>,
+    /// Numbers of solver queries, by category, over all paths for which
+    /// `PathMetrics` were recorded. See notes on `solver_queries` in
+    /// `PathMetrics`.
+    pub solver_queries: secret::SolverQueryCounts,
 }
 
 impl PathStatistics {
     /// A fresh `PathStatistics` with all zeroes
     pub(crate) fn new() -> Self {
         Self {
+            exhaustive: false,
             num_ct_paths: 0,
             num_ct_violations: 0,
+            num_secret_branches: 0,
+            num_secret_memory_addresses: 0,
+            num_secret_selects: 0,
+            num_variable_time_operations: 0,
+            num_secret_loop_bounds: 0,
+            num_hook_violations: 0,
             num_unsats: 0,
             num_loop_bound_exceeded: 0,
             num_null_ptr_deref: 0,
             num_function_not_found: 0,
             num_solver_errors: 0,
+            num_solver_timeouts: 0,
             num_unsupported_instruction: 0,
             num_malformed_instruction: 0,
             num_unreachable_instruction: 0,
             num_failed_resolve_fptr: 0,
             num_hook_retval_mismatch: 0,
             num_other_errors: 0,
+            num_timeouts: 0,
+            total_instructions: 0,
+            path_lengths: Vec::new(),
+            path_durations: Vec::new(),
+            path_solver_durations: Vec::new(),
+            solver_queries: secret::SolverQueryCounts::default(),
+        }
+    }
+
+    pub(crate) fn add_path_metrics(&mut self, path_metrics: &PathMetrics) {
+        self.total_instructions += path_metrics.num_instructions;
+        self.path_lengths.push(path_metrics.num_blocks);
+        self.path_durations.push(path_metrics.duration);
+        self.path_solver_durations.push(path_metrics.solver_duration);
+        self.solver_queries.add(&path_metrics.solver_queries);
+    }
+
+    /// The total number of paths, of any result
+    pub fn num_paths(&self) -> usize {
+        self.num_ct_paths
+            + self.num_ct_violations
+            + self.num_errors()
+            - self.num_timeouts  // timeouts aren't paths
+    }
+
+    /// The total number of paths which ended in an error other than a
+    /// constant-time violation (counting a timeout as an error)
+    pub fn num_errors(&self) -> usize {
+        self.error_counts().iter().sum()
+    }
+
+    /// The number of distinct kinds of errors (other than constant-time
+    /// violations) which were encountered, e.g., 2 if we found only Unsat and
+    /// LoopBoundExceeded errors
+    pub fn num_distinct_error_kinds(&self) -> usize {
+        self.error_counts().iter().filter(|&&num| num > 0).count()
+    }
+
+    /// The number of distinct kinds of constant-time violations which were
+    /// encountered
+    pub fn num_distinct_violation_kinds(&self) -> usize {
+        [
+            self.num_secret_branches,
+            self.num_secret_memory_addresses,
+            self.num_secret_selects,
+            self.num_variable_time_operations,
+            self.num_secret_loop_bounds,
+            self.num_hook_violations,
+        ].iter().filter(|&&num| num > 0).count()
+    }
+
+    /// The average number of basic blocks per path, or `None` if no
+    /// `PathMetrics` were recorded
+    pub fn average_path_length(&self) -> Option<f64> {
+        if self.path_lengths.is_empty() {
+            None
+        } else {
+            Some(self.path_lengths.iter().sum::<usize>() as f64 / self.path_lengths.len() as f64)
         }
     }
 
+    /// The median number of basic blocks per path, or `None` if no
+    /// `PathMetrics` were recorded. (For an even number of paths, this is the
+    /// lower of the two middle values.)
+    pub fn median_path_length(&self) -> Option<usize> {
+        let mut lengths = self.path_lengths.clone();
+        lengths.sort_unstable();
+        lengths.get(lengths.len().saturating_sub(1) / 2).copied()
+    }
+
+    /// The total wall-clock time taken to analyze all the paths for which
+    /// `PathMetrics` were recorded
+    pub fn total_duration(&self) -> Duration {
+        self.path_durations.iter().sum()
+    }
+
+    /// The total wall-clock time spent in solver queries, over all the paths
+    /// for which `PathMetrics` were recorded. See notes on `solver_duration`
+    /// in `PathMetrics`.
+    pub fn total_solver_duration(&self) -> Duration {
+        self.path_solver_durations.iter().sum()
+    }
+
+    /// The total wall-clock time spent outside of solver queries (that is,
+    /// `total_duration()` minus `total_solver_duration()`)
+    pub fn total_execution_duration(&self) -> Duration {
+        self.total_duration().checked_sub(self.total_solver_duration()).unwrap_or_default()
+    }
+
+    /// The counts of each kind of error other than constant-time violations
+    fn error_counts(&self) -> [usize; 13] {
+        [
+            self.num_unsats,
+            self.num_loop_bound_exceeded,
+            self.num_null_ptr_deref,
+            self.num_function_not_found,
+            self.num_solver_errors,
+            self.num_solver_timeouts,
+            self.num_unsupported_instruction,
+            self.num_malformed_instruction,
+            self.num_unreachable_instruction,
+            self.num_failed_resolve_fptr,
+            self.num_hook_retval_mismatch,
+            self.num_other_errors,
+            self.num_timeouts,
+        ]
+    }
+
     pub(crate) fn add_path_result(&mut self, path_result: &ConstantTimeResultForPath) {
         match path_result {
-            ConstantTimeResultForPath::IsConstantTime => self.num_ct_paths += 1,
-            ConstantTimeResultForPath::NotConstantTime { .. } => self.num_ct_violations += 1,
+            ConstantTimeResultForPath::IsConstantTime { .. } => self.num_ct_paths += 1,
+            ConstantTimeResultForPath::NotConstantTime { violation: CTViolation { kind, .. } } => {
+                self.num_ct_violations += 1;
+                match kind {
+                    CTViolationKind::Branch => self.num_secret_branches += 1,
+                    CTViolationKind::MemoryAddress => self.num_secret_memory_addresses += 1,
+                    CTViolationKind::Select => self.num_secret_selects += 1,
+                    CTViolationKind::VariableTimeOperation => self.num_variable_time_operations += 1,
+                    CTViolationKind::LoopBound => self.num_secret_loop_bounds += 1,
+                    CTViolationKind::Hook => self.num_hook_violations += 1,
+                }
+            },
             ConstantTimeResultForPath::OtherError { error: Error::Unsat, .. } => self.num_unsats += 1,
             ConstantTimeResultForPath::OtherError { error: Error::LoopBoundExceeded(_), .. } => self.num_loop_bound_exceeded += 1,
             ConstantTimeResultForPath::OtherError { error: Error::NullPointerDereference, .. } => self.num_null_ptr_deref += 1,
             ConstantTimeResultForPath::OtherError { error: Error::FunctionNotFound(_), .. } => self.num_function_not_found += 1,
+            ConstantTimeResultForPath::OtherError { error: error @ Error::SolverError(_), .. } if is_solver_timeout(error) => self.num_solver_timeouts += 1,
             ConstantTimeResultForPath::OtherError { error: Error::SolverError(_), .. } => self.num_solver_errors += 1,
             ConstantTimeResultForPath::OtherError { error: Error::UnsupportedInstruction(_), .. } => self.num_unsupported_instruction += 1,
             ConstantTimeResultForPath::OtherError { error: Error::MalformedInstruction(_), .. } => self.num_malformed_instruction += 1,
@@ -70,6 +294,7 @@ impl PathStatistics {
             ConstantTimeResultForPath::OtherError { error: Error::FailedToResolveFunctionPointer(_), .. } => self.num_failed_resolve_fptr += 1,
             ConstantTimeResultForPath::OtherError { error: Error::HookReturnValueMismatch(_), .. } => self.num_hook_retval_mismatch += 1,
             ConstantTimeResultForPath::OtherError { error: Error::OtherError(_), .. } => self.num_other_errors += 1,
+            ConstantTimeResultForPath::TimedOut { .. } => self.num_timeouts += 1,
         }
     }
 }
@@ -91,6 +316,19 @@ impl fmt::Display for PathStatistics {
                 self.num_ct_violations.to_string().normal()
             }
         )?;
+        // and break down the violations by kind, if there are any
+        for (num, kind) in &[
+            (self.num_secret_branches, CTViolationKind::Branch),
+            (self.num_secret_memory_addresses, CTViolationKind::MemoryAddress),
+            (self.num_secret_selects, CTViolationKind::Select),
+            (self.num_variable_time_operations, CTViolationKind::VariableTimeOperation),
+            (self.num_secret_loop_bounds, CTViolationKind::LoopBound),
+            (self.num_hook_violations, CTViolationKind::Hook),
+        ] {
+            if *num > 0 {
+                writeln!(f, "  {}: {}", kind, num.to_string().red())?;
+            }
+        }
 
         // For the other error types, we only show the entry if it's > 0
         if self.num_null_ptr_deref > 0 {
@@ -138,8 +376,13 @@ impl fmt::Display for PathStatistics {
                 self.num_hook_retval_mismatch.to_string().red()
             )?;
         }
+        if self.num_solver_timeouts > 0 {
+            writeln!(f, "paths with a solver timeout: {}",
+                self.num_solver_timeouts.to_string().red()
+            )?;
+        }
         if self.num_solver_errors > 0 {
-            writeln!(f, "solver errors, including timeouts: {}",
+            writeln!(f, "other solver errors: {}",
                 self.num_solver_errors.to_string().red()
             )?;
         }
@@ -148,6 +391,24 @@ impl fmt::Display for PathStatistics {
                 self.num_other_errors.to_string().red()
             )?;
         }
+        if self.num_timeouts > 0 {
+            writeln!(f, "{}", "analysis timed out".red())?;
+        }
+        if !self.path_durations.is_empty() {
+            writeln!(f, "time spent: {:.3}s in solver queries, {:.3}s elsewhere",
+                self.total_solver_duration().as_secs_f64(),
+                self.total_execution_duration().as_secs_f64(),
+            )?;
+            writeln!(f, "solver queries: {} ({} all-public read checks, {} all-secret read checks, {} secret-mask solves, {} other); {} public reads answered from cache, {} reads with constant secrecy",
+                self.solver_queries.total(),
+                self.solver_queries.read_all_public_checks,
+                self.solver_queries.read_all_secret_checks,
+                self.solver_queries.read_secret_mask_solves,
+                self.solver_queries.other_queries,
+                self.solver_queries.read_cache_hits,
+                self.solver_queries.read_constant_secrecy,
+            )?;
+        }
         Ok(())
     }
 }
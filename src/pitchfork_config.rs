@@ -1,3 +1,9 @@
+use crate::{AbstractData, PatternHook};
+use crate::secret::ObliviousAccessHook;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
 /// `pitchfork`-specific configuration options, in addition to the configuration
 /// options in `haybale::Config`.
 ///
@@ -41,6 +47,13 @@ pub struct PitchforkConfig {
     /// Default is `true`.
     pub dump_coverage_stats: bool,
 
+    /// If `true`, the `Display` for `ConstantTimeResultForFunction` includes
+    /// coverage stats when the function was verified constant-time. Either
+    /// way, the coverage stats are available in the `block_coverage` field.
+    ///
+    /// Default is `true`.
+    pub display_coverage_stats: bool,
+
     /// If `true`, `pitchfork` will provide detailed progress updates in a
     /// continuously-updated terminal display. This includes counts of paths
     /// verified / errors encountered / warnings generated; the current code
@@ -83,6 +96,322 @@ pub struct PitchforkConfig {
     ///
     /// Default is `false`.
     pub debug_logging: bool,
+
+    /// Directory in which to write the log file (with `progress_updates`),
+    /// the error dump (with `dump_errors`), and the coverage stats (with
+    /// `dump_coverage_stats`). Each function's files are written in a
+    /// subdirectory named after the function, and the directories are
+    /// created if necessary. `None` means `logs` in the current directory.
+    ///
+    /// If a directory or file can't be created (e.g., on a read-only
+    /// filesystem), a warning is logged and that particular output is skipped;
+    /// in the case of the log file, `progress_updates` is disabled.
+    ///
+    /// Default is `None`.
+    pub log_dir: Option<PathBuf>,
+
+    /// When an `AbstractData::default()` is expanded for a pointer to an
+    /// integer type (e.g., `char*`) or a pointer to an array of unspecified
+    /// length, the pointer will point to an allocated array of this many
+    /// elements.
+    ///
+    /// For more details, see the docs on
+    /// [`AbstractData::default()`](struct.AbstractData.html#method.default).
+    ///
+    /// Default is `AbstractData::DEFAULT_ARRAY_LENGTH` (1024).
+    pub default_array_length: usize,
+
+    /// When an `AbstractData::default()` is expanded for a struct which is
+    /// entirely opaque (no definition anywhere in the `Project`) and which
+    /// doesn't appear in the `StructDescriptions`, we allocate this many
+    /// unconstrained bytes for it and assume that's enough.
+    ///
+    /// A watchpoint is placed just past the end of those bytes, so any access
+    /// beyond them will be reported in the log (look for watchpoints named
+    /// `past_end_of_opaque_struct:<struct name>`). If that happens, you should
+    /// either increase this setting, or describe the struct in the
+    /// `StructDescriptions`. The `opaque_structs` field of
+    /// `ConstantTimeResultForFunction` lists the struct names which were treated
+    /// this way.
+    ///
+    /// Default is `AbstractData::OPAQUE_STRUCT_SIZE_BYTES` (64 KiB).
+    pub opaque_struct_size_bytes: usize,
+
+    /// When expanding `AbstractData` (in particular `AbstractData::default()`)
+    /// for the function arguments, structs may contain (or point to) other
+    /// structs, and so on. Infinitely recursive structs are detected, but
+    /// finite nesting can still be deep enough to overflow the stack for
+    /// pathological types. If the nesting exceeds this many structs, the
+    /// expansion instead panics with a message listing the structs it was
+    /// within.
+    ///
+    /// Default is `AbstractData::MAX_STRUCT_NESTING_DEPTH` (128).
+    pub max_struct_nesting_depth: usize,
+
+    /// A 'select' operation (LLVM `select` instruction) with a secret condition
+    /// may or may not be constant-time, depending on the target architecture and
+    /// how the instruction is ultimately compiled (e.g., to a constant-time
+    /// conditional move, or to a branch).
+    ///
+    /// If `true`, `pitchfork` will report a constant-time violation for any
+    /// 'select' with a secret condition.
+    /// If `false`, `pitchfork` will only log a warning in this case.
+    ///
+    /// Default is `false`.
+    pub secret_select_is_violation: bool,
+
+    /// Instructions whose timing may depend on the values of their operands on
+    /// many architectures, e.g., integer division.
+    /// `pitchfork` will report a constant-time violation whenever one of these
+    /// instructions is executed with an operand which is even partially secret.
+    ///
+    /// Default is `VariableTimeOpcode::UDiv`, `VariableTimeOpcode::SDiv`,
+    /// `VariableTimeOpcode::URem`, and `VariableTimeOpcode::SRem`.
+    pub variable_time_opcodes: HashSet<VariableTimeOpcode>,
+
+    /// If nonempty, only constant-time violations whose source location (see
+    /// `source_loc` in `CTViolation`) is in a file whose path starts with one
+    /// of these prefixes are reported. Paths are as given in the debug info,
+    /// joined with the compilation directory if they are relative; a prefix
+    /// matches if it matches either the joined path or the path as given.
+    ///
+    /// Violations which aren't reported are instead logged as warnings and
+    /// recorded in `filtered_violations` in `ConstantTimeResultForFunction`;
+    /// they don't count as errors for the purposes of `keep_going` or
+    /// `max_violations`. Violations with no source location are always
+    /// reported.
+    ///
+    /// Default is empty, meaning that violations in any file are reported.
+    pub violation_source_prefixes: Vec<String>,
+
+    /// Constant-time violations whose source location is in a file whose path
+    /// starts with one of these prefixes are not reported, even if the file
+    /// also matches `violation_source_prefixes`. Otherwise, this works the same
+    /// as `violation_source_prefixes`; see notes there.
+    ///
+    /// Default is empty.
+    pub ignored_violation_source_prefixes: Vec<String>,
+
+    /// Function hooks which apply to all called functions whose names match
+    /// a pattern, e.g., all instantiations of a C++ template. See
+    /// `PatternHook::new()` and `PatternHook::new_demangled()`.
+    ///
+    /// These are consulted by `pitchfork`'s default function hook, so they
+    /// only apply to functions which have no LLVM definition in the `Project`
+    /// and no hook for their exact name in `Config.function_hooks`; and they
+    /// don't apply at all if a different default hook was provided in
+    /// `Config.function_hooks`. If several patterns match, the first one in
+    /// this list is used.
+    ///
+    /// Default is empty.
+    pub pattern_hooks: Vec<PatternHook>,
+
+    /// If `Some`, then memory reads and writes at addresses which can be
+    /// influenced by secret data are not reported as constant-time violations.
+    /// Instead, they are passed to this hook, which determines the value read
+    /// (or the effect of the write), or returns an error.
+    ///
+    /// This is intended for analyzing designs such as oblivious data
+    /// structures, where a secret pointer is only used through primitives
+    /// which access memory in a way that doesn't depend on the secret, and
+    /// the hook models those primitives. Note that this applies to every
+    /// access with a secret address, so any other such access in the function
+    /// also goes unreported (unless the hook itself returns an error for it).
+    ///
+    /// Default is `None`.
+    pub oblivious_access_hook: Option<ObliviousAccessHook>,
+
+    /// Map from global variable names (as they appear in the LLVM) to
+    /// `AbstractData` descriptions of their contents. Before any paths are
+    /// analyzed (and after the function parameters are allocated), each of
+    /// these globals is reinitialized according to its description, replacing
+    /// its LLVM initializer. This is useful for analyzing code whose secrets
+    /// live in globals, e.g. a key schedule: use `AbstractData::secret()` to
+    /// make a global's entire contents secret.
+    ///
+    /// Globals not in this map keep their LLVM initializers, and are public.
+    /// Naming a global which doesn't exist in the `Project` results in a panic.
+    ///
+    /// Default is empty.
+    pub global_variables: BTreeMap<String, AbstractData>,
+
+    /// Normally, a branch on secret data is a constant-time violation which
+    /// ends the path it occurs on, so we never learn what would have happened
+    /// on either side of the branch.
+    ///
+    /// If `true`, then for debugging purposes, `pitchfork` will instead continue
+    /// exploring both successors of a secret branch, treating the branch
+    /// condition as if it were an unconstrained public value. Any path which
+    /// passes through a secret branch will be reported as `NotConstantTime`
+    /// (with the first secret branch on the path as the violation, if the path
+    /// otherwise completes successfully), and its `post_violation` flag will be
+    /// set to indicate that the rest of the path was explored under this
+    /// assumption.
+    ///
+    /// In this mode, a loop whose exit condition depends on secret data will
+    /// typically run until the `loop_bound` in `Config` is reached; this is
+    /// reported as a `CTViolationKind::LoopBound` violation, distinguishing it
+    /// from a loop which merely exceeds the loop bound on public data.
+    ///
+    /// Note that in this mode, all constraints on secret data are dropped,
+    /// including those from `llvm.assume` or from function hooks.
+    ///
+    /// Default is `false`.
+    pub continue_past_secret_branches: bool,
+
+    /// When `pitchfork` encounters a call of a function which has neither an
+    /// LLVM definition nor a function hook, and some argument to the call is
+    /// secret or points to secret data, the default behavior is to raise an
+    /// error, requiring a hook (or LLVM definition) to be provided for that
+    /// function.
+    ///
+    /// If `true`, `pitchfork` will instead stub out such functions, returning a
+    /// secret value if any argument is secret or points to secret data (as in
+    /// `hooks::propagate_taint`). This is less sound, since it ignores anything
+    /// the function might do with the secret data (including branching on it),
+    /// so a warning will be logged the first time each function is stubbed this
+    /// way.
+    ///
+    /// Default is `false`.
+    pub auto_stub_secret_functions: bool,
+
+    /// By default, memory which has never been written is treated as holding
+    /// public, unconstrained data. This means that if the `AbstractData` for an
+    /// argument doesn't describe some memory which the function reads (for
+    /// instance, an array which is too short), the analysis silently proceeds
+    /// with public data there.
+    ///
+    /// If `true`, reading memory which has never been written (neither by the
+    /// analyzed code nor when initializing the function arguments) is instead
+    /// an error. This is useful for catching underspecified arguments, at
+    /// some cost in performance.
+    ///
+    /// Default is `false`.
+    pub strict_uninitialized_memory: bool,
+
+    /// By default, `pitchfork` tracks whether memory contents are secret at
+    /// the granularity of individual bits. This is maximally precise, but
+    /// reading data which is only partially secret requires a solve for which
+    /// of its bits are secret.
+    ///
+    /// If `true`, secrecy is instead tracked per byte: writing data with any
+    /// secret bits in a byte marks that entire byte as secret. Reads then only
+    /// need to determine which _bytes_ are secret, which is much cheaper. For
+    /// most code, secrecy is byte-aligned anyway, so this loses little or no
+    /// precision; but it may report violations on sub-byte values (e.g.,
+    /// public flag bits packed into the same byte as secret bits) which the
+    /// default would not.
+    ///
+    /// Default is `false`.
+    pub byte_granularity_secrecy: bool,
+
+    /// If `true`, then for each constant-time violation, `pitchfork` asks the
+    /// solver for concrete values of the public parts of the function's
+    /// inputs (parameter values, and the initial contents of memory allocated
+    /// for pointer parameters) which lead to the violation. These are recorded
+    /// in `counterexample` in `CTViolation`, and included in its full message.
+    ///
+    /// This costs a solver query for each violation, and a snapshot of the
+    /// memory allocated for the parameters at the start of the analysis.
+    ///
+    /// Default is `false`.
+    pub counterexamples: bool,
+
+    /// With `counterexamples`, for each public parameter, `pitchfork` also
+    /// finds up to this many distinct values of that parameter which lead to
+    /// the violation (by repeatedly solving and excluding the previous
+    /// solution), to characterize the triggering inputs. These are recorded
+    /// in `possible_values` in `ArgCounterexample`. `0` disables this.
+    ///
+    /// This costs up to this many additional solver queries per public
+    /// parameter, for each violation.
+    ///
+    /// Default is `3`.
+    pub counterexample_num_values: usize,
+
+    /// If `true`, the memory allocated for each pointee in the function
+    /// arguments (e.g., for each `AbstractData::pub_pointer_to()`) is surrounded
+    /// by unused guard bytes with watchpoints on them, so that any access just
+    /// before or past the end of a pointee is reported. This catches, e.g.,
+    /// an array specified with fewer elements than the function actually uses.
+    ///
+    /// Accesses to the guard bytes are reported in the log and in the
+    /// `watchpoint_hits` of the `ConstantTimeResultForFunction` (look for
+    /// watchpoints named `outside_of_pointee:<pointee>`). Only accesses at
+    /// constant addresses, and within 64 bytes of the pointee, are detected.
+    ///
+    /// Default is `false`.
+    pub bounds_check_pointees: bool,
+
+    /// Maximum total time to spend analyzing a single function. This is
+    /// checked before starting each path; once it has elapsed, no further paths
+    /// are analyzed, and a `ConstantTimeResultForPath::TimedOut` is recorded
+    /// after the results of the paths which were already analyzed.
+    ///
+    /// Note that this does not interrupt a path which is already being
+    /// analyzed; to bound the time taken by individual solver queries, see the
+    /// `solver_query_timeout` setting in `Config`.
+    ///
+    /// `None` indicates no timeout.
+    ///
+    /// Default is `None`.
+    pub per_function_timeout: Option<Duration>,
+
+    /// If `keep_going == true`, stop analyzing a function once this many
+    /// constant-time violations have been found in it, even if there are more
+    /// paths to explore. This is useful for getting a sampling of the
+    /// violations in a large function without analyzing it to completion.
+    ///
+    /// This setting only applies if `keep_going == true`; with
+    /// `keep_going == false`, the analysis always stops at the first error or
+    /// violation.
+    ///
+    /// `None` indicates no limit.
+    ///
+    /// Default is `None`.
+    pub max_violations: Option<usize>,
+
+    /// The order in which paths are explored is determined by `haybale`, and
+    /// may vary between runs; so with `keep_going`, the order of the
+    /// `path_results` (and therefore which violation is reported "first") may
+    /// vary too. If this setting is `true`, `pitchfork` sorts the
+    /// `path_results` in a canonical order before returning them:
+    /// constant-time violations first (ordered by source location, then
+    /// function, block, and message), then other errors (ordered by message),
+    /// then paths with no errors or violations. Results with equal keys are
+    /// left in the order they were found.
+    ///
+    /// This only makes the order of the results reproducible; which paths are
+    /// analyzed before the analysis stops (e.g., with `keep_going == false`,
+    /// `max_violations`, or `per_function_timeout`) may still vary.
+    ///
+    /// Default is `false`.
+    pub sort_path_results: bool,
+
+    /// Number of functions which `main_func()` will analyze concurrently, each
+    /// in its own thread. Values of 0 and 1 both mean to analyze one function at
+    /// a time.
+    ///
+    /// When this is greater than 1, `progress_updates` is ignored (treated as
+    /// `false`), as the progress-updates UI can only display the progress of
+    /// one analysis at a time.
+    ///
+    /// This setting only affects `main_func()`; `check_for_ct_violation()` and
+    /// friends always analyze a single function on the current thread.
+    ///
+    /// Default is `1`.
+    pub jobs: usize,
+}
+
+/// LLVM instructions which may be configured as variable-time; see the
+/// `variable_time_opcodes` setting in `PitchforkConfig`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum VariableTimeOpcode {
+    Mul,
+    UDiv,
+    SDiv,
+    URem,
+    SRem,
 }
 
 impl Default for PitchforkConfig {
@@ -91,8 +420,36 @@ impl Default for PitchforkConfig {
             keep_going: false,
             dump_errors: true,
             dump_coverage_stats: true,
+            display_coverage_stats: true,
             progress_updates: true,
             debug_logging: false,
+            log_dir: None,
+            default_array_length: AbstractData::DEFAULT_ARRAY_LENGTH,
+            opaque_struct_size_bytes: AbstractData::OPAQUE_STRUCT_SIZE_BYTES,
+            max_struct_nesting_depth: AbstractData::MAX_STRUCT_NESTING_DEPTH,
+            secret_select_is_violation: false,
+            continue_past_secret_branches: false,
+            auto_stub_secret_functions: false,
+            strict_uninitialized_memory: false,
+            byte_granularity_secrecy: false,
+            counterexamples: false,
+            counterexample_num_values: 3,
+            bounds_check_pointees: false,
+            per_function_timeout: None,
+            max_violations: None,
+            sort_path_results: false,
+            jobs: 1,
+            variable_time_opcodes: vec![
+                VariableTimeOpcode::UDiv,
+                VariableTimeOpcode::SDiv,
+                VariableTimeOpcode::URem,
+                VariableTimeOpcode::SRem,
+            ].into_iter().collect(),
+            violation_source_prefixes: Vec::new(),
+            ignored_violation_source_prefixes: Vec::new(),
+            pattern_hooks: Vec::new(),
+            oblivious_access_hook: None,
+            global_variables: BTreeMap::new(),
         }
     }
 }
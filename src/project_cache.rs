@@ -0,0 +1,51 @@
+use haybale::Project;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The size and modification time of a bitcode file's contents
+type FileStamp = (u64, Option<SystemTime>);
+
+lazy_static! {
+    /// Map from the (canonicalized) paths of the bitcode files, to their
+    /// `FileStamp`s when they were parsed, and the `Project` parsed from them
+    static ref PROJECTS: Mutex<HashMap<Vec<PathBuf>, (Vec<FileStamp>, Arc<Project>)>> = Mutex::new(HashMap::new());
+}
+
+/// Get a `Project` containing the bitcode files at the given paths, reusing
+/// the `Project` from a previous call with the same paths if none of the files
+/// have changed (as judged by their size and modification time) since then.
+/// Otherwise, this parses the files with `Project::from_bc_paths()`.
+///
+/// This is useful for harnesses which analyze many functions, or the same
+/// function with many different arguments, from separate calls: parsing a
+/// large `Project` can take many seconds.
+///
+/// The cache holds one `Project` for each set of paths: if the files change,
+/// the newly parsed `Project` replaces the stale one, which is freed once
+/// callers drop their `Arc`s to it. The cache doesn't persist across process
+/// runs, as `Project`s (and the `llvm-ir` types they contain) can't be
+/// serialized.
+pub fn cached_project<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Arc<Project>, String> {
+    let paths: Vec<PathBuf> = paths.into_iter().map(|path| path.as_ref().to_owned()).collect();
+    let stamps = paths.iter()
+        .map(|path| {
+            let metadata = std::fs::metadata(path)
+                .map_err(|e| format!("Failed to read metadata of {}: {}", path.display(), e))?;
+            Ok((metadata.len(), metadata.modified().ok()))
+        })
+        .collect::<Result<Vec<FileStamp>, String>>()?;
+    let key: Vec<PathBuf> = paths.iter().map(|path| path.canonicalize().unwrap_or_else(|_| path.clone())).collect();
+    // hold the lock while parsing, so that concurrent callers don't parse the same files twice
+    let mut projects = PROJECTS.lock().unwrap();
+    if let Some((cached_stamps, proj)) = projects.get(&key) {
+        if *cached_stamps == stamps {
+            return Ok(Arc::clone(proj));
+        }
+    }
+    let proj = Arc::new(Project::from_bc_paths(&paths)?);
+    projects.insert(key, (stamps, Arc::clone(&proj)));
+    Ok(proj)
+}
@@ -0,0 +1,205 @@
+use crate::{ConstantTimeResultForFunction, ConstantTimeResultForPath};
+use crate::violation::is_solver_timeout;
+use colored::*;
+use haybale::Error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// How the result for a single function changed between two runs
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum FunctionChange {
+    /// The function was verified constant-time in both runs; or it wasn't in
+    /// either run, and no new kinds of violations or errors appeared
+    Unchanged,
+    /// The function was not verified constant-time in the old run, but is in
+    /// the new run
+    Fixed,
+    /// The function was verified constant-time in the old run, but isn't in
+    /// the new run; or it wasn't in either run, but new kinds of violations or
+    /// errors appeared
+    Regressed,
+    /// The function only appears in the new run
+    New,
+    /// The function only appears in the old run
+    Removed,
+}
+
+impl fmt::Display for FunctionChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FunctionChange::Unchanged => write!(f, "unchanged"),
+            FunctionChange::Fixed => write!(f, "fixed"),
+            FunctionChange::Regressed => write!(f, "regressed"),
+            FunctionChange::New => write!(f, "new"),
+            FunctionChange::Removed => write!(f, "removed"),
+        }
+    }
+}
+
+/// Describes how the result for a single function changed between two runs
+#[derive(Clone, Debug)]
+pub struct FunctionDiff {
+    /// Name of the toplevel function
+    pub funcname: String,
+    /// How the result changed
+    pub change: FunctionChange,
+    /// Kinds of constant-time violations and other errors which appear in the
+    /// new run but not the old run, e.g. `"secret-dependent control flow"` or
+    /// `"loop bound exceeded"`. Empty for `Removed` functions.
+    pub new_problem_kinds: Vec<String>,
+    /// Block coverage of the toplevel function in the old run, as a number
+    /// between 0 and 1, or `None` if the function doesn't appear in the old run
+    pub old_coverage: Option<f64>,
+    /// Block coverage of the toplevel function in the new run, as a number
+    /// between 0 and 1, or `None` if the function doesn't appear in the new run
+    pub new_coverage: Option<f64>,
+}
+
+impl FunctionDiff {
+    /// The change in block coverage of the toplevel function from the old run
+    /// to the new run (positive if coverage increased), or `None` if the
+    /// function doesn't appear in both runs
+    pub fn coverage_change(&self) -> Option<f64> {
+        match (self.old_coverage, self.new_coverage) {
+            (Some(old), Some(new)) => Some(new - old),
+            _ => None,
+        }
+    }
+}
+
+/// The differences between the results of two runs, as produced by
+/// `diff_results()`
+#[derive(Clone, Debug)]
+pub struct ResultDiff {
+    /// One `FunctionDiff` for each function appearing in either run, sorted by
+    /// function name
+    pub functions: Vec<FunctionDiff>,
+}
+
+impl ResultDiff {
+    /// The functions whose result had the given kind of change
+    pub fn functions_with_change(&self, change: FunctionChange) -> impl Iterator<Item = &FunctionDiff> {
+        self.functions.iter().filter(move |diff| diff.change == change)
+    }
+
+    /// Did any function regress? This is useful as a CI gate: "the results
+    /// didn't get worse"
+    pub fn has_regressions(&self) -> bool {
+        self.functions_with_change(FunctionChange::Regressed).next().is_some()
+    }
+}
+
+impl fmt::Display for ResultDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = |change| self.functions_with_change(change).count();
+        writeln!(f, "Compared results for {} functions: {} unchanged, {} fixed, {} regressed, {} new, {} removed",
+            self.functions.len(),
+            count(FunctionChange::Unchanged),
+            count(FunctionChange::Fixed),
+            count(FunctionChange::Regressed),
+            count(FunctionChange::New),
+            count(FunctionChange::Removed),
+        )?;
+        for diff in &self.functions {
+            let change = match diff.change {
+                FunctionChange::Unchanged => match diff.coverage_change() {
+                    Some(delta) if delta != 0.0 => diff.change.to_string().normal(),
+                    _ => continue,  // nothing to report
+                },
+                FunctionChange::Fixed => diff.change.to_string().green(),
+                FunctionChange::Regressed => diff.change.to_string().red(),
+                FunctionChange::New | FunctionChange::Removed => diff.change.to_string().normal(),
+            };
+            write!(f, "  {}: {}", diff.funcname, change)?;
+            if !diff.new_problem_kinds.is_empty() {
+                write!(f, " (new: {})", diff.new_problem_kinds.join(", "))?;
+            }
+            if let Some(delta) = diff.coverage_change() {
+                if delta != 0.0 {
+                    write!(f, "; block coverage {:.1}% -> {:.1}% ({:+.1}%)", 100.0 * diff.old_coverage.unwrap(), 100.0 * diff.new_coverage.unwrap(), 100.0 * delta)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the results of two runs (for instance, before and after a code
+/// change), matching functions by name.
+///
+/// If a function appears more than once in either run, only its last result
+/// in that run is used.
+pub fn diff_results(old: &[ConstantTimeResultForFunction], new: &[ConstantTimeResultForFunction]) -> ResultDiff {
+    crate::respect_no_color_env_var();
+    let old: BTreeMap<&str, &ConstantTimeResultForFunction> = old.iter().map(|result| (result.funcname, result)).collect();
+    let new: BTreeMap<&str, &ConstantTimeResultForFunction> = new.iter().map(|result| (result.funcname, result)).collect();
+    let funcnames: BTreeSet<&str> = old.keys().chain(new.keys()).copied().collect();
+    let functions = funcnames.into_iter().map(|funcname| {
+        let old = old.get(funcname).copied();
+        let new = new.get(funcname).copied();
+        let new_problem_kinds: Vec<String> = match new {
+            Some(new) => {
+                let old_kinds = old.map(problem_kinds).unwrap_or_default();
+                problem_kinds(new).into_iter().filter(|kind| !old_kinds.contains(kind)).collect()
+            },
+            None => Vec::new(),
+        };
+        let change = match (old, new) {
+            (None, Some(_)) => FunctionChange::New,
+            (Some(_), None) => FunctionChange::Removed,
+            (Some(old), Some(new)) => match (is_constant_time(old), is_constant_time(new)) {
+                (true, true) => FunctionChange::Unchanged,
+                (false, true) => FunctionChange::Fixed,
+                (true, false) => FunctionChange::Regressed,
+                (false, false) if new_problem_kinds.is_empty() => FunctionChange::Unchanged,
+                (false, false) => FunctionChange::Regressed,
+            },
+            (None, None) => unreachable!("funcname came from one of the two runs"),
+        };
+        FunctionDiff {
+            funcname: funcname.to_owned(),
+            change,
+            new_problem_kinds,
+            old_coverage: old.and_then(toplevel_coverage),
+            new_coverage: new.and_then(toplevel_coverage),
+        }
+    }).collect();
+    ResultDiff { functions }
+}
+
+fn is_constant_time(result: &ConstantTimeResultForFunction) -> bool {
+    result.path_results.len() == result.path_statistics().num_ct_paths
+}
+
+fn toplevel_coverage(result: &ConstantTimeResultForFunction) -> Option<f64> {
+    result.block_coverage.get(result.mangled_funcname).map(|coverage| coverage.percentage)
+}
+
+/// The kinds of constant-time violations and other errors appearing in the
+/// given result
+fn problem_kinds(result: &ConstantTimeResultForFunction) -> BTreeSet<String> {
+    result.path_results.iter().filter_map(|path_result| match path_result {
+        ConstantTimeResultForPath::IsConstantTime { .. } => None,
+        ConstantTimeResultForPath::NotConstantTime { violation } => Some(violation.kind.to_string()),
+        ConstantTimeResultForPath::OtherError { error, .. } => Some(error_kind(error).to_owned()),
+        ConstantTimeResultForPath::TimedOut { .. } => Some("timeout".to_owned()),
+    }).collect()
+}
+
+fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::Unsat => "unsat",
+        Error::LoopBoundExceeded(_) => "loop bound exceeded",
+        Error::NullPointerDereference => "null pointer dereference",
+        Error::FunctionNotFound(_) => "function not found",
+        Error::SolverError(_) if is_solver_timeout(error) => "solver timeout",
+        Error::SolverError(_) => "solver error",
+        Error::UnsupportedInstruction(_) => "unsupported instruction",
+        Error::MalformedInstruction(_) => "malformed instruction",
+        Error::UnreachableInstruction => "unreachable instruction",
+        Error::FailedToResolveFunctionPointer(_) => "failed to resolve function pointer",
+        Error::HookReturnValueMismatch(_) => "hook return value mismatch",
+        Error::OtherError(_) => "other error",
+    }
+}
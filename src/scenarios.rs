@@ -0,0 +1,107 @@
+use crate::coverage::{merge_block_coverage, BlockCoverage};
+use crate::{check_for_ct_violation, secret, AbstractData, CTViolation, ConstantTimeResultForFunction, ConstantTimeResultForPath, PitchforkConfig, StructDescriptions};
+use colored::*;
+use haybale::{Config, Project};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The results of analyzing a single function under several scenarios (i.e.,
+/// several different argument specifications), as produced by
+/// [`check_for_ct_violation_multi()`](fn.check_for_ct_violation_multi.html).
+pub struct MultiScenarioResult<'p> {
+    /// Name of the toplevel function we analyzed
+    pub funcname: &'p str,
+    /// For each scenario (in the order given), its label and the result of
+    /// analyzing the function in that scenario
+    pub scenarios: Vec<(String, ConstantTimeResultForFunction<'p>)>,
+    /// Block coverage merged across all of the scenarios (see
+    /// [`merge_coverage()`](fn.merge_coverage.html))
+    pub block_coverage: HashMap<String, BlockCoverage>,
+}
+
+impl<'p> MultiScenarioResult<'p> {
+    /// Was the function fully verified constant-time in every scenario, i.e.,
+    /// was every analysis exhaustive, with no errors or violations on any path
+    pub fn is_constant_time(&self) -> bool {
+        self.scenarios.iter().all(|(_, result)| result.exhaustive && result.first_error_or_violation().is_none())
+    }
+
+    /// All of the `CTViolation`s found, each with the label of the scenario
+    /// which produced it
+    pub fn violations(&self) -> Vec<(&str, &CTViolation)> {
+        self.scenarios.iter().flat_map(|(label, result)| {
+            result.path_results.iter().filter_map(move |path_result| match path_result {
+                ConstantTimeResultForPath::NotConstantTime { violation } => Some((label.as_str(), violation)),
+                _ => None,
+            })
+        }).collect()
+    }
+}
+
+/// Summarizes the result of each scenario, followed by the merged block
+/// coverage of the toplevel function. For the full description of each
+/// scenario's result, see the `Display` of its `ConstantTimeResultForFunction`.
+impl<'p> fmt::Display for MultiScenarioResult<'p> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "\nResults for {} across {} scenario(s):\n", self.funcname, self.scenarios.len())?;
+        for (label, result) in &self.scenarios {
+            let path_stats = result.path_statistics();
+            let num_errors = result.path_results.len() - path_stats.num_ct_paths - path_stats.num_ct_violations;
+            write!(f, "  {}: ", label)?;
+            if path_stats.num_ct_violations > 0 {
+                writeln!(f, "{} ({} violation(s))", "not constant-time".red(), path_stats.num_ct_violations)?;
+                for violation in result.violations_by_function().into_iter().flat_map(|(_, violations)| violations) {
+                    writeln!(f, "    {} in {}: {}", violation.kind, violation.funcname, violation.msg)?;
+                }
+            } else if num_errors > 0 {
+                writeln!(f, "{} ({} error(s))", "not verified".red(), num_errors)?;
+            } else if !result.exhaustive {
+                writeln!(f, "{}", "analysis truncated".yellow())?;
+            } else if result.path_results.is_empty() {
+                writeln!(f, "no valid paths")?;
+            } else {
+                writeln!(f, "{}", "constant-time".green())?;
+            }
+        }
+        match self.scenarios.first().and_then(|(_, result)| self.block_coverage.get(result.mangled_funcname)) {
+            Some(coverage) => writeln!(f, "\nBlock coverage of {} across all scenarios: {:.1}%", self.funcname, 100.0 * coverage.percentage)?,
+            None => writeln!(f, "\n{} was not executed in any scenario", self.funcname)?,
+        }
+        Ok(())
+    }
+}
+
+/// Checks whether a function is "constant-time" under each of several
+/// scenarios, for instance with an optional pointer argument either null or
+/// non-null. Each scenario is a label and an `args` specification, which is
+/// interpreted just as in [`check_for_ct_violation()`](fn.check_for_ct_violation.html).
+///
+/// Each scenario is analyzed separately (with a copy of `config`), and the
+/// results are combined into a `MultiScenarioResult`, which labels the
+/// results (and violations) by scenario and merges the block coverage across
+/// scenarios.
+///
+/// Other arguments are the same as for
+/// [`check_for_ct_violation()`](fn.check_for_ct_violation.html).
+pub fn check_for_ct_violation_multi<'p>(
+    funcname: &'p str,
+    project: &'p Project,
+    scenarios: Vec<(String, Option<Vec<AbstractData>>)>,
+    sd: &StructDescriptions,
+    config: Config<'p, secret::Backend>,
+    pitchfork_config: &PitchforkConfig,
+) -> MultiScenarioResult<'p> {
+    let scenarios: Vec<(String, ConstantTimeResultForFunction<'p>)> = scenarios
+        .into_iter()
+        .map(|(label, args)| {
+            let result = check_for_ct_violation(funcname, project, args, sd, config.clone(), pitchfork_config);
+            (label, result)
+        })
+        .collect();
+    let block_coverage = merge_block_coverage(scenarios.iter().map(|(_, result)| &result.block_coverage));
+    MultiScenarioResult {
+        funcname,
+        scenarios,
+        block_coverage,
+    }
+}
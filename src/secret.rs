@@ -7,11 +7,17 @@
 //! them with `haybale::backend::{BV, Memory, Backend}`,
 //! `haybale::{cell_memory,simple_memory}::Memory`, or `boolector::BV`.
 
+use crate::violation::{continuing_past_secret_branches, report_violation, CTViolationKind};
 use boolector::{Btor, BVSolution};
 use haybale::{Error, Result};
 use log::warn;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::convert::TryInto;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// This wrapper around `Rc<Btor>` exists simply so we can give it a different
 /// implementation of `haybale::backend::SolverRef` than the one provided by
@@ -118,6 +124,27 @@ impl BV {
         }
     }
 
+    /// Which bits of this `BV` are secret, without the overapproximation of
+    /// `is_secret()`
+    pub fn secrecy(&self) -> Secrecy {
+        match self {
+            BV::Public(_) => Secrecy::Public,
+            BV::Secret { .. } => Secrecy::Secret,
+            BV::PartiallySecret { secret_mask, .. } => Secrecy::PartiallySecret { secret_mask: secret_mask.clone() },
+        }
+    }
+
+    /// A concise description of which bits of this `BV` are secret, e.g.
+    /// "public", "secret", or "bits 0-31 secret, 32-63 public". Useful for
+    /// logging, where the `Debug` of a `secret_mask` is unwieldy.
+    pub fn describe_secrecy(&self) -> String {
+        match self {
+            BV::Public(_) => "public".into(),
+            BV::Secret { .. } => "secret".into(),
+            BV::PartiallySecret { secret_mask, .. } => describe_secret_mask(secret_mask),
+        }
+    }
+
     /// Gets the value out of a `BV::Public`, panicking if it is instead a `BV::Secret`
     pub fn as_public(&self) -> &boolector::BV<Rc<Btor>> {
         match self {
@@ -126,6 +153,140 @@ impl BV {
             BV::PartiallySecret { .. } => panic!("as_public on a BV::PartiallySecret"),
         }
     }
+
+    /// If this is a `BV::PartiallySecret` whose `secret_mask` is entirely
+    /// public, convert it to the equivalent `BV::Public`; otherwise, return it
+    /// unchanged.
+    ///
+    /// Operations which may produce a `BV::PartiallySecret` should call this on
+    /// their result, so that operations downstream can take advantage of
+    /// everything we know about the value being public (e.g., most binops
+    /// treat any `BV::PartiallySecret` operand as entirely secret).
+    pub fn collapse_public(self) -> Self {
+        match self {
+            BV::PartiallySecret { secret_mask, data, .. } if !secret_mask.iter().any(|b| *b) => BV::Public(data),
+            bv => bv,
+        }
+    }
+
+    /// Construct a `BV` with the given `secret_mask` and `data` (with the same
+    /// meanings as for `BV::PartiallySecret`), but using `BV::Public` or
+    /// `BV::Secret` instead if the mask is entirely public or entirely secret.
+    fn from_secret_mask(secret_mask: Vec<bool>, data: boolector::BV<Rc<Btor>>) -> Self {
+        if secret_mask.iter().all(|b| *b) {
+            BV::Secret { btor: data.get_btor().into(), width: data.get_width(), symbol: None }
+        } else {
+            BV::PartiallySecret { secret_mask, data, symbol: None }.collapse_public()
+        }
+    }
+
+    /// Get the `secret_mask` and `data` for this `BV`, with the same meanings
+    /// as for `BV::PartiallySecret`, regardless of which variant it is
+    fn secret_mask_and_data(&self) -> (Vec<bool>, boolector::BV<Rc<Btor>>) {
+        match self {
+            BV::Public(bv) => (vec![false; bv.get_width() as usize], bv.clone()),
+            BV::Secret { btor, width, .. } => (vec![true; *width as usize], boolector::BV::zero(btor.clone().into(), *width)),
+            BV::PartiallySecret { secret_mask, data, .. } => (secret_mask.clone(), data.clone()),
+        }
+    }
+
+    /// If one of `a` and `b` is a public constant and the other is (at least
+    /// partially) secret, returns the public constant and the other operand,
+    /// in that order
+    fn public_const_and_secret<'a>(a: &'a Self, b: &'a Self) -> Option<(&'a boolector::BV<Rc<Btor>>, &'a Self)> {
+        match (a, b) {
+            (BV::Public(c), other) | (other, BV::Public(c)) if c.is_const() && other.is_secret() => Some((c, other)),
+            _ => None,
+        }
+    }
+
+    /// Precise handling of `and` with a public constant: each bit of the result
+    /// where the constant has a 0 is a public 0, regardless of the other
+    /// operand. This is the usual constant-time masking idiom.
+    ///
+    /// Returns `None` if this special case doesn't apply.
+    fn and_with_public_const(&self, other: &Self) -> Option<Self> {
+        let (constant, operand) = Self::public_const_and_secret(self, other)?;
+        let (secret_mask, data) = operand.secret_mask_and_data();
+        let secret_mask = secret_mask.into_iter().zip(const_bits(constant)).map(|(secret, bit)| secret && bit).collect();
+        Some(Self::from_secret_mask(secret_mask, data.and(constant)))
+    }
+
+    /// Precise handling of `or` with a public constant: each bit of the result
+    /// where the constant has a 1 is a public 1, regardless of the other
+    /// operand.
+    ///
+    /// Returns `None` if this special case doesn't apply.
+    fn or_with_public_const(&self, other: &Self) -> Option<Self> {
+        let (constant, operand) = Self::public_const_and_secret(self, other)?;
+        let (secret_mask, data) = operand.secret_mask_and_data();
+        let secret_mask = secret_mask.into_iter().zip(const_bits(constant)).map(|(secret, bit)| secret && !bit).collect();
+        Some(Self::from_secret_mask(secret_mask, data.or(constant)))
+    }
+
+    /// If `self` is (at least partially) secret and `shift_amount` is a public
+    /// constant, returns the constant shift amount (as a number of bits)
+    fn public_const_shift_amount(&self, shift_amount: &Self) -> Option<usize> {
+        match shift_amount {
+            BV::Public(amount) if amount.is_const() && self.is_secret() => {
+                // amounts too large for a `usize` shift out all the bits anyway
+                Some(amount.as_u64().map_or(usize::MAX, |amount| amount.try_into().unwrap_or(usize::MAX)))
+            },
+            _ => None,
+        }
+    }
+
+    /// Precise handling of `sll` by a public constant: the secret mask shifts
+    /// along with the data, and the bits shifted in are public zeroes.
+    ///
+    /// Returns `None` if this special case doesn't apply.
+    fn sll_by_public_const(&self, shift_amount: &Self) -> Option<Self> {
+        let amount = self.public_const_shift_amount(shift_amount)?;
+        let (secret_mask, data) = self.secret_mask_and_data();
+        let secret_mask = (0 .. secret_mask.len())
+            .map(|i| i >= amount && secret_mask[i - amount])
+            .collect();
+        Some(Self::from_secret_mask(secret_mask, data.sll(shift_amount.as_public())))
+    }
+
+    /// Precise handling of `srl` by a public constant: the secret mask shifts
+    /// along with the data, and the bits shifted in are public zeroes.
+    ///
+    /// Returns `None` if this special case doesn't apply.
+    fn srl_by_public_const(&self, shift_amount: &Self) -> Option<Self> {
+        let amount = self.public_const_shift_amount(shift_amount)?;
+        let (secret_mask, data) = self.secret_mask_and_data();
+        let secret_mask = (0 .. secret_mask.len())
+            .map(|i| i.checked_add(amount).and_then(|src| secret_mask.get(src)).copied().unwrap_or(false))
+            .collect();
+        Some(Self::from_secret_mask(secret_mask, data.srl(shift_amount.as_public())))
+    }
+
+    /// Precise handling of `sra` by a public constant: the secret mask shifts
+    /// along with the data, and the bits shifted in are copies of the sign bit,
+    /// so they are secret iff the sign bit is secret.
+    ///
+    /// Returns `None` if this special case doesn't apply.
+    fn sra_by_public_const(&self, shift_amount: &Self) -> Option<Self> {
+        let amount = self.public_const_shift_amount(shift_amount)?;
+        let (secret_mask, data) = self.secret_mask_and_data();
+        let sign_bit_secret = *secret_mask.last().expect("arithmetic shift on a bitvector with width 0");
+        let secret_mask = (0 .. secret_mask.len())
+            .map(|i| i.checked_add(amount).and_then(|src| secret_mask.get(src)).copied().unwrap_or(sign_bit_secret))
+            .collect();
+        Some(Self::from_secret_mask(secret_mask, data.sra(shift_amount.as_public())))
+    }
+}
+
+/// Get the bits of the given constant `boolector::BV`, with index 0 being the
+/// least-significant bit (as in `BV::PartiallySecret`'s `secret_mask`)
+fn const_bits(bv: &boolector::BV<Rc<Btor>>) -> Vec<bool> {
+    bv.as_binary_str()
+        .expect("const_bits() on a non-constant BV")
+        .chars()
+        .rev()
+        .map(|c| c == '1')
+        .collect()
 }
 
 macro_rules! impl_unop_as_functor {
@@ -152,16 +313,32 @@ macro_rules! impl_unop_as_functor_return_bool {
     };
 }
 
+macro_rules! binop_as_functor {
+    ($f:ident, $lhs:ident, $rhs:ident) => {
+        match ($lhs, $rhs) {
+            (BV::Public(bv), BV::Public(other)) => BV::Public(bv.$f(other)),
+            (BV::Secret { btor, width, .. }, _) => BV::Secret { btor: btor.clone(), width: *width, symbol: None },
+            (_, BV::Secret { btor, width, .. }) => BV::Secret { btor: btor.clone(), width: *width, symbol: None },
+            // if one operand was even partially secret, we have no way of knowing in general which bits of the result may have been influenced by the secret input bits, so mark the entire output secret
+            (BV::PartiallySecret { data, .. }, _) => BV::Secret { btor: data.get_btor().into(), width: data.get_width(), symbol: None },
+            (_, BV::PartiallySecret { data, .. }) => BV::Secret { btor: data.get_btor().into(), width: data.get_width(), symbol: None },
+        }
+    };
+}
+
 macro_rules! impl_binop_as_functor {
     ($f:ident) => {
         fn $f(&self, other: &Self) -> Self {
-            match (self, other) {
-                (BV::Public(bv), BV::Public(other)) => BV::Public(bv.$f(other)),
-                (BV::Secret { btor, width, .. }, _) => BV::Secret { btor: btor.clone(), width: *width, symbol: None },
-                (_, BV::Secret { btor, width, .. }) => BV::Secret { btor: btor.clone(), width: *width, symbol: None },
-                // if one operand was even partially secret, we have no way of knowing in general which bits of the result may have been influenced by the secret input bits, so mark the entire output secret
-                (BV::PartiallySecret { data, .. }, _) => BV::Secret { btor: data.get_btor().into(), width: data.get_width(), symbol: None },
-                (_, BV::PartiallySecret { data, .. }) => BV::Secret { btor: data.get_btor().into(), width: data.get_width(), symbol: None },
+            binop_as_functor!($f, self, other)
+        }
+    };
+    // `$precise` is a method which handles special cases more precisely than the
+    // general case, returning `None` if none of its special cases apply
+    ($f:ident, precise: $precise:ident) => {
+        fn $f(&self, other: &Self) -> Self {
+            match self.$precise(other) {
+                Some(result) => result,
+                None => binop_as_functor!($f, self, other),
             }
         }
     };
@@ -317,11 +494,17 @@ impl haybale::backend::BV for BV {
         match self {
             BV::Public(bv) => {
                 bv.assert();
+                CONSTRAINTS_ASSERTED.with(|c| c.set(c.get() + 1));
+                Ok(())
+            },
+            BV::Secret { .. } | BV::PartiallySecret { .. } if continuing_past_secret_branches() => {
+                // treat the secret value as an unconstrained public value, i.e., don't constrain anything.
+                // The secret branch itself is detected and recorded by `instruction_checks`
                 Ok(())
             },
             BV::Secret { .. } | BV::PartiallySecret { .. } => {
                 // `Secret` values influencing a path constraint means they influenced a control flow decision
-                Err(Error::OtherError("Constant-time violation: control-flow may be influenced by secret data".to_owned()))
+                Err(report_violation(CTViolationKind::Branch, "control-flow may be influenced by secret data"))
             },
         }
     }
@@ -354,15 +537,15 @@ impl haybale::backend::BV for BV {
     impl_binop_as_functor_return_bool!(smulo);
     impl_binop_as_functor_return_bool!(sdivo);
     impl_unop_as_functor!(not);
-    impl_binop_as_functor!(and);
-    impl_binop_as_functor!(or);
+    impl_binop_as_functor!(and, precise: and_with_public_const);
+    impl_binop_as_functor!(or, precise: or_with_public_const);
     impl_binop_as_functor!(xor);
     impl_binop_as_functor!(nand);
     impl_binop_as_functor!(nor);
     impl_binop_as_functor!(xnor);
-    impl_binop_as_functor!(sll);
-    impl_binop_as_functor!(srl);
-    impl_binop_as_functor!(sra);
+    impl_binop_as_functor!(sll, precise: sll_by_public_const);
+    impl_binop_as_functor!(srl, precise: srl_by_public_const);
+    impl_binop_as_functor!(sra, precise: sra_by_public_const);
     impl_binop_as_functor!(rol);
     impl_binop_as_functor!(ror);
     impl_unop_as_functor_return_bool!(redand);
@@ -437,26 +620,13 @@ impl haybale::backend::BV for BV {
             BV::Public(bv) => BV::Public(bv.slice(high, low)),
             BV::Secret { btor, .. } => BV::Secret { btor: btor.clone(), width: high - low + 1, symbol: None },
             BV::PartiallySecret { secret_mask, data, .. } => {
-                let new_mask = secret_mask[low as usize ..= high as usize].to_vec();
-                if new_mask.iter().all(|b| *b) {
-                    // result is entirely secret
-                    BV::Secret { btor: data.get_btor().into(), width: high - low + 1, symbol: None }
-                } else if !new_mask.iter().any(|b| *b) {
-                    // result is entirely public
-                    BV::Public(data.slice(high, low))
-                } else {
-                    // result is still mixed public and secret
-                    BV::PartiallySecret {
-                        secret_mask: new_mask,
-                        data: data.slice(high, low),
-                        symbol: None,
-                    }
-                }
+                // the result may be entirely secret, entirely public, or still mixed
+                BV::from_secret_mask(secret_mask[low as usize ..= high as usize].to_vec(), data.slice(high, low))
             },
         }
     }
     fn concat(&self, other: &Self) -> Self {
-        match (self, other) {
+        let concatenated = match (self, other) {
             (BV::Public(bv), BV::Public(other)) => BV::Public(bv.concat(other)),
             (BV::Secret { btor, width, .. }, BV::Public(public)) => BV::PartiallySecret {
                 // secret in the high-order bits, public in the low-order bits
@@ -531,7 +701,9 @@ impl haybale::backend::BV for BV {
                 data: self_data.concat(&other_data),
                 symbol: None,
             },
-        }
+        };
+        // the result can only be entirely public if a `PartiallySecret` operand was, but in that case, collapse it now
+        concatenated.collapse_public()
     }
     fn repeat(&self, n: u32) -> Self {
         match self {
@@ -555,9 +727,9 @@ impl haybale::backend::BV for BV {
             width
         };
         if self.is_secret() {
-            warn!("'select' operation with a secret condition and {}-bit operands. This may not be constant-time, depending on the target architecture and other factors.", dest_width);
+            warn!("'select' operation with a {} condition and {}-bit operands. This may not be constant-time, depending on the target architecture and other factors. (To report this as a constant-time violation instead, use the `secret_select_is_violation` setting in `PitchforkConfig`.)", self.describe_secrecy(), dest_width);
         }
-        match (self, truebv, falsebv) {
+        let result = match (self, truebv, falsebv) {
             (BV::Public(bv), BV::Public(truebv), BV::Public(falsebv))
                 => BV::Public(bv.cond_bv(truebv, falsebv)),
             (BV::Secret { btor, .. }, _, _)
@@ -578,16 +750,256 @@ impl haybale::backend::BV for BV {
                     data: cond.cond_bv(true_data, false_data),
                     symbol: None,
                 },
+        };
+        result.collapse_public()
+    }
+}
+
+thread_local! {
+    /// Whether the `strict_uninitialized_memory` setting is active for the
+    /// current analysis. This applies to `Memory`s created while it is set.
+    static STRICT_UNINITIALIZED_MEMORY: Cell<bool> = Cell::new(false);
+}
+
+/// Set whether the `strict_uninitialized_memory` setting is active
+pub(crate) fn set_strict_uninitialized_memory(strict: bool) {
+    STRICT_UNINITIALIZED_MEMORY.with(|s| s.set(strict));
+}
+
+/// Is the `strict_uninitialized_memory` setting active
+pub(crate) fn strict_uninitialized_memory() -> bool {
+    STRICT_UNINITIALIZED_MEMORY.with(|s| s.get())
+}
+
+thread_local! {
+    /// Whether the `byte_granularity_secrecy` setting is active for the
+    /// current analysis. This applies to `Memory`s created while it is set.
+    static BYTE_GRANULARITY_SECRECY: Cell<bool> = Cell::new(false);
+}
+
+/// Set whether the `byte_granularity_secrecy` setting is active
+pub(crate) fn set_byte_granularity_secrecy(byte_granularity: bool) {
+    BYTE_GRANULARITY_SECRECY.with(|b| b.set(byte_granularity));
+}
+
+/// Is the `byte_granularity_secrecy` setting active
+fn byte_granularity_secrecy() -> bool {
+    BYTE_GRANULARITY_SECRECY.with(|b| b.get())
+}
+
+/// Numbers of the solver queries Pitchfork makes itself, by category. (As with
+/// `solver_duration` in `PathMetrics`, queries made inside haybale are not
+/// included.)
+///
+/// Reading public memory first tries the fast check that the data read is
+/// entirely public; if that fails, the fast check that it may be entirely
+/// secret; and only if that also fails, the slow solve for which of its bits
+/// may be secret. So, a high number of `read_secret_mask_solves` means that
+/// reads of partially-secret data are a large part of the solver cost.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct SolverQueryCounts {
+    /// Checks in `Memory::read` whether the data read is entirely public
+    /// (`bvs_must_be_equal`)
+    pub read_all_public_checks: usize,
+    /// Checks in `Memory::read` whether the data read may be entirely secret
+    /// (`bvs_can_be_equal`)
+    pub read_all_secret_checks: usize,
+    /// Solves in `Memory::read` for which bits of the data read may be secret
+    /// (`max_possible_solution_for_bv_as_binary_str`)
+    pub read_secret_mask_solves: usize,
+    /// All other queries, e.g. for `strict_uninitialized_memory`
+    pub other_queries: usize,
+    /// Reads in `Memory::read` which were known to be entirely public from an
+    /// earlier read of the same address and width, and so needed no query.
+    /// These are not counted in `total()`.
+    pub read_cache_hits: usize,
+    /// Reads in `Memory::read` for which the secrecy of the data read was a
+    /// constant, and so needed no query. These are not counted in `total()`.
+    pub read_constant_secrecy: usize,
+}
+
+impl SolverQueryCounts {
+    /// Total number of queries, of any category
+    pub fn total(&self) -> usize {
+        self.read_all_public_checks
+            + self.read_all_secret_checks
+            + self.read_secret_mask_solves
+            + self.other_queries
+    }
+
+    /// Add the counts in `other` to these counts
+    pub(crate) fn add(&mut self, other: &Self) {
+        self.read_all_public_checks += other.read_all_public_checks;
+        self.read_all_secret_checks += other.read_all_secret_checks;
+        self.read_secret_mask_solves += other.read_secret_mask_solves;
+        self.other_queries += other.other_queries;
+        self.read_cache_hits += other.read_cache_hits;
+        self.read_constant_secrecy += other.read_constant_secrecy;
+    }
+}
+
+/// The category of a solver query, for `SolverQueryCounts`
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) enum SolverQueryKind {
+    ReadAllPublic,
+    ReadAllSecret,
+    ReadSecretMask,
+    Other,
+}
+
+thread_local! {
+    /// Total wall-clock time spent in Pitchfork's own solver queries since the
+    /// last call to `take_solver_duration()`
+    static SOLVER_DURATION: Cell<Duration> = Cell::new(Duration::from_secs(0));
+    /// Numbers of Pitchfork's own solver queries since the last call to
+    /// `take_solver_query_counts()`
+    static SOLVER_QUERY_COUNTS: Cell<SolverQueryCounts> = Cell::new(SolverQueryCounts::default());
+    /// Number of constraints asserted since the last call to
+    /// `take_constraints_asserted()`
+    static CONSTRAINTS_ASSERTED: Cell<usize> = Cell::new(0);
+}
+
+/// Run the given solver query, adding the time it takes to the accumulated
+/// solver time, and counting it in the given category
+pub(crate) fn time_solver_query<T>(kind: SolverQueryKind, query: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = query();
+    let elapsed = start.elapsed();
+    SOLVER_DURATION.with(|d| d.set(d.get() + elapsed));
+    SOLVER_QUERY_COUNTS.with(|c| {
+        let mut counts = c.get();
+        match kind {
+            SolverQueryKind::ReadAllPublic => counts.read_all_public_checks += 1,
+            SolverQueryKind::ReadAllSecret => counts.read_all_secret_checks += 1,
+            SolverQueryKind::ReadSecretMask => counts.read_secret_mask_solves += 1,
+            SolverQueryKind::Other => counts.other_queries += 1,
+        }
+        c.set(counts);
+    });
+    result
+}
+
+/// Count a read which needed no solver query, in `read_cache_hits`
+fn count_read_cache_hit() {
+    SOLVER_QUERY_COUNTS.with(|c| {
+        let mut counts = c.get();
+        counts.read_cache_hits += 1;
+        c.set(counts);
+    });
+}
+
+/// Count a read which needed no solver query, in `read_constant_secrecy`
+fn count_read_constant_secrecy() {
+    SOLVER_QUERY_COUNTS.with(|c| {
+        let mut counts = c.get();
+        counts.read_constant_secrecy += 1;
+        c.set(counts);
+    });
+}
+
+/// Get the solver time accumulated since the last call to this function, and
+/// reset it to zero
+pub(crate) fn take_solver_duration() -> Duration {
+    SOLVER_DURATION.with(|d| d.replace(Duration::from_secs(0)))
+}
+
+/// Get the solver query counts accumulated since the last call to this
+/// function, and reset them to zero
+pub(crate) fn take_solver_query_counts() -> SolverQueryCounts {
+    SOLVER_QUERY_COUNTS.with(|c| c.replace(SolverQueryCounts::default()))
+}
+
+/// Get the number of constraints asserted since the last call to this
+/// function, and reset it to zero
+pub(crate) fn take_constraints_asserted() -> usize {
+    CONSTRAINTS_ASSERTED.with(|c| c.replace(0))
+}
+
+/// A memory access at an address which can be influenced by secret data, as
+/// passed to an `ObliviousAccessHook`
+pub struct ObliviousAccess<'a> {
+    /// The solver instance, e.g. for constructing the value of a read
+    pub btor: &'a BtorRef,
+    /// The address being accessed, which is `Secret` or `PartiallySecret`
+    pub address: &'a BV,
+    /// Size of the access in bits
+    pub bits: u32,
+    /// For a write, the value being written; for a read, `None`
+    pub write_value: Option<&'a BV>,
+}
+
+/// The type of the function in an `ObliviousAccessHook`.
+///
+/// For a read, this must return the value read, which must be `bits` bits
+/// wide. For a write, this must return `None`. Returning an `Err` ends the
+/// path with that error.
+pub type ObliviousAccessFn = dyn Fn(&ObliviousAccess) -> Result<Option<BV>> + Send + Sync;
+
+/// Handles memory accesses at addresses which can be influenced by secret
+/// data, in place of reporting them as constant-time violations. See notes on
+/// `oblivious_access_hook` in `PitchforkConfig`.
+#[derive(Clone)]
+pub struct ObliviousAccessHook(Arc<ObliviousAccessFn>);
+
+impl ObliviousAccessHook {
+    /// An `ObliviousAccessHook` which handles each access with the given
+    /// function; see `ObliviousAccessFn`
+    pub fn new(hook: impl Fn(&ObliviousAccess) -> Result<Option<BV>> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    /// Run the hook on the given access, checking that it returned a value if
+    /// and only if the access is a read
+    fn call(&self, access: &ObliviousAccess) -> Result<Option<BV>> {
+        let retval = (self.0)(access)?;
+        match (&access.write_value, &retval) {
+            (None, Some(value)) if haybale::backend::BV::get_width(value) == access.bits => Ok(retval),
+            (None, Some(value)) => Err(Error::OtherError(format!(
+                "oblivious_access_hook returned a {}-bit value for a {}-bit read",
+                haybale::backend::BV::get_width(value), access.bits,
+            ))),
+            (None, None) => Err(Error::OtherError("oblivious_access_hook returned no value for a read".into())),
+            (Some(_), None) => Ok(None),
+            (Some(_), Some(_)) => Err(Error::OtherError("oblivious_access_hook returned a value for a write".into())),
         }
     }
 }
 
+impl std::fmt::Debug for ObliviousAccessHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ObliviousAccessHook")
+    }
+}
+
+thread_local! {
+    /// The `oblivious_access_hook` for the current analysis
+    static OBLIVIOUS_ACCESS_HOOK: RefCell<Option<ObliviousAccessHook>> = RefCell::new(None);
+}
+
+/// Set the `oblivious_access_hook` for the current analysis
+pub(crate) fn set_oblivious_access_hook(hook: Option<ObliviousAccessHook>) {
+    OBLIVIOUS_ACCESS_HOOK.with(|h| *h.borrow_mut() = hook);
+}
+
+/// The `oblivious_access_hook` for the current analysis, if any
+fn oblivious_access_hook() -> Option<ObliviousAccessHook> {
+    OBLIVIOUS_ACCESS_HOOK.with(|h| h.borrow().clone())
+}
+
 /// A `Memory` which tracks which of its contents are public or secret, and
 /// reports constant-time violations whenever secret data is used as an address
-/// for operations on it.
+/// for operations on it (unless an `oblivious_access_hook` is set in the
+/// `PitchforkConfig`, in which case those operations are passed to it).
 ///
 /// All the contents of the `Memory` are initially marked public, until secret
 /// data is written to the `Memory`.
+///
+/// With the `strict_uninitialized_memory` setting, the `Memory` additionally
+/// tracks which of its contents have been written, and reading anything which
+/// hasn't been written is an error.
+///
+/// With the `byte_granularity_secrecy` setting, the `Memory` tracks secrecy
+/// per byte rather than per bit.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Memory {
     btor: BtorRef,
@@ -595,6 +1007,23 @@ pub struct Memory {
     mem: haybale::simple_memory::Memory,
     /// This memory is a bitmap, with each bit indicating if the corresponding bit of `mem` is secret or not (1 for secret, 0 for public)
     shadow_mem: haybale::simple_memory::Memory,
+    /// With `strict_uninitialized_memory`, this memory is a bitmap, with each
+    /// bit indicating if the corresponding bit of `mem` has been written (1) or
+    /// not (0). Without `strict_uninitialized_memory`, this is `None`.
+    written_mem: Option<haybale::simple_memory::Memory>,
+    /// (Constant) address and width in bits of reads which we have found to be
+    /// entirely public, and which haven't since been overlapped by a write.
+    /// Reading these again needs no solve.
+    ///
+    /// We only cache the all-public classification, because it can't be
+    /// changed by adding constraints to the solver (only by writing to the
+    /// memory), while whether bits _can_ be secret can be.
+    public_reads: RefCell<HashSet<(u64, u32)>>,
+    /// Whether the `byte_granularity_secrecy` setting was active when this
+    /// `Memory` was created. If so, each byte of `shadow_mem` is always either
+    /// all zeroes or all ones (except possibly after a write of less than a
+    /// byte), and reads only check the lowest bit of each byte.
+    byte_granularity: bool,
 }
 // note on the above: we use `haybale::simple_memory` over `haybale::memory`
 // because, at least in one relevant case, it can speed up an analysis from
@@ -614,9 +1043,13 @@ impl haybale::backend::Memory for Memory {
 
     fn new_uninitialized(btor: BtorRef, null_detection: bool, name: Option<&str>, addr_bits: u32) -> Self {
         assert_ne!(name, Some("shadow_mem"), "can't use {:?} as a name for a secret::Memory, as we reserve that name", name);
+        assert_ne!(name, Some("written_mem"), "can't use {:?} as a name for a secret::Memory, as we reserve that name", name);
         Self {
             mem: haybale::backend::Memory::new_uninitialized(btor.0.clone(), null_detection, name, addr_bits),
             shadow_mem: haybale::backend::Memory::new_zero_initialized(btor.0.clone(), null_detection, Some("shadow_mem"), addr_bits), // shadow bits are zero-initialized (all public) even though the memory contents are uninitialized
+            written_mem: Self::new_written_mem(&btor, null_detection, addr_bits),
+            public_reads: RefCell::new(HashSet::new()),
+            byte_granularity: byte_granularity_secrecy(),
             btor,  // out of order so it can be used above but moved in here
         }
     }
@@ -625,6 +1058,9 @@ impl haybale::backend::Memory for Memory {
         Self {
             mem: haybale::backend::Memory::new_zero_initialized(btor.0.clone(), null_detection, name, addr_bits),
             shadow_mem: haybale::backend::Memory::new_zero_initialized(btor.0.clone(), null_detection, Some("shadow_mem"), addr_bits), // initialize to all public zeroes
+            written_mem: None,  // the entire memory is initialized (to zeroes)
+            public_reads: RefCell::new(HashSet::new()),
+            byte_granularity: byte_granularity_secrecy(),
             btor,  // out of order so it can be used above but moved in here
         }
     }
@@ -632,7 +1068,17 @@ impl haybale::backend::Memory for Memory {
         match index {
             BV::Public(index) => {
                 use haybale::solver_utils::{bvs_must_be_equal, bvs_can_be_equal, max_possible_solution_for_bv_as_binary_str};
+                self.check_written(index, bits)?;
+                let const_addr = index.as_u64();
+                if let Some(addr) = const_addr {
+                    if self.public_reads.borrow().contains(&(addr, bits)) {
+                        count_read_cache_hit();
+                        return haybale::backend::Memory::read(&self.mem, index, bits).map(BV::Public);
+                    }
+                }
                 let shadow_cell = haybale::backend::Memory::read(&self.shadow_mem, index, bits)?;
+                // with `byte_granularity`, we only need to query one shadow bit per byte
+                let shadow_cell = if self.byte_granularity { Self::byte_flags(&shadow_cell) } else { shadow_cell };
                 // In Boolector, (at least when this comment was originally written) reads
                 // on a constant array that return the default value are nonetheless not
                 // constant (they are merely constrained to be equal to the default value).
@@ -641,14 +1087,41 @@ impl haybale::backend::Memory for Memory {
                 // However, in the common case the shadow value is either all zeroes (all
                 // public) or all ones (all secret). This means that usually we can get away
                 // with using faster `bvs_must_be_equal` / `bvs_can_be_equal` checks rather
-                // than a slow `get_possible_solutions_for_bv()` check.
+                // than a slow `get_possible_solutions_for_bv()` check. And when we find
+                // that a read at a constant address is all public, we remember that (in
+                // `public_reads`) until the next overlapping write, so that e.g. a loop
+                // repeatedly reading the same public data doesn't solve every time.
+                //
+                // Also, when the shadow value _is_ a constant (which `simple_memory`
+                // often manages), we can determine which bits are secret from it
+                // directly, with no solve at all.
+                if let Some(shadow_str) = shadow_cell.as_binary_str() {
+                    count_read_constant_secrecy();
+                    return if shadow_str.chars().all(|c| c == '0') {
+                        if let Some(addr) = const_addr {
+                            self.public_reads.borrow_mut().insert((addr, bits));
+                        }
+                        haybale::backend::Memory::read(&self.mem, index, bits).map(BV::Public)
+                    } else if shadow_str.chars().all(|c| c == '1') {
+                        Ok(BV::Secret { btor: self.btor.clone(), width: bits, symbol: None })
+                    } else {
+                        Ok(BV::PartiallySecret {
+                            secret_mask: self.secret_mask_from_shadow_str(&shadow_str, bits),
+                            data: haybale::backend::Memory::read(&self.mem, index, bits)?,
+                            symbol: None,
+                        })
+                    };
+                }
                 let rc: Rc<Btor> = self.btor.clone().into();
                 let all_zeroes = boolector::BV::zero(rc.clone(), shadow_cell.get_width());
                 let all_ones = boolector::BV::ones(rc.clone(), shadow_cell.get_width());
-                if bvs_must_be_equal(&rc, &shadow_cell, &all_zeroes)? {
+                if time_solver_query(SolverQueryKind::ReadAllPublic, || bvs_must_be_equal(&rc, &shadow_cell, &all_zeroes))? {
                     // the bits are all public
+                    if let Some(addr) = const_addr {
+                        self.public_reads.borrow_mut().insert((addr, bits));
+                    }
                     haybale::backend::Memory::read(&self.mem, index, bits).map(BV::Public)
-                } else if bvs_can_be_equal(&rc, &shadow_cell, &all_ones)? {
+                } else if time_solver_query(SolverQueryKind::ReadAllSecret, || bvs_can_be_equal(&rc, &shadow_cell, &all_ones))? {
                     // the bits all _can_ be secret. And any bit that _can_ be
                     // secret, we mark as secret (following the worst case).
                     // (Non-constant secrecy bits means that the bits could be
@@ -665,18 +1138,21 @@ impl haybale::backend::Memory for Memory {
                     // that is, that there is not a situation where a bit could
                     // be secret, but only if some other bit isn't.)
                     // Any bits that have 0s in that mask must be public.
-                    let secret_mask_as_str = max_possible_solution_for_bv_as_binary_str(rc, &shadow_cell)?.ok_or(Error::Unsat)?;
-                    let secret_mask = secret_mask_as_str.chars().rev().map(|c| c == '1').collect();
+                    let secret_mask_as_str = time_solver_query(SolverQueryKind::ReadSecretMask, || max_possible_solution_for_bv_as_binary_str(rc, &shadow_cell))?.ok_or(Error::Unsat)?;
                     Ok(BV::PartiallySecret {
-                        secret_mask,
+                        secret_mask: self.secret_mask_from_shadow_str(&secret_mask_as_str, bits),
                         data: haybale::backend::Memory::read(&self.mem, index, bits)?,
                         symbol: None,
                     })
                 }
             },
-            BV::Secret { .. } | BV::PartiallySecret { .. } => {
-                Err(Error::OtherError("Constant-time violation: memory read on an address which can be influenced by secret data".to_owned()))
-            }
+            BV::Secret { .. } | BV::PartiallySecret { .. } => match oblivious_access_hook() {
+                Some(hook) => {
+                    let access = ObliviousAccess { btor: &self.btor, address: index, bits, write_value: None };
+                    Ok(hook.call(&access)?.expect("ObliviousAccessHook::call() checks that reads return a value"))
+                },
+                None => Err(report_violation(CTViolationKind::MemoryAddress, "memory read on an address which can be influenced by secret data")),
+            },
         }
     }
     fn write(&mut self, index: &Self::Index, value: Self::Value) -> Result<()> {
@@ -685,6 +1161,11 @@ impl haybale::backend::Memory for Memory {
                 if !index.is_const() {
                     warn!("Memory write with a non-constant address {:?}", index);
                 }
+                self.invalidate_public_reads(index.as_u64(), haybale::backend::BV::get_width(&value));
+                if let Some(written_mem) = &mut self.written_mem {
+                    let all_ones = boolector::BV::ones(self.btor.clone().into(), haybale::backend::BV::get_width(&value));
+                    haybale::backend::Memory::write(written_mem, index, all_ones)?;  // these bits have now been written
+                }
                 match value {
                     BV::Public(value) => {
                         let all_zeroes = boolector::BV::zero(self.btor.clone().into(), value.get_width());
@@ -699,6 +1180,7 @@ impl haybale::backend::Memory for Memory {
                         Ok(())
                     },
                     BV::PartiallySecret { secret_mask, data, .. } => {
+                        let secret_mask = if self.byte_granularity { Self::round_mask_to_bytes(&secret_mask) } else { secret_mask };
                         let shadow_mem_string: String = secret_mask.iter().map(|b| if *b { "1" } else { "0" }).rev().collect();
                         let shadow_mem_bv = boolector::BV::from_binary_str(self.btor.clone().into(), &shadow_mem_string);
                         haybale::backend::Memory::write(&mut self.shadow_mem, index, shadow_mem_bv)?;
@@ -707,8 +1189,12 @@ impl haybale::backend::Memory for Memory {
                     },
                 }
             },
-            BV::Secret { .. } | BV::PartiallySecret { .. } => {
-                Err(Error::OtherError("Constant-time violation: memory write on an address which can be influenced by secret data".to_owned()))
+            BV::Secret { .. } | BV::PartiallySecret { .. } => match oblivious_access_hook() {
+                Some(hook) => {
+                    let access = ObliviousAccess { btor: &self.btor, address: index, bits: haybale::backend::BV::get_width(&value), write_value: Some(&value) };
+                    hook.call(&access).map(|_| ())
+                },
+                None => Err(report_violation(CTViolationKind::MemoryAddress, "memory write on an address which can be influenced by secret data")),
             },
         }
     }
@@ -718,10 +1204,211 @@ impl haybale::backend::Memory for Memory {
     fn change_solver(&mut self, new_solver: BtorRef) {
         self.mem.change_solver(new_solver.0.clone());
         self.shadow_mem.change_solver(new_solver.0.clone());
+        if let Some(written_mem) = &mut self.written_mem {
+            written_mem.change_solver(new_solver.0.clone());
+        }
         self.btor = new_solver;
+        debug_assert!(self.solvers_consistent(), "secret::Memory: sub-memories have different solvers after change_solver()");
+    }
+}
+
+impl Memory {
+    /// Do all of the sub-memories (`mem`, `shadow_mem`, and `written_mem`)
+    /// use the same solver as `btor`. If not, reads would combine data and
+    /// secrecy from different solvers, silently giving wrong results.
+    fn solvers_consistent(&self) -> bool {
+        let same_solver = |mem: &haybale::simple_memory::Memory| Rc::ptr_eq(&haybale::backend::Memory::get_solver(mem), &self.btor.0);
+        same_solver(&self.mem)
+            && same_solver(&self.shadow_mem)
+            && self.written_mem.as_ref().map_or(true, same_solver)
+    }
+
+    /// With `byte_granularity`, get the flags (one bit per byte, from the
+    /// lowest bit of the byte) indicating which bytes of the `shadow_cell` are
+    /// secret. A final partial byte gets a flag too.
+    fn byte_flags(shadow_cell: &boolector::BV<Rc<Btor>>) -> boolector::BV<Rc<Btor>> {
+        (8 .. shadow_cell.get_width())
+            .step_by(8)
+            .fold(shadow_cell.slice(0, 0), |flags, low_bit| shadow_cell.slice(low_bit, low_bit).concat(&flags))
+    }
+
+    /// Get the `secret_mask` for a read of `bits` bits, from the binary string
+    /// of the (possibly `byte_flags`) shadow value, which has 1s for bits (or
+    /// bytes) which may be secret
+    fn secret_mask_from_shadow_str(&self, shadow_str: &str, bits: u32) -> Vec<bool> {
+        let mask: Vec<bool> = shadow_str.chars().rev().map(|c| c == '1').collect();
+        if self.byte_granularity {
+            (0 .. bits as usize).map(|bit| mask[bit / 8]).collect()
+        } else {
+            mask
+        }
+    }
+
+    /// With `byte_granularity`, mark each byte (or final partial byte) of the
+    /// `secret_mask` entirely secret if any of its bits are secret
+    fn round_mask_to_bytes(secret_mask: &[bool]) -> Vec<bool> {
+        secret_mask
+            .chunks(8)
+            .flat_map(|byte| std::iter::repeat(byte.iter().any(|&b| b)).take(byte.len()))
+            .collect()
+    }
+
+    /// Forget any cached `public_reads` which may overlap a write of `bits`
+    /// bits at `addr`. If `addr` is `None` (a non-constant address), the write
+    /// may overlap anything, so we forget them all.
+    fn invalidate_public_reads(&self, addr: Option<u64>, bits: u32) {
+        let bytes = |bits: u32| (u64::from(bits) + 7) / 8;
+        match addr {
+            Some(addr) => self.public_reads.borrow_mut().retain(|&(read_addr, read_bits)| {
+                read_addr + bytes(read_bits) <= addr || addr + bytes(bits) <= read_addr
+            }),
+            None => self.public_reads.borrow_mut().clear(),
+        }
+    }
+
+    /// The `written_mem` for a new uninitialized `Memory`, according to the
+    /// `strict_uninitialized_memory` setting
+    fn new_written_mem(btor: &BtorRef, null_detection: bool, addr_bits: u32) -> Option<haybale::simple_memory::Memory> {
+        if strict_uninitialized_memory() {
+            Some(haybale::backend::Memory::new_zero_initialized(btor.0.clone(), null_detection, Some("written_mem"), addr_bits))
+        } else {
+            None
+        }
+    }
+
+    /// With `strict_uninitialized_memory`, return an error if any of the `bits`
+    /// bits at `index` may not have been written
+    fn check_written(&self, index: &boolector::BV<Rc<Btor>>, bits: u32) -> Result<()> {
+        if let Some(written_mem) = &self.written_mem {
+            let written_cell = haybale::backend::Memory::read(written_mem, index, bits)?;
+            let rc: Rc<Btor> = self.btor.clone().into();
+            let all_ones = boolector::BV::ones(rc.clone(), bits);
+            if !time_solver_query(SolverQueryKind::Other, || haybale::solver_utils::bvs_must_be_equal(&rc, &written_cell, &all_ones))? {
+                let addr = match index.as_u64() {
+                    Some(addr) => format!("{:#x}", addr),
+                    None => format!("{:?}", index),
+                };
+                return Err(Error::OtherError(format!(
+                    "Read of {} bits at address {} includes memory which was never written. (This is an error because `strict_uninitialized_memory` is set in the `PitchforkConfig`; it often means that the `AbstractData` for an argument doesn't describe all of the memory the function uses, e.g. an array which is too short.)",
+                    bits, addr,
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// For each of the given memory regions, given as (base address, size in
+    /// bytes), report whether its contents are currently public, secret, or
+    /// partially secret, according to the shadow memory.
+    ///
+    /// This is useful for debugging, e.g., to see which inputs have become
+    /// tainted with secret data at the end of a path.
+    pub fn taint_of_regions(&self, regions: &[(u64, u64)]) -> Result<Vec<RegionTaint>> {
+        regions.iter()
+            .map(|&(addr, bytes)| taint_of_region(|addr, bits| haybale::backend::Memory::read(self, addr, bits), &self.btor, addr, bytes))
+            .collect()
+    }
+}
+
+/// Describe a `secret_mask` as ranges of secret and public bits, e.g.
+/// "bits 0-31 secret, 32-63 public"
+fn describe_secret_mask(secret_mask: &[bool]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut start = 0;
+    while start < secret_mask.len() {
+        let secret = secret_mask[start];
+        let len = secret_mask[start ..].iter().take_while(|&&b| b == secret).count();
+        let end = start + len - 1;
+        let range = if start == end { start.to_string() } else { format!("{}-{}", start, end) };
+        ranges.push(format!("{} {}", range, if secret { "secret" } else { "public" }));
+        start = end + 1;
+    }
+    match ranges.first_mut() {
+        Some(first) if first.contains('-') => first.insert_str(0, "bits "),
+        Some(first) => first.insert_str(0, "bit "),
+        None => {},
+    }
+    ranges.join(", ")
+}
+
+/// Which bits of a `BV` are secret; see `BV::secrecy()`
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Secrecy {
+    /// All of the bits are public
+    Public,
+    /// Some of the bits are secret
+    PartiallySecret {
+        /// As in `BV::PartiallySecret`: `true` for each secret bit, starting
+        /// with the least-significant bit
+        secret_mask: Vec<bool>,
+    },
+    /// All of the bits are secret
+    Secret,
+}
+
+impl std::fmt::Display for Secrecy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Secrecy::Public => write!(f, "public"),
+            Secrecy::PartiallySecret { secret_mask } => {
+                let num_secret = secret_mask.iter().filter(|&&b| b).count();
+                write!(f, "partially secret ({} of {} bits secret)", num_secret, secret_mask.len())
+            },
+            Secrecy::Secret => write!(f, "secret"),
+        }
     }
 }
 
+/// Whether the contents of a memory region are public or secret; see
+/// `Memory::taint_of_regions()`
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RegionTaint {
+    /// All of the region is public
+    Public,
+    /// Some, but not all, of the region is (or may be) secret
+    PartiallySecret,
+    /// All of the region is (or may be) secret
+    Secret,
+}
+
+impl std::fmt::Display for RegionTaint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RegionTaint::Public => write!(f, "public"),
+            RegionTaint::PartiallySecret => write!(f, "partially secret"),
+            RegionTaint::Secret => write!(f, "secret"),
+        }
+    }
+}
+
+/// Determine the `RegionTaint` of the `bytes` bytes at `addr`, using the given
+/// function to read memory. Reads are done in chunks of at most 64 bits; the
+/// secrecy of each chunk is determined by the shadow memory as in
+/// `Memory::read()`.
+pub(crate) fn taint_of_region(read: impl Fn(&BV, u32) -> Result<BV>, btor: &BtorRef, addr: u64, bytes: u64) -> Result<RegionTaint> {
+    let mut any_public = false;
+    let mut any_secret = false;
+    let mut offset = 0;
+    while offset < bytes {
+        let chunk_bytes = std::cmp::min(8, bytes - offset);
+        let chunk_addr = haybale::backend::BV::from_u64(btor.clone(), addr + offset, 64);
+        match read(&chunk_addr, (chunk_bytes * 8) as u32)? {
+            BV::Public(_) => any_public = true,
+            BV::Secret { .. } => any_secret = true,
+            BV::PartiallySecret { .. } => {
+                any_public = true;
+                any_secret = true;
+            },
+        }
+        offset += chunk_bytes;
+    }
+    Ok(match (any_public, any_secret) {
+        (_, false) => RegionTaint::Public,
+        (false, true) => RegionTaint::Secret,
+        (true, true) => RegionTaint::PartiallySecret,
+    })
+}
+
 /// A `Backend` which performs dynamic taint tracking and reports constant-time
 /// violations.
 #[derive(Clone, Debug)]
@@ -814,6 +1501,110 @@ mod tests {
         assert!(!secret_ends.slice(50, 40).is_secret());
     }
 
+    #[test]
+    fn shifts_by_public_const() {
+        let btor = BtorRef::new();
+        let public = super::BV::new(btor.clone(), 32, Some("public"));
+        let eight = super::BV::from_u32(btor.clone(), 8, 32);
+        let too_big = super::BV::from_u32(btor.clone(), 40, 32);
+        let secret = super::BV::Secret { btor: btor.clone(), width: 32, symbol: None };
+        let secret_low = public.slice(15, 0).concat(&secret.slice(15, 0));
+        let secret_high = secret.slice(15, 0).concat(&public.slice(15, 0));
+
+        // shifting a secret: the bits shifted in are public, except for sra
+        let shifted = secret.sll(&eight);
+        assert!(!shifted.slice(7, 0).is_secret());
+        assert!(shifted.slice(31, 8).is_secret());
+        let shifted = secret.srl(&eight);
+        assert!(shifted.slice(23, 0).is_secret());
+        assert!(!shifted.slice(31, 24).is_secret());
+        assert!(secret.sra(&eight).slice(31, 24).is_secret());
+
+        // shifting a partially-secret value moves the secret bits
+        let shifted = secret_low.sll(&eight);
+        assert!(!shifted.slice(7, 0).is_secret());
+        assert!(shifted.slice(23, 8).is_secret());
+        assert!(!shifted.slice(31, 24).is_secret());
+        let shifted = secret_high.srl(&eight);
+        assert!(!shifted.slice(7, 0).is_secret());
+        assert!(shifted.slice(23, 8).is_secret());
+        assert!(!shifted.slice(31, 24).is_secret());
+
+        // sra fills with public bits iff the sign bit is public
+        let shifted = secret_low.sra(&eight);
+        assert!(shifted.slice(7, 0).is_secret());
+        assert!(!shifted.slice(31, 8).is_secret());
+        let shifted = secret_high.sra(&eight);
+        assert!(!shifted.slice(7, 0).is_secret());
+        assert!(shifted.slice(31, 8).is_secret());
+
+        // extracting a secret byte by shifting then masking
+        let byte = secret_high.srl(&super::BV::from_u32(btor.clone(), 16, 32)).and(&super::BV::from_u32(btor.clone(), 0xff, 32));
+        assert!(byte.slice(7, 0).is_secret());
+        assert!(!byte.slice(31, 8).is_secret());
+
+        // shifting the secret bits all the way out gives a public value
+        assert!(!secret_high.sll(&super::BV::from_u32(btor.clone(), 16, 32)).is_secret());
+        assert!(!secret_low.srl(&super::BV::from_u32(btor.clone(), 16, 32)).is_secret());
+        assert!(!secret.sll(&too_big).is_secret());
+        assert!(!secret.srl(&too_big).is_secret());
+        assert!(secret.sra(&too_big).is_secret());
+
+        // a non-constant shift amount gives no extra precision
+        assert!(secret_low.sll(&public).slice(31, 24).is_secret());
+        // and a secret shift amount taints everything
+        assert!(public.sll(&secret).is_secret());
+    }
+
+    #[test]
+    fn and_or_with_public_const() {
+        let btor = BtorRef::new();
+        let public = super::BV::new(btor.clone(), 32, Some("public"));
+        let low_byte_mask = super::BV::from_u32(btor.clone(), 0xff, 32);
+        let high_bits_set = super::BV::from_u32(btor.clone(), 0xffff_0000, 32);
+        let secret = super::BV::Secret { btor: btor.clone(), width: 32, symbol: None };
+
+        // masking a secret with a constant: bits cleared by the mask are public
+        let masked = secret.and(&low_byte_mask);
+        assert!(masked.is_secret());
+        assert!(masked.slice(7, 0).is_secret());
+        assert!(!masked.slice(31, 8).is_secret());
+        assert!(bvs_must_be_equal(&btor, &masked.slice(31, 8), &super::BV::zero(btor.clone(), 24)).unwrap());
+        // same with the operands in the other order
+        assert!(!low_byte_mask.and(&secret).slice(31, 8).is_secret());
+
+        // or-ing a secret with a constant: bits set by the constant are public
+        let set = secret.or(&high_bits_set);
+        assert!(set.slice(15, 0).is_secret());
+        assert!(!set.slice(31, 16).is_secret());
+        assert!(bvs_must_be_equal(&btor, &set.slice(31, 16), &super::BV::ones(btor.clone(), 16)).unwrap());
+
+        // masking a partially-secret value
+        let secret_low = public.slice(15, 0).concat(&secret.slice(15, 0));
+        let masked = secret_low.and(&low_byte_mask);
+        assert!(masked.slice(7, 0).is_secret());
+        assert!(!masked.slice(31, 8).is_secret());
+        // masking away all the secret bits gives a fully public value
+        assert!(!secret_low.and(&high_bits_set).is_secret());
+
+        // a partially-secret value with no secret bits left behaves as public in further ops
+        let cleared = secret_low.and(&high_bits_set);
+        assert!(!cleared.add(&public).is_secret());
+        assert!(!cleared.concat(&public).is_secret());
+        let no_secret_bits = super::BV::PartiallySecret {
+            secret_mask: vec![false; 8],
+            data: boolector::BV::zero(btor.clone().into(), 8),
+            symbol: None,
+        };
+        assert!(!no_secret_bits.clone().collapse_public().is_secret());
+        assert!(!no_secret_bits.concat(&public).is_secret());
+        assert!(masked.collapse_public().slice(7, 0).is_secret());
+
+        // a non-constant public operand gives no extra precision
+        assert!(secret.and(&public).slice(31, 8).is_secret());
+        assert!(secret.or(&public).slice(31, 16).is_secret());
+    }
+
     #[test]
     fn extensions() {
         let btor = BtorRef::new();
@@ -841,6 +1632,136 @@ mod tests {
         assert!(secret_high.sext(16).slice(79, 64).is_secret());
     }
 
+    #[test]
+    fn taint_of_regions() {
+        let btor = BtorRef::new();
+        let mut mem = super::Memory::new_uninitialized(btor.clone(), false, Some("mem"), 64);
+        let secret_32bits = super::BV::Secret { btor: btor.clone(), width: 32, symbol: Some("secret".into()) };
+        mem.write(&super::BV::from_u64(btor.clone(), 0x1000, 64), secret_32bits.clone()).unwrap();
+        mem.write(&super::BV::from_u64(btor.clone(), 0x1004, 64), secret_32bits.clone()).unwrap();
+        mem.write(&super::BV::from_u64(btor.clone(), 0x2000, 64), super::BV::from_u32(btor.clone(), 7, 32)).unwrap();
+        mem.write(&super::BV::from_u64(btor.clone(), 0x2004, 64), secret_32bits).unwrap();
+        let taints = mem.taint_of_regions(&[(0x1000, 8), (0x2000, 8), (0x2000, 4), (0x3000, 16)]).unwrap();
+        assert_eq!(taints, vec![RegionTaint::Secret, RegionTaint::PartiallySecret, RegionTaint::Public, RegionTaint::Public]);
+    }
+
+    #[test]
+    fn change_solver() {
+        // as when backtracking, which duplicates the solver
+        set_strict_uninitialized_memory(true);
+        let btor = BtorRef::new();
+        let mut mem = super::Memory::new_uninitialized(btor.clone(), false, Some("mem"), 64);
+        set_strict_uninitialized_memory(false);
+        let addr = super::BV::from_u64(btor.clone(), 0x1000, 64);
+        let public = super::BV::from_u32(btor.clone(), 577, 32);
+        let secret = super::BV::Secret { btor: btor.clone(), width: 32, symbol: None };
+        mem.write(&addr, public.concat(&secret)).unwrap();
+        assert!(mem.solvers_consistent());
+
+        let new_btor = btor.duplicate();
+        mem.change_solver(new_btor.clone());
+        assert!(Rc::ptr_eq(&mem.get_solver().0, &new_btor.0));
+        assert!(mem.solvers_consistent());
+
+        // the contents and their secrecy carry over to the new solver
+        let addr = super::BV::from_u64(new_btor.clone(), 0x1000, 64);
+        let data = mem.read(&addr, 64).unwrap();
+        assert!(data.slice(31, 0).is_secret());
+        assert!(!data.slice(63, 32).is_secret());
+        // and writes and reads with the new solver still work
+        let new_secret = super::BV::Secret { btor: new_btor.clone(), width: 64, symbol: None };
+        mem.write(&addr, new_secret).unwrap();
+        assert!(mem.read(&addr, 64).unwrap().is_secret());
+    }
+
+    #[test]
+    fn describe_secrecy() {
+        let btor = BtorRef::new();
+        let public = super::BV::new(btor.clone(), 32, Some("public"));
+        let secret = super::BV::Secret { btor: btor.clone(), width: 32, symbol: None };
+        assert_eq!(public.describe_secrecy(), "public");
+        assert_eq!(secret.describe_secrecy(), "secret");
+        assert_eq!(public.concat(&secret).describe_secrecy(), "bits 0-31 secret, 32-63 public");
+        assert_eq!(secret.slice(0, 0).concat(&public).concat(&secret.slice(0, 0)).describe_secrecy(), "bit 0 secret, 1-32 public, 33 secret");
+    }
+
+    #[test]
+    fn public_read_cache() {
+        let btor = BtorRef::new();
+        let mut mem = super::Memory::new_uninitialized(btor.clone(), false, Some("mem"), 64);
+        let addr = super::BV::from_u64(btor.clone(), 0x1000, 64);
+        let addr_plus_four = super::BV::from_u64(btor.clone(), 0x1004, 64);
+        let addr_plus_eight = super::BV::from_u64(btor.clone(), 0x1008, 64);
+        let symbolic_addr = super::BV::new(btor.clone(), 64, Some("symbolic_addr"));
+        let value = super::BV::from_u32(btor.clone(), 577, 64);
+        let secret_32bits = super::BV::Secret { btor: btor.clone(), width: 32, symbol: Some("secret".into()) };
+        mem.write(&addr, value.clone()).unwrap();
+
+        // the second read of the same public data is a cache hit
+        take_solver_query_counts();
+        assert!(!mem.read(&addr, 64).unwrap().is_secret());
+        let data = mem.read(&addr, 64).unwrap();
+        assert!(!data.is_secret());
+        assert!(bvs_must_be_equal(&btor, &value, &data).unwrap());
+        let counts = take_solver_query_counts();
+        assert_eq!(counts.read_all_public_checks + counts.read_constant_secrecy, 1);
+        assert_eq!(counts.read_cache_hits, 1);
+
+        // a non-overlapping write doesn't invalidate the cached read
+        mem.write(&addr_plus_eight, secret_32bits.clone()).unwrap();
+        assert!(mem.public_reads.borrow().contains(&(0x1000, 64)));
+
+        // an overlapping write does
+        mem.write(&addr_plus_four, secret_32bits.clone()).unwrap();
+        assert!(!mem.public_reads.borrow().contains(&(0x1000, 64)));
+        let data = mem.read(&addr, 64).unwrap();
+        assert!(!data.slice(31, 0).is_secret());
+        assert!(data.slice(63, 32).is_secret());
+
+        // a write at a non-constant address invalidates all cached reads
+        assert!(!mem.read(&addr, 32).unwrap().is_secret());
+        assert!(mem.public_reads.borrow().contains(&(0x1000, 32)));
+        mem.write(&symbolic_addr, secret_32bits).unwrap();
+        assert!(mem.public_reads.borrow().is_empty());
+        assert!(mem.read(&addr, 32).unwrap().is_secret());
+    }
+
+    #[test]
+    fn byte_granularity() {
+        set_byte_granularity_secrecy(true);
+        let btor = BtorRef::new();
+        let mut mem = super::Memory::new_uninitialized(btor.clone(), false, Some("mem"), 64);
+        set_byte_granularity_secrecy(false);
+        let addr = super::BV::from_u64(btor.clone(), 0x1000, 64);
+        let public = super::BV::from_u32(btor.clone(), 0x1234_5678, 32);
+        let secret = super::BV::Secret { btor: btor.clone(), width: 32, symbol: None };
+
+        // only bits 4 through 7 are secret, but with byte granularity, we get
+        // the whole low byte back as secret
+        let secret_nibble = public.slice(31, 8).concat(&secret.slice(3, 0)).concat(&public.slice(3, 0));
+        mem.write(&addr, secret_nibble).unwrap();
+        let data = mem.read(&addr, 32).unwrap();
+        assert!(data.slice(7, 0).is_secret());
+        assert!(data.slice(3, 0).is_secret());
+        assert!(!data.slice(31, 8).is_secret());
+        match data {
+            super::BV::PartiallySecret { secret_mask, .. } => {
+                assert_eq!(secret_mask.len(), 32);
+                assert!(secret_mask[.. 8].iter().all(|&b| b));
+                assert!(secret_mask[8 ..].iter().all(|&b| !b));
+            },
+            _ => panic!("Expected a PartiallySecret value, got {:?}", data),
+        }
+
+        // fully public and fully secret data are unaffected
+        mem.write(&addr, public).unwrap();
+        assert!(!mem.read(&addr, 32).unwrap().is_secret());
+        mem.write(&addr, secret).unwrap();
+        let data = mem.read(&addr, 32).unwrap();
+        assert!(data.slice(31, 24).is_secret());
+        assert!(data.slice(7, 0).is_secret());
+    }
+
     #[test]
     fn read_and_write() {
         let btor = BtorRef::new();
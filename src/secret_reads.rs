@@ -0,0 +1,99 @@
+use haybale::backend::{Backend, BV};
+use haybale::{Config, State};
+use llvm_ir::{Instruction, Name, Operand};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Records which of the toplevel function's arguments pointing to secret data
+/// ever had that data read, so that we can report secret inputs which can't
+/// have influenced the behavior of the function.
+///
+/// An argument's memory is considered read if any load reads from it, or if a
+/// pointer into it is passed to any call (since we can't see what function
+/// hooks do with their arguments). A load from a non-constant address is
+/// conservatively considered to read all of the arguments' memory.
+///
+/// Only the memory directly pointed to by each argument is tracked; memory
+/// reachable only through further pointers is not.
+#[derive(Clone)]
+pub struct SecretArgReads(Rc<RefCell<SecretArgReadsInner>>);
+
+struct SecretArgReadsInner {
+    /// The tracked regions, as (parameter name, address, size in bytes)
+    regions: Vec<(Name, u64, u64)>,
+    /// Names of the parameters whose memory has been read
+    read: HashSet<Name>,
+}
+
+impl SecretArgReads {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(SecretArgReadsInner {
+            regions: Vec::new(),
+            read: HashSet::new(),
+        })))
+    }
+
+    /// Set the tracked regions, as (parameter name, address, size in bytes)
+    pub fn set_regions(&self, regions: Vec<(Name, u64, u64)>) {
+        self.0.borrow_mut().regions = regions;
+    }
+
+    /// Add the instruction callback which records reads of the tracked
+    /// regions into this `SecretArgReads`
+    pub fn add_callbacks<'p, B: Backend>(&self, config: &mut Config<'p, B>) {
+        let reads = self.clone();
+        config.callbacks.add_instruction_callback(move |inst: &Instruction, state: &State<B>| {
+            match inst {
+                Instruction::Load(load) => {
+                    let bytes = state.size_in_bits(&state.type_of(load)).map(|bits| std::cmp::max(u64::from(bits) / 8, 1));
+                    reads.record_access(&load.address, bytes, state);
+                },
+                Instruction::Call(call) => {
+                    for (arg, _) in &call.arguments {
+                        if let Some(addr) = state.operand_to_bv(arg).ok().and_then(|bv| bv.as_u64()) {
+                            reads.record_addr(addr, 1);
+                        }
+                    }
+                },
+                _ => {},
+            }
+            Ok(())
+        });
+    }
+
+    fn record_access<B: Backend>(&self, address: &Operand, bytes: Option<u64>, state: &State<B>) {
+        if self.0.borrow().regions.is_empty() {
+            return;
+        }
+        match (state.operand_to_bv(address).ok().and_then(|bv| bv.as_u64()), bytes) {
+            (Some(addr), Some(bytes)) => self.record_addr(addr, bytes),
+            _ => {
+                // we can't tell which memory this reads, so conservatively assume it could read any of it
+                let mut inner = self.0.borrow_mut();
+                let names: Vec<Name> = inner.regions.iter().map(|(name, _, _)| name.clone()).collect();
+                inner.read.extend(names);
+            },
+        }
+    }
+
+    fn record_addr(&self, addr: u64, bytes: u64) {
+        let mut inner = self.0.borrow_mut();
+        let names: Vec<Name> = inner.regions.iter()
+            .filter(|(_, region_addr, region_bytes)| addr < region_addr + region_bytes && *region_addr < addr + bytes)
+            .map(|(name, _, _)| name.clone())
+            .collect();
+        inner.read.extend(names);
+    }
+
+    /// Names of the parameters pointing to secret data which was never read,
+    /// on any path so far
+    pub fn unread(&self) -> Vec<Name> {
+        let inner = self.0.borrow();
+        inner.regions.iter()
+            .map(|(name, _, _)| name)
+            .filter(|name| !inner.read.contains(name))
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,540 @@
+use crate::{secret, PitchforkConfig};
+use crate::coverage::source_line;
+use either::Either;
+use haybale::{Error, State};
+use haybale::state::{BBInstrIndex, Location, PathEntry};
+use llvm_ir::{BasicBlock, Constant, DebugLoc, Function, HasDebugLoc, Instruction, Name, Operand, Terminator};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// The kinds of constant-time violations which `pitchfork` can detect
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum CTViolationKind {
+    /// Control flow (e.g., a conditional branch) may be influenced by secret data
+    Branch,
+    /// A memory read or write is performed at an address which may be
+    /// influenced by secret data. This is also reported for an
+    /// `extractelement` or `insertelement` with a secret element index.
+    MemoryAddress,
+    /// A 'select' operation has a secret condition. (This is only reported
+    /// with the `secret_select_is_violation` setting in `PitchforkConfig`.)
+    Select,
+    /// An instruction configured as variable-time has a secret operand. (See
+    /// the `variable_time_opcodes` setting in `PitchforkConfig`.)
+    VariableTimeOperation,
+    /// A loop ran until the `loop_bound` in `Config` was reached, and the
+    /// loop's exit condition depended on secret data, so the number of
+    /// iterations may be influenced by secret data. (This can only be detected
+    /// with the `continue_past_secret_branches` setting in `PitchforkConfig`;
+    /// otherwise, the first secret exit condition is reported as a `Branch`.)
+    LoopBound,
+    /// A user-written function hook reported a violation by returning an
+    /// `Error::OtherError` whose message begins with "Constant-time violation:"
+    Hook,
+}
+
+impl fmt::Display for CTViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CTViolationKind::Branch => write!(f, "secret-dependent control flow"),
+            CTViolationKind::MemoryAddress => write!(f, "secret-dependent memory address"),
+            CTViolationKind::Select => write!(f, "select with a secret condition"),
+            CTViolationKind::VariableTimeOperation => write!(f, "variable-time operation on secret data"),
+            CTViolationKind::LoopBound => write!(f, "secret-dependent loop bound"),
+            CTViolationKind::Hook => write!(f, "violation reported by a function hook"),
+        }
+    }
+}
+
+/// One frame of the call stack at a constant-time violation
+#[derive(Clone, Debug)]
+pub struct CallFrame {
+    /// (Mangled) name of the function
+    pub funcname: String,
+    /// The source location of the call (in the previous frame's function)
+    /// which entered this function, if available. This is always `None` for
+    /// the toplevel function.
+    pub callsite_source_loc: Option<DebugLoc>,
+}
+
+/// Concrete values for the public parts of one function parameter, as part of
+/// a counterexample; see `counterexample` in `CTViolation`
+#[derive(Clone, Debug)]
+pub struct ArgCounterexample {
+    /// The value of the parameter itself, as a hexadecimal number (or, for
+    /// values wider than 64 bits, a binary string). `None` if the value is
+    /// (even partially) secret.
+    pub value: Option<String>,
+    /// For pointer parameters which we allocated memory for, the initial
+    /// contents of that memory (as of the start of the analysis), one entry
+    /// per byte. Entries are `None` for bytes which are (even partially)
+    /// secret. `None` for all other parameters.
+    pub pointee_bytes: Option<Vec<Option<u8>>>,
+    /// Several distinct values of the parameter itself (formatted as for
+    /// `value`) which all lead to the violation, up to
+    /// `counterexample_num_values` in `PitchforkConfig`. Empty if the value is
+    /// (even partially) secret.
+    pub possible_values: Vec<String>,
+    /// `true` if `possible_values` are all of the values of the parameter
+    /// which lead to the violation
+    pub all_possible_values: bool,
+}
+
+/// Describes a constant-time violation found on a particular path
+#[derive(Clone, Debug)]
+pub struct CTViolation {
+    /// What kind of violation this is
+    pub kind: CTViolationKind,
+    /// Short message describing the violation (this is the message of the
+    /// `haybale::Error` which ended the path)
+    pub msg: String,
+    /// (Mangled) name of the function containing the instruction where the
+    /// violation occurred
+    pub funcname: String,
+    /// Name of the basic block containing the instruction where the violation
+    /// occurred
+    pub bbname: Name,
+    /// The LLVM instruction or terminator where the violation occurred
+    pub instruction: Either<Instruction, Terminator>,
+    /// The source location of that instruction or terminator, if available
+    pub source_loc: Option<DebugLoc>,
+    /// The call stack at the violation, starting with the toplevel function
+    /// and ending with the function containing the violation (`funcname`).
+    /// Calls handled by function hooks don't appear here.
+    pub call_stack: Vec<CallFrame>,
+    /// With the `counterexamples` setting in `PitchforkConfig`, a map from
+    /// parameter name to concrete values for the public parts of that
+    /// parameter (and the memory it points to) which lead to this violation.
+    /// These come from a single satisfying assignment of the path's
+    /// constraints, so together they form a reproducible test vector; they
+    /// are not necessarily minimal. `None` without that setting, or if the
+    /// values couldn't be determined.
+    pub counterexample: Option<HashMap<Name, ArgCounterexample>>,
+    /// The full message with "rich context" (backtrace, full path, etc)
+    pub full_message: String,
+    /// `true` if this path was explored past a secret branch, treating the
+    /// branch condition as an unconstrained public value. This can only happen
+    /// with the `continue_past_secret_branches` setting in `PitchforkConfig`.
+    pub post_violation: bool,
+}
+
+impl CTViolation {
+    /// Construct a `CTViolation` describing the violation of the given `kind`
+    /// which just ended the current path in `state`
+    pub(crate) fn new(kind: CTViolationKind, error: &Error, full_message: String, state: &State<secret::Backend>, post_violation: bool) -> Self {
+        let loc = &state.cur_loc;
+        Self {
+            kind,
+            msg: error.to_string(),
+            funcname: loc.func.name.clone(),
+            bbname: loc.bb.name.clone(),
+            instruction: match loc.instr {
+                BBInstrIndex::Instr(idx) => Either::Left(loc.bb.instrs[idx].clone()),
+                BBInstrIndex::Terminator => Either::Right(loc.bb.term.clone()),
+            },
+            source_loc: loc.source_loc.cloned(),
+            call_stack: call_stack_of_path(state.get_path()),
+            counterexample: None,
+            full_message,
+            post_violation,
+        }
+    }
+
+    /// Construct a `CTViolation` describing the secret branch at the end of
+    /// the given block, for a path which continued past it (see
+    /// `continue_past_secret_branches`). The block must be on the current path
+    /// in `state`.
+    pub(crate) fn at_secret_branch(func: &Function, bb: &BasicBlock, state: &State<secret::Backend>) -> Self {
+        let msg = "Constant-time violation: control-flow may be influenced by secret data".to_owned();
+        let source_loc = bb.term.get_debug_loc().clone();
+        let full_message = format!(
+            "{}\n  at the terminator of block {} in function {:?}{}\n\nThis path continued past the secret branch (treating its condition as unconstrained public data) because `continue_past_secret_branches` is set in `PitchforkConfig`.\n",
+            msg,
+            bb.name,
+            func.name,
+            match &source_loc {
+                Some(source_loc) => format!(" ({})", source_loc),
+                None => String::new(),
+            },
+        );
+        Self {
+            kind: CTViolationKind::Branch,
+            msg,
+            funcname: func.name.clone(),
+            bbname: bb.name.clone(),
+            instruction: Either::Right(bb.term.clone()),
+            source_loc,
+            call_stack: call_stack_at_block(state, func, bb),
+            counterexample: None,
+            full_message,
+            post_violation: true,
+        }
+    }
+
+    /// Construct a `CTViolation` describing a loop which exceeded the loop
+    /// bound, where the given block (in the loop) ends with a secret branch.
+    /// `full_message` is the full message of the `LoopBoundExceeded` error.
+    /// The block must be on the current path in `state`.
+    pub(crate) fn at_secret_loop_bound(func: &Function, bb: &BasicBlock, error: &Error, full_message: &str, state: &State<secret::Backend>) -> Self {
+        let msg = "Constant-time violation: loop bound may be influenced by secret data".to_owned();
+        let source_loc = bb.term.get_debug_loc().clone();
+        let full_message = format!(
+            "{}\n  the loop containing block {} in function {:?}{} has an exit condition which depends on secret data,\n  and the path reached the loop bound. Details of the loop bound error follow.\n\n{}",
+            msg,
+            bb.name,
+            func.name,
+            match &source_loc {
+                Some(source_loc) => format!(" ({})", source_loc),
+                None => String::new(),
+            },
+            full_message,
+        );
+        Self {
+            kind: CTViolationKind::LoopBound,
+            msg: format!("{} ({})", msg, error),
+            funcname: func.name.clone(),
+            bbname: bb.name.clone(),
+            instruction: Either::Right(bb.term.clone()),
+            source_loc,
+            call_stack: call_stack_at_block(state, func, bb),
+            counterexample: None,
+            full_message,
+            post_violation: true,
+        }
+    }
+
+    /// Should this violation be reported, according to the
+    /// `violation_source_prefixes` and `ignored_violation_source_prefixes`
+    /// settings in the `PitchforkConfig`
+    pub(crate) fn passes_source_filter(&self, pitchfork_config: &PitchforkConfig) -> bool {
+        let source_loc = match &self.source_loc {
+            Some(source_loc) => source_loc,
+            None => return true,  // we can't tell where the violation is, so report it
+        };
+        let paths = [source_line(source_loc).0, source_loc.filename.clone()];
+        let matches = |prefixes: &[String]| prefixes.iter().any(|prefix| paths.iter().any(|path| path.starts_with(prefix.as_str())));
+        (pitchfork_config.violation_source_prefixes.is_empty() || matches(&pitchfork_config.violation_source_prefixes))
+            && !matches(&pitchfork_config.ignored_violation_source_prefixes)
+    }
+}
+
+/// Displays the full message, with context
+impl fmt::Display for CTViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.full_message)
+    }
+}
+
+/// A short summary of the error which ended a path (other than a
+/// constant-time violation), for triaging errors without reading the full
+/// message; see `ConstantTimeResultForPath::OtherError`
+#[derive(Clone, Debug)]
+pub struct PathErrorSummary {
+    /// (Mangled) name of the function containing the instruction where the
+    /// error occurred
+    pub funcname: String,
+    /// Name of the basic block containing the instruction where the error
+    /// occurred
+    pub bbname: Name,
+    /// The LLVM instruction or terminator where the error occurred
+    pub instruction: Either<Instruction, Terminator>,
+    /// The source location of that instruction or terminator, if available
+    pub source_loc: Option<DebugLoc>,
+    /// `true` if the error was a solver query timing out (see
+    /// `solver_query_timeout` in `Config`)
+    pub solver_timed_out: bool,
+    /// Number of constraints asserted while exploring this path. This doesn't
+    /// include constraints from the part of the path shared with previously
+    /// explored paths, or from setting up the function parameters.
+    pub constraints_asserted: usize,
+}
+
+impl PathErrorSummary {
+    /// Construct a `PathErrorSummary` describing the `error` which just ended
+    /// the current path in `state`
+    pub(crate) fn new(error: &Error, state: &State<secret::Backend>, constraints_asserted: usize) -> Self {
+        let loc = &state.cur_loc;
+        Self {
+            funcname: loc.func.name.clone(),
+            bbname: loc.bb.name.clone(),
+            instruction: match loc.instr {
+                BBInstrIndex::Instr(idx) => Either::Left(loc.bb.instrs[idx].clone()),
+                BBInstrIndex::Terminator => Either::Right(loc.bb.term.clone()),
+            },
+            source_loc: loc.source_loc.cloned(),
+            solver_timed_out: is_solver_timeout(error),
+            constraints_asserted,
+        }
+    }
+}
+
+/// Displays the location of the error, and the other details, on one line
+impl fmt::Display for PathErrorSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "in block {} of function {:?}", self.bbname, self.funcname)?;
+        if let Some(source_loc) = &self.source_loc {
+            write!(f, " ({})", source_loc)?;
+        }
+        write!(f, "; {} constraint(s) asserted on this path", self.constraints_asserted)?;
+        if self.solver_timed_out {
+            write!(f, "; the solver timed out")?;
+        }
+        Ok(())
+    }
+}
+
+/// Is `error` the result of a solver query timing out. haybale reports
+/// timeouts as a `SolverError` mentioning the timeout.
+pub(crate) fn is_solver_timeout(error: &Error) -> bool {
+    match error {
+        Error::SolverError(msg) => {
+            let msg = msg.to_lowercase();
+            msg.contains("timeout") || msg.contains("timed out")
+        },
+        _ => false,
+    }
+}
+
+thread_local! {
+    /// The kind and message of the most recent violation reported with
+    /// `report_violation()` and not yet taken with `take_reported_violation()`
+    static REPORTED_VIOLATION: RefCell<Option<(CTViolationKind, String)>> = RefCell::new(None);
+}
+
+/// Get the `Error` which should be returned to report a constant-time violation
+/// of the given `kind`. `description` should describe the violation.
+///
+/// This also records the `kind`, so that it can be recovered (with
+/// `take_reported_violation()`) when the `Error` ends the path.
+pub(crate) fn report_violation(kind: CTViolationKind, description: impl fmt::Display) -> Error {
+    let msg = format!("Constant-time violation: {}", description);
+    REPORTED_VIOLATION.with(|reported| *reported.borrow_mut() = Some((kind, msg.clone())));
+    Error::OtherError(msg)
+}
+
+/// If `error` is (or contains) the `Error` most recently returned by
+/// `report_violation()`, returns the kind of that violation. Otherwise, if
+/// `error` is an `Error::OtherError` whose message begins with
+/// "Constant-time violation:" (as user-written hooks may return), returns
+/// `CTViolationKind::Hook`.
+///
+/// In any case, clears the record of the most recent violation.
+pub(crate) fn take_reported_violation(error: &Error) -> Option<CTViolationKind> {
+    match REPORTED_VIOLATION.with(|reported| reported.borrow_mut().take()) {
+        Some((kind, msg)) if error.to_string().contains(&msg) => Some(kind),
+        _ => match error {
+            Error::OtherError(msg) if msg.starts_with("Constant-time violation:") => Some(CTViolationKind::Hook),
+            _ => None,
+        },
+    }
+}
+
+thread_local! {
+    /// Whether the `continue_past_secret_branches` setting is active for the
+    /// current analysis
+    static CONTINUE_PAST_SECRET_BRANCHES: Cell<bool> = Cell::new(false);
+
+    /// With `continue_past_secret_branches`, the secret branches we have seen
+    static SECRET_BRANCHES: RefCell<SecretBranches> = RefCell::new(SecretBranches::default());
+}
+
+/// The blocks which ended with a secret branch, on the paths where they did.
+///
+/// Whether a block's branch condition is secret can depend on the path taken
+/// to the block, so each secret branch is recorded together with the path
+/// leading up to it. Two paths with the same prefix execute that prefix
+/// identically, so a path continued past a secret branch exactly when one was
+/// recorded for one of its prefixes.
+#[derive(Default)]
+pub(crate) struct SecretBranches {
+    /// The (function name, block name) of each block we have seen end with a
+    /// secret branch on some path
+    blocks: HashSet<(String, Name)>,
+    /// For each secret branch, the path up to and including the block which
+    /// ended with it, as (function name, block name) pairs
+    paths: HashSet<Vec<(String, Name)>>,
+}
+
+impl SecretBranches {
+    /// Record that the last block on `path` ended with a secret branch
+    pub(crate) fn record(&mut self, path: Vec<(String, Name)>) {
+        if let Some(block) = path.last() {
+            self.blocks.insert(block.clone());
+            self.paths.insert(path);
+        }
+    }
+
+    /// The indices of the blocks on `path` which ended with a secret branch
+    /// on this path, in order
+    pub(crate) fn indices_on_path(&self, path: &[(String, Name)]) -> Vec<usize> {
+        if self.paths.is_empty() {
+            return Vec::new();
+        }
+        (0 .. path.len())
+            .filter(|&idx| self.blocks.contains(&path[idx]) && self.paths.contains(&path[..= idx]))
+            .collect()
+    }
+}
+
+/// Get the given path as (function name, block name) pairs
+fn path_blocks(path: &[PathEntry]) -> Vec<(String, Name)> {
+    path.iter().map(|pathentry| (pathentry.0.func.name.clone(), pathentry.0.bb.name.clone())).collect()
+}
+
+/// Set whether the `continue_past_secret_branches` setting is active. This
+/// also forgets any secret branches recorded for a previous analysis.
+pub(crate) fn set_continue_past_secret_branches(continue_past: bool) {
+    CONTINUE_PAST_SECRET_BRANCHES.with(|c| c.set(continue_past));
+    SECRET_BRANCHES.with(|branches| *branches.borrow_mut() = SecretBranches::default());
+}
+
+/// Is the `continue_past_secret_branches` setting active
+pub(crate) fn continuing_past_secret_branches() -> bool {
+    CONTINUE_PAST_SECRET_BRANCHES.with(|c| c.get())
+}
+
+/// Record that the current block in `state` ends with a branch on a secret
+/// condition, on the current path
+pub(crate) fn record_secret_branch(state: &State<secret::Backend>) {
+    let path = path_blocks(state.get_path());
+    SECRET_BRANCHES.with(|branches| branches.borrow_mut().record(path));
+}
+
+/// Find the first block on the current path in `state` which ended with a
+/// secret branch (that the path then continued past), if there is one
+pub(crate) fn first_secret_branch_on_path<'p>(state: &State<'p, secret::Backend>) -> Option<(&'p Function, &'p BasicBlock)> {
+    let path = state.get_path();
+    // the last block on the path is where the path ended, so the path didn't continue past its terminator
+    let continued_past = &path[.. path.len().saturating_sub(1)];
+    let indices = SECRET_BRANCHES.with(|branches| branches.borrow().indices_on_path(&path_blocks(continued_past)));
+    indices.first().map(|&idx| (path[idx].0.func, path[idx].0.bb))
+}
+
+/// Find a block on the current path in `state` which ended with a secret branch
+/// and which appears on the path more than once, i.e., a loop whose exit
+/// condition depends on secret data. Returns the first such block, if there is
+/// one.
+pub(crate) fn secret_loop_branch_on_path<'p>(state: &State<'p, secret::Backend>) -> Option<(&'p Function, &'p BasicBlock)> {
+    let path = state.get_path();
+    let blocks = path_blocks(path);
+    let indices = SECRET_BRANCHES.with(|branches| branches.borrow().indices_on_path(&blocks));
+    indices.into_iter()
+        .find(|&idx| blocks.iter().filter(|&block| *block == blocks[idx]).count() > 1)
+        .map(|idx| (path[idx].0.func, path[idx].0.bb))
+}
+
+/// How the path reached one of its entries
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) enum PathStep {
+    /// Called the function containing the entry
+    Call,
+    /// Returned to the caller, resuming the caller's block at the entry
+    Return,
+    /// Branched to the entry's block, within the current function
+    Branch,
+}
+
+/// Determine how the path reached the entry at `loc`, given the location of
+/// the previous entry on the path (if any).
+///
+/// The path records each block entered, and also where the path resumed in
+/// the caller's block after each return. Entering a function's entry block
+/// (which can't be the target of a branch) must be a call; resuming partway
+/// through a block, just after a `Call` instruction, must be a return; and
+/// entering any other block is a branch, unless it is in a different function
+/// than the previous entry, in which case it is a return from an `invoke` to
+/// its normal destination.
+fn path_step(loc: &Location, prev: Option<&Location>) -> PathStep {
+    let is_call = |inst: &Instruction| match inst {
+        Instruction::Call(_) => true,
+        _ => false,
+    };
+    let resumed_after_call = match loc.instr {
+        BBInstrIndex::Instr(idx) => idx > 0 && is_call(&loc.bb.instrs[idx - 1]),
+        BBInstrIndex::Terminator => loc.bb.instrs.last().map_or(false, is_call),
+    };
+    if resumed_after_call {
+        PathStep::Return
+    } else if loc.func.basic_blocks.first().map_or(false, |entry| entry.name == loc.bb.name) {
+        PathStep::Call
+    } else if prev.map_or(false, |prev| prev.func.name != loc.func.name) {
+        PathStep::Return
+    } else {
+        PathStep::Branch
+    }
+}
+
+/// Given how the path reached each of its entries, get the frames of the call
+/// stack at the end of the path, starting with the toplevel function. For each
+/// frame, returns the index of the path entry which entered the frame, and the
+/// index of the most recent path entry in that frame.
+pub(crate) fn call_stack_indices(steps: &[PathStep]) -> Vec<(usize, usize)> {
+    let mut frames: Vec<(usize, usize)> = Vec::new();
+    for (idx, step) in steps.iter().enumerate() {
+        match step {
+            _ if frames.is_empty() => frames.push((idx, idx)),
+            PathStep::Call => frames.push((idx, idx)),
+            PathStep::Return => {
+                if frames.len() > 1 {
+                    frames.pop();
+                }
+                if let Some(frame) = frames.last_mut() {
+                    frame.1 = idx;
+                }
+            },
+            PathStep::Branch => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.1 = idx;
+                }
+            },
+        }
+    }
+    frames
+}
+
+/// Get the call stack at the end of the given path, as described on
+/// `call_stack` in `CTViolation`
+fn call_stack_of_path(path: &[PathEntry]) -> Vec<CallFrame> {
+    let steps: Vec<PathStep> = path.iter().enumerate()
+        .map(|(idx, pathentry)| path_step(&pathentry.0, idx.checked_sub(1).map(|prev| &path[prev].0)))
+        .collect();
+    let frames = call_stack_indices(&steps);
+    frames.iter().enumerate().map(|(i, &(entered, _))| {
+        let funcname = path[entered].0.func.name.clone();
+        // while a frame is live, its caller's most recent path entry is where the call happened
+        let callsite_source_loc = i.checked_sub(1).and_then(|caller| callsite_source_loc(&path[frames[caller].1].0, &funcname));
+        CallFrame { funcname, callsite_source_loc }
+    }).collect()
+}
+
+/// Get the call stack at the first point the given block appears on the
+/// current path in `state` (or at the end of the path, if it doesn't appear)
+fn call_stack_at_block(state: &State<secret::Backend>, func: &Function, bb: &BasicBlock) -> Vec<CallFrame> {
+    let path = state.get_path();
+    let len = path.iter()
+        .position(|pathentry| pathentry.0.func.name == func.name && pathentry.0.bb.name == bb.name)
+        .map_or(path.len(), |idx| idx + 1);
+    call_stack_of_path(&path[.. len])
+}
+
+/// Find the source location of the first direct call to `callee` in the block
+/// at `caller_loc`, at or after `caller_loc`
+fn callsite_source_loc(caller_loc: &Location, callee: &str) -> Option<DebugLoc> {
+    let start = match caller_loc.instr {
+        BBInstrIndex::Instr(idx) => idx,
+        BBInstrIndex::Terminator => return None,
+    };
+    caller_loc.bb.instrs[start ..].iter()
+        .find(|inst| match inst {
+            Instruction::Call(call) => match &call.function {
+                Either::Right(Operand::ConstantOperand(cref)) => match cref.as_ref() {
+                    Constant::GlobalReference { name: Name::Name(name), .. } => name.as_str() == callee,
+                    _ => false,
+                },
+                _ => false,
+            },
+            _ => false,
+        })
+        .and_then(|inst| inst.get_debug_loc().clone())
+}
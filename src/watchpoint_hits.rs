@@ -0,0 +1,154 @@
+use haybale::backend::{Backend, BV};
+use haybale::{Config, State};
+use llvm_ir::{DebugLoc, Instruction, Name, Operand};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// Whether a watched memory region was read or written
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum WatchpointAccess {
+    Read,
+    Write,
+}
+
+impl fmt::Display for WatchpointAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchpointAccess::Read => write!(f, "read"),
+            WatchpointAccess::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// Describes a single access to memory covered by a watchpoint (see
+/// `AbstractData::with_watchpoint()`)
+#[derive(Clone, Debug)]
+pub struct WatchpointHit {
+    /// Name of the watchpoint
+    pub name: String,
+    /// Whether the watched memory was read or written
+    pub access: WatchpointAccess,
+    /// (Mangled) name of the function containing the instruction which
+    /// accessed the watched memory
+    pub funcname: String,
+    /// Name of the basic block containing that instruction
+    pub bbname: Name,
+    /// The source location of that instruction, if available
+    pub source_loc: Option<DebugLoc>,
+}
+
+impl fmt::Display for WatchpointHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "watchpoint {:?}: {} in function {:?}, block {}", self.name, self.access, self.funcname, self.bbname)?;
+        if let Some(source_loc) = &self.source_loc {
+            write!(f, " ({})", source_loc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the loads and stores which access memory covered by the
+/// watchpoints installed for `AbstractData::with_watchpoint()`, so that they
+/// can be attributed to individual paths.
+///
+/// Only loads and stores at constant addresses are detected; in particular,
+/// accesses made by function hooks (e.g., for `memcpy`) are not recorded.
+#[derive(Clone)]
+pub struct WatchpointHits(Rc<RefCell<WatchpointHitsInner>>);
+
+struct WatchpointHitsInner {
+    /// The watched regions, as (name, address, size in bytes)
+    watchpoints: Vec<(String, u64, u64)>,
+    /// The hits recorded so far, each with the index on the path of the block
+    /// in which it occurred
+    hits: Vec<(usize, WatchpointHit)>,
+    /// The (function name, block name) of each block on the last path which
+    /// was completed, if we haven't yet discarded that path's hits which
+    /// aren't shared with the current path
+    last_path: Option<Vec<(String, Name)>>,
+}
+
+impl WatchpointHits {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(WatchpointHitsInner {
+            watchpoints: Vec::new(),
+            hits: Vec::new(),
+            last_path: None,
+        })))
+    }
+
+    /// Set the watched regions, as (name, address, size in bytes)
+    pub fn set_watchpoints(&self, watchpoints: Vec<(String, u64, u64)>) {
+        self.0.borrow_mut().watchpoints = watchpoints;
+    }
+
+    /// Add the instruction callback which records loads and stores of watched
+    /// memory into this `WatchpointHits`
+    pub fn add_callbacks<'p, B: Backend>(&self, config: &mut Config<'p, B>) {
+        let hits = self.clone();
+        config.callbacks.add_instruction_callback(move |inst: &Instruction, state: &State<B>| {
+            match inst {
+                Instruction::Load(load) => hits.record_access(&load.address, state.size_in_bits(&state.type_of(load)), WatchpointAccess::Read, state),
+                Instruction::Store(store) => hits.record_access(&store.address, state.size_in_bits(&state.type_of(&store.value)), WatchpointAccess::Write, state),
+                _ => {},
+            }
+            Ok(())
+        });
+    }
+
+    fn record_access<B: Backend>(&self, address: &Operand, bits: Option<u32>, access: WatchpointAccess, state: &State<B>) {
+        let mut inner = self.0.borrow_mut();
+        if inner.watchpoints.is_empty() {
+            return;
+        }
+        let addr = match state.operand_to_bv(address).ok().and_then(|bv| bv.as_u64()) {
+            Some(addr) => addr,
+            None => return,  // we only detect accesses at constant addresses
+        };
+        let bytes = match bits {
+            Some(bits) => std::cmp::max(u64::from(bits) / 8, 1),
+            None => return,
+        };
+        inner.discard_hits_not_on_path(state);
+        let path_index = state.get_path().len().saturating_sub(1);
+        let new_hits: Vec<WatchpointHit> = inner.watchpoints.iter()
+            .filter(|(_, wp_addr, wp_bytes)| addr < wp_addr + wp_bytes && *wp_addr < addr + bytes)
+            .map(|(name, _, _)| WatchpointHit {
+                name: name.clone(),
+                access,
+                funcname: state.cur_loc.func.name.clone(),
+                bbname: state.cur_loc.bb.name.clone(),
+                source_loc: state.cur_loc.source_loc.cloned(),
+            })
+            .collect();
+        inner.hits.extend(new_hits.into_iter().map(|hit| (path_index, hit)));
+    }
+
+    /// Get the hits on the path which just completed in `state`
+    pub fn hits_for_completed_path<B: Backend>(&self, state: &State<B>) -> Vec<WatchpointHit> {
+        let mut inner = self.0.borrow_mut();
+        if inner.watchpoints.is_empty() {
+            return Vec::new();
+        }
+        inner.discard_hits_not_on_path(state);
+        inner.last_path = Some(state.get_path().iter().map(|pathentry| (pathentry.0.func.name.clone(), pathentry.0.bb.name.clone())).collect());
+        inner.hits.iter().map(|(_, hit)| hit.clone()).collect()
+    }
+}
+
+impl WatchpointHitsInner {
+    /// After backtracking, the current path shares only a prefix with the last
+    /// completed path. Discard the hits which occurred after that prefix.
+    fn discard_hits_not_on_path<B: Backend>(&mut self, state: &State<B>) {
+        if let Some(last_path) = self.last_path.take() {
+            let path = state.get_path();
+            let shared = last_path.iter()
+                .zip(path.iter())
+                .take_while(|((funcname, bbname), pathentry)| *funcname == pathentry.0.func.name && *bbname == pathentry.0.bb.name)
+                .count();
+            // the last shared block is where the path diverged, so it was executed in full before the divergence
+            self.hits.retain(|(path_index, _)| *path_index < shared);
+        }
+    }
+}
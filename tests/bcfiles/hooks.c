@@ -0,0 +1,68 @@
+#include <stdlib.h>
+#include <string.h>
+
+struct Buffer {
+  int len;
+  char data[64];
+};
+
+// copies `buf->len` bytes, so not constant-time if the length is secret
+void memcpy_secret_len(char* dst, struct Buffer* buf) {
+  memcpy(dst, buf->data, buf->len);
+}
+
+// likewise, clears `buf->len` bytes
+void memset_secret_len(struct Buffer* buf) {
+  memset(buf->data, 0, buf->len);
+}
+
+volatile int table[16];
+
+// `calloc`ed memory is zeroed, so this never accesses `table` at a secret index
+int calloc_zeroed(unsigned n, int secret) {
+  int* p = calloc(n, sizeof(int));
+  if (*(volatile int*)p != 0) {
+    table[secret & 15] = 1;
+  }
+  free(p);
+  return 0;
+}
+
+// `malloc`ed memory is uninitialized, so this may access `table` at a secret index
+int malloc_uninitialized(unsigned n, int secret) {
+  int* p = malloc(n * sizeof(int));
+  if (*(volatile int*)p != 0) {
+    table[secret & 15] = 1;
+  }
+  free(p);
+  return 0;
+}
+
+int get_random(void* ctx, unsigned char* output, size_t output_len);
+
+// branches on randomness, which is fine; but uses the secret as an index if
+// `get_random` fails
+int use_randomness(int secret) {
+  unsigned char buf[16];
+  if (get_random(0, buf, sizeof(buf)) != 0) {
+    table[secret & 15] = 1;
+  }
+  if (buf[0] & 1) {
+    table[0] = 1;
+  }
+  return 0;
+}
+
+int primitive(int x);
+
+// calls `primitive` once with a public argument, and once with a secret one
+int call_primitive_twice(int pub, int secret) {
+  return primitive(pub) + primitive(secret);
+}
+
+int opaque_hash(const unsigned char* data, size_t len);
+
+// hashes a 32-byte buffer with an opaque function
+int hash_buffer(const unsigned char* data) {
+  return opaque_hash(data, 32);
+}
@@ -1,5 +1,6 @@
 use haybale_pitchfork::*;
 use std::path::Path;
+use std::time::Duration;
 
 fn init_logging() {
     // since our tests will run with `progress_updates == false`,
@@ -23,11 +24,13 @@ fn pitchfork_config() -> PitchforkConfig {
 fn assert_no_ct_violation(res: ConstantTimeResultForFunction) {
     match res.first_error_or_violation() {
         None => {},  // pass
-        Some(ConstantTimeResultForPath::IsConstantTime) => panic!("first_error_or_violation should return an error or violation"),
-        Some(ConstantTimeResultForPath::NotConstantTime { violation_message }) =>
-            panic!("Expected no ct violation, but found one:\n  {}", violation_message),
+        Some(ConstantTimeResultForPath::IsConstantTime { .. }) => panic!("first_error_or_violation should return an error or violation"),
+        Some(ConstantTimeResultForPath::NotConstantTime { violation }) =>
+            panic!("Expected no ct violation, but found one:\n  {}", violation),
         Some(ConstantTimeResultForPath::OtherError { full_message, .. }) =>
             panic!("Encountered an unexpected error:\n  {}", full_message),
+        Some(ConstantTimeResultForPath::TimedOut { timeout }) =>
+            panic!("Analysis unexpectedly timed out after {:?}", timeout),
     }
 }
 
@@ -36,22 +39,49 @@ fn assert_is_ct_violation(res: ConstantTimeResultForFunction) {
     // even if there was also a ct violation reported
     for path_result in &res.path_results {
         match path_result {
-            ConstantTimeResultForPath::IsConstantTime => {},
+            ConstantTimeResultForPath::IsConstantTime { .. } => {},
             ConstantTimeResultForPath::NotConstantTime { .. } => {},
             ConstantTimeResultForPath::OtherError { full_message, .. } => {
                 panic!("Encountered an unexpected error: {}", full_message);
             }
+            ConstantTimeResultForPath::TimedOut { timeout } => {
+                panic!("Analysis unexpectedly timed out after {:?}", timeout);
+            }
         }
     }
     // If we get here, there are no `OtherError`s, so just check for the ct violation we're interested in
     let _ = res.first_ct_violation().expect("Expected a ct violation but didn't get one");
 }
 
+#[test]
+fn cached_project_is_reused() {
+    let proj = cached_project(&["tests/bcfiles/ct.bc"]).unwrap();
+    let again = cached_project(&["tests/bcfiles/ct.bc"]).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&proj, &again));
+    assert!(proj.get_func_by_name("ct_simple").is_some());
+}
+
+#[test]
+fn cached_project_is_replaced_when_stale() {
+    let path = std::env::temp_dir().join("pitchfork_test_cached_project.bc");
+    std::fs::copy("tests/bcfiles/ct.bc", &path).unwrap();
+    let proj = cached_project(&[&path]).unwrap();
+    // rewrite the file, with a later modification time (allowing for filesystems with coarse timestamps)
+    std::thread::sleep(Duration::from_millis(1100));
+    std::fs::copy("tests/bcfiles/ct.bc", &path).unwrap();
+    let reparsed = cached_project(&[&path]).unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&proj, &reparsed));
+    assert!(std::sync::Arc::ptr_eq(&reparsed, &cached_project(&[&path]).unwrap()));
+    // the stale `Project` stays usable for as long as we hold it
+    assert!(proj.get_func_by_name("ct_simple").is_some());
+}
+
 #[test]
 fn ct_simple() {
     init_logging();
     let project = get_project();
     let result = check_for_ct_violation_in_inputs("ct_simple", &project, Config::default(), &pitchfork_config());
+    assert!(result.exhaustive, "Expected the analysis to explore every path");
     assert_no_ct_violation(result);
 }
 
@@ -59,7 +89,15 @@ fn ct_simple() {
 fn ct_simple2() {
     init_logging();
     let project = get_project();
+    // by default, division of a secret is considered variable-time
     let result = check_for_ct_violation_in_inputs("ct_simple2", &project, Config::default(), &pitchfork_config());
+    assert_eq!(result.first_ct_violation().map(|violation| violation.kind), Some(CTViolationKind::VariableTimeOperation));
+    assert_is_ct_violation(result);
+
+    // but otherwise, there are no secret-dependent branches or memory addresses
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.variable_time_opcodes.clear();
+    let result = check_for_ct_violation_in_inputs("ct_simple2", &project, Config::default(), &pitchfork_config);
     assert_no_ct_violation(result);
 }
 
@@ -68,9 +106,77 @@ fn notct_branch() {
     init_logging();
     let project = get_project();
     let result = check_for_ct_violation_in_inputs("notct_branch", &project, Config::default(), &pitchfork_config());
+    assert_eq!(result.first_ct_violation().map(|violation| violation.kind), Some(CTViolationKind::Branch));
+    let call_stack: Vec<&str> = result.first_ct_violation().unwrap().call_stack.iter().map(|frame| frame.funcname.as_str()).collect();
+    assert_eq!(call_stack, vec!["notct_branch"]);
+    assert_eq!(result.violations_by_function().keys().copied().collect::<Vec<_>>(), vec!["notct_branch"]);
+    assert_is_ct_violation(result);
+}
+
+#[test]
+fn log_dir() {
+    init_logging();
+    let project = get_project();
+    let dir = std::env::temp_dir().join("pitchfork_test_log_dir");
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.dump_errors = true;
+    pitchfork_config.log_dir = Some(dir.clone());
+    let result = check_for_ct_violation_in_inputs("notct_branch", &project, Config::default(), &pitchfork_config);
+    let error_filename = result.error_filename.clone().expect("Expected an error file");
+    assert!(Path::new(&error_filename).starts_with(dir.join("notct_branch")), "{}", error_filename);
+    assert!(Path::new(&error_filename).is_file(), "{}", error_filename);
+    let coverage_filename = result.coverage_filename.clone().expect("Expected a coverage file");
+    assert!(Path::new(&coverage_filename).starts_with(dir.join("notct_branch")), "{}", coverage_filename);
+}
+
+#[test]
+fn unwritable_log_dir() {
+    init_logging();
+    let project = get_project();
+    // a regular file, so that the log directory can't be created under it
+    let not_a_dir = std::env::temp_dir().join("pitchfork_test_not_a_dir");
+    std::fs::write(&not_a_dir, "").unwrap();
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.dump_errors = true;
+    pitchfork_config.log_dir = Some(not_a_dir);
+    let result = check_for_ct_violation_in_inputs("notct_branch", &project, Config::default(), &pitchfork_config);
+    assert_eq!(result.error_filename, None);
+    assert_eq!(result.coverage_filename, None);
     assert_is_ct_violation(result);
 }
 
+#[test]
+fn json_output() {
+    init_logging();
+    let project = get_project();
+    let result = check_for_ct_violation_in_inputs("notct_branch", &project, Config::default(), &pitchfork_config());
+    let path = std::env::temp_dir().join("pitchfork_test_json_output.json");
+    write_json(&[result], &path).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(json[0]["funcname"], "notct_branch");
+    assert_eq!(json[0]["is_constant_time"], false);
+    assert_eq!(json[0]["violations"][0]["kind"], "Branch");
+    assert_eq!(json[0]["violations"][0]["call_stack"], serde_json::json!(["notct_branch"]));
+}
+
+#[test]
+fn analyses_one_after_another() {
+    let project = get_project();
+    let analysis = CtAnalysis::new("ct_simple", &project, None, &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_eq!(analysis.count(), 1);
+    // the first analysis has been consumed, so we can create another
+    let analysis = CtAnalysis::new("notct_branch", &project, None, &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert!(analysis.count() > 0);
+}
+
+#[test]
+#[should_panic(expected = "while another one still exists on the same thread")]
+fn two_analyses_at_once() {
+    let project = get_project();
+    let _first = CtAnalysis::new("ct_simple", &project, None, &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    let _second = CtAnalysis::new("ct_simple", &project, None, &StructDescriptions::new(), Config::default(), &pitchfork_config());
+}
+
 #[test]
 fn notct_mem() {
     init_logging();
@@ -79,6 +185,27 @@ fn notct_mem() {
     assert_is_ct_violation(result);
 }
 
+#[test]
+fn oblivious_access_hook() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    init_logging();
+    let project = get_project();
+    let reads = Arc::new(AtomicUsize::new(0));
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.oblivious_access_hook = Some(secret::ObliviousAccessHook::new({
+        let reads = reads.clone();
+        move |access| {
+            assert!(access.write_value.is_none(), "notct_mem shouldn't write to a secret address");
+            reads.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(secret::BV::Secret { btor: access.btor.clone(), width: access.bits, symbol: None }))
+        }
+    }));
+    let result = check_for_ct_violation_in_inputs("notct_mem", &project, Config::default(), &pitchfork_config);
+    assert_no_ct_violation(result);
+    assert!(reads.load(Ordering::SeqCst) > 0, "Expected the hook to be called");
+}
+
 #[test]
 fn notct_truepath() {
     init_logging();
@@ -94,6 +221,73 @@ fn notct_truepath() {
     assert_is_ct_violation(result);
 }
 
+#[test]
+fn notct_truepath_const_bounds() {
+    init_logging();
+    let project = get_project();
+    let check = |notsecret: AbstractValue| check_for_ct_violation(
+        "notct_truepath",
+        &project,
+        Some(vec![AbstractData::sec_i32(), AbstractData::sec_i32(), AbstractData::pub_i32(notsecret)]),
+        &StructDescriptions::new(),
+        Config::default(),
+        &pitchfork_config(),
+    );
+    // the violation is only on the path where `notsecret > 3` (signed)
+    assert_no_ct_violation(check(AbstractValue::SignedLessThanConst(4)));
+    assert_is_ct_violation(check(AbstractValue::SignedGreaterThanConst(-5)));
+    assert_no_ct_violation(check(AbstractValue::UnsignedLessThanConst(4)));
+    assert_is_ct_violation(check(AbstractValue::UnsignedGreaterThanConst(3)));
+    assert_no_ct_violation(check(AbstractValue::All(vec![AbstractValue::SignedGreaterThanConst(-100), AbstractValue::SignedLessThanConst(2)])));
+}
+
+#[test]
+fn counterexamples() {
+    init_logging();
+    let project = get_project();
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.counterexamples = true;
+    let result = check_for_ct_violation(
+        "notct_truepath",
+        &project,
+        Some(vec![AbstractData::sec_i32(), AbstractData::sec_i32(), AbstractData::pub_i32(AbstractValue::Unconstrained)]),
+        &StructDescriptions::new(),
+        Config::default(),
+        &pitchfork_config,
+    );
+    let counterexample = result.first_ct_violation().expect("Expected a ct violation").counterexample.clone().expect("Expected a counterexample");
+    assert_eq!(counterexample.len(), 3);
+    // only the public argument has a concrete value, and it must take the path with the violation
+    let values: Vec<&String> = counterexample.values().filter_map(|arg| arg.value.as_ref()).collect();
+    assert_eq!(values.len(), 1);
+    let notsecret = u32::from_str_radix(values[0].trim_start_matches("0x"), 16).unwrap() as i32;
+    assert!(notsecret > 3, "Expected notsecret > 3, got {}", notsecret);
+    // and it has several other possible values, all of which take that path
+    let arg = counterexample.values().find(|arg| arg.value.is_some()).unwrap();
+    assert_eq!(arg.possible_values.len(), 3);
+    assert!(!arg.all_possible_values);
+    for value in &arg.possible_values {
+        let notsecret = u32::from_str_radix(value.trim_start_matches("0x"), 16).unwrap() as i32;
+        assert!(notsecret > 3, "Expected notsecret > 3, got {}", notsecret);
+    }
+
+    // with a narrow range, we find all of the possible values
+    let result = check_for_ct_violation(
+        "notct_truepath",
+        &project,
+        Some(vec![AbstractData::sec_i32(), AbstractData::sec_i32(), AbstractData::pub_i32(AbstractValue::Range(2, 5))]),
+        &StructDescriptions::new(),
+        Config::default(),
+        &pitchfork_config,
+    );
+    let counterexample = result.first_ct_violation().expect("Expected a ct violation").counterexample.clone().expect("Expected a counterexample");
+    let arg = counterexample.values().find(|arg| arg.value.is_some()).unwrap();
+    assert!(arg.all_possible_values);
+    let mut values = arg.possible_values.clone();
+    values.sort();
+    assert_eq!(values, vec!["0x4".to_owned(), "0x5".to_owned()]);
+}
+
 #[test]
 fn notct_falsepath() {
     init_logging();
@@ -127,11 +321,32 @@ fn two_ct_violations() {
     assert_eq!(path_stats.num_ct_violations, 2, "Expected exactly two ct violations, but found {}", path_stats.num_ct_violations);
     assert_eq!(result.path_results.len(), 3, "Encountered an unexpected error: {}",
         result.path_results.iter().find_map(|res| match res {
-            ConstantTimeResultForPath::IsConstantTime => None,
+            ConstantTimeResultForPath::IsConstantTime { .. } => None,
             ConstantTimeResultForPath::NotConstantTime { .. } => None,
             ConstantTimeResultForPath::OtherError { full_message, .. } => Some(full_message),
+            ConstantTimeResultForPath::TimedOut { .. } => None,
         }).expect("Expected to find a non-ct-violation error here, but didn't")
     );
+    assert_eq!(path_stats.num_paths(), 3);
+    assert_eq!(path_stats.num_distinct_violation_kinds(), 1);
+    assert_eq!(path_stats.num_distinct_error_kinds(), 0);
+    assert_eq!(path_stats.path_lengths.len(), 3, "Expected path metrics for each of the three paths");
+    assert!(path_stats.total_instructions > 0);
+
+    // with sort_path_results, the violations come before the passing path
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.sort_path_results = true;
+    let result = check_for_ct_violation(
+        "two_ct_violations",
+        &project,
+        Some(vec![AbstractData::sec_i32(), AbstractData::sec_i32(), AbstractData::pub_i32(AbstractValue::Unconstrained)]),
+        &StructDescriptions::new(),
+        Config::default(),
+        &pitchfork_config,
+    );
+    let is_violation: Vec<bool> = result.path_results.iter().map(|path_result| path_result.violation_kind().is_some()).collect();
+    assert_eq!(is_violation, vec![true, true, false]);
+    assert_eq!(result.path_metrics.len(), 3);
 
     // with keep_going = false, we should get only one violation
     let mut pitchfork_config = pitchfork_config();
@@ -148,13 +363,91 @@ fn two_ct_violations() {
     assert_eq!(path_stats.num_ct_violations, 1, "Expected exactly one ct violation, but found {}", path_stats.num_ct_violations);
     for res in &result.path_results {
          match res {
-            ConstantTimeResultForPath::IsConstantTime => {},
+            ConstantTimeResultForPath::IsConstantTime { .. } => {},
             ConstantTimeResultForPath::NotConstantTime { .. } => {},
             ConstantTimeResultForPath::OtherError { full_message, .. } => {
                 panic!("Encountered an unexpected error: {}", full_message);
             },
+            ConstantTimeResultForPath::TimedOut { timeout } => {
+                panic!("Analysis unexpectedly timed out after {:?}", timeout);
+            },
          }
     }
+
+    // with max_violations = 1, we should also get only one violation
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.max_violations = Some(1);
+    let result = check_for_ct_violation(
+        "two_ct_violations",
+        &project,
+        Some(vec![AbstractData::sec_i32(), AbstractData::sec_i32(), AbstractData::pub_i32(AbstractValue::Unconstrained)]),
+        &StructDescriptions::new(),
+        Config::default(),
+        &pitchfork_config,
+    );
+    assert_eq!(result.path_statistics().num_ct_violations, 1);
+    assert!(result.path_results.last().and_then(|path_result| path_result.violation_kind()).is_some(), "Expected the analysis to stop at the violation");
+    assert!(!result.exhaustive);
+}
+
+#[test]
+fn violation_source_filter() {
+    init_logging();
+    let project = get_project();
+    // ct.bc has no debug info, so the violations have no source locations,
+    // and are reported regardless of the source filters
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.violation_source_prefixes = vec!["crypto/".to_owned()];
+    pitchfork_config.ignored_violation_source_prefixes = vec!["libc/".to_owned()];
+    let result = check_for_ct_violation(
+        "two_ct_violations",
+        &project,
+        Some(vec![AbstractData::sec_i32(), AbstractData::sec_i32(), AbstractData::pub_i32(AbstractValue::Unconstrained)]),
+        &StructDescriptions::new(),
+        Config::default(),
+        &pitchfork_config,
+    );
+    assert_eq!(result.path_statistics().num_ct_violations, 2);
+    assert!(result.filtered_violations.is_empty());
+    assert_eq!(result.path_metrics.len(), result.path_results.len());
+}
+
+#[test]
+fn panic_during_analysis() {
+    init_logging();
+    let project = get_project();
+    let mut config = Config::default();
+    config.callbacks.add_instruction_callback(|_, _| panic!("callback panicked"));
+    let result = check_for_ct_violation_in_inputs("ct_simple", &project, config, &pitchfork_config());
+    assert_eq!(result.path_results.len(), 1);
+    match &result.path_results[0] {
+        ConstantTimeResultForPath::OtherError { full_message, summary, .. } => {
+            assert!(full_message.contains("callback panicked"), "Unexpected error message: {}", full_message);
+            assert!(summary.is_none(), "Expected no summary for a panic");
+        },
+        path_result => panic!("Expected the panic to be recorded as an error, but got {:?}", path_result),
+    }
+    assert_eq!(result.path_metrics.len(), 1);
+    assert!(!result.exhaustive);
+}
+
+#[test]
+fn per_function_timeout() {
+    init_logging();
+    let project = get_project();
+    let mut pitchfork_config = pitchfork_config();
+    pitchfork_config.per_function_timeout = Some(Duration::from_secs(0));
+    let result = check_for_ct_violation(
+        "two_ct_violations",
+        &project,
+        Some(vec![AbstractData::sec_i32(), AbstractData::sec_i32(), AbstractData::pub_i32(AbstractValue::Unconstrained)]),
+        &StructDescriptions::new(),
+        Config::default(),
+        &pitchfork_config,
+    );
+    assert!(result.timed_out(), "Expected the analysis to time out");
+    assert!(!result.exhaustive);
+    assert_eq!(result.path_statistics().num_timeouts, 1);
 }
 
 #[test]
@@ -175,6 +468,61 @@ fn ct_onearg() {
     assert_is_ct_violation(result);
 }
 
+#[test]
+fn direct_overrides() {
+    init_logging();
+    let project = get_project();
+    let args = vec![
+        AbstractData::with_watchpoint("x", AbstractData::pub_i32(AbstractValue::Unconstrained)),
+        AbstractData::void_override(None, AbstractData::sec_i32()),
+    ];
+    let result = check_for_ct_violation("ct_onearg", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+}
+
+#[test]
+fn prebuilt_execution_manager() {
+    init_logging();
+    let project = get_project();
+    let publicx_secrety = vec![
+        AbstractData::pub_i32(AbstractValue::Unconstrained),
+        AbstractData::sec_i32(),
+    ];
+    let secretx_publicy = vec![
+        AbstractData::sec_i32(),
+        AbstractData::pub_i32(AbstractValue::Unconstrained),
+    ];
+    let em = haybale::symex_function("ct_onearg", &project, Config::default(), None).unwrap();
+    let result = check_for_ct_violation_with_execution_manager("ct_onearg", &project, em, Some(publicx_secrety), &StructDescriptions::new(), &pitchfork_config(), |_| {});
+    assert_no_ct_violation(result);
+    let em = haybale::symex_function("ct_onearg", &project, Config::default(), None).unwrap();
+    let result = check_for_ct_violation_with_execution_manager("ct_onearg", &project, em, Some(secretx_publicy), &StructDescriptions::new(), &pitchfork_config(), |_| {});
+    assert_is_ct_violation(result);
+}
+
+#[test]
+fn diff_results_between_runs() {
+    init_logging();
+    let project = get_project();
+    let publicx_secrety = vec![
+        AbstractData::pub_i32(AbstractValue::Unconstrained),
+        AbstractData::sec_i32(),
+    ];
+    let secretx_publicy = vec![
+        AbstractData::sec_i32(),
+        AbstractData::pub_i32(AbstractValue::Unconstrained),
+    ];
+    let ct = check_for_ct_violation("ct_onearg", &project, Some(publicx_secrety), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    let notct = check_for_ct_violation("ct_onearg", &project, Some(secretx_publicy), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    let simple = check_for_ct_violation("ct_simple", &project, None, &StructDescriptions::new(), Config::default(), &pitchfork_config());
+
+    let diff = diff_results(&[ct], &[notct, simple]);
+    assert!(diff.has_regressions());
+    let funcs: Vec<(&str, FunctionChange)> = diff.functions.iter().map(|f| (f.funcname.as_str(), f.change)).collect();
+    assert_eq!(funcs, vec![("ct_onearg", FunctionChange::Regressed), ("ct_simple", FunctionChange::New)]);
+    assert!(diff.functions[0].new_problem_kinds.contains(&CTViolationKind::Branch.to_string()));
+}
+
 #[test]
 fn ct_secrets() {
     init_logging();
@@ -186,6 +534,218 @@ fn ct_secrets() {
     assert_no_ct_violation(result);
 }
 
+#[test]
+fn solver_query_counts() {
+    init_logging();
+    let project = get_project();
+    let arg = vec![
+        AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::sec_i32(), 100)),
+    ];
+    let result = check_for_ct_violation("ct_secrets", &project, Some(arg), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    let stats = result.path_statistics();
+    // reading the secret array is classified either directly from its
+    // (constant) secrecy, or by the all-public and then all-secret checks;
+    // but never by the slow secret-mask solve
+    assert!(stats.solver_queries.read_constant_secrecy + stats.solver_queries.read_all_secret_checks > 0);
+    assert_eq!(stats.solver_queries.read_secret_mask_solves, 0);
+    assert!(format!("{}", stats).contains("solver queries: "));
+}
+
+#[test]
+fn config_with_pitchfork_defaults() {
+    init_logging();
+    let config = pitchfork_config_defaults();
+    assert!(config.function_hooks.has_default_hook());
+    assert!(config.function_hooks.is_hooked("hook_uninitialized_function_pointer"));
+    let project = get_project();
+    let result = check_for_ct_violation_in_inputs("ct_simple", &project, config, &pitchfork_config());
+    assert_no_ct_violation(result);
+}
+
+#[test]
+fn ct_secrets_tuple_array() {
+    init_logging();
+    let project = get_project();
+    let elements = (0 .. 100).map(|i| if i == 20 {
+        AbstractData::sec_i32()
+    } else {
+        AbstractData::pub_i32(AbstractValue::ExactValue(i))
+    });
+    let arg = vec![
+        AbstractData::pub_pointer_to(AbstractData::tuple_array(elements)),
+    ];
+    let result = check_for_ct_violation("ct_secrets", &project, Some(arg), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+}
+
+#[test]
+fn secret_global_variable() {
+    init_logging();
+    let project = get_project();
+    let args = || Some(vec![AbstractData::pub_i32(AbstractValue::Unconstrained), AbstractData::pub_i32(AbstractValue::Unconstrained), AbstractData::pub_i32(AbstractValue::ExactValue(200))]);
+    let returns_secret = |result: &ConstantTimeResultForFunction| result.path_results.iter().any(|path_result| match path_result {
+        ConstantTimeResultForPath::IsConstantTime { return_secrecy: Some(secrecy) } => *secrecy != secret::Secrecy::Public,
+        _ => false,
+    });
+    // `z[0]` comes from the global initializer for `z`, which is public by default
+    let result = check_for_ct_violation("two_ct_violations", &project, args(), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert!(!returns_secret(&result));
+    let mut pconfig = pitchfork_config();
+    pconfig.global_variables.insert("__const.two_ct_violations.z".into(), AbstractData::secret());
+    let result = check_for_ct_violation("two_ct_violations", &project, args(), &StructDescriptions::new(), Config::default(), &pconfig);
+    assert!(returns_secret(&result), "Expected the value read from the secret global to be secret");
+}
+
+#[test]
+#[should_panic(expected = "Failed to find global variable")]
+fn nonexistent_global_variable() {
+    let project = get_project();
+    let mut pconfig = pitchfork_config();
+    pconfig.global_variables.insert("no_such_global".into(), AbstractData::secret());
+    check_for_ct_violation_in_inputs("ct_simple", &project, Config::default(), &pconfig);
+}
+
+#[test]
+fn notct_secrets_with_secret_range() {
+    init_logging();
+    let project = get_project();
+    // `notct_secrets` branches on element 20 of the array, but never uses element 1
+    let arg = |secret_offset_bytes| vec![
+        AbstractData::pointer_override(None, AbstractData::mostly_public_with_secret_range(400, secret_offset_bytes, 4)),
+    ];
+    let result = check_for_ct_violation("notct_secrets", &project, Some(arg(4)), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+    let result = check_for_ct_violation("notct_secrets", &project, Some(arg(80)), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert!(result.first_ct_violation().is_some(), "Expected a violation from the branch on element 20");
+}
+
+#[test]
+fn ct_secrets_multi() {
+    init_logging();
+    let project = get_project();
+    let scenarios = vec![
+        ("secret array".to_owned(), Some(vec![AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::sec_i32(), 100))])),
+        ("public array".to_owned(), Some(vec![AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::pub_i32(AbstractValue::Unconstrained), 100))])),
+    ];
+    let result = check_for_ct_violation_multi("ct_secrets", &project, scenarios, &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_eq!(result.scenarios.len(), 2);
+    assert_eq!(result.scenarios[1].0, "public array");
+    assert!(result.is_constant_time(), "{}", result);
+    assert!(result.violations().is_empty());
+    assert!(result.block_coverage.contains_key("ct_secrets"));
+    let display = result.to_string();
+    assert!(display.contains("secret array: "), "{}", display);
+    assert!(display.contains("Block coverage of ct_secrets across all scenarios"), "{}", display);
+}
+
+#[test]
+fn coverage_stats_of_unexecuted_function() {
+    let stats = pretty_coverage_stats("ct_simple", "ct_simple", &std::collections::HashMap::new()).unwrap();
+    assert!(stats.contains("Toplevel function (ct_simple) was not executed"), "{}", stats);
+}
+
+#[test]
+fn display_without_coverage_stats() {
+    init_logging();
+    let project = get_project();
+    let result = check_for_ct_violation_in_inputs("ct_simple", &project, Config::default(), &pitchfork_config());
+    assert!(result.to_string().contains("Coverage stats"), "{}", result);
+    let mut pconfig = pitchfork_config();
+    pconfig.display_coverage_stats = false;
+    let result = check_for_ct_violation_in_inputs("ct_simple", &project, Config::default(), &pconfig);
+    assert!(!result.to_string().contains("Coverage stats"), "{}", result);
+    assert!(result.block_coverage.contains_key("ct_simple"));
+}
+
+#[test]
+fn describe_ct_secrets_args() {
+    let project = get_project();
+    let arg = vec![
+        AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::sec_i32(), 100)),
+    ];
+    let description = describe_args("ct_secrets", Some(arg), &StructDescriptions::new(), &project, &pitchfork_config())
+        .expect("Failed to describe args");
+    assert!(description.contains("(64 bits): a pointer to an array of 100 elements"), "{}", description);
+    assert!(description.contains("P a pointer (offset 0, 64 bits)"), "{}", description);
+    assert!(description.contains("* an array of 100 elements (offset 0, 3200 bits)"), "{}", description);
+    assert!(description.contains("S [each of 100] a 32-bit secret value (offset 0, 32 bits)"), "{}", description);
+    assert!(describe_args("ct_secrets", Some(vec![]), &StructDescriptions::new(), &project, &pitchfork_config()).is_err());
+    assert!(describe_args("no_such_function", None, &StructDescriptions::new(), &project, &pitchfork_config()).is_err());
+}
+
+#[test]
+fn strict_uninitialized_memory() {
+    init_logging();
+    let project = get_project();
+    let mut pconfig = pitchfork_config();
+    pconfig.strict_uninitialized_memory = true;
+    // `ct_secrets` reads element 20 of the array
+    let long_enough = vec![
+        AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::pub_i32(AbstractValue::Unconstrained), 100)),
+    ];
+    let result = check_for_ct_violation("ct_secrets", &project, Some(long_enough), &StructDescriptions::new(), Config::default(), &pconfig);
+    assert_no_ct_violation(result);
+    let too_short = vec![
+        AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::pub_i32(AbstractValue::Unconstrained), 10)),
+    ];
+    let result = check_for_ct_violation("ct_secrets", &project, Some(too_short.clone()), &StructDescriptions::new(), Config::default(), &pconfig);
+    match result.first_error_or_violation() {
+        Some(ConstantTimeResultForPath::OtherError { full_message, summary, .. }) => {
+            assert!(full_message.contains("never written"), "unexpected error: {}", full_message);
+            let summary = summary.as_ref().expect("Expected a summary of the error");
+            assert_eq!(summary.funcname, "ct_secrets");
+            assert!(!summary.solver_timed_out);
+            assert!(summary.to_string().contains("ct_secrets"));
+        },
+        other => panic!("Expected an error for reading uninitialized memory, but got {:?}", other),
+    }
+    // without strict mode, the read just sees public data
+    let result = check_for_ct_violation("ct_secrets", &project, Some(too_short), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+}
+
+#[test]
+fn bounds_check_pointees() {
+    init_logging();
+    let project = get_project();
+    let mut pconfig = pitchfork_config();
+    pconfig.bounds_check_pointees = true;
+    let is_out_of_bounds = |hit: &WatchpointHit| hit.name.starts_with("outside_of_pointee:");
+    // `ct_secrets` reads element 20 of the array
+    let long_enough = vec![
+        AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::sec_i32(), 100)),
+    ];
+    let result = check_for_ct_violation("ct_secrets", &project, Some(long_enough), &StructDescriptions::new(), Config::default(), &pconfig);
+    assert!(result.watchpoint_hits.iter().flatten().all(|hit| !is_out_of_bounds(hit)));
+    assert_no_ct_violation(result);
+    let too_short = vec![
+        AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::sec_i32(), 10)),
+    ];
+    let result = check_for_ct_violation("ct_secrets", &project, Some(too_short), &StructDescriptions::new(), Config::default(), &pconfig);
+    assert!(result.watchpoint_hits.iter().flatten().any(|hit| is_out_of_bounds(hit) && hit.access == WatchpointAccess::Read));
+}
+
+#[test]
+fn aligned_pointer() {
+    use haybale::backend::BV;
+    init_logging();
+    let project = get_project();
+    let args = vec![
+        AbstractData::aligned_pointer_to(AbstractData::array_of(AbstractData::sec_i32(), 100), 4096),
+    ];
+    let result = check_for_ct_violation("ct_secrets", &project, Some(args.clone()), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    let base_address = result.allocated_args.values().next().unwrap().base_address.as_ref().unwrap().as_u64().unwrap();
+    assert_eq!(base_address % 4096, 0);
+    assert_no_ct_violation(result);
+    // the alignment should also be respected together with the bounds-checking guards
+    let mut pconfig = pitchfork_config();
+    pconfig.bounds_check_pointees = true;
+    let result = check_for_ct_violation("ct_secrets", &project, Some(args), &StructDescriptions::new(), Config::default(), &pconfig);
+    let base_address = result.allocated_args.values().next().unwrap().base_address.as_ref().unwrap().as_u64().unwrap();
+    assert_eq!(base_address % 4096, 0);
+    assert_no_ct_violation(result);
+}
+
 #[test]
 fn notct_secrets() {
     init_logging();
@@ -225,6 +785,41 @@ fn ct_struct() {
     // now check again, using `args==None` and `StructDescriptions`
     let result = check_for_ct_violation("ct_struct", &project, None, &sd, Config::default(), &pitchfork_config());
     assert_no_ct_violation(result);
+    // now check again, using a struct description derived from the struct type
+    let from_type = AbstractData::from_struct_type(&project, "PartiallySecret")
+        .with_field(&[0], AbstractData::pub_i32(AbstractValue::Range(0, 4096)))
+        .with_field(&[1], AbstractData::sec_i32());
+    let sd = std::iter::once(("struct.PartiallySecret".to_owned(), from_type)).collect();
+    let result = check_for_ct_violation("ct_struct", &project, None, &sd, Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+}
+
+#[test]
+fn ct_struct_array_of_len() {
+    init_logging();
+    let project = get_project();
+    // here the array index is described only as `named`, and then bounded by
+    // the array's length specification
+    let args = vec![
+        AbstractData::pub_pointer_to_array_of_len(AbstractData::pub_i32(AbstractValue::Unconstrained), "idx", 100),
+        AbstractData::pub_pointer_to(AbstractData::_struct("PartiallySecret", vec![
+            AbstractData::pub_i32(AbstractValue::named("idx", AbstractValue::Unconstrained)),
+            AbstractData::sec_i32(),
+        ])),
+    ];
+    let result = check_for_ct_violation("ct_struct", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+}
+
+#[test]
+#[should_panic(expected = "no value or parameter named")]
+fn array_of_len_without_len() {
+    let project = get_project();
+    let args = vec![
+        AbstractData::pub_pointer_to_array_of_len(AbstractData::pub_i32(AbstractValue::Unconstrained), "no_such_len", 100),
+        AbstractData::pub_pointer_to(struct_partially_secret()),
+    ];
+    check_for_ct_violation("ct_struct", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
 }
 
 #[test]
@@ -356,6 +951,19 @@ fn indirectly_recursive_struct() {
     assert_is_ct_violation(result);
 }
 
+#[test]
+#[should_panic(expected = "Exceeded the maximum struct nesting depth of 0")]
+fn struct_nesting_depth_exceeded() {
+    let project = get_project();
+    let mut pconfig = pitchfork_config();
+    pconfig.max_struct_nesting_depth = 0;
+    let args = vec![
+        AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::pub_i32(AbstractValue::Unconstrained), 100)),
+        AbstractData::pub_pointer_to(struct_partially_secret()),
+    ];
+    check_for_ct_violation("ct_struct", &project, Some(args), &StructDescriptions::new(), Config::default(), &pconfig);
+}
+
 #[test]
 fn related_args() {
     init_logging();
@@ -378,6 +986,55 @@ fn related_args() {
     assert_is_ct_violation(result);
 }
 
+#[test]
+fn continue_past_secret_branches_per_path() {
+    init_logging();
+    let project = get_project();
+    let mut pconfig = pitchfork_config();
+    pconfig.continue_past_secret_branches = true;
+    // every path ends by branching on `arr[x]` in the same block. `arr[x]` may
+    // be secret on the paths where the loop wrote to `arr`, but is public on
+    // the paths where `length` is 20 and the loop doesn't run at all
+    let args = vec![
+        AbstractData::pub_i32(AbstractValue::Range(0, 20)),
+        AbstractData::pub_i32(AbstractValue::UnsignedLessThanConst(20)),
+        AbstractData::sec_i32(),
+    ];
+    let result = check_for_ct_violation("related_args", &project, Some(args), &StructDescriptions::new(), Config::default(), &pconfig);
+    assert!(result.path_results.iter().any(|path_result| path_result.violation_kind() == Some(CTViolationKind::Branch)), "Expected a secret branch on the paths where the loop runs");
+    let num_ct_paths = result.path_results.iter().filter(|path_result| match path_result {
+        ConstantTimeResultForPath::IsConstantTime { .. } => true,
+        _ => false,
+    }).count();
+    assert!(num_ct_paths > 0, "Expected the paths which skip the loop to be constant-time");
+}
+
+#[test]
+#[should_panic(expected = "cycle: bound -> length -> bound")]
+fn related_args_cycle() {
+    init_logging();
+    let project = get_project();
+    let args = vec![
+        AbstractData::pub_i32(AbstractValue::named("length", AbstractValue::EqualTo("bound".to_owned()))),
+        AbstractData::pub_i32(AbstractValue::named("bound", AbstractValue::SignedLessThan("length".to_owned()))),
+        AbstractData::sec_i32(),
+    ];
+    let _ = check_for_ct_violation("related_args", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+}
+
+#[test]
+#[should_panic(expected = "never defined with `AbstractValue::Named`: [\"lenght\"]")]
+fn related_args_undefined_name() {
+    init_logging();
+    let project = get_project();
+    let args = vec![
+        AbstractData::pub_i32(AbstractValue::named("length", AbstractValue::Range(0, 20))),
+        AbstractData::pub_i32(AbstractValue::UnsignedLessThan("lenght".to_owned())),
+        AbstractData::sec_i32(),
+    ];
+    let _ = check_for_ct_violation("related_args", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+}
+
 #[test]
 fn struct_related_fields() {
     init_logging();
@@ -390,6 +1047,24 @@ fn struct_related_fields() {
     let result = check_for_ct_violation("struct_related_fields", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
     assert_no_ct_violation(result);
 
+    // same constraint, but referring to the sibling field by index rather than by name
+    let args = vec![AbstractData::pub_pointer_to(AbstractData::_struct("StructWithRelatedFields", vec![
+        AbstractData::pub_i32(AbstractValue::Range(0, 20)),
+        AbstractData::pub_i32(AbstractValue::UnsignedLessThanField(0)),
+        AbstractData::sec_i32(),
+    ]))];
+    let result = check_for_ct_violation("struct_related_fields", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+
+    // combining several constraints on the same field
+    let args = vec![AbstractData::pub_pointer_to(AbstractData::_struct("StructWithRelatedFields", vec![
+        AbstractData::pub_i32(AbstractValue::Range(0, 20)),
+        AbstractData::pub_i32(AbstractValue::All(vec![AbstractValue::Range(0, 100), AbstractValue::UnsignedLessThanField(0)])),
+        AbstractData::sec_i32(),
+    ]))];
+    let result = check_for_ct_violation("struct_related_fields", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
+    assert_no_ct_violation(result);
+
     // but if we don't have the constraint, then there should be a violation
     let args = vec![AbstractData::pub_pointer_to(AbstractData::_struct("StructWithRelatedFields", vec![
         AbstractData::pub_i32(AbstractValue::Range(0, 20)),
@@ -399,3 +1074,157 @@ fn struct_related_fields() {
     let result = check_for_ct_violation("struct_related_fields", &project, Some(args), &StructDescriptions::new(), Config::default(), &pitchfork_config());
     assert_is_ct_violation(result);
 }
+
+// `hooks.bc` isn't checked in yet; generate it from `hooks.c` with the
+// Makefile in tests/bcfiles (which uses clang-9) to run the tests which use it
+fn get_hooks_project() -> Project {
+    Project::from_bc_path(&Path::new("tests/bcfiles/hooks.bc"))
+        .unwrap_or_else(|e| panic!("Failed to create project: {}", e))
+}
+
+fn struct_buffer(len: AbstractData) -> AbstractData {
+    AbstractData::_struct("Buffer", vec![
+        len,
+        AbstractData::array_of(AbstractData::pub_i8(AbstractValue::Unconstrained), 64),
+    ])
+}
+
+fn config_with_mem_hooks<'p>() -> Config<'p, secret::Backend> {
+    let mut config = Config::default();
+    config.function_hooks.add("memcpy", &hook_helpers::memcpy_checking_secret_length);
+    config.function_hooks.add("llvm.memcpy.p0i8.p0i8.i64", &hook_helpers::memcpy_checking_secret_length);
+    config.function_hooks.add("memset", &hook_helpers::memset_checking_secret_length);
+    config.function_hooks.add("llvm.memset.p0i8.i64", &hook_helpers::memset_checking_secret_length);
+    config
+}
+
+#[test]
+#[ignore = "needs tests/bcfiles/hooks.bc, which is generated from hooks.c by `make` in tests/bcfiles"]
+fn mem_hooks_checking_secret_length() {
+    init_logging();
+    let project = get_hooks_project();
+    let dst = || AbstractData::pub_pointer_to(AbstractData::array_of(AbstractData::pub_i8(AbstractValue::Unconstrained), 64));
+
+    // with a secret length, the hooks report a violation naming the called function
+    let args = vec![dst(), AbstractData::pub_pointer_to(struct_buffer(AbstractData::sec_i32()))];
+    let result = check_for_ct_violation("memcpy_secret_len", &project, Some(args), &StructDescriptions::new(), config_with_mem_hooks(), &pitchfork_config());
+    let violation = result.first_ct_violation().expect("Expected a ct violation for memcpy with a secret length");
+    assert_eq!(violation.kind, CTViolationKind::MemoryAddress);
+    assert!(violation.msg.contains("llvm.memcpy"), "{}", violation.msg);
+    assert_is_ct_violation(result);
+    let args = vec![AbstractData::pub_pointer_to(struct_buffer(AbstractData::sec_i32()))];
+    let result = check_for_ct_violation("memset_secret_len", &project, Some(args), &StructDescriptions::new(), config_with_mem_hooks(), &pitchfork_config());
+    let violation = result.first_ct_violation().expect("Expected a ct violation for memset with a secret length");
+    assert_eq!(violation.kind, CTViolationKind::MemoryAddress);
+    assert!(violation.msg.contains("llvm.memset"), "{}", violation.msg);
+    assert_is_ct_violation(result);
+
+    // with a public length, they behave like the built-in hooks
+    let args = vec![dst(), AbstractData::pub_pointer_to(struct_buffer(AbstractData::pub_i32(AbstractValue::ExactValue(16))))];
+    let result = check_for_ct_violation("memcpy_secret_len", &project, Some(args), &StructDescriptions::new(), config_with_mem_hooks(), &pitchfork_config());
+    assert_no_ct_violation(result);
+    let args = vec![AbstractData::pub_pointer_to(struct_buffer(AbstractData::pub_i32(AbstractValue::ExactValue(16))))];
+    let result = check_for_ct_violation("memset_secret_len", &project, Some(args), &StructDescriptions::new(), config_with_mem_hooks(), &pitchfork_config());
+    assert_no_ct_violation(result);
+}
+
+#[test]
+#[ignore = "needs tests/bcfiles/hooks.bc, which is generated from hooks.c by `make` in tests/bcfiles"]
+fn allocation_hooks() {
+    init_logging();
+    let project = get_hooks_project();
+    let malloc = hook_helpers::malloc_hook(4096);
+    let calloc = hook_helpers::calloc_hook(4096);
+    let config = || {
+        let mut config = Config::default();
+        config.function_hooks.add("malloc", &malloc);
+        config.function_hooks.add("calloc", &calloc);
+        config.function_hooks.add("free", &hook_helpers::free_hook);
+        config
+    };
+    // a symbolic (but bounded) number of elements, and a secret
+    let args = || Some(vec![AbstractData::pub_i32(AbstractValue::Range(1, 64)), AbstractData::sec_i32()]);
+
+    // `calloc`ed memory reads back as zero, so `calloc_zeroed` never uses the secret as an index
+    let result = check_for_ct_violation("calloc_zeroed", &project, args(), &StructDescriptions::new(), config(), &pitchfork_config());
+    assert!(result.hooked_functions.contains_key("calloc"));
+    assert_no_ct_violation(result);
+
+    // `malloc`ed memory is uninitialized, so it may be nonzero, and then `malloc_uninitialized` does
+    let result = check_for_ct_violation("malloc_uninitialized", &project, args(), &StructDescriptions::new(), config(), &pitchfork_config());
+    assert_eq!(result.first_ct_violation().map(|violation| violation.kind), Some(CTViolationKind::MemoryAddress));
+    assert_is_ct_violation(result);
+}
+
+#[test]
+#[ignore = "needs tests/bcfiles/hooks.bc, which is generated from hooks.c by `make` in tests/bcfiles"]
+fn fill_buffer_with_public_symbolic() {
+    init_logging();
+    let project = get_hooks_project();
+    let get_random = hook_helpers::fill_buffer_with_public_symbolic(1, 2);
+    let mut config = Config::default();
+    config.function_hooks.add("get_random", &get_random);
+    // reading the buffer is an error unless the hook wrote it
+    let mut pconfig = pitchfork_config();
+    pconfig.strict_uninitialized_memory = true;
+    // the hook returns success, so the secret is never used as an index; and
+    // the bytes it writes are public, so branching on them is fine
+    let result = check_for_ct_violation("use_randomness", &project, Some(vec![AbstractData::sec_i32()]), &StructDescriptions::new(), config, &pconfig);
+    assert!(result.hooked_functions.contains_key("get_random"));
+    assert_no_ct_violation(result);
+}
+
+thread_local! {
+    /// Which of the hooks given to `conditional_on_secret` have run, in order
+    static PRIMITIVE_HOOKS_RUN: std::cell::RefCell<Vec<&'static str>> = std::cell::RefCell::new(Vec::new());
+}
+
+fn public_primitive_hook(state: &mut haybale::State<secret::Backend>, call: &dyn haybale::function_hooks::IsCall) -> haybale::Result<haybale::ReturnValue<secret::BV>> {
+    PRIMITIVE_HOOKS_RUN.with(|hooks_run| hooks_run.borrow_mut().push("public"));
+    hooks::return_public_unconstrained(state, call)
+}
+
+fn secret_primitive_hook(state: &mut haybale::State<secret::Backend>, call: &dyn haybale::function_hooks::IsCall) -> haybale::Result<haybale::ReturnValue<secret::BV>> {
+    PRIMITIVE_HOOKS_RUN.with(|hooks_run| hooks_run.borrow_mut().push("secret"));
+    hooks::return_secret(state, call)
+}
+
+#[test]
+#[ignore = "needs tests/bcfiles/hooks.bc, which is generated from hooks.c by `make` in tests/bcfiles"]
+fn conditional_on_secret() {
+    init_logging();
+    let project = get_hooks_project();
+    let primitive = hook_helpers::conditional_on_secret(public_primitive_hook, secret_primitive_hook);
+    let mut config = Config::default();
+    config.function_hooks.add("primitive", &primitive);
+    PRIMITIVE_HOOKS_RUN.with(|hooks_run| hooks_run.borrow_mut().clear());
+    let args = vec![AbstractData::pub_i32(AbstractValue::Unconstrained), AbstractData::sec_i32()];
+    let result = check_for_ct_violation("call_primitive_twice", &project, Some(args), &StructDescriptions::new(), config, &pitchfork_config());
+    // `primitive` is called first with the public argument, then with the secret one
+    assert_eq!(PRIMITIVE_HOOKS_RUN.with(|hooks_run| hooks_run.borrow().clone()), vec!["public", "secret"]);
+    assert_no_ct_violation(result);
+}
+
+#[test]
+#[ignore = "needs tests/bcfiles/hooks.bc, which is generated from hooks.c by `make` in tests/bcfiles"]
+fn taint_propagating_stub_hook() {
+    init_logging();
+    let project = get_hooks_project();
+    let opaque_hash = hook_helpers::taint_propagating_stub_hook(32);
+    let config = || {
+        let mut config = Config::default();
+        config.function_hooks.add("opaque_hash", &opaque_hash);
+        config
+    };
+    let return_secrecy = |result: &ConstantTimeResultForFunction| match result.path_results.as_slice() {
+        [ConstantTimeResultForPath::IsConstantTime { return_secrecy: Some(secrecy) }] => secrecy.clone(),
+        path_results => panic!("Expected a single constant-time path returning a value, but got {:?}", path_results),
+    };
+    let arg = |element: AbstractData| Some(vec![AbstractData::pub_pointer_to(AbstractData::array_of(element, 32))]);
+    // the buffer is secret, so the hash is too
+    let result = check_for_ct_violation("hash_buffer", &project, arg(AbstractData::sec_i8()), &StructDescriptions::new(), config(), &pitchfork_config());
+    assert_eq!(return_secrecy(&result), secret::Secrecy::Secret);
+    // the buffer is public, so the hash is too
+    let result = check_for_ct_violation("hash_buffer", &project, arg(AbstractData::pub_i8(AbstractValue::Unconstrained)), &StructDescriptions::new(), config(), &pitchfork_config());
+    assert_eq!(return_secrecy(&result), secret::Secrecy::Public);
+}